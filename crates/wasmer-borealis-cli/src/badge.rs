@@ -0,0 +1,71 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Error};
+use wasmer_borealis::{experiment::Results, history::History};
+
+#[derive(Debug, clap::Parser)]
+pub struct Badge {
+    /// The text on the left-hand side of the badge.
+    #[clap(long, default_value = "registry compat")]
+    label: String,
+    /// Save the badge to this file instead of printing it.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// Use the latest run in this history store (the default history store
+    /// is used when no results.json file is given).
+    #[clap(long, env = "BOREALIS_HISTORY", conflicts_with = "json")]
+    history: Option<PathBuf>,
+    /// Only look at runs of this experiment in the history store.
+    #[clap(long, conflicts_with = "json")]
+    experiment: Option<String>,
+    /// The results.json file generated during an experiment run.
+    json: Option<PathBuf>,
+}
+
+impl Badge {
+    pub fn execute(self) -> Result<(), Error> {
+        let success_rate = match &self.json {
+            Some(path) => Results::load(path)?.success_rate(),
+            None => self.latest_success_rate()?,
+        };
+
+        let svg = wasmer_borealis::render::badge(&self.label, success_rate)?;
+
+        match &self.output {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(path, svg)
+                    .with_context(|| format!("Unable to save to \"{}\"", path.display()))?;
+            }
+            None => println!("{svg}"),
+        }
+
+        Ok(())
+    }
+
+    fn latest_success_rate(&self) -> Result<f64, Error> {
+        let history = History::new(
+            self.history
+                .clone()
+                .unwrap_or_else(History::default_location),
+        );
+        let records = match &self.experiment {
+            Some(experiment) => history.runs_of(experiment)?,
+            None => history.records()?,
+        };
+
+        let latest = records
+            .into_iter()
+            .max_by_key(|r| r.started)
+            .with_context(|| {
+                format!(
+                    "There are no runs in \"{}\" to make a badge from",
+                    history.path().display()
+                )
+            })?;
+
+        Ok(latest.success_rate())
+    }
+}
@@ -3,7 +3,10 @@ use clap::Parser;
 use directories::ProjectDirs;
 use once_cell::sync::Lazy;
 use tracing_subscriber::EnvFilter;
-use wasmer_borealis_cli::{New, Report, Run};
+use wasmer_borealis_cli::{
+    Badge, Browse, Cache, Daemon, Doctor, Login, New, Pack, Packages, Report, Reproduce, Run, Runs,
+    Serve, Site, Triage, Wasmer,
+};
 
 pub static DIRS: Lazy<ProjectDirs> =
     Lazy::new(|| ProjectDirs::from("io", "wasmer", "borealis").unwrap());
@@ -15,8 +18,22 @@ fn main() -> Result<(), Error> {
 
     match cmd {
         Cmd::Run(r) => r.execute(),
+        Cmd::Runs(r) => r.execute(),
+        Cmd::Site(s) => s.execute(),
         Cmd::New(n) => n.execute(),
         Cmd::Report(r) => r.execute(),
+        Cmd::Daemon(d) => d.execute(),
+        Cmd::Serve(s) => s.execute(),
+        Cmd::Wasmer(w) => w.execute(),
+        Cmd::Cache(c) => c.execute(),
+        Cmd::Triage(t) => t.execute(),
+        Cmd::Browse(b) => b.execute(),
+        Cmd::Doctor(d) => d.execute(),
+        Cmd::Reproduce(r) => r.execute(),
+        Cmd::Login(l) => l.execute(),
+        Cmd::Packages(p) => p.execute(),
+        Cmd::Badge(b) => b.execute(),
+        Cmd::Pack(p) => p.execute(),
     }
 }
 
@@ -34,9 +51,38 @@ enum Cmd {
     /// Create a new experiment.
     New(New),
     /// Run an experiment.
-    Run(Run),
+    Run(Box<Run>),
+    /// Find, inspect, and clean up previous runs.
+    Runs(Runs),
+    /// Publish the recorded runs as a static website.
+    Site(Site),
     /// Generate a report from an experiment's results.
     Report(Report),
+    /// Keep running experiments on a schedule.
+    Daemon(Daemon),
+    /// Serve a HTTP API for launching and monitoring experiments.
+    Serve(Serve),
+    /// Manage the wasmer CLIs used by experiments.
+    Wasmer(Wasmer),
+    /// Manage the package cache.
+    Cache(Cache),
+    /// Annotate the failures in an experiment's results.
+    Triage(Triage),
+    /// Interactively browse an experiment's results.
+    Browse(Browse),
+    /// Check that the environment is ready for running experiments.
+    Doctor(Doctor),
+    /// Re-create a test case's working directory and run it again.
+    Reproduce(Reproduce),
+    /// Save a registry token to the operating system's keychain.
+    Login(Login),
+    /// Find the packages on a registry without running anything.
+    Packages(Packages),
+    /// Make an SVG badge showing an experiment's success rate.
+    Badge(Badge),
+    /// Bundle an experiment's results and logs into a single file for
+    /// sharing.
+    Pack(Pack),
 }
 
 /// Initialize logging.
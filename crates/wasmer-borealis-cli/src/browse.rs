@@ -0,0 +1,355 @@
+use std::{
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Error;
+use wasmer_borealis::{
+    config::Experiment,
+    experiment::{read_log, Category, Report, Results},
+};
+
+/// How many reports are shown on each page of the list.
+const PAGE_SIZE: usize = 25;
+/// The number of lines from the end of each log file to show.
+const LOG_LINES: usize = 15;
+
+const HELP: &str = "\
+Commands:
+  <number>          Show the details for a report
+  o <outcome>       Only show successes, failures, or bugs (\"o all\" to reset)
+  n <namespace>     Only show packages from a namespace (\"n all\" to reset)
+  ] / [             Next / previous page
+  l                 List the reports again
+  h                 Show this help
+  q                 Quit";
+
+#[derive(Debug, clap::Parser)]
+pub struct Browse {
+    /// The results.json file generated during an experiment run, or a bundle
+    /// created by "wasmer-borealis pack".
+    json: PathBuf,
+}
+
+impl Browse {
+    pub fn execute(self) -> Result<(), Error> {
+        let results = crate::load_results(&self.json)?;
+
+        let stdin = std::io::stdin();
+        let stdout = std::io::stdout();
+        Browser::new(&results).run(stdin.lock(), stdout.lock())
+    }
+}
+
+/// A line-oriented results browser.
+#[derive(Debug)]
+struct Browser<'a> {
+    results: &'a Results,
+    outcome: Option<Category>,
+    namespace: Option<String>,
+    page: usize,
+}
+
+impl<'a> Browser<'a> {
+    fn new(results: &'a Results) -> Self {
+        Browser {
+            results,
+            outcome: None,
+            namespace: None,
+            page: 0,
+        }
+    }
+
+    fn run(mut self, mut input: impl BufRead, mut out: impl Write) -> Result<(), Error> {
+        self.list(&mut out)?;
+
+        loop {
+            write!(out, "> ")?;
+            out.flush()?;
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                return Ok(());
+            }
+
+            let mut words = line.split_whitespace();
+            let command = words.next().unwrap_or("l");
+            let argument = words.next();
+
+            match (command, argument) {
+                ("q", _) => return Ok(()),
+                ("h", _) | ("?", _) => writeln!(out, "{HELP}")?,
+                ("l", _) => self.list(&mut out)?,
+                ("]", _) => {
+                    if (self.page + 1) * PAGE_SIZE < self.visible().len() {
+                        self.page += 1;
+                    }
+                    self.list(&mut out)?;
+                }
+                ("[", _) => {
+                    self.page = self.page.saturating_sub(1);
+                    self.list(&mut out)?;
+                }
+                ("o", Some(outcome)) => {
+                    self.outcome = match outcome {
+                        "success" | "successes" => Some(Category::Success),
+                        "failure" | "failures" => Some(Category::Failure),
+                        "bug" | "bugs" => Some(Category::Bug),
+                        "all" => None,
+                        other => {
+                            writeln!(out, "Unknown outcome, \"{other}\"")?;
+                            continue;
+                        }
+                    };
+                    self.page = 0;
+                    self.list(&mut out)?;
+                }
+                ("n", Some(namespace)) => {
+                    self.namespace = (namespace != "all").then(|| namespace.to_string());
+                    self.page = 0;
+                    self.list(&mut out)?;
+                }
+                (number, _) => match number.parse::<usize>() {
+                    Ok(n) if n >= 1 && n <= self.visible().len() => {
+                        let report = self.visible()[n - 1];
+                        show(&self.results.experiment, report, &mut out)?;
+                    }
+                    _ => writeln!(out, "Unknown command, type \"h\" for help")?,
+                },
+            }
+        }
+    }
+
+    fn visible(&self) -> Vec<&'a Report> {
+        self.results
+            .reports
+            .iter()
+            .filter(|r| self.outcome.map_or(true, |o| r.category() == o))
+            .filter(|r| {
+                self.namespace
+                    .as_deref()
+                    .map_or(true, |ns| r.namespace() == ns)
+            })
+            .collect()
+    }
+
+    fn list(&self, mut out: impl Write) -> Result<(), Error> {
+        let visible = self.visible();
+        let start = self.page * PAGE_SIZE;
+        let end = visible.len().min(start + PAGE_SIZE);
+
+        // Clear the screen
+        write!(out, "\x1b[2J\x1b[H")?;
+        writeln!(
+            out,
+            "{} of {} reports (outcome: {}, namespace: {})",
+            visible.len(),
+            self.results.reports.len(),
+            self.outcome.map_or("all", category_name),
+            self.namespace.as_deref().unwrap_or("all"),
+        )?;
+        writeln!(out)?;
+
+        for (i, report) in visible.iter().enumerate().take(end).skip(start) {
+            writeln!(
+                out,
+                "{:>5}  {:<8} {}@{}",
+                i + 1,
+                category_name(report.category()),
+                report.display_name,
+                report.version(),
+            )?;
+        }
+
+        if visible.len() > PAGE_SIZE {
+            writeln!(out)?;
+            writeln!(
+                out,
+                "Page {} of {}",
+                self.page + 1,
+                (visible.len() + PAGE_SIZE - 1) / PAGE_SIZE
+            )?;
+        }
+        writeln!(out, "Type \"h\" for help")?;
+
+        Ok(())
+    }
+}
+
+fn category_name(category: Category) -> &'static str {
+    match category {
+        Category::Success => "success",
+        Category::Failure => "failure",
+        Category::Bug => "bug",
+    }
+}
+
+fn show(experiment: &Experiment, report: &Report, mut out: impl Write) -> Result<(), Error> {
+    writeln!(out)?;
+    writeln!(
+        out,
+        "{}@{} ({})",
+        report.display_name,
+        report.version(),
+        category_name(report.category())
+    )?;
+
+    if let Some(annotation) = &report.annotation {
+        writeln!(
+            out,
+            "Triage: {} {}",
+            annotation.disposition.description(),
+            annotation.note
+        )?;
+    }
+
+    if let Some(error) = report.outcome.error() {
+        writeln!(out, "Error: {}", error.error)?;
+        for cause in &error.causes {
+            writeln!(out, "  Caused by: {cause}")?;
+        }
+    }
+
+    for analysis in &report.analyses {
+        let output = analysis
+            .output
+            .as_ref()
+            .map(|o| o.to_string())
+            .or_else(|| analysis.error.clone())
+            .unwrap_or_default();
+        writeln!(out, "Analyzer \"{}\": {output}", analysis.name)?;
+    }
+
+    if let Some(base_dir) = report.outcome.base_dir() {
+        writeln!(out, "Working directory: {}", base_dir.display())?;
+        writeln!(out, "Reproduce with:")?;
+        match &report.command {
+            Some(command) => writeln!(out, "  {}", command.shell())?,
+            None => {
+                let experiment = experiment.with_overrides(report.namespace());
+                writeln!(
+                    out,
+                    "  cd {} && {}",
+                    base_dir.display(),
+                    reproduce(&experiment)
+                )?;
+            }
+        }
+        show_log(base_dir, "stdout.txt", &mut out)?;
+        show_log(base_dir, "stderr.txt", &mut out)?;
+    }
+
+    Ok(())
+}
+
+/// An approximation of the command that was run, before any templates were
+/// expanded, for results recorded before the exact command was saved.
+fn reproduce(experiment: &Experiment) -> String {
+    let mut command = format!("wasmer run {}", experiment.package);
+
+    if let Some(name) = &experiment.command {
+        command.push_str(&format!(" --command-name={name}"));
+    }
+
+    for arg in &experiment.wasmer.args {
+        command.push(' ');
+        command.push_str(arg.as_str());
+    }
+    for (name, value) in &experiment.env {
+        command.push_str(&format!(" --env={name}={}", value.as_str()));
+    }
+    command.push_str(" --");
+    for arg in &experiment.args {
+        command.push(' ');
+        command.push_str(arg.as_str());
+    }
+
+    command
+}
+
+fn show_log(base_dir: &Path, name: &str, mut out: impl Write) -> Result<(), Error> {
+    let Ok(Some(contents)) = read_log(base_dir, name) else {
+        return Ok(());
+    };
+    if contents.trim().is_empty() {
+        return Ok(());
+    }
+
+    let lines: Vec<&str> = contents.lines().collect();
+    let start = lines.len().saturating_sub(LOG_LINES);
+
+    writeln!(out, "--- {name} ---")?;
+    for line in &lines[start..] {
+        writeln!(out, "{line}")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn report(name: &str, outcome: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "display_name": name,
+            "package_version": {
+                "id": name,
+                "version": "1.0.0",
+                "distribution": {
+                    "downloadUrl": "https://example.com/package.tar.gz",
+                    "piritaDownloadUrl": null,
+                },
+            },
+            "outcome": outcome,
+        })
+    }
+
+    #[test]
+    fn filter_and_inspect_reports() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join("stderr.txt"), "Segmentation fault\n").unwrap();
+        let completed = |success: bool| {
+            serde_json::json!({
+                "outcome": "completed",
+                "base_dir": temp.path(),
+                "status": { "success": success, "code": if success { 0 } else { 1 } },
+                "run_time": { "secs": 1, "nanos": 0 },
+            })
+        };
+        let results: Results = serde_json::from_value(serde_json::json!({
+            "experiment": { "package": "wasmer/wasi" },
+            "reports": [
+                report("wasmer/python", completed(true)),
+                report("wasmer/php", completed(false)),
+                report("syrusakbary/cowsay", completed(false)),
+            ],
+            "total_time": { "secs": 1, "nanos": 0 },
+            "experiment_dir": "/tmp/experiment",
+        }))
+        .unwrap();
+        let script = "o failures\nn syrusakbary\n1\nexplode\nq\nl\n";
+        let mut out = Vec::new();
+
+        Browser::new(&results)
+            .run(script.as_bytes(), &mut out)
+            .unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let screens: Vec<&str> = out.split("\x1b[2J\x1b[H").skip(1).collect();
+        // Everything is listed at first, then each filter redraws the list
+        assert_eq!(screens.len(), 3);
+        assert!(screens[0].starts_with("3 of 3 reports (outcome: all, namespace: all)"));
+        assert!(screens[0].contains("    1  success  wasmer/python@1.0.0\n"));
+        assert!(screens[1].starts_with("2 of 3 reports (outcome: failure, namespace: all)"));
+        assert!(!screens[1].contains("wasmer/python"));
+        assert!(screens[2].starts_with("1 of 3 reports (outcome: failure, namespace: syrusakbary)"));
+        assert!(screens[2].contains("    1  failure  syrusakbary/cowsay@1.0.0\n"));
+        // Picking a number shows that report's details and logs
+        assert!(screens[2].contains("syrusakbary/cowsay@1.0.0 (failure)\n"));
+        assert!(screens[2].contains("--- stderr.txt ---\nSegmentation fault\n"));
+        assert!(screens[2].contains("Unknown command, type \"h\" for help\n"));
+        // Nothing after "q" is read
+        assert!(out.ends_with("> "));
+    }
+}
@@ -0,0 +1,101 @@
+use std::path::PathBuf;
+
+use anyhow::Error;
+use clap::{Parser, Subcommand};
+use wasmer_borealis::experiment::{CacheEntry, EntryStatus};
+
+#[derive(Parser, Debug)]
+pub struct Cache {
+    /// The directory downloaded packages are cached in.
+    #[clap(long, global = true, env = "BOREALIS_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    #[clap(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Check every cached package against the checksums recorded when it was
+    /// downloaded.
+    Verify {
+        /// Delete any corrupted packages so they are re-downloaded next time.
+        #[clap(long)]
+        delete: bool,
+        /// Re-download any corrupted packages.
+        #[clap(long, conflicts_with = "delete")]
+        repair: bool,
+        /// Also report packages that were cached before checksums were
+        /// recorded.
+        #[clap(long)]
+        show_unverified: bool,
+    },
+}
+
+impl Cache {
+    pub fn execute(self) -> Result<(), Error> {
+        let Cache { cache_dir, cmd } = self;
+        let cache_dir = crate::cache_dir(cache_dir)?;
+
+        match cmd {
+            Cmd::Verify {
+                delete,
+                repair,
+                show_unverified,
+            } => {
+                let entries = wasmer_borealis::experiment::verify_cache(&cache_dir)?;
+                let mut ok = 0;
+                let mut unverified = 0;
+                let mut corrupted: Vec<&CacheEntry> = Vec::new();
+
+                for entry in &entries {
+                    match &entry.status {
+                        EntryStatus::Ok => ok += 1,
+                        EntryStatus::Unverified => {
+                            unverified += 1;
+                            if show_unverified {
+                                println!("{}: no checksums recorded", entry.dir.display());
+                            }
+                        }
+                        EntryStatus::Corrupted { problems } => {
+                            println!("{}: corrupted", entry.dir.display());
+                            for problem in problems {
+                                println!("  - {problem}");
+                            }
+                            corrupted.push(entry);
+                        }
+                    }
+                }
+
+                println!(
+                    "Checked {} packages... ok: {ok}, corrupted: {}, unverified: {unverified}",
+                    entries.len(),
+                    corrupted.len()
+                );
+
+                if delete {
+                    for entry in &corrupted {
+                        std::fs::remove_dir_all(&entry.dir)?;
+                        println!("Deleted {}", entry.dir.display());
+                    }
+                } else if repair {
+                    let client = crate::run::client(None)?;
+                    let rt = tokio::runtime::Runtime::new()?;
+
+                    for entry in &corrupted {
+                        rt.block_on(wasmer_borealis::experiment::repair_entry(
+                            &client, &cache_dir, &entry.dir,
+                        ))?;
+                        println!("Repaired {}", entry.dir.display());
+                    }
+                } else if !corrupted.is_empty() {
+                    anyhow::bail!(
+                        "Found {} corrupted packages. Re-run with --delete or --repair to fix them.",
+                        corrupted.len()
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,304 @@
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{Context, Error};
+use chrono::{DateTime, Local, NaiveDateTime, Utc};
+use cron::Schedule;
+use wasmer_borealis::history::History;
+
+use crate::{run::Runs, Run};
+
+/// Where `--watch` remembers when it last checked for new packages, inside
+/// the daemon's output directory.
+const LAST_CHECK_FILE: &str = ".last-check";
+
+/// How each run's experiment directory is named, so they sort
+/// chronologically.
+const DIR_FORMAT: &str = "%Y-%m-%dT%H-%M-%S";
+
+#[derive(Debug, clap::Parser)]
+pub struct Daemon {
+    /// A cron expression saying when the experiments should be run, in local
+    /// time (e.g. "0 3 * * *" for 3am every day).
+    #[clap(long, value_parser = parse_schedule)]
+    schedule: Schedule,
+    /// The number of experiment directories to keep before the oldest ones are
+    /// deleted.
+    #[clap(long, default_value_t = 7)]
+    keep: usize,
+    /// Only run the experiment against package versions published since the
+    /// previous check, alerting (see --notify and --email) when any of them
+    /// fail. Nothing is run when no new versions were published.
+    #[clap(long, conflicts_with_all = ["package_list", "locked"])]
+    watch: bool,
+    #[clap(flatten)]
+    run: Run,
+}
+
+impl Daemon {
+    pub fn execute(self) -> Result<(), Error> {
+        let Daemon {
+            schedule,
+            keep,
+            watch,
+            mut run,
+        } = self;
+
+        let root = run
+            .output
+            .clone()
+            .unwrap_or_else(|| crate::DIRS.data_local_dir().join("daemon"));
+        let history = History::new(
+            run.history
+                .clone()
+                .unwrap_or_else(History::default_location),
+        );
+        let started = Utc::now();
+
+        loop {
+            let next = schedule
+                .upcoming(Local)
+                .next()
+                .context("The schedule will never run again")?;
+            tracing::info!(%next, "Waiting for the next run");
+
+            let delay = (next - Local::now()).to_std().unwrap_or_default();
+            std::thread::sleep(delay);
+
+            let experiment_dir = root.join(next.format(DIR_FORMAT).to_string());
+
+            let checked_at = Utc::now();
+            if watch {
+                let since = match last_check(&root) {
+                    Ok(last_check) => last_check.unwrap_or(started),
+                    Err(e) => {
+                        tracing::warn!(
+                            error = &*e,
+                            "Unable to read when packages were last checked"
+                        );
+                        started
+                    }
+                };
+
+                match run.discover_published_since(since) {
+                    Ok(packages) if packages.is_empty() => {
+                        tracing::info!(%since, "No new package versions were published");
+                        save_last_check(&root, checked_at);
+                        continue;
+                    }
+                    Ok(packages) => {
+                        tracing::info!(%since, count = packages.len(), "Found new package versions");
+                        run.set_packages(packages);
+                    }
+                    Err(e) => {
+                        tracing::error!(error = &*e, "Unable to check for new package versions");
+                        continue;
+                    }
+                }
+            }
+
+            match run.run_all(Some(experiment_dir), Some(&history)) {
+                Ok(runs) => {
+                    if watch {
+                        save_last_check(&root, checked_at);
+                        alert_on_failures(&run, &runs);
+                    }
+
+                    for (name, results) in &runs.results {
+                        tracing::info!(
+                            experiment = name.as_str(),
+                            success = results.successes().count(),
+                            failures = results.failures().count(),
+                            bugs = results.bugs().count(),
+                            dir = %results.experiment_dir.display(),
+                            "Scheduled run complete",
+                        );
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(error = &*e, "Scheduled run failed");
+                }
+            }
+
+            if let Err(e) = rotate(&root, keep) {
+                tracing::warn!(error = &*e, "Unable to remove old experiment directories");
+            }
+        }
+    }
+}
+
+/// Let the user know about any newly published packages which failed.
+fn alert_on_failures(run: &Run, runs: &Runs) {
+    let failing: Vec<String> = runs
+        .results
+        .iter()
+        .flat_map(|(_, results)| results.failures().chain(results.bugs()))
+        .map(|r| format!("{}@{}", r.display_name, r.version()))
+        .collect();
+    if failing.is_empty() {
+        return;
+    }
+
+    for package in &failing {
+        tracing::warn!(
+            package = package.as_str(),
+            "A newly published package failed"
+        );
+    }
+
+    if run.notify {
+        if let Err(e) = crate::notify::new_failures(&failing) {
+            tracing::warn!(error = &*e, "Unable to send a desktop notification");
+        }
+    }
+
+    if run.email.is_some() {
+        let report = runs
+            .results
+            .first()
+            .map(|(_, results)| results.experiment_dir.join("report.html"));
+        let summary = crate::email::Summary {
+            runs: &runs.results,
+            alerts: &runs.alerts,
+            slowdowns: 0,
+            report: report.as_deref(),
+        };
+        let sent = crate::user_config().and_then(|config| crate::email::send(config, &summary));
+        if let Err(e) = sent {
+            tracing::warn!(error = &*e, "Unable to email the run summary");
+        }
+    }
+}
+
+/// When `--watch` last checked for new packages, if it has checked before.
+fn last_check(root: &Path) -> Result<Option<DateTime<Utc>>, Error> {
+    let path = root.join(LAST_CHECK_FILE);
+
+    match std::fs::read_to_string(&path) {
+        Ok(timestamp) => {
+            let timestamp = DateTime::parse_from_rfc3339(timestamp.trim())
+                .with_context(|| format!("\"{}\" is corrupted", path.display()))?;
+            Ok(Some(timestamp.with_timezone(&Utc)))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(Error::new(e).context(format!("Unable to read \"{}\"", path.display()))),
+    }
+}
+
+fn save_last_check(root: &Path, checked_at: DateTime<Utc>) {
+    let path = root.join(LAST_CHECK_FILE);
+    let result =
+        std::fs::create_dir_all(root).and_then(|_| std::fs::write(&path, checked_at.to_rfc3339()));
+
+    if let Err(e) = result {
+        tracing::warn!(
+            error = &e as &dyn std::error::Error,
+            path = %path.display(),
+            "Unable to save when packages were last checked",
+        );
+    }
+}
+
+/// Parse a cron expression, accepting both the traditional 5-field form and
+/// the 6-field form that includes seconds.
+fn parse_schedule(s: &str) -> Result<Schedule, Error> {
+    let expression = if s.split_whitespace().count() == 5 {
+        format!("0 {s}")
+    } else {
+        s.to_string()
+    };
+
+    Schedule::from_str(&expression).with_context(|| format!("Invalid schedule, \"{s}\""))
+}
+
+/// Delete all but the `keep` most recent experiment directories.
+///
+/// Only directories named after a run's timestamp are touched, so anything
+/// else kept in the output directory is left alone.
+fn rotate(root: &Path, keep: usize) -> Result<(), Error> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(root)
+        .with_context(|| format!("Unable to read \"{}\"", root.display()))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_type().map(|t| t.is_dir()).unwrap_or(false))
+        .filter(|entry| {
+            entry.file_name().to_str().map_or(false, |name| {
+                NaiveDateTime::parse_from_str(name, DIR_FORMAT).is_ok()
+            })
+        })
+        .map(|entry| entry.path())
+        .collect();
+
+    // Directories are named after their timestamp, so sorting by name also
+    // sorts them chronologically.
+    dirs.sort();
+
+    let excess = dirs.len().saturating_sub(keep);
+
+    for dir in &dirs[..excess] {
+        tracing::info!(dir = %dir.display(), "Removing an old experiment directory");
+        std::fs::remove_dir_all(dir)
+            .with_context(|| format!("Unable to remove \"{}\"", dir.display()))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedules_can_leave_out_the_seconds() {
+        let five = parse_schedule("0 3 * * *").unwrap();
+        let six = parse_schedule("0 0 3 * * *").unwrap();
+
+        let start = Utc::now();
+        assert_eq!(five.after(&start).next(), six.after(&start).next());
+        let next = five.after(&start).next().unwrap();
+        assert_eq!(next.format("%H:%M:%S").to_string(), "03:00:00");
+    }
+
+    #[test]
+    fn invalid_schedules_are_rejected() {
+        let error = parse_schedule("every day").unwrap_err();
+
+        assert_eq!(error.to_string(), "Invalid schedule, \"every day\"");
+    }
+
+    #[test]
+    fn only_the_most_recent_runs_are_kept() {
+        let temp = tempfile::tempdir().unwrap();
+        let runs = [
+            "2024-06-01T03-00-00",
+            "2024-06-02T03-00-00",
+            "2024-06-03T03-00-00",
+        ];
+        for run in runs {
+            std::fs::create_dir(temp.path().join(run)).unwrap();
+        }
+        std::fs::create_dir(temp.path().join("baselines")).unwrap();
+        std::fs::create_dir(temp.path().join("0-important")).unwrap();
+        std::fs::write(temp.path().join(LAST_CHECK_FILE), "").unwrap();
+
+        rotate(temp.path(), 2).unwrap();
+
+        let mut remaining: Vec<_> = std::fs::read_dir(temp.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        remaining.sort();
+        assert_eq!(
+            remaining,
+            [
+                LAST_CHECK_FILE,
+                "0-important",
+                "2024-06-02T03-00-00",
+                "2024-06-03T03-00-00",
+                "baselines",
+            ]
+        );
+    }
+}
@@ -0,0 +1,287 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::Error;
+use wasmer_borealis::{
+    experiment::available_space,
+    registry::{format_graphql, RetryPolicy},
+    toolchain::Toolchains,
+    user_config::UserConfig,
+};
+
+use crate::run::{client, load_experiment};
+
+/// Warn when a directory has less than this much free space.
+const MIN_FREE_SPACE: u64 = 10 * 1024 * 1024 * 1024;
+/// Warn when the open file limit is lower than this.
+const MIN_OPEN_FILES: u64 = 4096;
+
+#[derive(Debug, clap::Parser)]
+pub struct Doctor {
+    /// The Wasmer registry to query packages from.
+    ///
+    /// This may be a URL, a domain name (e.g. "wasmer.io"), or one of "prod",
+    /// "staging", or "local". Defaults to the user config file's "registry",
+    /// or "wasmer.io".
+    #[clap(long, env = "WASMER_REGISTRY")]
+    registry: Option<String>,
+    #[clap(long, short, env = "WASMER_TOKEN")]
+    token: Option<String>,
+    /// The directory downloaded packages will be cached in.
+    #[clap(long, env = "BOREALIS_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    /// The directory experiments will be written to.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// Check the wasmer CLI used by this experiment instead of the one on
+    /// $PATH.
+    #[clap(long)]
+    experiment: Option<PathBuf>,
+}
+
+impl Doctor {
+    pub fn execute(self) -> Result<(), Error> {
+        // A broken config file is reported as a problem below
+        let cache_dir = crate::cache_dir(self.cache_dir.clone())
+            .unwrap_or_else(|_| crate::DIRS.cache_dir().to_path_buf());
+        let output = self
+            .output
+            .clone()
+            .unwrap_or_else(|| crate::DIRS.data_local_dir().to_path_buf());
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let checks = [
+            ("config file", check_user_config()),
+            ("wasmer CLI", rt.block_on(self.check_wasmer(&cache_dir))),
+            ("registry", rt.block_on(self.check_registry())),
+            ("cache disk space", check_disk_space(&cache_dir)),
+            ("output disk space", check_disk_space(&output)),
+            ("symlinks", check_symlinks(&output)),
+            ("open file limit", check_open_files()),
+        ];
+
+        let mut problems = 0;
+
+        for (name, result) in checks {
+            match result {
+                Ok(detail) => println!("✔ {name}: {detail}"),
+                Err(problem) => {
+                    problems += 1;
+                    println!("✘ {name}: {}", problem.message);
+                    println!("    fix: {}", problem.fix);
+                }
+            }
+        }
+
+        anyhow::ensure!(problems == 0, "Found {problems} problem(s)");
+
+        Ok(())
+    }
+
+    async fn check_wasmer(&self, cache_dir: &Path) -> Result<String, Problem> {
+        let version = match &self.experiment {
+            Some(path) => {
+                let experiment = load_experiment(path).map_err(|e| {
+                    Problem::new(format!("{e:#}"), "Check the experiment file is valid")
+                })?;
+                experiment.wasmer.version
+            }
+            None => Default::default(),
+        };
+
+        let toolchains = Toolchains::in_cache_dir(cache_dir)
+            .map_err(|e| Problem::new(format!("{e:#}"), "Check the cache directory is writable"))?;
+        let wasmer = toolchains.resolve(&version).await.map_err(|e| {
+            Problem::new(
+                format!("{e:#}"),
+                "Install wasmer (https://docs.wasmer.io/install) or fix \"wasmer.version\"",
+            )
+        })?;
+
+        let output = Command::new(&wasmer)
+            .arg("--version")
+            .output()
+            .map_err(|e| {
+                Problem::new(
+                    format!("Unable to run \"{}\": {e}", wasmer.display()),
+                    "Install wasmer (https://docs.wasmer.io/install) and make sure it is on $PATH",
+                )
+            })?;
+
+        if !output.status.success() {
+            return Err(Problem::new(
+                format!(
+                    "\"{} --version\" failed ({})",
+                    wasmer.display(),
+                    output.status
+                ),
+                "Make sure the wasmer binary is runnable on this machine",
+            ));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    async fn check_registry(&self) -> Result<String, Problem> {
+        let registry = crate::registry(self.registry.as_deref())
+            .map_err(|e| Problem::new(format!("{e:#}"), "Fix the config file"))?;
+        let endpoint = format_graphql(&registry);
+        let token = crate::token(self.token.as_deref(), &registry).map_err(|e| {
+            Problem::new(
+                format!("{e:#}"),
+                "Check the config file's token settings, or use \"wasmer-borealis login\"",
+            )
+        })?;
+        let client = client(token.as_deref())
+            .map_err(|e| Problem::new(format!("{e:#}"), "Check the token is valid"))?;
+
+        let user = wasmer_borealis::registry::whoami(&client, &endpoint, &RetryPolicy::NONE)
+            .await
+            .map_err(|e| {
+                Problem::new(
+                    format!("Unable to reach {endpoint}: {:#}", Error::from(e)),
+                    "Check your network connection and the --registry flag",
+                )
+            })?;
+
+        match (user, &token) {
+            (Some(user), _) => Ok(format!("{endpoint} (logged in as {user})")),
+            (None, Some(_)) => Err(Problem::new(
+                format!("{endpoint} rejected the token"),
+                "Generate a new token and save it with \"wasmer-borealis login\" (or pass it with --token or $WASMER_TOKEN)",
+            )),
+            (None, None) => Ok(format!("{endpoint} (anonymous)")),
+        }
+    }
+}
+
+fn check_user_config() -> Result<String, Problem> {
+    let path = UserConfig::default_location();
+
+    if !path.exists() {
+        return Ok(format!(
+            "{} (not found, using the defaults)",
+            path.display()
+        ));
+    }
+
+    match UserConfig::load(&path) {
+        Ok(_) => Ok(path.display().to_string()),
+        Err(e) => Err(Problem::new(
+            format!("{e:#}"),
+            "Fix or remove the config file (or point $BOREALIS_CONFIG somewhere else)",
+        )),
+    }
+}
+
+#[derive(Debug)]
+struct Problem {
+    message: String,
+    fix: &'static str,
+}
+
+impl Problem {
+    fn new(message: impl Into<String>, fix: &'static str) -> Self {
+        Problem {
+            message: message.into(),
+            fix,
+        }
+    }
+}
+
+fn check_disk_space(dir: &Path) -> Result<String, Problem> {
+    std::fs::create_dir_all(dir).map_err(|e| {
+        Problem::new(
+            format!("Unable to create \"{}\": {e}", dir.display()),
+            "Check the directory's permissions",
+        )
+    })?;
+
+    let available = available_space(dir).map_err(|e| {
+        Problem::new(
+            format!("{e:#}"),
+            "Make sure \"df\" is installed, or check the disk space manually",
+        )
+    })?;
+    let gib = available as f64 / (1024.0 * 1024.0 * 1024.0);
+
+    if available < MIN_FREE_SPACE {
+        return Err(Problem::new(
+            format!("only {gib:.1} GiB free in \"{}\"", dir.display()),
+            "Free up some space or point borealis at a bigger disk",
+        ));
+    }
+
+    Ok(format!("{gib:.1} GiB free in \"{}\"", dir.display()))
+}
+
+fn check_symlinks(dir: &Path) -> Result<String, Problem> {
+    let temp = tempfile::tempdir_in(dir).map_err(|e| {
+        Problem::new(
+            format!(
+                "Unable to create a temporary directory in \"{}\": {e}",
+                dir.display()
+            ),
+            "Check the directory's permissions",
+        )
+    })?;
+    let original = temp.path().join("original");
+    let link = temp.path().join("link");
+    std::fs::write(&original, "").map_err(|e| {
+        Problem::new(
+            format!("Unable to write to \"{}\": {e}", dir.display()),
+            "Check the directory's permissions",
+        )
+    })?;
+
+    symlink(&original, &link).map_err(|e| {
+        Problem::new(
+            format!("Unable to create symlinks in \"{}\": {e}", dir.display()),
+            "Use a filesystem that supports symlinks (or enable Developer Mode on Windows)",
+        )
+    })?;
+
+    Ok("supported".to_string())
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(original, link)
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(original, link)
+}
+
+fn check_open_files() -> Result<String, Problem> {
+    if cfg!(windows) {
+        return Ok("not applicable".to_string());
+    }
+
+    let limit = Command::new("sh")
+        .arg("-c")
+        .arg("ulimit -n")
+        .output()
+        .ok()
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        .ok_or_else(|| {
+            Problem::new(
+                "Unable to run \"ulimit -n\"",
+                "Check the open file limit manually",
+            )
+        })?;
+
+    match limit.parse::<u64>() {
+        Ok(n) if n < MIN_OPEN_FILES => Err(Problem::new(
+            format!("only {n} open files allowed"),
+            "Raise the limit with \"ulimit -n 65536\" before running borealis",
+        )),
+        _ => Ok(limit),
+    }
+}
@@ -0,0 +1,357 @@
+//! Emailing run summaries (with the HTML report attached) through an SMTP
+//! server, using `curl`.
+
+use std::{
+    io::{ErrorKind, Write},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Error};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chrono::Utc;
+use wasmer_borealis::{alerts::SuccessRateDrop, experiment::Results, user_config::UserConfig};
+
+/// When to email a summary of the run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum When {
+    /// Every time a run finishes.
+    Always,
+    /// Only when a success rate dropped or a benchmark got slower.
+    Regressions,
+}
+
+/// What happened during a run.
+#[derive(Debug)]
+pub(crate) struct Summary<'a> {
+    pub runs: &'a [(String, Results)],
+    pub alerts: &'a [SuccessRateDrop],
+    /// How many benchmarks got significantly slower than the baseline.
+    pub slowdowns: usize,
+    /// The HTML report (or the summary, when several experiments were run).
+    pub report: Option<&'a Path>,
+}
+
+impl Summary<'_> {
+    pub(crate) fn has_regressions(&self) -> bool {
+        !self.alerts.is_empty() || self.slowdowns > 0
+    }
+}
+
+/// Email the `summary` to everyone in the user config's `email-to`.
+pub(crate) fn send(config: &UserConfig, summary: &Summary<'_>) -> Result<(), Error> {
+    let server = config
+        .smtp_server
+        .as_deref()
+        .context("Set \"smtp-server\" in the config file to email run summaries")?;
+    let from = config
+        .email_from
+        .as_deref()
+        .context("Set \"email-from\" in the config file to email run summaries")?;
+    let recipients = config.email_recipients();
+    anyhow::ensure!(
+        !recipients.is_empty(),
+        "Set \"email-to\" in the config file to email run summaries"
+    );
+
+    let attachment = match summary.report {
+        Some(path) => Some(
+            std::fs::read(path)
+                .with_context(|| format!("Unable to read \"{}\"", path.display()))?,
+        ),
+        None => None,
+    };
+    let message = message(from, &recipients, summary, attachment.as_deref())?;
+
+    let mut temp = tempfile::NamedTempFile::new()?;
+    temp.write_all(message.as_bytes())?;
+
+    let mut cmd = Command::new("curl");
+    cmd.arg("--silent")
+        .arg("--show-error")
+        .arg("--url")
+        .arg(server)
+        .arg("--mail-from")
+        .arg(from)
+        .arg("--upload-file")
+        .arg(temp.path())
+        .arg("--crlf");
+    for recipient in &recipients {
+        cmd.arg("--mail-rcpt").arg(recipient);
+    }
+
+    // Credentials are passed as a config file on stdin so they don't show up
+    // in the process list
+    let credentials = match &config.smtp_username {
+        Some(username) => {
+            if server.starts_with("smtp://") {
+                // Don't send the password in the clear
+                cmd.arg("--ssl-reqd");
+            }
+            let password = config.smtp_password()?.unwrap_or_default();
+            cmd.arg("--config").arg("-");
+            Some(format!(
+                "user = {}\n",
+                curl_string(&format!("{username}:{password}"))
+            ))
+        }
+        None => None,
+    };
+
+    let mut child = match cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) if e.kind() == ErrorKind::NotFound => {
+            anyhow::bail!("Unable to run \"curl\", is it installed?")
+        }
+        Err(e) => return Err(Error::new(e).context("Unable to run \"curl\"")),
+    };
+
+    if let (Some(credentials), Some(mut stdin)) = (credentials, child.stdin.take()) {
+        stdin.write_all(credentials.as_bytes())?;
+    }
+
+    let output = child.wait_with_output()?;
+    anyhow::ensure!(
+        output.status.success(),
+        "Unable to send the email via \"{server}\" ({}): {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    );
+
+    tracing::info!(recipients = recipients.len(), "Emailed the run summary");
+
+    Ok(())
+}
+
+fn message(
+    from: &str,
+    to: &[&str],
+    summary: &Summary<'_>,
+    attachment: Option<&[u8]>,
+) -> Result<String, Error> {
+    let boundary = format!("borealis-{}", Utc::now().timestamp_millis());
+    let mut msg = String::new();
+
+    msg.push_str(&format!("From: {from}\n"));
+    msg.push_str(&format!("To: {}\n", to.join(", ")));
+    msg.push_str(&format!("Subject: {}\n", header_value(&subject(summary))));
+    msg.push_str(&format!("Date: {}\n", Utc::now().to_rfc2822()));
+    msg.push_str("MIME-Version: 1.0\n");
+    msg.push_str(&format!(
+        "Content-Type: multipart/mixed; boundary=\"{boundary}\"\n\n"
+    ));
+
+    msg.push_str(&format!("--{boundary}\n"));
+    msg.push_str("Content-Type: text/plain; charset=utf-8\n");
+    msg.push_str("Content-Transfer-Encoding: base64\n\n");
+    msg.push_str(&wrapped_base64(body(summary)?.as_bytes()));
+
+    if let (Some(attachment), Some(report)) = (attachment, summary.report) {
+        let filename = report
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "report.html".to_string());
+        msg.push_str(&format!("--{boundary}\n"));
+        msg.push_str(&format!(
+            "Content-Type: text/html; charset=utf-8; name=\"{filename}\"\n"
+        ));
+        msg.push_str(&format!(
+            "Content-Disposition: attachment; filename=\"{filename}\"\n"
+        ));
+        msg.push_str("Content-Transfer-Encoding: base64\n\n");
+        msg.push_str(&wrapped_base64(attachment));
+    }
+
+    msg.push_str(&format!("--{boundary}--\n"));
+
+    Ok(msg)
+}
+
+fn subject(summary: &Summary<'_>) -> String {
+    let name = match summary.runs {
+        [(name, _)] => format!("\"{name}\""),
+        runs => format!("{} experiments", runs.len()),
+    };
+
+    if summary.has_regressions() {
+        format!("wasmer-borealis: regressions detected in {name}")
+    } else {
+        format!("wasmer-borealis: finished running {name}")
+    }
+}
+
+/// The same summary `wasmer-borealis run` prints, plus any regressions.
+fn body(summary: &Summary<'_>) -> Result<String, Error> {
+    let mut body = Vec::new();
+
+    for (name, results) in summary.runs {
+        write!(body, "{name}: ")?;
+        wasmer_borealis::render::text(results, &mut body)?;
+    }
+
+    for alert in summary.alerts {
+        writeln!(
+            body,
+            "\nThe success rate for \"{}\" dropped from {:.1}% to {:.1}%",
+            alert.experiment, alert.previous, alert.current
+        )?;
+        for pkg in &alert.newly_failing {
+            writeln!(body, "  - {pkg} no longer passes")?;
+        }
+    }
+
+    if summary.slowdowns > 0 {
+        writeln!(
+            body,
+            "\n{} benchmark(s) got significantly slower than the baseline",
+            summary.slowdowns
+        )?;
+    }
+
+    Ok(String::from_utf8(body)?)
+}
+
+/// Encode non-ASCII header values as described in RFC 2047.
+fn header_value(value: &str) -> String {
+    if value.is_ascii() {
+        value.to_string()
+    } else {
+        format!("=?utf-8?B?{}?=", STANDARD.encode(value))
+    }
+}
+
+/// Base64-encode some data, keeping lines under the 76 characters MIME
+/// allows.
+fn wrapped_base64(data: &[u8]) -> String {
+    let encoded = STANDARD.encode(data);
+    let mut wrapped = String::with_capacity(encoded.len() + encoded.len() / 76 + 1);
+
+    for line in encoded.as_bytes().chunks(76) {
+        // Base64 output is always ASCII
+        wrapped.push_str(std::str::from_utf8(line).unwrap());
+        wrapped.push('\n');
+    }
+
+    wrapped
+}
+
+/// Quote a value for a `curl` config file, escaping anything which would end
+/// the string or the line early.
+fn curl_string(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            other => quoted.push(other),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn results() -> Results {
+        serde_json::from_value(serde_json::json!({
+            "experiment": { "package": "wasmer/wasi" },
+            "reports": [],
+            "total_time": { "secs": 1, "nanos": 0 },
+            "experiment_dir": "/tmp/experiment",
+        }))
+        .unwrap()
+    }
+
+    fn decode(lines: &str) -> Vec<u8> {
+        STANDARD.decode(lines.replace('\n', "")).unwrap()
+    }
+
+    #[test]
+    fn message_is_split_into_mime_parts() {
+        let runs = [("nightly".to_string(), results())];
+        let summary = Summary {
+            runs: &runs,
+            alerts: &[],
+            slowdowns: 0,
+            report: Some(Path::new("/tmp/experiment/report.html")),
+        };
+
+        let msg = message(
+            "borealis@example.com",
+            &["a@example.com", "b@example.com"],
+            &summary,
+            Some(b"<html></html>"),
+        )
+        .unwrap();
+
+        let boundary = msg
+            .split("boundary=\"")
+            .nth(1)
+            .and_then(|rest| rest.split('"').next())
+            .unwrap();
+        let parts: Vec<_> = msg.split(&format!("--{boundary}")).collect();
+        // The headers, the body, the attachment, and the closing "--"
+        assert_eq!(parts.len(), 4);
+        assert!(parts[0].contains("To: a@example.com, b@example.com\n"));
+        assert!(parts[0].contains("Subject: wasmer-borealis: finished running \"nightly\"\n"));
+        assert!(parts[3].starts_with("--"));
+
+        let (headers, body) = parts[1].split_once("\n\n").unwrap();
+        assert!(headers.contains("Content-Type: text/plain; charset=utf-8"));
+        let body = String::from_utf8(decode(body)).unwrap();
+        assert!(body.starts_with("nightly: Experiment result... success: 0"));
+
+        let (headers, attachment) = parts[2].split_once("\n\n").unwrap();
+        assert!(headers.contains("Content-Disposition: attachment; filename=\"report.html\""));
+        assert_eq!(decode(attachment), b"<html></html>");
+    }
+
+    #[test]
+    fn base64_lines_are_at_most_76_characters() {
+        let data: Vec<u8> = (0..=255).cycle().take(1000).collect();
+
+        let wrapped = wrapped_base64(&data);
+
+        assert!(wrapped.ends_with('\n'));
+        assert!(wrapped.lines().all(|line| line.len() <= 76));
+        assert_eq!(wrapped.lines().next().unwrap().len(), 76);
+        assert_eq!(decode(&wrapped), data);
+    }
+
+    #[test]
+    fn non_ascii_headers_are_encoded() {
+        assert_eq!(header_value("plain subject"), "plain subject");
+
+        let encoded = header_value("finished running \"café\"");
+
+        let payload = encoded
+            .strip_prefix("=?utf-8?B?")
+            .and_then(|rest| rest.strip_suffix("?="))
+            .unwrap();
+        assert_eq!(decode(payload), "finished running \"café\"".as_bytes());
+    }
+
+    #[test]
+    fn credentials_are_quoted_for_curl() {
+        assert_eq!(curl_string("user:pass"), r#""user:pass""#);
+        assert_eq!(curl_string("user:p\"a\\ss"), r#""user:p\"a\\ss""#,);
+
+        let quoted = curl_string("user:line\nbreak\r\ttab");
+
+        assert_eq!(quoted, r#""user:line\nbreak\r\ttab""#);
+        assert!(!quoted.contains(['\n', '\r']));
+    }
+}
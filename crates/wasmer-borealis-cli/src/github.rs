@@ -0,0 +1,115 @@
+//! Integration with GitHub Actions.
+//!
+//! See <https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions>.
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Error};
+use wasmer_borealis::{alerts::SuccessRateDrop, experiment::Results};
+
+/// Are we running inside a GitHub Actions workflow?
+pub(crate) fn is_github_actions() -> bool {
+    std::env::var("GITHUB_ACTIONS").as_deref() == Ok("true")
+}
+
+/// Write a job summary, annotations, and step outputs for the experiments
+/// that were run.
+pub(crate) fn report(runs: &[(String, Results)], alerts: &[SuccessRateDrop]) -> Result<(), Error> {
+    if let Some(path) = env_path("GITHUB_STEP_SUMMARY") {
+        let summary = wasmer_borealis::render::markdown(
+            runs.iter().map(|(name, results)| (name.as_str(), results)),
+            alerts,
+        )?;
+        append(&path, &summary)?;
+    }
+
+    let stdout = std::io::stdout();
+    let mut stdout = stdout.lock();
+
+    for alert in alerts {
+        let message = format!(
+            "The success rate for \"{}\" dropped from {:.1}% to {:.1}%",
+            alert.experiment, alert.previous, alert.current
+        );
+        annotate(&mut stdout, "error", "Regression", &message)?;
+
+        for pkg in &alert.newly_failing {
+            let message = format!(
+                "{pkg} no longer passes the \"{}\" experiment",
+                alert.experiment
+            );
+            annotate(&mut stdout, "error", "Regression", &message)?;
+        }
+    }
+
+    for (name, results) in runs {
+        for report in results.bugs() {
+            let message = format!(
+                "Something went wrong before {}@{} could be run in the \"{name}\" experiment",
+                report.display_name,
+                report.version()
+            );
+            annotate(&mut stdout, "warning", "Bug", &message)?;
+        }
+    }
+
+    if let Some(path) = env_path("GITHUB_OUTPUT") {
+        let count = |f: fn(&Results) -> usize| runs.iter().map(|(_, r)| f(r)).sum::<usize>();
+        let outputs = [
+            ("total", count(|r| r.reports.len())),
+            ("success", count(|r| r.successes().count())),
+            ("failures", count(|r| r.failures().count())),
+            ("bugs", count(|r| r.bugs().count())),
+            ("regressions", alerts.len()),
+        ];
+
+        let outputs: String = outputs
+            .iter()
+            .map(|(name, value)| format!("{name}={value}\n"))
+            .collect();
+        append(&path, &outputs)?;
+    }
+
+    Ok(())
+}
+
+fn env_path(name: &str) -> Option<PathBuf> {
+    std::env::var_os(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+fn append(path: &Path, contents: &str) -> Result<(), Error> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(contents.as_bytes()))
+        .with_context(|| format!("Unable to write to \"{}\"", path.display()))
+}
+
+/// Emit a workflow command which creates an annotation (e.g. `::error
+/// title=Bug::something went wrong`).
+fn annotate(mut out: impl Write, level: &str, title: &str, message: &str) -> std::io::Result<()> {
+    writeln!(
+        out,
+        "::{level} title={}::{}",
+        escape_property(title),
+        escape_data(message)
+    )
+}
+
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
@@ -1,13 +1,109 @@
+mod badge;
+mod browse;
+mod cache;
+mod daemon;
+mod doctor;
+mod email;
+mod github;
+mod login;
 mod new;
+mod notify;
+mod pack;
+mod packages;
 mod report;
+mod reproduce;
 mod run;
+mod runs;
+mod serve;
+mod site;
+mod triage;
+mod wasmer;
 
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Error};
 use directories::ProjectDirs;
-use once_cell::sync::Lazy;
+use once_cell::sync::{Lazy, OnceCell};
+use wasmer_borealis::{experiment::Results, registry::format_graphql, user_config::UserConfig};
 
-pub use crate::{new::New, report::Report, run::Run};
+pub use crate::{
+    badge::Badge, browse::Browse, cache::Cache, daemon::Daemon, doctor::Doctor, login::Login,
+    new::New, pack::Pack, packages::Packages, report::Report, reproduce::Reproduce, run::Run,
+    runs::Runs, serve::Serve, site::Site, triage::Triage, wasmer::Wasmer,
+};
 
 pub static DIRS: Lazy<ProjectDirs> =
     Lazy::new(|| ProjectDirs::from("io", "wasmer", "borealis").unwrap());
 
 pub const USER_AGENT: &str = concat!(env!("CARGO_PKG_NAME"), "/", env!("CARGO_PKG_VERSION"));
+
+const DEFAULT_REGISTRY: &str = "wasmer.io";
+
+/// The user's config file, which is only loaded the first time it's needed.
+pub(crate) fn user_config() -> Result<&'static UserConfig, Error> {
+    static CONFIG: OnceCell<UserConfig> = OnceCell::new();
+    CONFIG.get_or_try_init(|| UserConfig::load(UserConfig::default_location()))
+}
+
+/// Use the `--registry` flag, falling back to the user's config file.
+pub(crate) fn registry(flag: Option<&str>) -> Result<String, Error> {
+    match flag {
+        Some(registry) => Ok(registry.to_string()),
+        None => Ok(user_config()?
+            .registry
+            .clone()
+            .unwrap_or_else(|| DEFAULT_REGISTRY.to_string())),
+    }
+}
+
+/// Use the `--token` flag, falling back to the user's config file and then
+/// whatever `wasmer-borealis login` saved to the keychain.
+pub(crate) fn token(flag: Option<&str>, registry: &str) -> Result<Option<String>, Error> {
+    // The token might come from a slow password manager, so only ask once
+    // per registry
+    static TOKENS: Lazy<Mutex<HashMap<String, Option<String>>>> = Lazy::new(Mutex::default);
+
+    if let Some(token) = flag {
+        return Ok(Some(token.to_string()));
+    }
+
+    let endpoint = format_graphql(registry);
+    let mut tokens = TOKENS.lock().unwrap();
+    if let Some(token) = tokens.get(&endpoint) {
+        return Ok(token.clone());
+    }
+
+    let token = match user_config()?.token()? {
+        Some(token) => Some(token),
+        None => wasmer_borealis::registry::load_token(&endpoint)?,
+    };
+    tokens.insert(endpoint, token.clone());
+
+    Ok(token)
+}
+
+/// Load a `results.json` file, or the results from a bundle created by
+/// `wasmer-borealis pack` (unpacking it next to the bundle first).
+pub(crate) fn load_results(path: &Path) -> Result<Results, Error> {
+    if wasmer_borealis::bundle::is_bundle(path) {
+        wasmer_borealis::bundle::open(path)
+            .with_context(|| format!("Unable to open the bundle at \"{}\"", path.display()))
+    } else {
+        Results::load(path)
+    }
+}
+
+/// Use the `--cache-dir` flag, falling back to the user's config file.
+pub(crate) fn cache_dir(flag: Option<PathBuf>) -> Result<PathBuf, Error> {
+    match flag {
+        Some(dir) => Ok(dir),
+        None => Ok(user_config()?
+            .cache_dir
+            .clone()
+            .unwrap_or_else(|| DIRS.cache_dir().to_path_buf())),
+    }
+}
@@ -0,0 +1,116 @@
+use std::{
+    io::{BufRead, IsTerminal},
+    process::{Command, Stdio},
+};
+
+use anyhow::{Context, Error};
+use wasmer_borealis::registry::{self, format_graphql, RetryPolicy};
+
+use crate::run::client;
+
+#[derive(Debug, clap::Parser)]
+pub struct Login {
+    /// The Wasmer registry to log in to.
+    ///
+    /// This may be a URL, a domain name (e.g. "wasmer.io"), or one of "prod",
+    /// "staging", or "local". Defaults to the user config file's "registry",
+    /// or "wasmer.io".
+    registry: Option<String>,
+    /// Remove the saved token instead.
+    #[clap(long)]
+    logout: bool,
+}
+
+impl Login {
+    pub fn execute(self) -> Result<(), Error> {
+        let Login { registry, logout } = self;
+        let endpoint = format_graphql(&crate::registry(registry.as_deref())?);
+
+        if logout {
+            registry::delete_token(&endpoint)?;
+            println!("Logged out of {endpoint}");
+            return Ok(());
+        }
+
+        // Read the token from stdin so it never ends up in shell history
+        let token = read_token(&endpoint).context("Unable to read the token")?;
+        let token = token.trim();
+        anyhow::ensure!(!token.is_empty(), "No token was provided");
+
+        let client = client(Some(token))?;
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let user = rt
+            .block_on(registry::whoami(
+                &client,
+                &endpoint,
+                &RetryPolicy::default(),
+            ))
+            .with_context(|| format!("Unable to check the token with {endpoint}"))?
+            .with_context(|| format!("{endpoint} rejected the token"))?;
+
+        registry::save_token(&endpoint, token)
+            .context("Unable to save the token to the keychain")?;
+        println!("Logged in to {endpoint} as {user}");
+
+        Ok(())
+    }
+}
+
+/// Read a token from stdin, hiding it as it is typed when stdin is a
+/// terminal.
+fn read_token(endpoint: &str) -> Result<String, Error> {
+    let stdin = std::io::stdin();
+    let mut token = String::new();
+
+    if !stdin.is_terminal() {
+        stdin.lock().read_line(&mut token)?;
+        return Ok(token);
+    }
+
+    eprint!("Token for {endpoint}: ");
+    {
+        let _echo = HiddenInput::new();
+        stdin.lock().read_line(&mut token)?;
+    }
+    // The user's newline wasn't echoed either
+    eprintln!();
+
+    Ok(token)
+}
+
+/// Turns off the terminal's echo until it is dropped.
+///
+/// This uses `stty` rather than poking at the terminal directly, the same way
+/// the keychain goes through each platform's command-line tools. Input is
+/// still echoed on platforms without `stty`.
+struct HiddenInput {
+    hidden: bool,
+}
+
+impl HiddenInput {
+    fn new() -> Self {
+        let hidden = cfg!(unix) && stty("-echo");
+        HiddenInput { hidden }
+    }
+}
+
+impl Drop for HiddenInput {
+    fn drop(&mut self) {
+        if self.hidden && !stty("echo") {
+            tracing::warn!("Unable to turn the terminal's echo back on, try running \"stty echo\"");
+        }
+    }
+}
+
+/// Change a terminal setting, returning whether it worked.
+fn stty(setting: &str) -> bool {
+    // stty works on the terminal connected to its stdin, so it needs ours
+    Command::new("stty")
+        .arg(setting)
+        .stdin(Stdio::inherit())
+        .stderr(Stdio::null())
+        .status()
+        .map_or(false, |status| status.success())
+}
@@ -1,9 +1,17 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{
+    io::{BufRead, IsTerminal, Write},
+    path::PathBuf,
+    str::FromStr,
+};
 
 use anyhow::{Context, Error};
 use clap::Parser;
 
-use wasmer_borealis::config::{Document, Experiment, Filters, TemplatedString, WasmerConfig};
+use indexmap::IndexMap;
+use wasmer_borealis::config::{
+    Capabilities, Document, Expectations, Experiment, Filters, Hooks, TemplatedString,
+    WasmerConfig, WasmerVersion,
+};
 
 #[derive(Parser, Debug)]
 pub struct New {
@@ -14,7 +22,10 @@ pub struct New {
     #[clap(short, long)]
     env: Vec<EnvironmentVariable>,
     /// The package to test.
-    package: String,
+    ///
+    /// If this is left out, you will be walked through creating the
+    /// experiment interactively.
+    package: Option<String>,
     #[clap(last = true)]
     args: Vec<TemplatedString>,
 }
@@ -28,18 +39,18 @@ impl New {
             args,
         } = self;
 
-        let experiment = Experiment {
-            package,
-            args,
-            command: None,
-            env: env
-                .into_iter()
-                .map(|EnvironmentVariable { name, value }| (name, value))
-                .collect(),
-            wasmer: WasmerConfig::default(),
-            filters: Filters::default(),
+        let Some(package) = package else {
+            anyhow::ensure!(
+                std::io::stdin().is_terminal(),
+                "A package is required when not running interactively"
+            );
+            let stdin = std::io::stdin();
+            let stdout = std::io::stdout();
+            return wizard(stdin.lock(), stdout.lock(), output);
         };
 
+        let experiment = experiment(package, args, env);
+
         let doc = Document::new(experiment);
         let yaml = serde_json::to_string_pretty(&doc).context("Serialization failed")?;
 
@@ -57,6 +68,226 @@ impl New {
     }
 }
 
+fn experiment(
+    package: String,
+    args: Vec<TemplatedString>,
+    env: Vec<EnvironmentVariable>,
+) -> Experiment {
+    Experiment {
+        name: None,
+        description: None,
+        metadata: IndexMap::new(),
+        package,
+        args,
+        command: None,
+        env: env
+            .into_iter()
+            .map(|EnvironmentVariable { name, value }| (name, value))
+            .collect(),
+        wasmer: WasmerConfig::default(),
+        filters: Filters::default(),
+        local: None,
+        test_cases: Vec::new(),
+        timeout: None,
+        output_limit: None,
+        expectations: Expectations::default(),
+        capabilities: Capabilities::default(),
+        overrides: IndexMap::new(),
+        hooks: Hooks::default(),
+        analyzers: Vec::new(),
+        benchmark: None,
+        repetitions: None,
+        check_determinism: false,
+        redact: Vec::new(),
+    }
+}
+
+/// Walk the user through creating an experiment, one question at a time.
+fn wizard(
+    mut input: impl BufRead,
+    mut out: impl Write,
+    output: Option<PathBuf>,
+) -> Result<(), Error> {
+    writeln!(
+        out,
+        "Let's create a new experiment. Press enter to accept the default in [brackets]."
+    )?;
+    writeln!(out)?;
+
+    let package = loop {
+        let package = ask(
+            &mut input,
+            &mut out,
+            "Which package should be run (e.g. wasmer/python)?",
+            None,
+        )?;
+        if !package.is_empty() {
+            break package;
+        }
+    };
+
+    let namespaces = loop {
+        match ask(
+            &mut input,
+            &mut out,
+            "Test packages from [a]ll namespaces or only [s]ome?",
+            Some("a"),
+        )?
+        .as_str()
+        {
+            "a" => break Vec::new(),
+            "s" => {
+                let namespaces = ask(
+                    &mut input,
+                    &mut out,
+                    "Which namespaces (comma-separated)?",
+                    None,
+                )?;
+                let namespaces: Vec<String> = namespaces
+                    .split(',')
+                    .map(|ns| ns.trim().to_string())
+                    .filter(|ns| !ns.is_empty())
+                    .collect();
+                if !namespaces.is_empty() {
+                    break namespaces;
+                }
+            }
+            _ => continue,
+        }
+    };
+
+    let default_registry = crate::registry(None)?;
+    let registry = ask(
+        &mut input,
+        &mut out,
+        "Which registry should packages come from?",
+        Some(&default_registry),
+    )?;
+
+    let args = ask(
+        &mut input,
+        &mut out,
+        "Arguments to pass to the package (space-separated)?",
+        Some(""),
+    )?;
+    let args = args.split_whitespace().map(TemplatedString::new).collect();
+
+    writeln!(
+        out,
+        "Environment variables for the package, as KEY=value (leave blank to finish):"
+    )?;
+    let mut env = Vec::new();
+    loop {
+        let var = ask(&mut input, &mut out, "  Variable?", Some(""))?;
+        if var.is_empty() {
+            break;
+        }
+        match var.parse() {
+            Ok(var) => env.push(var),
+            Err(e) => writeln!(out, "  {e}")?,
+        }
+    }
+
+    let include_every_version = loop {
+        match ask(
+            &mut input,
+            &mut out,
+            "Test every version of each package, not just the latest (y/n)?",
+            Some("n"),
+        )?
+        .as_str()
+        {
+            "y" => break true,
+            "n" => break false,
+            _ => continue,
+        }
+    };
+
+    let version = ask(
+        &mut input,
+        &mut out,
+        "Which wasmer version (a version, \"latest\", \"nightly\", or a path)?",
+        Some("latest"),
+    )?;
+    let version: WasmerVersion = version.parse()?;
+
+    let description = ask(
+        &mut input,
+        &mut out,
+        "Describe what this experiment is testing (optional)?",
+        Some(""),
+    )?;
+
+    let default_output = format!(
+        "{}.experiment.json",
+        package.rsplit('/').next().unwrap_or(&package)
+    );
+    let output = match output {
+        Some(output) => output,
+        None => PathBuf::from(ask(
+            &mut input,
+            &mut out,
+            "Where should the experiment be saved?",
+            Some(&default_output),
+        )?),
+    };
+
+    let mut experiment = experiment(package, args, env);
+    experiment.filters.namespaces = namespaces;
+    experiment.filters.include_every_version = include_every_version;
+    experiment.wasmer.version = version;
+    experiment.description = Some(description).filter(|d| !d.is_empty());
+
+    let doc = Document::new(experiment);
+    let json = serde_json::to_string_pretty(&doc).context("Serialization failed")?;
+    std::fs::write(&output, json)
+        .with_context(|| format!("Unable to save to \"{}\"", output.display()))?;
+
+    writeln!(out)?;
+    writeln!(
+        out,
+        "Saved the experiment to {}. Run it with:",
+        output.display()
+    )?;
+    writeln!(out)?;
+    if registry == default_registry {
+        writeln!(out, "  wasmer-borealis run {}", output.display())?;
+    } else {
+        // The registry isn't part of the experiment, so it needs to be
+        // passed in each time
+        writeln!(
+            out,
+            "  wasmer-borealis run {} --registry {registry}",
+            output.display()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Ask a question, falling back to the default (if there is one) when the
+/// user just presses enter.
+fn ask(
+    mut input: impl BufRead,
+    mut out: impl Write,
+    question: &str,
+    default: Option<&str>,
+) -> Result<String, Error> {
+    match default {
+        Some(default) if !default.is_empty() => write!(out, "{question} [{default}] ")?,
+        _ => write!(out, "{question} ")?,
+    }
+    out.flush()?;
+
+    let mut line = String::new();
+    anyhow::ensure!(input.read_line(&mut line)? > 0, "Cancelled");
+
+    match line.trim() {
+        "" => Ok(default.unwrap_or_default().to_string()),
+        answer => Ok(answer.to_string()),
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 struct EnvironmentVariable {
     name: String,
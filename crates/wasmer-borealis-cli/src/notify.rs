@@ -0,0 +1,69 @@
+//! Desktop notifications, sent using whichever tool the platform provides.
+
+use std::process::Command;
+
+use anyhow::{Context, Error};
+use wasmer_borealis::experiment::Results;
+
+/// Let the user know that a run has finished.
+pub(crate) fn finished(runs: &[(String, Results)]) -> Result<(), Error> {
+    let count = |f: fn(&Results) -> usize| runs.iter().map(|(_, r)| f(r)).sum::<usize>();
+    let success = count(|r| r.successes().count());
+    let failures = count(|r| r.failures().count());
+    let bugs = count(|r| r.bugs().count());
+
+    let title = match runs {
+        [(name, _)] => format!("Finished running \"{name}\""),
+        _ => format!("Finished running {} experiments", runs.len()),
+    };
+    let body = format!("{success} succeeded, {failures} failed, {bugs} bugs");
+
+    send(&title, &body)
+}
+
+/// Let the user know that some newly published packages failed.
+pub(crate) fn new_failures(packages: &[String]) -> Result<(), Error> {
+    let title = format!("{} newly published package(s) failed", packages.len());
+    let mut body = packages
+        .iter()
+        .take(5)
+        .cloned()
+        .collect::<Vec<_>>()
+        .join(", ");
+    if packages.len() > 5 {
+        body.push_str(&format!(", and {} more", packages.len() - 5));
+    }
+
+    send(&title, &body)
+}
+
+fn send(title: &str, body: &str) -> Result<(), Error> {
+    let mut cmd = if cfg!(target_os = "macos") {
+        let script = format!(
+            "display notification {} with title {}",
+            applescript_string(body),
+            applescript_string(title)
+        );
+        let mut cmd = Command::new("osascript");
+        cmd.arg("-e").arg(script);
+        cmd
+    } else if cfg!(unix) {
+        let mut cmd = Command::new("notify-send");
+        cmd.arg("--app-name=wasmer-borealis").arg(title).arg(body);
+        cmd
+    } else {
+        anyhow::bail!("Desktop notifications aren't supported on this platform");
+    };
+
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let status = cmd
+        .status()
+        .with_context(|| format!("Unable to run \"{program}\", is it installed?"))?;
+    anyhow::ensure!(status.success(), "\"{program}\" failed ({status})");
+
+    Ok(())
+}
+
+fn applescript_string(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
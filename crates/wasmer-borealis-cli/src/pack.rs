@@ -0,0 +1,45 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Error};
+
+#[derive(Debug, clap::Parser)]
+pub struct Pack {
+    /// Where to save the bundle (defaults to `<directory name>.tar.gz` in the
+    /// current directory).
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// The experiment directory, or the results.json file inside it.
+    dir: PathBuf,
+}
+
+impl Pack {
+    pub fn execute(self) -> Result<(), Error> {
+        let dir = if self.dir.is_file() {
+            self.dir
+                .parent()
+                .context("Unable to determine the experiment directory")?
+                .to_path_buf()
+        } else {
+            self.dir
+        };
+        let dir = dir
+            .canonicalize()
+            .with_context(|| format!("Unable to find \"{}\"", dir.display()))?;
+
+        let output = match self.output {
+            Some(output) => output,
+            None => {
+                let name = dir
+                    .file_name()
+                    .context("Unable to determine the bundle's name")?;
+                PathBuf::from(format!("{}.tar.gz", name.to_string_lossy()))
+            }
+        };
+
+        wasmer_borealis::bundle::pack(&dir, &output)
+            .with_context(|| format!("Unable to bundle \"{}\"", dir.display()))?;
+        println!("Saved the bundle to \"{}\"", output.display());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,205 @@
+use std::{
+    io::{BufWriter, Write},
+    path::PathBuf,
+};
+
+use anyhow::Error;
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use wasmer_borealis::{
+    config::Filters,
+    experiment::TestCase,
+    registry::{format_graphql, RetryPolicy},
+};
+
+#[derive(Parser, Debug)]
+pub struct Packages {
+    #[clap(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// List the package versions an experiment would be run against.
+    List(List),
+}
+
+#[derive(Parser, Debug)]
+struct List {
+    /// The Wasmer registry to query packages from (defaults to the user
+    /// config file's "registry", or "wasmer.io").
+    #[clap(long, env = "WASMER_REGISTRY")]
+    registry: Option<String>,
+    #[clap(long, short, env = "WASMER_TOKEN")]
+    token: Option<String>,
+    /// Only include packages under this namespace.
+    #[clap(long = "namespace")]
+    namespaces: Vec<String>,
+    /// Only include packages owned by this user.
+    #[clap(long = "user")]
+    users: Vec<String>,
+    /// Skip packages matching this pattern (e.g. "wasmer/*" or
+    /// "wasmer/python@2.*").
+    #[clap(long = "blacklist")]
+    blacklist: Vec<String>,
+    /// Include every version of each package instead of just the latest.
+    #[clap(long)]
+    include_every_version: bool,
+    /// Include (at most) this many of the most recent versions of each
+    /// package.
+    #[clap(long)]
+    max_versions_per_package: Option<usize>,
+    /// How to print the packages.
+    #[clap(long, value_enum, default_value = "table")]
+    format: Format,
+    /// Use the filters from this experiment, plus any given on the command
+    /// line.
+    experiment: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+impl Packages {
+    pub fn execute(self) -> Result<(), Error> {
+        let Packages { cmd } = self;
+
+        match cmd {
+            Cmd::List(list) => list.execute(),
+        }
+    }
+}
+
+impl List {
+    fn execute(self) -> Result<(), Error> {
+        let filters = self.filters()?;
+        let registry = crate::registry(self.registry.as_deref())?;
+        let endpoint = format_graphql(&registry).parse()?;
+        let token = crate::token(self.token.as_deref(), &registry)?;
+        let client = crate::run::client(token.as_deref())?;
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let mut test_cases = rt.block_on(wasmer_borealis::experiment::discover(
+            client,
+            endpoint,
+            filters,
+            RetryPolicy::default(),
+        ))?;
+        test_cases
+            .sort_by(|a, b| (a.display_name(), a.version()).cmp(&(b.display_name(), b.version())));
+
+        let rows: Vec<Row> = test_cases.iter().map(Row::from).collect();
+        let stdout = std::io::stdout();
+        let mut stdout = BufWriter::new(stdout.lock());
+
+        match self.format {
+            Format::Table => table(&rows, &mut stdout)?,
+            Format::Json => {
+                serde_json::to_writer_pretty(&mut stdout, &rows)?;
+                writeln!(stdout)?;
+            }
+            Format::Csv => csv(&rows, &mut stdout)?,
+        }
+
+        stdout.flush()?;
+
+        Ok(())
+    }
+
+    /// The experiment's filters (if there is one), extended with the ones
+    /// from the command line.
+    fn filters(&self) -> Result<Filters, Error> {
+        let mut filters = match &self.experiment {
+            Some(path) => crate::run::load_experiment(path)?.filters,
+            None => Filters::default(),
+        };
+
+        filters.namespaces.extend(self.namespaces.iter().cloned());
+        filters.users.extend(self.users.iter().cloned());
+        filters.blacklist.extend(self.blacklist.iter().cloned());
+        filters.include_every_version |= self.include_every_version;
+        if self.max_versions_per_package.is_some() {
+            filters.max_versions_per_package = self.max_versions_per_package;
+        }
+
+        Ok(filters)
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Row<'a> {
+    package: String,
+    version: &'a str,
+    published: Option<DateTime<Utc>>,
+    download_url: &'a str,
+}
+
+impl<'a> From<&'a TestCase> for Row<'a> {
+    fn from(test_case: &'a TestCase) -> Self {
+        Row {
+            package: test_case.display_name(),
+            version: test_case.version(),
+            published: test_case.package_version.created_at,
+            download_url: test_case.tarball_url(),
+        }
+    }
+}
+
+fn table(rows: &[Row<'_>], mut dest: impl Write) -> Result<(), Error> {
+    let width = rows
+        .iter()
+        .map(|r| r.package.len())
+        .max()
+        .unwrap_or_default()
+        .max("PACKAGE".len());
+
+    writeln!(dest, "{:<width$}  {:<16}  PUBLISHED", "PACKAGE", "VERSION")?;
+
+    for row in rows {
+        let published = row
+            .published
+            .map(|p| p.format("%Y-%m-%d").to_string())
+            .unwrap_or_else(|| "-".to_string());
+        writeln!(
+            dest,
+            "{:<width$}  {:<16}  {published}",
+            row.package, row.version
+        )?;
+    }
+
+    writeln!(dest, "{} package versions", rows.len())?;
+
+    Ok(())
+}
+
+fn csv(rows: &[Row<'_>], mut dest: impl Write) -> Result<(), Error> {
+    writeln!(dest, "package,version,published,download_url")?;
+
+    for row in rows {
+        let published = row.published.map(|p| p.to_rfc3339()).unwrap_or_default();
+        writeln!(
+            dest,
+            "{},{},{},{}",
+            csv_field(&row.package),
+            csv_field(row.version),
+            csv_field(&published),
+            csv_field(row.download_url),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
@@ -1,6 +1,7 @@
 use std::path::PathBuf;
 
 use anyhow::{Context, Error};
+use wasmer_borealis::experiment::Results;
 
 #[derive(Debug, clap::Parser)]
 pub struct Report {
@@ -10,21 +11,48 @@ pub struct Report {
     /// Open the report in the browser (implies --html)
     #[clap(long)]
     open: bool,
-    /// The results.json file generated during an experiment run
-    json: PathBuf,
+    /// Print a paginated summary to a PDF at this location.
+    ///
+    /// This needs a Chromium-based browser (Chromium, Chrome, or Edge). Set
+    /// $BOREALIS_BROWSER if it can't be found automatically.
+    #[clap(long)]
+    pdf: Option<PathBuf>,
+    /// Write a Markdown summary of the failing packages for each namespace
+    /// (e.g. `<dir>/wasmer.md`) to this directory, so their maintainers can
+    /// be told what broke.
+    #[clap(long)]
+    owners: Option<PathBuf>,
+    /// The results.json file generated during an experiment run, or a bundle
+    /// created by "wasmer-borealis pack".
+    ///
+    /// Passing multiple files (e.g. one per shard) will merge them into a
+    /// single report.
+    #[clap(required = true)]
+    json: Vec<PathBuf>,
 }
 
 impl Report {
     pub fn execute(self) -> Result<(), Error> {
-        let raw = std::fs::read_to_string(&self.json)?;
-        let results: wasmer_borealis::experiment::Results = serde_json::from_str(&raw)?;
+        let all_results = self
+            .json
+            .iter()
+            .map(|path| crate::load_results(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let results = Results::merge(all_results)?;
 
         wasmer_borealis::render::text(&results, std::io::stdout())?;
 
         if self.open || self.html.is_some() {
             let html = self
                 .html
-                .or_else(|| Some(self.json.parent()?.join("report.html")))
+                .or_else(|| {
+                    let first = self.json.first()?;
+                    if wasmer_borealis::bundle::is_bundle(first) {
+                        Some(wasmer_borealis::bundle::unpacked_dir(first).join("report.html"))
+                    } else {
+                        Some(first.parent()?.join("report.html"))
+                    }
+                })
                 .context("Unable to determine the html path")?;
 
             if let Some(parent) = html.parent() {
@@ -39,6 +67,32 @@ impl Report {
             }
         }
 
+        if let Some(pdf) = &self.pdf {
+            if let Some(parent) = pdf.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            wasmer_borealis::render::pdf(&results, pdf)
+                .with_context(|| format!("Unable to save the PDF to \"{}\"", pdf.display()))?;
+        }
+
+        if let Some(dir) = &self.owners {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Unable to create \"{}\"", dir.display()))?;
+
+            let summaries = wasmer_borealis::render::owner_summaries(&results)?;
+            for (namespace, summary) in &summaries {
+                let path = dir.join(format!("{namespace}.md"));
+                std::fs::write(&path, summary)
+                    .with_context(|| format!("Unable to save to \"{}\"", path.display()))?;
+            }
+            println!(
+                "Wrote summaries for {} owner(s) to \"{}\"",
+                summaries.len(),
+                dir.display()
+            );
+        }
+
         Ok(())
     }
 }
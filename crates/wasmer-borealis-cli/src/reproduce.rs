@@ -0,0 +1,124 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Error};
+use reqwest::Url;
+use wasmer_borealis::{
+    experiment::{Report, Reproduction, Results},
+    registry::format_graphql,
+    toolchain::Toolchains,
+};
+
+use crate::run::client;
+
+#[derive(Debug, clap::Parser)]
+pub struct Reproduce {
+    /// The Wasmer registry the package came from.
+    ///
+    /// This may be a URL, a domain name (e.g. "wasmer.io"), or one of "prod",
+    /// "staging", or "local". Defaults to the user config file's "registry",
+    /// or "wasmer.io".
+    #[clap(long, env = "WASMER_REGISTRY")]
+    registry: Option<String>,
+    #[clap(long, short, env = "WASMER_TOKEN")]
+    token: Option<String>,
+    /// The directory downloaded packages are cached in.
+    #[clap(long, env = "BOREALIS_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    /// Install wasmer releases even if they don't publish a SHA-256 sum to
+    /// verify the download against.
+    #[clap(long)]
+    allow_unverified: bool,
+    /// Print the command instead of running it.
+    #[clap(long)]
+    print: bool,
+    /// The results.json file generated during an experiment run.
+    json: PathBuf,
+    /// The test case to reproduce (e.g. "wasmer/python" or
+    /// "wasmer/python@3.12.0").
+    package: String,
+}
+
+impl Reproduce {
+    pub fn execute(self) -> Result<(), Error> {
+        let results = Results::load(&self.json)?;
+        let report = find_report(&results, &self.package)?;
+
+        let cache_dir = crate::cache_dir(self.cache_dir.clone())?;
+        let registry = crate::registry(self.registry.as_deref())?;
+        let endpoint = Url::parse(&format_graphql(&registry))?;
+        let hostname = endpoint.host_str().unwrap_or("unknown");
+        let token = crate::token(self.token.as_deref(), &registry)?;
+        // The token is only attached to downloads from the registry
+        let client = client(None)?;
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+
+        let reproduction = rt.block_on(async {
+            let toolchains =
+                Toolchains::in_cache_dir(&cache_dir)?.with_allow_unverified(self.allow_unverified);
+            let wasmer = toolchains
+                .resolve(&results.experiment.wasmer.version)
+                .await
+                .context("Unable to resolve the wasmer CLI")?;
+
+            Reproduction::prepare(
+                &client,
+                &cache_dir,
+                &wasmer,
+                &results,
+                report,
+                hostname,
+                token.as_deref(),
+            )
+            .await
+        })?;
+
+        let command_line = reproduction.command_line();
+
+        if self.print {
+            println!("{}", command_line.shell());
+            return Ok(());
+        }
+
+        eprintln!("Running: {}", command_line.shell());
+        let status = reproduction
+            .command()
+            .status()
+            .with_context(|| format!("Unable to run \"{}\"", command_line.program.display()))?;
+        eprintln!("Exited with {status}");
+
+        Ok(())
+    }
+}
+
+fn find_report<'a>(results: &'a Results, package: &str) -> Result<&'a Report, Error> {
+    let (name, version) = match package.split_once('@') {
+        Some((name, version)) => (name, Some(version)),
+        None => (package, None),
+    };
+
+    let matches: Vec<&Report> = results
+        .reports
+        .iter()
+        .filter(|r| r.display_name == name)
+        .filter(|r| version.map_or(true, |v| r.version() == v))
+        .collect();
+
+    match matches.as_slice() {
+        [report] => Ok(report),
+        [] => anyhow::bail!(
+            "\"{package}\" isn't in \"{}\"",
+            results.experiment_dir.display()
+        ),
+        reports => {
+            let versions: Vec<&str> = reports.iter().map(|r| r.version()).collect();
+            anyhow::bail!(
+                "Multiple versions of \"{name}\" were tested ({}), please specify one (e.g. \"{name}@{}\")",
+                versions.join(", "),
+                versions[0],
+            )
+        }
+    }
+}
@@ -1,102 +1,890 @@
-use std::path::PathBuf;
+use std::{
+    io::{BufReader, ErrorKind, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
 use anyhow::{Context, Error};
+use chrono::{DateTime, Utc};
 use clap::Parser;
+use once_cell::sync::OnceCell;
 use reqwest::{header::HeaderMap, Client, ClientBuilder, Url};
-use wasmer_borealis::{config::Document, experiment::ExperimentBuilder};
+use wasmer_borealis::{
+    alerts::SuccessRateDrop,
+    config::{self, Document, Experiment, WasmerVersion, IGNORE_FILE},
+    experiment::{
+        diverging_outputs, Bucket, Credentials, ExperimentBuilder, Heartbeat, LockFile,
+        PackageSpec, RemoteCache, Results, RunTimes, Shard,
+    },
+    history::{History, RunRecord},
+    registry::{format_graphql, RetryPolicy},
+    runs::{IndexedRun, RunIndex},
+    toolchain::Toolchains,
+};
 
 #[derive(Parser, Debug)]
 pub struct Run {
     /// The Wasmer registry to query packages from.
-    #[clap(long, default_value = "wasmer.io", env = "WASMER_REGISTRY")]
-    registry: String,
+    ///
+    /// This may be a URL, a domain name (e.g. "wasmer.io"), or one of "prod",
+    /// "staging", or "local". Defaults to the user config file's "registry",
+    /// or "wasmer.io".
+    #[clap(long, env = "WASMER_REGISTRY")]
+    registry: Option<String>,
     #[clap(long, short, env = "WASMER_TOKEN")]
     token: Option<String>,
     /// A directory all experiment-related files will be written to.
+    ///
+    /// When running multiple experiments, each experiment's files will be
+    /// written to a sub-directory named after its experiment file.
     #[clap(short, long)]
-    output: Option<PathBuf>,
-    /// The experiment to run.
-    experiment: PathBuf,
+    pub(crate) output: Option<PathBuf>,
+    /// A name for this run (e.g. "nightly-2024-06-01"), used to find it again
+    /// with the `runs` command. Defaults to the time the run started.
+    #[clap(long)]
+    name: Option<String>,
+    /// Run multiple experiments concurrently instead of one after another.
+    #[clap(long)]
+    concurrent: bool,
+    /// The directory downloaded packages will be cached in.
+    #[clap(long, env = "BOREALIS_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    /// The maximum number of test cases to run at the same time (defaults to
+    /// the number of CPUs).
+    #[clap(long, short = 'j')]
+    parallelism: Option<NonZeroUsize>,
+    /// Only run the experiment against a random sample of this many test
+    /// cases.
+    #[clap(long)]
+    max_packages: Option<usize>,
+    /// The seed to use when sampling test cases (random by default).
+    #[clap(long, requires = "max_packages")]
+    seed: Option<u64>,
+    /// Only run the test cases belonging to one shard of the experiment
+    /// (e.g. "2/4").
+    #[clap(long)]
+    shard: Option<Shard>,
+    /// Record a summary of each run in this history store.
+    #[clap(long, env = "BOREALIS_HISTORY")]
+    pub(crate) history: Option<PathBuf>,
+    /// Alert when an experiment's success rate drops by more than this many
+    /// percentage points since its previous run (requires --history).
+    #[clap(long, default_value_t = 1.0)]
+    alert_threshold: f64,
+    /// Exit with an error if the success rate dropped for any experiment, or
+    /// a benchmark got significantly slower than the --baseline.
+    #[clap(long)]
+    fail_on_alert: bool,
+    /// A results.json file from an earlier benchmark (e.g. using a different
+    /// wasmer version) to check for performance regressions against.
+    #[clap(long)]
+    baseline: Option<PathBuf>,
+    /// How many times a registry request will be retried when the registry
+    /// is overloaded or temporarily unavailable.
+    #[clap(long, default_value_t = RetryPolicy::default().max_retries)]
+    max_retries: u32,
+    /// Use this wasmer CLI instead of the one from the experiment file (a
+    /// version, version range, "nightly", "latest", or the path to a binary).
+    #[clap(long)]
+    wasmer: Option<WasmerVersion>,
+    /// Set $RUST_LOG for the wasmer CLI (e.g. "wasmer_wasix=debug") and save
+    /// its logs to a wasmer.log in each test case's directory.
+    #[clap(long)]
+    wasmer_log: Option<String>,
+    /// Publish progress events and each test case's report as JSON lines on
+    /// a Unix socket at this path while each experiment is running.
+    ///
+    /// Not supported on Windows.
+    #[clap(long, conflicts_with = "concurrent")]
+    event_socket: Option<PathBuf>,
+    /// Print a one-line summary of each experiment's progress (discovered,
+    /// downloaded, completed, and failed test cases, plus an ETA) every this
+    /// many seconds. Handy for keeping CI logs alive without debug logging.
+    #[clap(long, value_name = "SECONDS", conflicts_with = "concurrent")]
+    heartbeat: Option<u64>,
+    /// Stop starting new test cases when there are fewer than this many GiB
+    /// free in the cache or output directory (0 disables the check).
+    #[clap(long, default_value_t = 1.0)]
+    min_free_space: f64,
+    /// A mirror to download packages from when their original URL fails
+    /// (e.g. `https://mirror.example.com/wasmer/`). Mirrors are tried in the
+    /// order they are provided.
+    #[clap(long = "mirror", env = "BOREALIS_MIRRORS", value_delimiter = ',')]
+    mirrors: Vec<Url>,
+    /// Share downloaded packages with other machines via a bucket (e.g.
+    /// "s3://my-bucket/borealis" or "gs://my-bucket/borealis").
+    ///
+    /// Credentials are read from $AWS_ACCESS_KEY_ID, $AWS_SECRET_ACCESS_KEY,
+    /// and $AWS_SESSION_TOKEN.
+    #[clap(long, env = "BOREALIS_REMOTE_CACHE")]
+    remote_cache: Option<String>,
+    /// The region the remote cache's S3 bucket is in.
+    #[clap(long, env = "AWS_REGION", default_value = "us-east-1")]
+    remote_cache_region: String,
+    /// Show a desktop notification with the summary counts when the run
+    /// finishes.
+    #[clap(long)]
+    pub(crate) notify: bool,
+    /// Email a summary of the run (with the HTML report attached) to the
+    /// "email-to" addresses from the config file.
+    #[clap(long, env = "BOREALIS_EMAIL", value_enum)]
+    pub(crate) email: Option<crate::email::When>,
+    /// Open the HTML report in the browser once the run finishes (the
+    /// summary, when running several experiments).
+    #[clap(long)]
+    open: bool,
+    /// Replay exactly the test cases (and package versions) pinned by the
+    /// experiment.lock file from an earlier run, instead of asking the
+    /// registry.
+    #[clap(long)]
+    locked: Option<PathBuf>,
+    /// Run exactly the packages listed in this file ("-" for stdin) instead
+    /// of discovering them from the registry.
+    ///
+    /// Each line is a package in the form "namespace/name[@version]", where
+    /// the latest version is used if none is given.
+    #[clap(long, conflicts_with = "locked")]
+    package_list: Option<PathBuf>,
+    /// The packages from --package-list, which are only read once so stdin
+    /// can be shared between experiments.
+    #[clap(skip)]
+    packages: OnceCell<Vec<PackageSpec>>,
+    /// Start the test cases which took longest in the experiment's previous
+    /// run first (falling back to the largest packages), so the end of the
+    /// run isn't spent waiting on a single slow package.
+    #[clap(long)]
+    longest_first: bool,
+    /// Query the registry for test cases again instead of reusing the ones
+    /// discovered by a recent run with the same filters.
+    #[clap(long)]
+    refresh: bool,
+    /// Echo each test case's stdout and stderr to the terminal (prefixed with
+    /// "namespace/name@version") as well as saving them to its logs.
+    #[clap(long)]
+    tee_logs: bool,
+    /// Pin test cases to these CPU cores (e.g. "2,3"), keeping downloads and
+    /// other work on the remaining cores. Linux only.
+    #[clap(long, value_delimiter = ',')]
+    test_cores: Vec<usize>,
+    /// Run borealis itself at this niceness (e.g. 10) so test cases get
+    /// priority. Linux only.
+    #[clap(long, allow_hyphen_values = true)]
+    niceness: Option<i32>,
+    /// Install wasmer releases even if they don't publish a SHA-256 sum to
+    /// verify the download against.
+    #[clap(long)]
+    allow_unverified: bool,
+    /// The experiments to run.
+    #[clap(required = true)]
+    experiments: Vec<PathBuf>,
 }
 
 impl Run {
     #[tracing::instrument(level = "debug", skip_all)]
     pub fn execute(self) -> Result<(), Error> {
-        let experiment = std::fs::read_to_string(&self.experiment)
-            .with_context(|| format!("Unable to read \"{}\"", self.experiment.display()))?;
-        let Document { experiment, .. } = serde_json::from_str(&experiment)
-            .context("Unable to deserialize the experiment file")?;
+        let history = self.history.as_ref().map(History::new);
+        let baseline = self.baseline.as_ref().map(Results::load).transpose()?;
+        let Runs { results, alerts } = self.run_all(self.output.clone(), history.as_ref())?;
 
-        let url = format_graphql(&self.registry);
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        let mut report = None;
 
-        let client = self.client()?;
-        let mut builder = ExperimentBuilder::new(experiment)
-            .with_endpoint(url)?
-            .with_client(client);
+        if let [(_, results)] = results.as_slice() {
+            wasmer_borealis::render::text(results, &mut stdout)?;
+            writeln!(
+                stdout,
+                "Experiment dir: {}",
+                results.experiment_dir.display()
+            )?;
+            report = Some(results.experiment_dir.join("report.html"));
+        } else {
+            for (name, results) in &results {
+                write!(stdout, "{name}: ")?;
+                wasmer_borealis::render::text(results, &mut stdout)?;
+            }
 
-        if let Some(output) = self.output {
-            builder = builder.with_experiment_dir(output);
+            if let Some(output) = results.first().and_then(|(_, r)| r.experiment_dir.parent()) {
+                let summary = output.join("summary.html");
+                writeln!(stdout, "Summary: {}", summary.display())?;
+                report = Some(summary);
+            }
         }
 
-        let results = builder.run()?;
+        let mut slowdowns = 0;
 
-        let stdout = std::io::stdout();
-        wasmer_borealis::render::text(&results, &mut stdout.lock())?;
-        println!("Experiment dir: {}", results.experiment_dir.display());
+        if let Some(baseline) = &baseline {
+            for (name, results) in &results {
+                for regression in results.performance_regressions(baseline) {
+                    writeln!(
+                        stdout,
+                        "{name}: {}@{} is {:.1}% slower than the baseline ({:.3?} -> {:.3?})",
+                        regression.display_name,
+                        regression.version,
+                        regression.slowdown() * 100.0,
+                        regression.before.mean,
+                        regression.after.mean,
+                    )?;
+                    slowdowns += 1;
+                }
+            }
+        }
+
+        if self.notify {
+            if let Err(e) = crate::notify::finished(&results) {
+                tracing::warn!(error = &*e, "Unable to send a desktop notification");
+            }
+        }
+
+        if let Some(when) = self.email {
+            let summary = crate::email::Summary {
+                runs: &results,
+                alerts: &alerts,
+                slowdowns,
+                report: report.as_deref(),
+            };
+            if when == crate::email::When::Always || summary.has_regressions() {
+                if let Err(e) = crate::email::send(crate::user_config()?, &summary) {
+                    tracing::warn!(error = &*e, "Unable to email the run summary");
+                }
+            }
+        }
+
+        if self.open {
+            if let Some(report) = report {
+                open::that_detached(report)?;
+            }
+        }
+
+        if crate::github::is_github_actions() {
+            crate::github::report(&results, &alerts)?;
+        }
+
+        if self.fail_on_alert && !alerts.is_empty() {
+            anyhow::bail!(
+                "The success rate dropped for {} experiment(s)",
+                alerts.len()
+            );
+        }
+        if self.fail_on_alert && slowdowns > 0 {
+            anyhow::bail!("{slowdowns} benchmark(s) got significantly slower");
+        }
 
         Ok(())
     }
 
-    fn client(&self) -> Result<Client, Error> {
-        let builder = ClientBuilder::new();
-        let mut headers = HeaderMap::new();
+    /// Run every experiment, saving the results to `output` and adding them
+    /// to the `history` store (if provided).
+    pub(crate) fn run_all(
+        &self,
+        output: Option<PathBuf>,
+        history: Option<&History>,
+    ) -> Result<Runs, Error> {
+        let Plan {
+            experiments,
+            sweeps,
+        } = self.plan()?;
+        let started = Utc::now();
+
+        let index = RunIndex::new(RunIndex::default_location());
+        let run_name = match &self.name {
+            Some(name) => {
+                anyhow::ensure!(
+                    !name.is_empty()
+                        && !name.starts_with('.')
+                        && !name.contains(['/', '\\'])
+                        && name != "latest",
+                    "\"{name}\" isn't a valid run name",
+                );
+                name.clone()
+            }
+            None => started.format("%Y-%m-%dT%H-%M-%S").to_string(),
+        };
+        let output = match output {
+            Some(output) => output,
+            None => {
+                let dir = index.run_dir(&run_name);
+                anyhow::ensure!(!dir.exists(), "A run called \"{run_name}\" already exists",);
+                dir
+            }
+        };
+
+        let all_results = if let [(name, experiment)] = experiments.as_slice() {
+            vec![self.run_experiment(name, experiment.clone(), Some(output))?]
+        } else {
+            let all_results = self.run_many(&experiments, &output)?;
+
+            let summary = wasmer_borealis::render::summary_html(
+                experiments
+                    .iter()
+                    .map(|(name, _)| name.as_str())
+                    .zip(&all_results),
+            )?;
+            save(&output.join("summary.html"), summary)?;
+
+            for sweep in &sweeps {
+                let columns: Vec<_> = experiments
+                    .iter()
+                    .zip(&all_results)
+                    .filter(|((name, _), _)| sweep.runs.contains(name))
+                    .map(|((name, _), results)| (name.as_str(), results))
+                    .collect();
+                let diverged = diverging_outputs(columns.iter().map(|(_, r)| *r));
+
+                for &(package, version) in &diverged {
+                    tracing::warn!(
+                        sweep = sweep.name.as_str(),
+                        package,
+                        version,
+                        "Output differs between wasmer versions",
+                    );
+                }
+
+                let matrix =
+                    wasmer_borealis::render::matrix_html(columns.iter().copied(), &diverged)?;
+                save(&output.join(format!("{}.matrix.html", sweep.name)), matrix)?;
+            }
+
+            all_results
+        };
+
+        let results: Vec<_> = experiments
+            .into_iter()
+            .map(|(name, _)| name)
+            .zip(all_results)
+            .collect();
+        let mut alerts = Vec::new();
+
+        for (name, results) in &results {
+            let run = IndexedRun {
+                name: run_name.clone(),
+                record: RunRecord::new(name, started, results),
+            };
+            if let Err(e) = index.record(&run) {
+                tracing::warn!(error = &*e, "Unable to add the run to the index");
+            }
+        }
+
+        if let Some(keep) = crate::user_config()?.keep_runs {
+            match index.prune(keep) {
+                Ok(removed) if !removed.is_empty() => {
+                    tracing::info!(?removed, keep, "Removed old runs");
+                }
+                Ok(_) => {}
+                Err(e) => tracing::warn!(error = &*e, "Unable to remove old runs"),
+            }
+        }
+
+        if let Some(history) = history {
+            for (name, results) in &results {
+                if let Some(previous) = history.runs_of(name)?.pop() {
+                    if let Some(alert) =
+                        SuccessRateDrop::check(&previous, results, self.alert_threshold)
+                    {
+                        tracing::warn!(
+                            experiment = name.as_str(),
+                            previous = alert.previous,
+                            current = alert.current,
+                            newly_failing = alert.newly_failing.len(),
+                            "Success rate dropped",
+                        );
+                        eprintln!("{alert}");
+                        alerts.push(alert);
+                    }
+                }
+
+                history.record(&RunRecord::new(name, started, results))?;
+
+                // Now the history is up to date, the report can show how
+                // this run compares to the previous ones
+                let report =
+                    wasmer_borealis::render::html_with_history(results, &history.runs_of(name)?)?;
+                save(&results.experiment_dir.join("report.html"), report)?;
+            }
+        }
+
+        Ok(Runs { results, alerts })
+    }
+
+    /// Load each experiment, expanding any that sweep over a range of
+    /// `wasmer` releases into one experiment per release.
+    fn plan(&self) -> Result<Plan, Error> {
+        self.plan_with(|version| self.expand_versions(version))
+    }
+
+    /// Like [`Run::plan()`], using `expand` to find the releases a version
+    /// range matches.
+    fn plan_with(
+        &self,
+        mut expand: impl FnMut(&WasmerVersion) -> Result<Vec<WasmerVersion>, Error>,
+    ) -> Result<Plan, Error> {
+        let names = experiment_names(&self.experiments)?;
+        let mut plan = Plan::default();
 
-        headers.insert(
-            reqwest::header::USER_AGENT,
-            crate::USER_AGENT.parse().unwrap(),
+        for (name, path) in names.into_iter().zip(&self.experiments) {
+            let mut experiment = load_experiment(path)?;
+            if let Some(version) = &self.wasmer {
+                experiment.wasmer.version = version.clone();
+            }
+            if let Some(filter) = &self.wasmer_log {
+                experiment.wasmer.log = Some(filter.clone());
+            }
+
+            if !matches!(experiment.wasmer.version, WasmerVersion::Range(_)) {
+                plan.experiments.push((name, experiment));
+                continue;
+            }
+
+            let versions = expand(&experiment.wasmer.version)?;
+            let mut sweep = Sweep {
+                name: name.clone(),
+                runs: Vec::new(),
+            };
+
+            for version in versions {
+                let WasmerVersion::Release(v) = &version else {
+                    unreachable!("Ranges always expand to releases");
+                };
+                let run_name = format!("{name}@{v}");
+
+                let unsupported = experiment.wasmer.features.unsupported_by(v);
+                if !unsupported.is_empty() {
+                    let unsupported = unsupported.join(", ");
+                    tracing::warn!(
+                        experiment = run_name.as_str(),
+                        features = unsupported.as_str(),
+                        "Skipping a wasmer version which doesn't support the experiment's features",
+                    );
+                    eprintln!("Skipping {run_name}: wasmer {v} doesn't support {unsupported}");
+                    continue;
+                }
+
+                let mut experiment = experiment.clone();
+                experiment.wasmer.version = version;
+
+                sweep.runs.push(run_name.clone());
+                plan.experiments.push((run_name, experiment));
+            }
+
+            plan.sweeps.push(sweep);
+        }
+
+        Ok(plan)
+    }
+
+    fn expand_versions(&self, version: &WasmerVersion) -> Result<Vec<WasmerVersion>, Error> {
+        let cache_dir = crate::cache_dir(self.cache_dir.clone())?;
+        let toolchains = Toolchains::in_cache_dir(&cache_dir)?;
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let versions = rt.block_on(toolchains.expand(version))?;
+
+        tracing::info!(
+            count = versions.len(),
+            "Sweeping over multiple wasmer releases"
         );
 
-        if let Some(token) = self.token.as_deref() {
-            let auth_header = format!("bearer {token}").parse()?;
-            headers.append(reqwest::header::AUTHORIZATION, auth_header);
+        Ok(versions)
+    }
+
+    /// Find the package versions published at or after `since` which the
+    /// experiment would be run against.
+    pub(crate) fn discover_published_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<PackageSpec>, Error> {
+        let [path] = self.experiments.as_slice() else {
+            anyhow::bail!("Only one experiment can be watched at a time");
+        };
+        let mut filters = load_experiment(path)?.filters;
+        filters.published_after = Some(
+            filters
+                .published_after
+                .map_or(since, |after| after.max(since)),
+        );
+
+        let registry = crate::registry(self.registry.as_deref())?;
+        let endpoint = format_graphql(&registry).parse()?;
+        let token = crate::token(self.token.as_deref(), &registry)?;
+        let retry = RetryPolicy {
+            max_retries: self.max_retries,
+            ..Default::default()
+        };
+
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        let test_cases = rt.block_on(wasmer_borealis::experiment::discover(
+            client(token.as_deref())?,
+            endpoint,
+            filters,
+            retry,
+        ))?;
+
+        Ok(test_cases
+            .iter()
+            .map(|test_case| PackageSpec {
+                namespace: test_case.namespace.clone(),
+                name: test_case.package_name.clone(),
+                version: Some(test_case.version().to_string()),
+            })
+            .collect())
+    }
+
+    /// Run exactly these packages, as if they had been passed in with
+    /// --package-list.
+    pub(crate) fn set_packages(&mut self, packages: Vec<PackageSpec>) {
+        self.packages = OnceCell::with_value(packages);
+    }
+
+    /// Read the --package-list, if one was provided.
+    fn packages(&self) -> Result<Option<&[PackageSpec]>, Error> {
+        let Some(path) = &self.package_list else {
+            return Ok(self.packages.get().map(Vec::as_slice));
+        };
+
+        let packages = self.packages.get_or_try_init(|| {
+            if path.as_os_str() == "-" {
+                PackageSpec::read_list(std::io::stdin().lock())
+            } else {
+                let f = std::fs::File::open(path)
+                    .with_context(|| format!("Unable to open \"{}\"", path.display()))?;
+                PackageSpec::read_list(BufReader::new(f))
+                    .with_context(|| format!("Unable to read \"{}\"", path.display()))
+            }
+        })?;
+
+        Ok(Some(packages))
+    }
+
+    fn run_many(
+        &self,
+        experiments: &[(String, Experiment)],
+        output: &Path,
+    ) -> Result<Vec<Results>, Error> {
+        if !self.concurrent {
+            return experiments
+                .iter()
+                .map(|(name, experiment)| {
+                    self.run_experiment(name, experiment.clone(), Some(output.join(name)))
+                })
+                .collect();
         }
 
-        let client = builder.default_headers(headers).build()?;
+        std::thread::scope(|s| {
+            let handles: Vec<_> = experiments
+                .iter()
+                .map(|(name, experiment)| {
+                    let dir = output.join(name);
+                    s.spawn(move || self.run_experiment(name, experiment.clone(), Some(dir)))
+                })
+                .collect();
 
-        Ok(client)
+            handles
+                .into_iter()
+                .map(|h| h.join().expect("The experiment thread panicked"))
+                .collect()
+        })
     }
+
+    fn run_experiment(
+        &self,
+        name: &str,
+        experiment: Experiment,
+        experiment_dir: Option<PathBuf>,
+    ) -> Result<Results, Error> {
+        let registry = crate::registry(self.registry.as_deref())?;
+        let url = format_graphql(&registry);
+        let token = crate::token(self.token.as_deref(), &registry)?;
+        let cache_dir = crate::cache_dir(self.cache_dir.clone())?;
+
+        let mut builder = ExperimentBuilder::new(experiment)
+            .with_endpoint(url)?
+            .with_retry_policy(RetryPolicy {
+                max_retries: self.max_retries,
+                ..Default::default()
+            })
+            .with_min_free_space((self.min_free_space * 1024.0 * 1024.0 * 1024.0) as u64)
+            .with_mirrors(self.mirrors.iter().cloned())
+            .with_cache_dir(&cache_dir);
+
+        if let Some(token) = token {
+            builder = builder.with_token(token);
+        }
+
+        if let Some(experiment_dir) = experiment_dir {
+            builder = builder.with_experiment_dir(experiment_dir);
+        }
+
+        if let Some(parallelism) = self.parallelism.or(crate::user_config()?.parallelism) {
+            builder = builder.with_parallelism(parallelism);
+        }
+
+        if let Some(max_packages) = self.max_packages {
+            builder = builder.with_max_packages(max_packages);
+        }
+
+        if let Some(seed) = self.seed {
+            builder = builder.with_seed(seed);
+        }
+
+        if let Some(shard) = self.shard {
+            builder = builder.with_shard(shard);
+        }
+
+        if let Some(event_socket) = &self.event_socket {
+            builder = builder.with_event_socket(event_socket);
+        }
+
+        if let Some(secs) = self.heartbeat {
+            let heartbeat = Heartbeat::start(Duration::from_secs(secs.max(1)))
+                .context("Unable to start the heartbeat")?;
+            builder = builder.with_progress(heartbeat);
+        }
+
+        if let Some(locked) = &self.locked {
+            builder = builder.with_locked(LockFile::load(locked)?);
+        }
+
+        if let Some(packages) = self.packages()? {
+            builder = builder.with_packages(packages.iter().cloned());
+        }
+
+        if self.longest_first {
+            builder = builder.with_longest_first(previous_run_times(name));
+        }
+
+        if self.refresh {
+            builder = builder.with_discovery_ttl(Duration::ZERO);
+        }
+
+        if self.tee_logs {
+            builder = builder.with_tee_logs(true);
+        }
+
+        if !self.test_cores.is_empty() {
+            builder = builder.with_test_cores(self.test_cores.iter().copied());
+        }
+
+        if let Some(niceness) = self.niceness {
+            builder = builder.with_niceness(niceness);
+        }
+
+        if self.allow_unverified {
+            builder = builder.with_allow_unverified(true);
+        }
+
+        if let Some(remote_cache) = &self.remote_cache {
+            let credentials = Credentials::from_env().context(
+                "$AWS_ACCESS_KEY_ID and $AWS_SECRET_ACCESS_KEY must be set to use a remote cache",
+            )?;
+            let bucket = Bucket::from_url(remote_cache, &self.remote_cache_region, credentials)?;
+            builder =
+                builder.with_storage(RemoteCache::new(cache_dir, bucket, self::client(None)?));
+        }
+
+        // Comparing runs (and recording them in the history) needs every
+        // report, so load the full results back in
+        builder.run()?.load()
+    }
+}
+
+/// The experiments that will be run.
+#[derive(Debug, Default)]
+struct Plan {
+    experiments: Vec<(String, Experiment)>,
+    sweeps: Vec<Sweep>,
+}
+
+/// An experiment which is run against several `wasmer` releases.
+#[derive(Debug)]
+struct Sweep {
+    name: String,
+    /// The name of each run in the sweep, in version order.
+    runs: Vec<String>,
 }
 
-pub fn format_graphql(registry: &str) -> String {
-    if let Ok(mut url) = Url::parse(registry) {
-        // Looks like we've got a valid URL. Let's try to use it as-is.
-        if url.has_host() {
-            if url.path() == "/" {
-                // make sure we convert http://registry.wasmer.io/ to
-                // http://registry.wasmer.io/graphql
-                url.set_path("/graphql");
+pub(crate) fn load_experiment(path: &Path) -> Result<Experiment, Error> {
+    let src = std::fs::read_to_string(path)
+        .with_context(|| format!("Unable to read \"{}\"", path.display()))?;
+    let Document { mut experiment, .. } = Document::from_json(&src)
+        .with_context(|| format!("Unable to load \"{}\"", path.display()))?;
+
+    if let (Some(local), Some(dir)) = (&mut experiment.local, path.parent()) {
+        local.path = dir.join(&local.path);
+    }
+
+    if let Some(dir) = path.parent() {
+        let ignore_file = dir.join(IGNORE_FILE);
+        match std::fs::read_to_string(&ignore_file) {
+            Ok(src) => {
+                let entries = config::parse_ignore_file(&src);
+                tracing::debug!(
+                    path = %ignore_file.display(),
+                    entries = entries.len(),
+                    "Loaded the ignore file",
+                );
+                experiment.filters.blacklist.extend(entries);
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => {
+                return Err(
+                    Error::new(e).context(format!("Unable to read \"{}\"", ignore_file.display()))
+                )
             }
+        }
+    }
 
-            return url.to_string();
+    Ok(experiment)
+}
+
+/// How long each test case took the last time this experiment was run, if
+/// it has been run before.
+fn previous_run_times(experiment: &str) -> RunTimes {
+    let index = RunIndex::new(RunIndex::default_location());
+    let path = index.latest(experiment).join("results.json");
+    if !path.exists() {
+        return RunTimes::default();
+    }
+
+    match Results::load(&path) {
+        Ok(results) => RunTimes::from_results(&results),
+        Err(e) => {
+            tracing::warn!(
+                error = &*e,
+                path = %path.display(),
+                "Unable to load the previous run's results, so test cases will be ordered by size",
+            );
+            RunTimes::default()
         }
     }
+}
+
+fn save(path: &Path, contents: String) -> Result<(), Error> {
+    std::fs::write(path, contents)
+        .with_context(|| format!("Unable to save to \"{}\"", path.display()))
+}
+
+/// The experiments that were run by [`Run::run_all()`].
+#[derive(Debug)]
+pub(crate) struct Runs {
+    pub results: Vec<(String, Results)>,
+    /// Experiments whose success rate dropped since their previous run.
+    pub alerts: Vec<SuccessRateDrop>,
+}
+
+/// Create a HTTP client which will authenticate with the registry using the
+/// provided token.
+pub(crate) fn client(token: Option<&str>) -> Result<Client, Error> {
+    let builder = ClientBuilder::new();
+    let mut headers = HeaderMap::new();
+
+    headers.insert(
+        reqwest::header::USER_AGENT,
+        crate::USER_AGENT.parse().unwrap(),
+    );
+
+    if let Some(token) = token {
+        let auth_header = format!("bearer {token}").parse()?;
+        headers.append(reqwest::header::AUTHORIZATION, auth_header);
+    }
 
-    if !registry.contains("://") && !registry.contains('/') {
-        return endpoint_from_domain_name(registry);
+    let client = builder.default_headers(headers).build()?;
+
+    Ok(client)
+}
+
+/// Derive a unique name for each experiment from its filename (e.g.
+/// `wasix.experiment.json` becomes `wasix`).
+fn experiment_names(experiments: &[PathBuf]) -> Result<Vec<String>, Error> {
+    let mut names: Vec<String> = Vec::new();
+
+    for path in experiments {
+        let filename = path
+            .file_name()
+            .and_then(|f| f.to_str())
+            .with_context(|| format!("Invalid experiment path, \"{}\"", path.display()))?;
+        let name = filename.strip_suffix(".json").unwrap_or(filename);
+        let name = name.strip_suffix(".experiment").unwrap_or(name);
+
+        anyhow::ensure!(
+            !names.iter().any(|n| n == name),
+            "Multiple experiments are named \"{name}\""
+        );
+        names.push(name.to_string());
     }
 
-    // looks like we've received something we can't deal with. Just pass it
-    // through as-is and hopefully it'll either work or the end user can figure
-    // it out
-    registry.to_string()
+    Ok(names)
 }
 
-/// By convention, something like `"wasmer.io"` should be converted to
-/// `"https://registry.wasmer.io/graphql"`.
-fn endpoint_from_domain_name(domain_name: &str) -> String {
-    if domain_name.contains("localhost") {
-        return format!("http://{domain_name}/graphql");
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn release(version: &str) -> WasmerVersion {
+        WasmerVersion::Release(version.parse().unwrap())
     }
 
-    format!("https://registry.{domain_name}/graphql")
+    #[test]
+    fn version_ranges_are_expanded_into_a_sweep() {
+        let temp = tempfile::tempdir().unwrap();
+        let sweep = temp.path().join("sweep.experiment.json");
+        std::fs::write(
+            &sweep,
+            r#"{
+                "$schema": "experiment.schema.json",
+                "package": "wasmer/python",
+                "wasmer": {
+                    "args": [],
+                    "version": ">=4.0",
+                    "features": { "memory64": true }
+                }
+            }"#,
+        )
+        .unwrap();
+        let fixed = temp.path().join("fixed.json");
+        std::fs::write(
+            &fixed,
+            r#"{
+                "$schema": "experiment.schema.json",
+                "package": "wasmer/python",
+                "wasmer": { "args": [], "version": "4.2.0" }
+            }"#,
+        )
+        .unwrap();
+        let run =
+            Run::try_parse_from(["run".as_ref(), sweep.as_os_str(), fixed.as_os_str()]).unwrap();
+        let mut expanded = Vec::new();
+
+        let Plan {
+            experiments,
+            sweeps,
+        } = run
+            .plan_with(|version| {
+                expanded.push(version.clone());
+                Ok(vec![release("4.2.0"), release("6.0.0"), release("6.1.0")])
+            })
+            .unwrap();
+
+        assert_eq!(expanded, [WasmerVersion::Range(">=4.0".parse().unwrap())]);
+        let planned: Vec<_> = experiments
+            .iter()
+            .map(|(name, experiment)| (name.as_str(), &experiment.wasmer.version))
+            .collect();
+        assert_eq!(
+            planned,
+            [
+                ("sweep@6.0.0", &release("6.0.0")),
+                ("sweep@6.1.0", &release("6.1.0")),
+                ("fixed", &release("4.2.0")),
+            ]
+        );
+        // wasmer 4.2.0 doesn't support memory64, so it was skipped
+        assert_eq!(sweeps.len(), 1);
+        assert_eq!(sweeps[0].name, "sweep");
+        assert_eq!(sweeps[0].runs, ["sweep@6.0.0", "sweep@6.1.0"]);
+    }
 }
@@ -0,0 +1,98 @@
+use anyhow::Error;
+use clap::{Parser, Subcommand};
+use wasmer_borealis::runs::{IndexedRun, RunIndex};
+
+#[derive(Parser, Debug)]
+pub struct Runs {
+    #[clap(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// List previous runs, oldest first.
+    List,
+    /// Show where a run's results are and how it went.
+    Show {
+        /// The run's name.
+        name: String,
+    },
+    /// Delete runs and their results.
+    Clean {
+        /// The runs to delete.
+        #[clap(required_unless_present = "keep")]
+        names: Vec<String>,
+        /// Delete everything except this many of the most recent runs.
+        #[clap(long, conflicts_with = "names")]
+        keep: Option<usize>,
+    },
+}
+
+impl Runs {
+    pub fn execute(self) -> Result<(), Error> {
+        let Runs { cmd } = self;
+
+        let index = RunIndex::new(RunIndex::default_location());
+
+        match cmd {
+            Cmd::List => {
+                for run in index.runs()? {
+                    let r = &run.record;
+                    println!(
+                        "{:<24} {:<24} {}  {:>4} ✔ {:>4} ❌ {:>4} 🐛  {}",
+                        run.name,
+                        r.experiment,
+                        r.started.format("%Y-%m-%d %H:%M"),
+                        r.success,
+                        r.failures,
+                        r.bugs,
+                        r.experiment_dir.display(),
+                    );
+                }
+            }
+            Cmd::Show { name } => {
+                let runs = index.find(&name)?;
+                anyhow::ensure!(!runs.is_empty(), "There is no run called \"{name}\"");
+
+                for IndexedRun { record: r, .. } in runs {
+                    println!("{} ({})", r.experiment, r.package);
+                    println!("  Started:    {}", r.started.to_rfc3339());
+                    println!("  Took:       {:.1?}", r.total_time);
+                    println!(
+                        "  Results:    {} success, {} failures, {} bugs ({:.1}% success)",
+                        r.success,
+                        r.failures,
+                        r.bugs,
+                        r.success_rate() * 100.0
+                    );
+                    println!("  Directory:  {}", r.experiment_dir.display());
+                    println!(
+                        "  Report:     {}",
+                        r.experiment_dir.join("report.html").display()
+                    );
+                }
+            }
+            Cmd::Clean {
+                keep: Some(keep), ..
+            } => {
+                for name in index.prune(keep)? {
+                    println!("Removed {name}");
+                }
+            }
+            Cmd::Clean { names, keep: None } => {
+                for name in &names {
+                    remove(&index, name)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn remove(index: &RunIndex, name: &str) -> Result<(), Error> {
+    let removed = index.remove(name)?;
+    anyhow::ensure!(!removed.is_empty(), "There is no run called \"{name}\"");
+    println!("Removed {name}");
+    Ok(())
+}
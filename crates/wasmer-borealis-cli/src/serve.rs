@@ -0,0 +1,65 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use anyhow::Error;
+use clap::Parser;
+use reqwest::Url;
+use wasmer_borealis::{experiment::ExperimentBuilder, server::Server};
+
+#[derive(Parser, Debug)]
+pub struct Serve {
+    /// The address to listen on.
+    #[clap(long, default_value = "127.0.0.1:8080")]
+    addr: SocketAddr,
+    /// The Wasmer registry to query packages from.
+    ///
+    /// This may be a URL, a domain name (e.g. "wasmer.io"), or one of "prod",
+    /// "staging", or "local". Defaults to the user config file's "registry",
+    /// or "wasmer.io".
+    #[clap(long, env = "WASMER_REGISTRY")]
+    registry: Option<String>,
+    #[clap(long, short, env = "WASMER_TOKEN")]
+    token: Option<String>,
+    /// The token clients must send (as `Authorization: Bearer <token>`) to
+    /// use the API.
+    #[clap(long, env = "BOREALIS_API_TOKEN")]
+    api_token: String,
+    /// The directory each experiment's files will be written to.
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// The directory downloaded packages will be cached in.
+    #[clap(long, env = "BOREALIS_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+}
+
+impl Serve {
+    pub fn execute(self) -> Result<(), Error> {
+        let Serve {
+            addr,
+            registry,
+            token,
+            api_token,
+            output,
+            cache_dir,
+        } = self;
+
+        let registry = crate::registry(registry.as_deref())?;
+        let endpoint: Url = wasmer_borealis::registry::format_graphql(&registry).parse()?;
+        let token = crate::token(token.as_deref(), &registry)?;
+        let cache_dir = crate::cache_dir(cache_dir)?;
+        let output = output.unwrap_or_else(|| crate::DIRS.data_local_dir().join("server"));
+
+        let server = Server::new(output, api_token, move |experiment| {
+            let builder = ExperimentBuilder::new(experiment)
+                .with_endpoint(&endpoint)
+                .expect("The endpoint was already validated")
+                .with_cache_dir(&cache_dir);
+
+            match &token {
+                Some(token) => builder.with_token(token.clone()),
+                None => builder,
+            }
+        });
+
+        tokio::runtime::Runtime::new()?.block_on(server.serve(addr))
+    }
+}
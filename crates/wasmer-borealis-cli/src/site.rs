@@ -0,0 +1,53 @@
+use std::path::PathBuf;
+
+use anyhow::{Context, Error};
+use clap::{Parser, Subcommand};
+use wasmer_borealis::runs::RunIndex;
+
+#[derive(Parser, Debug)]
+pub struct Site {
+    #[clap(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// Render a static site with every recorded run and how each experiment
+    /// has trended over time.
+    Build {
+        /// Where to write the site.
+        output_dir: PathBuf,
+        /// Open the site in the browser once it has been built.
+        #[clap(long)]
+        open: bool,
+    },
+}
+
+impl Site {
+    pub fn execute(self) -> Result<(), Error> {
+        let Site { cmd } = self;
+
+        match cmd {
+            Cmd::Build { output_dir, open } => {
+                let index = RunIndex::new(RunIndex::default_location());
+                let runs = index.runs()?;
+
+                let site =
+                    wasmer_borealis::render::build_site(&runs, &output_dir).with_context(|| {
+                        format!("Unable to build the site in \"{}\"", output_dir.display())
+                    })?;
+
+                for name in &site.skipped {
+                    eprintln!("Skipped \"{name}\" because its results are missing");
+                }
+                println!("Wrote {} run(s) to {}", site.pages, site.index.display());
+
+                if open {
+                    open::that_detached(&site.index)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
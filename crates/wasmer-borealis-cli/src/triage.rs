@@ -0,0 +1,224 @@
+use std::{
+    io::{BufRead, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::Error;
+use wasmer_borealis::experiment::{read_log, Annotation, Category, Disposition, Report, Results};
+
+/// The number of lines from the end of `stderr.txt` to show.
+const STDERR_LINES: usize = 20;
+
+#[derive(Debug, clap::Parser)]
+pub struct Triage {
+    /// Also revisit reports which have already been annotated.
+    #[clap(long)]
+    all: bool,
+    /// The results.json file to annotate.
+    json: PathBuf,
+}
+
+impl Triage {
+    pub fn execute(self) -> Result<(), Error> {
+        let mut results = Results::load(&self.json)?;
+
+        let mut pending: Vec<usize> = results
+            .reports
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| r.category() != Category::Success)
+            .filter(|(_, r)| self.all || r.annotation.is_none())
+            .map(|(i, _)| i)
+            .collect();
+        // Bugs first, because they are usually more interesting
+        pending.sort_by_key(|&i| std::cmp::Reverse(results.reports[i].category()));
+
+        if pending.is_empty() {
+            println!("Nothing to triage");
+            return Ok(());
+        }
+
+        let stdin = std::io::stdin();
+        let mut stdin = stdin.lock();
+        let stdout = std::io::stdout();
+        let mut stdout = stdout.lock();
+        let total = pending.len();
+
+        for (i, index) in pending.into_iter().enumerate() {
+            let report = &mut results.reports[index];
+            writeln!(stdout)?;
+            writeln!(stdout, "[{}/{total}]", i + 1)?;
+            show(report, &mut stdout)?;
+
+            match prompt(&mut stdin, &mut stdout)? {
+                Action::Annotate(annotation) => {
+                    report.annotation = Some(annotation);
+                    // Save after every annotation so nothing is lost if we
+                    // quit early
+                    results.save(&self.json)?;
+                }
+                Action::Skip => {}
+                Action::Quit => break,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn show(report: &Report, mut out: impl Write) -> Result<(), Error> {
+    let category = match report.category() {
+        Category::Success => "success",
+        Category::Failure => "failure",
+        Category::Bug => "bug",
+    };
+    writeln!(
+        out,
+        "{}@{} ({category})",
+        report.display_name,
+        report.version()
+    )?;
+
+    if let Some(annotation) = &report.annotation {
+        writeln!(
+            out,
+            "Previously marked as \"{}\" {}",
+            annotation.disposition.description(),
+            annotation.note
+        )?;
+    }
+
+    if let Some(error) = report.outcome.error() {
+        writeln!(out, "Error: {}", error.error)?;
+        for cause in &error.causes {
+            writeln!(out, "  Caused by: {cause}")?;
+        }
+    }
+
+    if let Some(base_dir) = report.outcome.base_dir() {
+        writeln!(out, "Working directory: {}", base_dir.display())?;
+        show_stderr(base_dir, &mut out)?;
+    }
+
+    Ok(())
+}
+
+fn show_stderr(base_dir: &Path, mut out: impl Write) -> Result<(), Error> {
+    let Ok(Some(stderr)) = read_log(base_dir, "stderr.txt") else {
+        return Ok(());
+    };
+
+    let lines: Vec<&str> = stderr.lines().collect();
+    let start = lines.len().saturating_sub(STDERR_LINES);
+    writeln!(out, "--- stderr.txt ---")?;
+    for line in &lines[start..] {
+        writeln!(out, "{line}")?;
+    }
+    writeln!(out, "------------------")?;
+
+    Ok(())
+}
+
+#[derive(Debug, PartialEq)]
+enum Action {
+    Annotate(Annotation),
+    Skip,
+    Quit,
+}
+
+fn prompt(mut input: impl BufRead, mut out: impl Write) -> Result<Action, Error> {
+    loop {
+        write!(
+            out,
+            "[k]nown issue, [p]ackage bug, [w]asmer bug, [i]gnore, [s]kip, [q]uit: "
+        )?;
+        out.flush()?;
+
+        let disposition = match read_line(&mut input)?.as_deref() {
+            Some("k") => Disposition::KnownIssue,
+            Some("p") => Disposition::PackageBug,
+            Some("w") => Disposition::WasmerBug,
+            Some("i") => Disposition::Ignore,
+            Some("s") | Some("") => return Ok(Action::Skip),
+            Some("q") | None => return Ok(Action::Quit),
+            Some(_) => continue,
+        };
+
+        write!(out, "Note (optional): ")?;
+        out.flush()?;
+        let note = read_line(&mut input)?.unwrap_or_default();
+
+        return Ok(Action::Annotate(Annotation { disposition, note }));
+    }
+}
+
+/// Read a trimmed line, returning `None` at the end of input.
+fn read_line(mut input: impl BufRead) -> Result<Option<String>, Error> {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(line.trim().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt_with(input: &str) -> (Action, String) {
+        let mut output = Vec::new();
+        let action = prompt(input.as_bytes(), &mut output).unwrap();
+        (action, String::from_utf8(output).unwrap())
+    }
+
+    #[test]
+    fn annotate_with_a_note() {
+        let (action, output) = prompt_with("w\n  panics in wasix  \n");
+
+        assert_eq!(
+            action,
+            Action::Annotate(Annotation {
+                disposition: Disposition::WasmerBug,
+                note: "panics in wasix".to_string(),
+            })
+        );
+        assert!(output.ends_with("Note (optional): "));
+    }
+
+    #[test]
+    fn the_note_is_optional() {
+        let (action, _) = prompt_with("k\n");
+
+        assert_eq!(
+            action,
+            Action::Annotate(Annotation {
+                disposition: Disposition::KnownIssue,
+                note: String::new(),
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_answers_ask_again() {
+        let (action, output) = prompt_with("x\np\n\n");
+
+        assert_eq!(
+            action,
+            Action::Annotate(Annotation {
+                disposition: Disposition::PackageBug,
+                note: String::new(),
+            })
+        );
+        assert_eq!(output.matches("[k]nown issue").count(), 2);
+    }
+
+    #[test]
+    fn skip_and_quit() {
+        assert_eq!(prompt_with("s\n").0, Action::Skip);
+        assert_eq!(prompt_with("\n").0, Action::Skip);
+        assert_eq!(prompt_with("q\n").0, Action::Quit);
+        // Running out of input (e.g. Ctrl-D) also quits
+        assert_eq!(prompt_with("").0, Action::Quit);
+    }
+}
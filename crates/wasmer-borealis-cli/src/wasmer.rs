@@ -0,0 +1,85 @@
+use std::path::PathBuf;
+
+use anyhow::Error;
+use clap::{Parser, Subcommand};
+use wasmer_borealis::toolchain::Toolchains;
+
+#[derive(Parser, Debug)]
+pub struct Wasmer {
+    /// The directory downloaded packages will be cached in.
+    #[clap(long, global = true, env = "BOREALIS_CACHE_DIR")]
+    cache_dir: Option<PathBuf>,
+    #[clap(subcommand)]
+    cmd: Cmd,
+}
+
+#[derive(Subcommand, Debug)]
+enum Cmd {
+    /// List the wasmer CLIs that have been downloaded or built.
+    Ls,
+    /// Remove downloaded or built wasmer CLIs.
+    Prune {
+        /// The toolchains to remove (e.g. "v4.2.0").
+        #[clap(required_unless_present = "all")]
+        names: Vec<String>,
+        /// Remove every toolchain, including git checkouts and build
+        /// artifacts.
+        #[clap(long, conflicts_with = "names")]
+        all: bool,
+    },
+}
+
+impl Wasmer {
+    pub fn execute(self) -> Result<(), Error> {
+        let Wasmer { cache_dir, cmd } = self;
+
+        let cache_dir = crate::cache_dir(cache_dir)?;
+        let toolchains = Toolchains::in_cache_dir(&cache_dir)?;
+
+        match cmd {
+            Cmd::Ls => {
+                for toolchain in toolchains.installed()? {
+                    println!(
+                        "{:<24} {:<16} {:>10}  {}",
+                        toolchain.name,
+                        toolchain.platform,
+                        format_size(toolchain.size),
+                        toolchain.binary.display(),
+                    );
+                }
+            }
+            Cmd::Prune { all: true, .. } => {
+                toolchains.remove_all()?;
+                println!("Removed \"{}\"", toolchains.dir().display());
+            }
+            Cmd::Prune { names, all: false } => {
+                let installed = toolchains.installed()?;
+
+                for name in &names {
+                    let matches: Vec<_> = installed.iter().filter(|t| &t.name == name).collect();
+                    anyhow::ensure!(!matches.is_empty(), "\"{name}\" isn't installed");
+
+                    for toolchain in matches {
+                        toolchains.remove(toolchain)?;
+                        println!("Removed {} ({})", toolchain.name, toolchain.platform);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
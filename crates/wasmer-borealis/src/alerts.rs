@@ -0,0 +1,172 @@
+//! Detecting when an experiment has gotten worse since it was last run.
+
+use std::fmt::Display;
+
+use crate::{
+    experiment::{Category, Results},
+    history::RunRecord,
+};
+
+/// The success rate of an experiment dropped compared to its previous run.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SuccessRateDrop {
+    /// The experiment's name.
+    pub experiment: String,
+    /// The success rate of the previous run, as a percentage.
+    pub previous: f64,
+    /// The success rate of the current run, as a percentage.
+    pub current: f64,
+    /// Packages (as `namespace/name@version`) that succeeded in the previous
+    /// run but not in the current one.
+    ///
+    /// This will be empty if the previous run's results are no longer
+    /// available.
+    pub newly_failing: Vec<String>,
+}
+
+impl SuccessRateDrop {
+    /// Compare a run against the previous run of the same experiment, returning
+    /// a [`SuccessRateDrop`] if the success rate dropped by more than
+    /// `threshold` percentage points.
+    pub fn check(previous: &RunRecord, current: &Results, threshold: f64) -> Option<Self> {
+        let previous_rate = previous.success_rate() * 100.0;
+        let current_rate = current.success_rate() * 100.0;
+
+        if previous_rate - current_rate <= threshold {
+            return None;
+        }
+
+        let newly_failing = match Results::load(previous.results_path()) {
+            Ok(previous) => newly_failing(&previous, current),
+            Err(e) => {
+                tracing::debug!(
+                    error = &*e,
+                    "Unable to load the previous results to find newly failing packages",
+                );
+                Vec::new()
+            }
+        };
+
+        Some(SuccessRateDrop {
+            experiment: previous.experiment.clone(),
+            previous: previous_rate,
+            current: current_rate,
+            newly_failing,
+        })
+    }
+}
+
+impl Display for SuccessRateDrop {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let SuccessRateDrop {
+            experiment,
+            previous,
+            current,
+            newly_failing,
+        } = self;
+
+        write!(
+            f,
+            "The success rate for \"{experiment}\" dropped from {previous:.1}% to {current:.1}%"
+        )?;
+
+        if !newly_failing.is_empty() {
+            write!(f, ". Newly failing packages:")?;
+            for pkg in newly_failing {
+                write!(f, "\n- {pkg}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+fn newly_failing(previous: &Results, current: &Results) -> Vec<String> {
+    current
+        .diff(previous)
+        .regressed
+        .into_iter()
+        .filter(|change| change.before == Category::Success)
+        .map(|change| format!("{}@{}", change.display_name, change.version))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+
+    use super::*;
+    use crate::test_utils::{completed, fetch_failed, report, results};
+
+    /// Record `previous` as the last run, saving its results to `dir`.
+    fn record(dir: &std::path::Path, mut previous: Results) -> RunRecord {
+        previous.experiment_dir = dir.to_path_buf();
+        previous.save(dir.join("results.json")).unwrap();
+        RunRecord::new("nightly", Utc::now(), &previous)
+    }
+
+    #[test]
+    fn only_drops_larger_than_the_threshold_are_reported() {
+        let temp = tempfile::tempdir().unwrap();
+        let previous = record(
+            temp.path(),
+            results(vec![
+                report("a", "1.0.0", completed(true, 1)),
+                report("b", "1.0.0", completed(true, 1)),
+                report("c", "1.0.0", completed(true, 1)),
+                report("d", "1.0.0", completed(true, 1)),
+            ]),
+        );
+        let current = results(vec![
+            report("a", "1.0.0", completed(true, 1)),
+            report("b", "1.0.0", completed(true, 1)),
+            report("c", "1.0.0", completed(true, 1)),
+            report("d", "1.0.0", completed(false, 1)),
+        ]);
+
+        assert_eq!(SuccessRateDrop::check(&previous, &current, 25.0), None);
+
+        let drop = SuccessRateDrop::check(&previous, &current, 24.9).unwrap();
+        assert_eq!(drop.experiment, "nightly");
+        assert_eq!(drop.previous, 100.0);
+        assert_eq!(drop.current, 75.0);
+        assert_eq!(drop.newly_failing, ["wasmer/d@1.0.0"]);
+
+        // Getting better is never a regression
+        let improved = record(temp.path(), current);
+        let fixed = results(vec![report("d", "1.0.0", completed(true, 1))]);
+        assert_eq!(SuccessRateDrop::check(&improved, &fixed, 0.0), None);
+    }
+
+    #[test]
+    fn only_packages_that_used_to_pass_are_newly_failing() {
+        let temp = tempfile::tempdir().unwrap();
+        let previous = record(
+            temp.path(),
+            results(vec![
+                report("a", "1.0.0", completed(true, 1)),
+                report("b", "1.0.0", completed(true, 1)),
+                report("c", "1.0.0", fetch_failed()),
+                report("d", "1.0.0", completed(true, 1)),
+                report("e", "1.0.0", completed(false, 1)),
+            ]),
+        );
+        let current = results(vec![
+            report("a", "1.0.0", completed(true, 1)),
+            report("b", "1.0.0", completed(false, 1)),
+            report("c", "1.0.0", fetch_failed()),
+            report("d", "1.0.0", fetch_failed()),
+            report("e", "1.0.0", fetch_failed()),
+        ]);
+
+        let drop = SuccessRateDrop::check(&previous, &current, 1.0).unwrap();
+
+        assert_eq!(drop.newly_failing, ["wasmer/b@1.0.0", "wasmer/d@1.0.0"]);
+
+        // The drop is still reported when the old results have been deleted
+        std::fs::remove_file(previous.results_path()).unwrap();
+        let drop = SuccessRateDrop::check(&previous, &current, 1.0).unwrap();
+        assert!(drop.newly_failing.is_empty());
+    }
+}
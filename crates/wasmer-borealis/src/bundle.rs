@@ -0,0 +1,219 @@
+//! Packing an experiment directory into a single `*.tar.gz` bundle, so a
+//! complete run can be attached to an issue and inspected on another machine.
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Error};
+
+use crate::experiment::Results;
+
+const RESULTS_FILENAME: &str = "results.json";
+
+/// Entries in the experiment directory which aren't needed to inspect a run
+/// (the compiled module cache and the lock held while it is running).
+const SKIPPED: &[&str] = &["module-cache", ".borealis.lock"];
+
+/// Entries in each test case's directory which only hold copies of the
+/// package and `wasmer`'s own caches.
+const SKIPPED_IN_TEST_CASE: &[&str] = &["fixtures", "wasmer"];
+
+/// Does this path look like a bundle rather than a `results.json` file?
+pub fn is_bundle(path: &Path) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    name.ends_with(".tar.gz") || name.ends_with(".tgz")
+}
+
+/// Bundle the results, report, logs, and `test_case.json` files from an
+/// experiment directory into a `*.tar.gz` file.
+pub fn pack(experiment_dir: &Path, bundle: &Path) -> Result<(), Error> {
+    anyhow::ensure!(
+        experiment_dir.join(RESULTS_FILENAME).exists(),
+        "\"{}\" doesn't contain a {RESULTS_FILENAME}. Has the experiment finished?",
+        experiment_dir.display(),
+    );
+
+    let files = files(experiment_dir)?;
+    let mut file_list = tempfile::NamedTempFile::new()?;
+    for file in &files {
+        writeln!(file_list, "{}", file.display())?;
+    }
+    file_list.flush()?;
+
+    if let Some(parent) = bundle.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Unable to create \"{}\"", parent.display()))?;
+    }
+
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(bundle)
+        .arg("-C")
+        .arg(experiment_dir)
+        .arg("-T")
+        .arg(file_list.path())
+        .status()
+        .context("Unable to run \"tar\"")?;
+    anyhow::ensure!(status.success(), "\"tar\" failed ({status})");
+
+    Ok(())
+}
+
+/// The directory a bundle is unpacked into (`run.tar.gz` is unpacked into
+/// `run/`, next to it).
+pub fn unpacked_dir(bundle: &Path) -> PathBuf {
+    let name = bundle
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let stem = name
+        .strip_suffix(".tar.gz")
+        .or_else(|| name.strip_suffix(".tgz"))
+        .unwrap_or(&name);
+
+    bundle.with_file_name(stem)
+}
+
+/// Unpack a bundle (unless that has already been done) and load its results,
+/// pointing them at the unpacked logs.
+pub fn open(bundle: &Path) -> Result<Results, Error> {
+    let dir = unpacked_dir(bundle);
+    let results_json = dir.join(RESULTS_FILENAME);
+
+    if !results_json.exists() {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Unable to create \"{}\"", dir.display()))?;
+
+        let status = Command::new("tar")
+            .arg("-xzf")
+            .arg(bundle)
+            .arg("-C")
+            .arg(&dir)
+            .status()
+            .context("Unable to run \"tar\"")?;
+        anyhow::ensure!(
+            status.success(),
+            "Unable to unpack \"{}\" (\"tar\" failed with {status})",
+            bundle.display()
+        );
+    }
+
+    let mut results = Results::load(&results_json)?;
+    results.relocate(dir);
+
+    Ok(results)
+}
+
+/// Every file that should go in the bundle, relative to the experiment
+/// directory.
+fn files(experiment_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    let mut pending = vec![PathBuf::new()];
+
+    while let Some(relative) = pending.pop() {
+        let dir = experiment_dir.join(&relative);
+        let is_test_case = dir.join("test_case.json").exists();
+        let entries = std::fs::read_dir(&dir)
+            .with_context(|| format!("Unable to read \"{}\"", dir.display()))?;
+
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name();
+            let skipped = if relative.as_os_str().is_empty() {
+                SKIPPED
+            } else if is_test_case {
+                SKIPPED_IN_TEST_CASE
+            } else {
+                &[]
+            };
+            if skipped.iter().any(|s| name == *s) {
+                continue;
+            }
+
+            let path = relative.join(&name);
+            if entry.file_type()?.is_dir() {
+                pending.push(path);
+            } else {
+                files.push(path);
+            }
+        }
+    }
+
+    files.sort();
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bundles_are_unpacked_next_to_themselves() {
+        assert_eq!(
+            unpacked_dir(Path::new("/tmp/runs/python.tar.gz")),
+            Path::new("/tmp/runs/python")
+        );
+        assert_eq!(unpacked_dir(Path::new("run.tgz")), Path::new("run"));
+        assert!(is_bundle(Path::new("run.tar.gz")));
+        assert!(!is_bundle(Path::new("results.json")));
+    }
+
+    #[test]
+    fn pack_and_open_a_run() {
+        let temp = tempfile::tempdir().unwrap();
+        let experiment_dir = temp.path().join("original");
+        let test_dir = experiment_dir.join("experiments/wasmer/python/1.0.0");
+        std::fs::create_dir_all(test_dir.join("fixtures")).unwrap();
+        std::fs::create_dir_all(experiment_dir.join("module-cache")).unwrap();
+        std::fs::write(test_dir.join("test_case.json"), "{}").unwrap();
+        std::fs::write(test_dir.join("stdout.txt"), "Hello, World!").unwrap();
+        std::fs::write(test_dir.join("fixtures/package.tar.gz"), "").unwrap();
+        std::fs::write(experiment_dir.join("module-cache/python.bin"), "").unwrap();
+        let results = serde_json::json!({
+            "experiment": { "package": "wasmer/python" },
+            "reports": [{
+                "display_name": "wasmer/python",
+                "package_version": {
+                    "id": "1",
+                    "version": "1.0.0",
+                    "distribution": {
+                        "downloadUrl": "https://example.com/python.tar.gz",
+                        "piritaDownloadUrl": null,
+                    },
+                },
+                "outcome": {
+                    "outcome": "completed",
+                    "base_dir": test_dir,
+                    "status": { "success": true, "code": 0 },
+                    "run_time": { "secs": 1, "nanos": 0 },
+                },
+            }],
+            "total_time": { "secs": 1, "nanos": 0 },
+            "experiment_dir": experiment_dir,
+        });
+        std::fs::write(
+            experiment_dir.join(RESULTS_FILENAME),
+            serde_json::to_string(&results).unwrap(),
+        )
+        .unwrap();
+        let bundle = temp.path().join("shared/run.tar.gz");
+
+        pack(&experiment_dir, &bundle).unwrap();
+        let results = open(&bundle).unwrap();
+
+        let unpacked = temp.path().join("shared/run");
+        assert_eq!(results.experiment_dir, unpacked);
+        let base_dir = results.reports[0].outcome.base_dir().unwrap();
+        assert_eq!(base_dir, unpacked.join("experiments/wasmer/python/1.0.0"));
+        assert!(base_dir.join("stdout.txt").exists());
+        assert!(!base_dir.join("fixtures").exists());
+        assert!(!unpacked.join("module-cache").exists());
+    }
+}
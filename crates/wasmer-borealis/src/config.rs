@@ -1,19 +1,33 @@
 use std::{
     borrow::Cow,
+    fmt,
+    num::NonZeroUsize,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
+use anyhow::Error;
+use chrono::{DateTime, Utc};
 use indexmap::IndexMap;
-use semver::Version;
+use semver::{Version, VersionReq};
+use serde::{
+    de::{
+        value::MapAccessDeserializer, DeserializeSeed, Error as _, IntoDeserializer, MapAccess,
+        Visitor,
+    },
+    Deserialize,
+};
 
 /// The document object for a serialized [`Experiment`].
 ///
 /// This only really exists so editors can use the `$schema` property to provide
 /// auto-complete JSON schema.
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, serde::Serialize)]
 pub struct Document {
     #[serde(rename = "$schema")]
     pub schema: String,
+    /// The version of the document format this was written with.
+    pub version: u32,
     #[serde(flatten)]
     pub experiment: Experiment,
 }
@@ -22,9 +36,232 @@ impl Document {
     pub fn new(experiment: Experiment) -> Self {
         Document {
             schema: schema_url(),
+            version: DOCUMENT_VERSION,
             experiment,
         }
     }
+
+    /// Parse a document from JSON, producing an error message which points
+    /// at the problem and suggests a fix for misspelled fields.
+    ///
+    /// Documents written with an older version of the format are upgraded
+    /// to the current one, logging a warning for each change.
+    pub fn from_json(src: &str) -> Result<Self, Error> {
+        let value: serde_json::Value =
+            serde_json::from_str(src).map_err(|e| anyhow::anyhow!(diagnose(src, &e)))?;
+        let version = document_version(&value)?;
+
+        if version == DOCUMENT_VERSION {
+            return serde_json::from_str(src).map_err(|e| anyhow::anyhow!(diagnose(src, &e)));
+        }
+
+        let (value, changes) = migrate(value, version, MIGRATIONS)?;
+        for change in &changes {
+            tracing::warn!(
+                from = version,
+                to = DOCUMENT_VERSION,
+                "Upgraded an old experiment document: {change}"
+            );
+        }
+
+        // Round-trip through a string so errors still point at a line
+        let src = serde_json::to_string_pretty(&value)?;
+        serde_json::from_str(&src).map_err(|e| {
+            anyhow::anyhow!(
+                "{} (after upgrading from version {version})",
+                diagnose(&src, &e)
+            )
+        })
+    }
+}
+
+/// The current version of the experiment document format.
+///
+/// Documents without a `version` were written before the format was
+/// versioned, and are treated as version 1.
+pub const DOCUMENT_VERSION: u32 = 1;
+
+/// Upgrades a document from one version of the format to the next.
+struct Migration {
+    /// The version this migration upgrades from.
+    from: u32,
+    /// Rewrite the document, returning a description of each change so the
+    /// user can be told how to update their file.
+    apply: fn(&mut serde_json::Map<String, serde_json::Value>) -> Vec<String>,
+}
+
+/// Every migration, oldest first.
+///
+/// When the format changes in a way that would break existing documents,
+/// bump [`DOCUMENT_VERSION`] and add a migration from the previous version.
+const MIGRATIONS: &[Migration] = &[];
+
+fn document_version(document: &serde_json::Value) -> Result<u32, Error> {
+    let version = match document.get("version") {
+        None => return Ok(1),
+        Some(v) => v
+            .as_u64()
+            .and_then(|v| u32::try_from(v).ok())
+            .filter(|v| *v > 0)
+            .ok_or_else(|| {
+                anyhow::anyhow!("The document version should be a positive integer, found {v}")
+            })?,
+    };
+
+    anyhow::ensure!(
+        version <= DOCUMENT_VERSION,
+        "This experiment uses version {version} of the document format, but this version of \
+         wasmer-borealis only understands up to version {DOCUMENT_VERSION}. Try upgrading \
+         wasmer-borealis."
+    );
+
+    Ok(version)
+}
+
+/// Apply each migration needed to bring a document up to
+/// [`DOCUMENT_VERSION`], returning the upgraded document and a description
+/// of everything that was changed.
+fn migrate(
+    mut document: serde_json::Value,
+    mut version: u32,
+    migrations: &[Migration],
+) -> Result<(serde_json::Value, Vec<String>), Error> {
+    let object = document
+        .as_object_mut()
+        .ok_or_else(|| anyhow::anyhow!("An experiment should be a JSON object"))?;
+    let mut changes = Vec::new();
+
+    while version < DOCUMENT_VERSION {
+        let migration = migrations
+            .iter()
+            .find(|m| m.from == version)
+            .ok_or_else(|| {
+                anyhow::anyhow!("Unable to upgrade from version {version} of the document format")
+            })?;
+        changes.extend((migration.apply)(object));
+        version += 1;
+    }
+
+    object.insert("version".to_string(), version.into());
+
+    Ok((document, changes))
+}
+
+// We can't use #[serde(flatten)] when deserializing because it buffers the
+// whole object (so errors have no location) and silently disables
+// #[serde(deny_unknown_fields)] on the Experiment.
+impl<'de> serde::Deserialize<'de> for Document {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(DocumentVisitor)
+    }
+}
+
+struct DocumentVisitor;
+
+impl<'de> Visitor<'de> for DocumentVisitor {
+    type Value = Document;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("an experiment")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Document, A::Error> {
+        let mut schema = None;
+        let mut version = None;
+        let experiment = Experiment::deserialize(MapAccessDeserializer::new(WithoutSchema {
+            map,
+            schema: &mut schema,
+            version: &mut version,
+        }))?;
+        let schema = schema.ok_or_else(|| A::Error::missing_field("$schema"))?;
+        if experiment.package.is_empty() && experiment.local.is_none() {
+            return Err(A::Error::missing_field("package"));
+        }
+
+        Ok(Document {
+            schema,
+            version: version.unwrap_or(1),
+            experiment,
+        })
+    }
+}
+
+/// Forwards every entry except `$schema` and `version` to the
+/// [`Experiment`].
+struct WithoutSchema<'a, A> {
+    map: A,
+    schema: &'a mut Option<String>,
+    version: &'a mut Option<u32>,
+}
+
+impl<'de, A: MapAccess<'de>> MapAccess<'de> for WithoutSchema<'_, A> {
+    type Error = A::Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, A::Error> {
+        while let Some(key) = self.map.next_key::<String>()? {
+            if key == "$schema" {
+                *self.schema = Some(self.map.next_value()?);
+            } else if key == "version" {
+                *self.version = Some(self.map.next_value()?);
+            } else {
+                return seed.deserialize(key.into_deserializer()).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, A::Error> {
+        self.map.next_value_seed(seed)
+    }
+}
+
+/// Turn a [`serde_json::Error`] into a message showing the offending line
+/// and, if a field or variant name was misspelled, the closest valid name.
+fn diagnose(src: &str, error: &serde_json::Error) -> String {
+    let message = error.to_string();
+    let location = format!(" at line {} column {}", error.line(), error.column());
+    let message = message.strip_suffix(&location).unwrap_or(&message);
+
+    let mut diagnostic = message.to_string();
+
+    if error.line() > 0 {
+        diagnostic.push_str(&format!(" at line {}", error.line()));
+    }
+
+    if let Some(suggestion) = suggestion(message) {
+        diagnostic.push_str(&format!(", did you mean `{suggestion}`?"));
+    }
+
+    if let Some(line) = src.lines().nth(error.line().saturating_sub(1)) {
+        if error.line() > 0 {
+            let number = error.line().to_string();
+            let padding = " ".repeat(number.len());
+            let caret = " ".repeat(error.column().saturating_sub(1));
+            diagnostic.push_str(&format!("\n\n {number} | {line}\n {padding} | {caret}^"));
+        }
+    }
+
+    diagnostic
+}
+
+/// Find the closest match for an unknown field or variant, using serde's
+/// "unknown field `x`, expected one of `a`, `b`" error messages.
+pub(crate) fn suggestion(message: &str) -> Option<&str> {
+    let rest = message
+        .strip_prefix("unknown field ")
+        .or_else(|| message.strip_prefix("unknown variant "))?;
+    let mut names = rest.split('`').skip(1).step_by(2);
+    let unknown = names.next()?;
+
+    names
+        .map(|candidate| (candidate, strsim::jaro_winkler(unknown, candidate)))
+        .filter(|(_, similarity)| *similarity > 0.8)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(candidate, _)| candidate)
 }
 
 fn schema_url() -> String {
@@ -40,12 +277,27 @@ fn schema_url() -> String {
 #[cfg_attr(test, derive(schemars::JsonSchema))]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 pub struct Experiment {
+    /// A human-friendly name for the experiment, shown in reports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// What the experiment is testing and why, shown in reports.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// Free-form key/value pairs (e.g. a ticket or release number) recorded
+    /// in the results and shown in reports.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub metadata: IndexMap<String, String>,
     /// The name of the package used when running the experiment.
+    ///
+    /// When testing a `local` package, this defaults to the local package
+    /// itself.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
     pub package: String,
     /// The command to run.
     ///
     /// Primarily used when the package doesn't specify an entrypoint and there
-    /// are multiple commands available.
+    /// are multiple commands available. This is passed to the `wasmer` CLI as
+    /// `--command-name`.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub command: Option<String>,
     /// Arguments that should be passed through to the package.
@@ -58,6 +310,291 @@ pub struct Experiment {
     pub wasmer: WasmerConfig,
     #[serde(default, skip_serializing_if = "Filters::is_empty")]
     pub filters: Filters,
+    /// Test a package on the local filesystem instead of packages from the
+    /// registry (the `filters` are ignored), so it can be checked before it
+    /// is published.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub local: Option<LocalPackage>,
+    /// An explicit list of package versions to test instead of discovering
+    /// them with the `filters`, so a curated suite covers exactly the same
+    /// packages every time it is run.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub test_cases: Vec<PinnedTestCase>,
+    /// How long each test case may run for before it is killed (e.g. `"30s"`
+    /// or `"5m"`).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "humantime_serde"
+    )]
+    #[cfg_attr(test, schemars(with = "Option<String>"))]
+    pub timeout: Option<Duration>,
+    /// How much each test case may write to stdout and stderr, so a single
+    /// package can't fill the disk with logs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_limit: Option<OutputLimit>,
+    /// What a successful run looks like.
+    #[serde(default, skip_serializing_if = "Expectations::is_default")]
+    pub expectations: Expectations,
+    /// What packages are allowed to do while they run.
+    #[serde(default, skip_serializing_if = "Capabilities::is_default")]
+    pub capabilities: Capabilities,
+    /// Settings that should be applied to every package in a particular
+    /// namespace, keyed by the namespace's name.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub overrides: IndexMap<String, Overrides>,
+    /// Commands to run at various points in the experiment's lifecycle.
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+    /// Commands that inspect each test case after it has run, attaching
+    /// their findings to the test case's report.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub analyzers: Vec<Analyzer>,
+    /// Run each test case several times and record statistics about how long
+    /// it took.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub benchmark: Option<Benchmark>,
+    /// Run each test case this many times and record how often it passed,
+    /// so flaky packages can be told apart from broken ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repetitions: Option<NonZeroUsize>,
+    /// Run each test case a second time under the same conditions and flag
+    /// it if the exit code or stdout changed, so non-deterministic packages
+    /// aren't mistaken for regressions in the runtime.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub check_determinism: bool,
+    /// Environment variables whose values should be scrubbed from recorded
+    /// commands, logs, and `results.json`.
+    ///
+    /// Anything that looks like a secret (e.g. `*_TOKEN` or `*_PASSWORD`) is
+    /// always redacted.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub redact: Vec<String>,
+}
+
+impl Experiment {
+    /// Get the settings that should be used when running a package from a
+    /// particular namespace.
+    pub fn with_overrides(&self, namespace: &str) -> Cow<'_, Experiment> {
+        match self.overrides.get(namespace) {
+            Some(overrides) => Cow::Owned(overrides.apply(self)),
+            None => Cow::Borrowed(self),
+        }
+    }
+
+    /// Get the settings that should be used when running one of the `local`
+    /// package's variants.
+    pub fn with_variant(&self, variant: &str) -> Cow<'_, Experiment> {
+        match self.local.as_ref().and_then(|l| l.variants.get(variant)) {
+            Some(overrides) => Cow::Owned(overrides.apply(self)),
+            None => Cow::Borrowed(self),
+        }
+    }
+}
+
+/// Settings that override the experiment's defaults for every package in a
+/// namespace.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Overrides {
+    /// Arguments to pass to the package instead of the experiment's `args`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<TemplatedString>>,
+    /// Extra environment variables, merged with the experiment's `env`.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub env: IndexMap<String, TemplatedString>,
+    /// A timeout to use instead of the experiment's `timeout`.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "humantime_serde"
+    )]
+    #[cfg_attr(test, schemars(with = "Option<String>"))]
+    pub timeout: Option<Duration>,
+    /// Expectations to use instead of the experiment's `expectations`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expectations: Option<Expectations>,
+}
+
+impl Overrides {
+    fn apply(&self, experiment: &Experiment) -> Experiment {
+        let Overrides {
+            args,
+            env,
+            timeout,
+            expectations,
+        } = self;
+
+        let mut experiment = experiment.clone();
+
+        if let Some(args) = args {
+            experiment.args = args.clone();
+        }
+        experiment
+            .env
+            .extend(env.iter().map(|(k, v)| (k.clone(), v.clone())));
+        if let Some(timeout) = timeout {
+            experiment.timeout = Some(*timeout);
+        }
+        if let Some(expectations) = expectations {
+            experiment.expectations = expectations.clone();
+        }
+
+        experiment
+    }
+}
+
+/// A package on the local filesystem, tested instead of packages from the
+/// registry.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct LocalPackage {
+    /// A `*.webc` file or a directory containing a `wasmer.toml`.
+    ///
+    /// Relative paths are resolved against the directory containing the
+    /// experiment file.
+    pub path: PathBuf,
+    /// Run the package once for each variant, keyed by name, with the
+    /// variant's settings applied on top of the experiment's.
+    ///
+    /// Without any variants, the package is run once.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub variants: IndexMap<String, Overrides>,
+}
+
+/// A specific version of a package, listed in the experiment rather than
+/// discovered from the registry.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PinnedTestCase {
+    /// The registry the package is published to (e.g. `wasmer.io`),
+    /// defaulting to the one the experiment is run against.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    pub namespace: String,
+    pub name: String,
+    pub version: String,
+}
+
+/// The criteria used to decide whether a test case passed.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Expectations {
+    /// The exit code a successful run should finish with.
+    #[serde(default)]
+    pub exit_code: i32,
+}
+
+impl Expectations {
+    fn is_default(&self) -> bool {
+        *self == Expectations::default()
+    }
+}
+
+/// What packages are allowed to do, so experiments are explicit about it and
+/// results are comparable across machines.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Capabilities {
+    /// Whether packages may access the network.
+    ///
+    /// When this isn't set, the `wasmer` CLI's default is used.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub network: Option<bool>,
+}
+
+impl Capabilities {
+    fn is_default(&self) -> bool {
+        *self == Capabilities::default()
+    }
+}
+
+/// How each test case is run when benchmarking.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Benchmark {
+    /// The number of times each test case is run before measurements start,
+    /// so caches are warm.
+    #[serde(default = "Benchmark::default_warm_up")]
+    pub warm_up: usize,
+    /// The number of runs to measure.
+    #[serde(default = "Benchmark::default_iterations")]
+    pub iterations: usize,
+}
+
+impl Benchmark {
+    fn default_warm_up() -> usize {
+        1
+    }
+
+    fn default_iterations() -> usize {
+        10
+    }
+}
+
+impl Default for Benchmark {
+    fn default() -> Self {
+        Benchmark {
+            warm_up: Benchmark::default_warm_up(),
+            iterations: Benchmark::default_iterations(),
+        }
+    }
+}
+
+/// Shell commands that are run at various points in an experiment's
+/// lifecycle.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Hooks {
+    /// Commands to run before any packages are discovered (e.g. `wasmer
+    /// login` or starting a local registry).
+    ///
+    /// Each command is run from the experiment directory with the
+    /// `$BOREALIS_EXPERIMENT_DIR` environment variable set, and the experiment
+    /// is aborted if any of them fail.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pre_run: Vec<String>,
+    /// Commands to run after the experiment's results have been written to
+    /// disk.
+    ///
+    /// Each command is run from the experiment directory and the
+    /// `$BOREALIS_RESULTS`, `$BOREALIS_REPORT`, `$BOREALIS_TOTAL`,
+    /// `$BOREALIS_SUCCESS`, `$BOREALIS_FAILURES`, and `$BOREALIS_BUGS`
+    /// environment variables are set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub post_run: Vec<String>,
+}
+
+impl Hooks {
+    pub(crate) fn is_empty(&self) -> bool {
+        let Hooks { pre_run, post_run } = self;
+        pre_run.is_empty() && post_run.is_empty()
+    }
+}
+
+/// A command which inspects a test case after it has run (e.g. to look for a
+/// particular error message in its `stderr.txt`).
+///
+/// The command is run from the test case's directory with the
+/// `$BOREALIS_TEST_DIR`, `$BOREALIS_OUTCOME`, `$BOREALIS_EXIT_CODE`,
+/// `$PKG_NAMESPACE`, `$PKG_NAME`, and `$PKG_VERSION` environment variables
+/// set. Anything it writes to stdout is parsed as JSON and attached to the
+/// report, along with its exit code.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Analyzer {
+    /// A name used to identify this analyzer's results.
+    pub name: String,
+    /// The shell command to run.
+    pub command: String,
 }
 
 /// Configuration for the `wasmer` CLI being used.
@@ -73,11 +610,184 @@ pub struct WasmerConfig {
     /// Environment variables passed to the `wasmer` CLI.
     #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
     pub env: IndexMap<String, TemplatedString>,
+    /// Which `$WASMER_DIR` each test case should use.
+    #[serde(default, skip_serializing_if = "WasmerDir::is_inherit")]
+    pub wasmer_dir: WasmerDir,
+    /// Where the `wasmer` CLI should cache compiled modules.
+    #[serde(default, skip_serializing_if = "ModuleCache::is_default")]
+    pub module_cache: ModuleCache,
+    /// Experimental WebAssembly features to enable.
+    #[serde(default, skip_serializing_if = "WasmerFeatures::is_empty")]
+    pub features: WasmerFeatures,
+    /// Collect a core dump when a test case crashes.
+    ///
+    /// This uses `--coredump-on-trap` when the `wasmer` CLI supports it, and
+    /// falls back to the operating system's core dumps otherwise.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub coredumps: bool,
+    /// A `$RUST_LOG` filter (e.g. `"wasmer_wasix=debug"`) for the `wasmer`
+    /// CLI's own logging.
+    ///
+    /// The logs are moved out of the guest's `stderr.txt` and into a
+    /// `wasmer.log` file in the test case's directory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log: Option<String>,
+    /// Run each test case by name (`wasmer run namespace/name@version`) and
+    /// let the `wasmer` CLI download it from the registry, instead of running
+    /// the experiment's `package` against a copy borealis downloaded.
+    ///
+    /// This exercises wasmer's own registry client and the CDN. The
+    /// experiment's `package` is ignored.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub by_name: bool,
 }
 
 fn should_show_wasmer_config(cfg: &WasmerConfig) -> bool {
-    let WasmerConfig { version, args, env } = cfg;
-    version.is_latest() && args.is_empty() && env.is_empty()
+    let WasmerConfig {
+        version,
+        args,
+        env,
+        wasmer_dir,
+        module_cache,
+        features,
+        coredumps,
+        log,
+        by_name,
+    } = cfg;
+    version.is_latest()
+        && args.is_empty()
+        && env.is_empty()
+        && wasmer_dir.is_inherit()
+        && module_cache.is_default()
+        && features.is_empty()
+        && !coredumps
+        && log.is_none()
+        && !by_name
+}
+
+/// Experimental WebAssembly features which the `wasmer` CLI can enable.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct WasmerFeatures {
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub threads: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub simd: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bulk_memory: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reference_types: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub multi_value: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub memory64: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub exceptions: bool,
+}
+
+/// A feature's name, the `wasmer` CLI flag which enables it, and the first
+/// release with that flag.
+type FeatureFlag = (&'static str, &'static str, Version);
+
+impl WasmerFeatures {
+    fn is_empty(&self) -> bool {
+        *self == WasmerFeatures::default()
+    }
+
+    fn enabled(&self) -> Vec<FeatureFlag> {
+        let WasmerFeatures {
+            threads,
+            simd,
+            bulk_memory,
+            reference_types,
+            multi_value,
+            memory64,
+            exceptions,
+        } = *self;
+
+        [
+            (
+                threads,
+                "threads",
+                "--enable-threads",
+                Version::new(1, 0, 0),
+            ),
+            (simd, "simd", "--enable-simd", Version::new(1, 0, 0)),
+            (
+                bulk_memory,
+                "bulk-memory",
+                "--enable-bulk-memory",
+                Version::new(1, 0, 0),
+            ),
+            (
+                reference_types,
+                "reference-types",
+                "--enable-reference-types",
+                Version::new(1, 0, 0),
+            ),
+            (
+                multi_value,
+                "multi-value",
+                "--enable-multi-value",
+                Version::new(1, 0, 0),
+            ),
+            (
+                memory64,
+                "memory64",
+                "--enable-memory64",
+                Version::new(6, 0, 0),
+            ),
+            (
+                exceptions,
+                "exceptions",
+                "--enable-exceptions",
+                Version::new(6, 0, 0),
+            ),
+        ]
+        .into_iter()
+        .filter(|(enabled, ..)| *enabled)
+        .map(|(_, name, flag, since)| (name, flag, since))
+        .collect()
+    }
+
+    /// The flags to pass to `wasmer run`.
+    pub fn flags(&self) -> Vec<&'static str> {
+        self.enabled()
+            .into_iter()
+            .map(|(_, flag, _)| flag)
+            .collect()
+    }
+
+    /// The names of any enabled features which this version of the `wasmer`
+    /// CLI doesn't support.
+    pub fn unsupported_by(&self, version: &Version) -> Vec<&'static str> {
+        self.enabled()
+            .into_iter()
+            .filter(|(_, _, since)| version < since)
+            .map(|(name, ..)| name)
+            .collect()
+    }
+}
+
+/// How the `$WASMER_DIR` (where the `wasmer` CLI keeps its config, registry
+/// credentials, and caches) is set up for each test case.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum WasmerDir {
+    /// Use the host's `$WASMER_DIR`.
+    #[default]
+    Inherit,
+    /// Give each test case its own empty `$WASMER_DIR` so state can't leak
+    /// between test cases.
+    Isolated,
+}
+
+impl WasmerDir {
+    fn is_inherit(&self) -> bool {
+        matches!(self, WasmerDir::Inherit)
+    }
 }
 
 /// The `wasmer` CLI version to use.
@@ -91,9 +801,24 @@ pub enum WasmerVersion {
         /// The path.
         path: PathBuf,
     },
+    /// Build the `wasmer` CLI from a particular git revision.
+    Git {
+        /// The repository to clone (e.g. `https://github.com/wasmerio/wasmer`).
+        git: String,
+        /// The commit, branch, or tag to build.
+        rev: String,
+    },
     /// A released version.
     #[cfg_attr(test, schemars(with = "VersionRef"))]
     Release(Version),
+    /// Every release matching a version requirement (e.g. `">=4.0, <4.3"`).
+    ///
+    /// When running a single experiment, the most recent matching release is
+    /// used.
+    #[cfg_attr(test, schemars(with = "String"))]
+    Range(VersionReq),
+    /// A release channel (e.g. `"nightly"`).
+    Channel(Channel),
     /// Use the most recent version.
     #[default]
     Latest,
@@ -105,6 +830,92 @@ impl WasmerVersion {
     }
 }
 
+impl std::str::FromStr for WasmerVersion {
+    type Err = std::convert::Infallible;
+
+    /// Parse a version the same way as the experiment file, treating anything
+    /// that isn't a version, range, or channel as the path to a local binary.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let version = match s {
+            "latest" => WasmerVersion::Latest,
+            "nightly" => WasmerVersion::Channel(Channel::Nightly),
+            _ => {
+                if let Ok(v) = s.parse() {
+                    WasmerVersion::Release(v)
+                } else if let Ok(req) = s.parse() {
+                    WasmerVersion::Range(req)
+                } else {
+                    WasmerVersion::Local { path: s.into() }
+                }
+            }
+        };
+
+        Ok(version)
+    }
+}
+
+/// A cap on how much of a test case's output is kept.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct OutputLimit {
+    /// The most bytes to keep from each of stdout and stderr.
+    pub max_bytes: u64,
+    /// What to do when a test case writes more than this.
+    #[serde(default)]
+    pub action: OutputLimitAction,
+}
+
+/// What happens when a test case goes over its [`OutputLimit`].
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputLimitAction {
+    /// Keep running, but only keep the start and end of the output with a
+    /// marker showing how much was dropped in between.
+    #[default]
+    Truncate,
+    /// Kill the test case, recording an "output-limit-exceeded" outcome.
+    Kill,
+}
+
+/// How compiled modules are cached between test cases.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum ModuleCache {
+    /// Let the `wasmer` CLI use its normal module cache.
+    #[default]
+    Default,
+    /// Point every test case at one module cache inside the experiment
+    /// directory.
+    Shared,
+    /// Use a shared module cache and run each test case once before the real
+    /// run so its modules are already compiled, meaning run times only
+    /// measure runtime behaviour.
+    Warm,
+    /// Give each test case its own empty module cache and run it twice, once
+    /// cold and once warm, recording both run times.
+    ///
+    /// This shows how much the module cache actually helps each package.
+    ColdAndWarm,
+}
+
+impl ModuleCache {
+    fn is_default(&self) -> bool {
+        matches!(self, ModuleCache::Default)
+    }
+}
+
+/// A named release channel for the `wasmer` CLI.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum Channel {
+    /// The most recent pre-release published on GitHub.
+    Nightly,
+}
+
 /// A string that supports environment variable interpolation.
 #[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
 #[cfg_attr(test, derive(schemars::JsonSchema))]
@@ -145,17 +956,183 @@ pub struct Filters {
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub users: Vec<String>,
     /// Packages that should be ignored.
+    ///
+    /// Entries may contain `*` wildcards and an optional version (e.g.
+    /// `wasmer/python`, `wasmer/*`, or `wasmer/python@1.2.*`). An entry
+    /// starting with `!` brings back packages an earlier entry ignored.
+    ///
+    /// Entries from a `.borealisignore` file next to the experiment are
+    /// added to the end of this list.
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub blacklist: Vec<String>,
     /// Should every version of the package be published, or just the most
     /// recent one?
     #[serde(default, skip_serializing_if = "std::ops::Not::not")]
     pub include_every_version: bool,
+    /// Test (at most) this many of the most recent versions of each package.
+    ///
+    /// Versions are ordered by their semver version number, falling back to
+    /// when they were published.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_versions_per_package: Option<usize>,
+    /// Only include versions published at or after this time (e.g.
+    /// `"2023-06-01"` or `"2023-06-01T12:00:00Z"`).
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "date_or_datetime"
+    )]
+    #[cfg_attr(test, schemars(with = "Option<String>"))]
+    pub published_after: Option<DateTime<Utc>>,
+    /// Only include versions published before this time.
+    ///
+    /// When only the latest version of each package is being tested, the
+    /// most recent version published before this time is used instead. This
+    /// can be used to reproduce a historical snapshot of the registry.
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        with = "date_or_datetime"
+    )]
+    #[cfg_attr(test, schemars(with = "Option<String>"))]
+    pub published_before: Option<DateTime<Utc>>,
 }
 
 impl Filters {
     fn is_empty(&self) -> bool {
-        self.namespaces.is_empty() && self.blacklist.is_empty()
+        self.namespaces.is_empty()
+            && self.blacklist.is_empty()
+            && self.max_versions_per_package.is_none()
+            && self.published_after.is_none()
+            && self.published_before.is_none()
+    }
+
+    /// Was something published within the `published-after` and
+    /// `published-before` range?
+    ///
+    /// Versions with an unknown publish date are only included when no range
+    /// was specified.
+    pub fn published_within_range(&self, published: Option<DateTime<Utc>>) -> bool {
+        if self.published_after.is_none() && self.published_before.is_none() {
+            return true;
+        }
+
+        let Some(published) = published else {
+            return false;
+        };
+
+        self.published_after
+            .map_or(true, |after| published >= after)
+            && self
+                .published_before
+                .map_or(true, |before| published < before)
+    }
+
+    /// Does a package version (e.g. `wasmer/python` and `1.2.0`) match any
+    /// of the [`Filters::blacklist`] entries?
+    ///
+    /// Like a `.gitignore` file, the last matching entry wins, so a `!entry`
+    /// can undo an earlier one.
+    pub fn is_blacklisted(&self, display_name: &str, version: &str) -> bool {
+        let mut blacklisted = false;
+
+        for entry in &self.blacklist {
+            let (negated, entry) = match entry.strip_prefix('!') {
+                Some(entry) => (true, entry),
+                None => (false, entry.as_str()),
+            };
+            let (name_pattern, version_pattern) = match entry.split_once('@') {
+                Some((name, version)) => (name, Some(version)),
+                None => (entry, None),
+            };
+
+            if glob_matches(name_pattern, display_name)
+                && version_pattern.map_or(true, |pattern| glob_matches(pattern, version))
+            {
+                blacklisted = !negated;
+            }
+        }
+
+        blacklisted
+    }
+}
+
+/// The name of an optional file next to an experiment document which adds
+/// entries to its [`Filters::blacklist`].
+pub const IGNORE_FILE: &str = ".borealisignore";
+
+/// Parse an ignore file (see [`IGNORE_FILE`]), which has one
+/// [`Filters::blacklist`] entry per line.
+///
+/// Blank lines and lines starting with `#` are skipped. Use `\#` for an
+/// entry which really does start with a `#`.
+pub fn parse_ignore_file(src: &str) -> Vec<String> {
+    src.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.strip_prefix('\\').unwrap_or(line).to_string())
+        .collect()
+}
+
+/// Match some text against a pattern where `*` matches any sequence of
+/// characters.
+fn glob_matches(pattern: &str, text: &str) -> bool {
+    let Some((first, rest)) = pattern.split_once('*') else {
+        return pattern == text;
+    };
+
+    let Some(mut remaining) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let mut pieces: Vec<&str> = rest.split('*').collect();
+    let last = pieces.pop().unwrap_or_default();
+
+    for piece in pieces {
+        match remaining.find(piece) {
+            Some(index) => remaining = &remaining[index + piece.len()..],
+            None => return false,
+        }
+    }
+
+    remaining.len() >= last.len() && remaining.ends_with(last)
+}
+
+/// (De)serialize an optional timestamp, where a plain date (e.g.
+/// `"2023-06-01"`) is interpreted as midnight UTC.
+mod date_or_datetime {
+    use chrono::{DateTime, NaiveDate, Utc};
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S>(value: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match value {
+            Some(timestamp) => serializer.serialize_str(&timestamp.to_rfc3339()),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let Some(raw) = Option::<String>::deserialize(deserializer)? else {
+            return Ok(None);
+        };
+
+        if let Ok(timestamp) = DateTime::parse_from_rfc3339(&raw) {
+            return Ok(Some(timestamp.with_timezone(&Utc)));
+        }
+
+        let date = NaiveDate::parse_from_str(&raw, "%Y-%m-%d").map_err(|_| {
+            D::Error::custom(format!(
+                "Expected a date (YYYY-MM-DD) or RFC 3339 timestamp, found \"{raw}\""
+            ))
+        })?;
+
+        Ok(Some(date.and_hms_opt(0, 0, 0).unwrap().and_utc()))
     }
 }
 
@@ -168,59 +1145,287 @@ struct VersionRef(String);
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::{ensure_file_contents, project_root};
 
     #[test]
     fn experiment_schema_is_up_to_date() {
         let project_root = project_root();
         let dest = project_root.join("experiment.schema.json");
-        let schema = schemars::schema_for!(Experiment);
+        let mut schema = schemars::schema_for!(Experiment);
+        // The version belongs to the Document, not the Experiment
+        let mut version = schemars::schema_for!(u32).schema;
+        version.metadata().description =
+            Some("The version of the document format this was written with.".to_string());
+        schema
+            .schema
+            .object()
+            .properties
+            .insert("version".to_string(), version.into());
         let schema = serde_json::to_string_pretty(&schema).unwrap();
 
         ensure_file_contents(dest, schema);
     }
 
-    /// Get the root directory for this repository.
-    fn project_root() -> &'static Path {
-        let root_dir = Path::new(env!("CARGO_MANIFEST_DIR"))
-            .ancestors()
-            .nth(2)
-            .unwrap();
-        assert!(root_dir.join(".git").exists());
+    #[test]
+    fn namespace_overrides_are_merged_with_the_defaults() {
+        let experiment: Experiment = serde_json::from_value(serde_json::json!({
+            "package": "wasmer/python",
+            "args": ["--version"],
+            "env": { "A": "1", "B": "2" },
+            "timeout": "1m",
+            "overrides": {
+                "syrusakbary": {
+                    "env": { "B": "3" },
+                    "timeout": "5m",
+                    "expectations": { "exit-code": 1 },
+                },
+            },
+        }))
+        .unwrap();
 
-        root_dir
+        let unchanged = experiment.with_overrides("wasmer");
+        assert_eq!(*unchanged, experiment);
+
+        let overridden = experiment.with_overrides("syrusakbary");
+        assert_eq!(overridden.args, experiment.args);
+        assert_eq!(overridden.env["A"].as_str(), "1");
+        assert_eq!(overridden.env["B"].as_str(), "3");
+        assert_eq!(overridden.timeout, Some(Duration::from_secs(5 * 60)));
+        assert_eq!(overridden.expectations.exit_code, 1);
     }
 
-    /// Check that a particular file has the desired contents.
-    ///
-    /// If the file is missing or outdated, this function will update the file and
-    /// trigger a panic to fail any test this is called from.
-    fn ensure_file_contents(path: impl AsRef<Path>, contents: impl AsRef<str>) {
-        let path = path.as_ref();
-        let contents = normalize_newlines(contents.as_ref());
+    #[test]
+    fn blacklist_entries_support_globs_and_versions() {
+        let filters = Filters {
+            blacklist: vec![
+                "wasmer/python".to_string(),
+                "broken/*".to_string(),
+                "wasmer/php@8.*".to_string(),
+                "*/qjs@*-rc*".to_string(),
+            ],
+            ..Default::default()
+        };
 
-        if let Ok(old_contents) = std::fs::read_to_string(path) {
-            if contents == normalize_newlines(&old_contents) {
-                // File is already up to date
-                return;
-            }
-        }
+        assert!(filters.is_blacklisted("wasmer/python", "3.12.0"));
+        assert!(filters.is_blacklisted("broken/anything", "1.0.0"));
+        assert!(filters.is_blacklisted("wasmer/php", "8.1.0"));
+        assert!(!filters.is_blacklisted("wasmer/php", "7.4.0"));
+        assert!(filters.is_blacklisted("saghul/qjs", "1.0.0-rc1"));
+        assert!(!filters.is_blacklisted("saghul/qjs", "1.0.0"));
+        assert!(!filters.is_blacklisted("wasmer/python-ext", "1.0.0"));
+        assert!(!filters.is_blacklisted("notbroken/thing", "1.0.0"));
+    }
 
-        let display_path = path.strip_prefix(project_root()).unwrap_or(path);
+    #[test]
+    fn later_blacklist_entries_can_bring_packages_back() {
+        let filters = Filters {
+            blacklist: vec![
+                "wasmer/*".to_string(),
+                "!wasmer/python".to_string(),
+                "wasmer/python@2.*".to_string(),
+            ],
+            ..Default::default()
+        };
 
-        eprintln!("{} was not up-to-date, updating...", display_path.display());
+        assert!(filters.is_blacklisted("wasmer/php", "8.1.0"));
+        assert!(!filters.is_blacklisted("wasmer/python", "3.12.0"));
+        assert!(filters.is_blacklisted("wasmer/python", "2.7.0"));
+    }
+
+    #[test]
+    fn parse_an_ignore_file() {
+        let src = r"
+            # Known to hang
+            wasmer/python@2.*
+
+            broken/*
+            \#not-a-comment
+            !broken/fixed
+        ";
+
+        let entries = parse_ignore_file(src);
+
+        assert_eq!(
+            entries,
+            [
+                "wasmer/python@2.*",
+                "broken/*",
+                "#not-a-comment",
+                "!broken/fixed"
+            ]
+        );
+    }
+
+    #[test]
+    fn publish_dates_can_be_dates_or_timestamps() {
+        let filters: Filters = serde_json::from_value(serde_json::json!({
+            "published-after": "2023-06-01",
+            "published-before": "2023-07-01T12:30:00+02:00",
+        }))
+        .unwrap();
+
+        let date = |s: &str| Some(DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc));
+        assert_eq!(filters.published_after, date("2023-06-01T00:00:00Z"));
+        assert_eq!(filters.published_before, date("2023-07-01T10:30:00Z"));
+        assert!(filters.published_within_range(date("2023-06-01T00:00:00Z")));
+        assert!(!filters.published_within_range(date("2023-07-01T10:30:00Z")));
+        assert!(!filters.published_within_range(None));
+        assert!(Filters::default().published_within_range(None));
+    }
+
+    #[test]
+    fn parse_wasmer_versions() {
+        let inputs = [
+            (serde_json::json!(null), WasmerVersion::Latest),
+            (
+                serde_json::json!("4.2.0"),
+                WasmerVersion::Release("4.2.0".parse().unwrap()),
+            ),
+            (
+                serde_json::json!(">=4.0, <4.3"),
+                WasmerVersion::Range(">=4.0, <4.3".parse().unwrap()),
+            ),
+            (
+                serde_json::json!("nightly"),
+                WasmerVersion::Channel(Channel::Nightly),
+            ),
+            (
+                serde_json::json!({ "path": "/usr/bin/wasmer" }),
+                WasmerVersion::Local {
+                    path: "/usr/bin/wasmer".into(),
+                },
+            ),
+            (
+                serde_json::json!({ "git": "https://github.com/wasmerio/wasmer", "rev": "abc123" }),
+                WasmerVersion::Git {
+                    git: "https://github.com/wasmerio/wasmer".into(),
+                    rev: "abc123".into(),
+                },
+            ),
+        ];
 
-        if std::env::var("CI").is_ok() {
-            eprintln!("Note: run `cargo test` locally and commit the updated files");
+        for (input, expected) in inputs {
+            let version: WasmerVersion = serde_json::from_value(input).unwrap();
+            assert_eq!(version, expected);
         }
+    }
+
+    #[test]
+    fn wasmer_features_are_checked_against_the_version() {
+        let features: WasmerFeatures = serde_json::from_value(serde_json::json!({
+            "threads": true,
+            "memory64": true,
+        }))
+        .unwrap();
+
+        assert_eq!(features.flags(), ["--enable-threads", "--enable-memory64"]);
+        assert_eq!(
+            features.unsupported_by(&Version::new(4, 2, 0)),
+            ["memory64"]
+        );
+        assert!(features.unsupported_by(&Version::new(6, 0, 0)).is_empty());
+        assert!(WasmerFeatures::default().flags().is_empty());
+    }
+
+    #[test]
+    fn misspelled_fields_are_rejected_with_a_suggestion() {
+        let src = r#"{
+  "$schema": "https://example.com/experiment.schema.json",
+  "package": "wasmer/python",
+  "wasmmer": {
+    "args": []
+  }
+}"#;
 
-        if let Some(parent) = path.parent() {
-            let _ = std::fs::create_dir_all(parent);
+        let error = Document::from_json(src).unwrap_err().to_string();
+
+        assert!(error.starts_with("unknown field `wasmmer`"), "{error}");
+        assert!(
+            error.contains("at line 4, did you mean `wasmer`?"),
+            "{error}"
+        );
+        assert!(error.contains(r#" 4 |   "wasmmer": {"#), "{error}");
+    }
+
+    #[test]
+    fn unversioned_documents_are_the_first_version() {
+        let src = r#"{ "$schema": "...", "package": "wasmer/python" }"#;
+
+        let doc = Document::from_json(src).unwrap();
+
+        assert_eq!(doc.version, 1);
+        assert_eq!(doc.experiment.package, "wasmer/python");
+    }
+
+    #[test]
+    fn documents_from_the_future_are_rejected() {
+        let src = r#"{ "$schema": "...", "version": 999, "package": "wasmer/python" }"#;
+
+        let error = Document::from_json(src).unwrap_err().to_string();
+
+        assert!(
+            error.contains("version 999 of the document format"),
+            "{error}"
+        );
+    }
+
+    #[test]
+    fn old_documents_are_migrated_one_version_at_a_time() {
+        fn rename_blacklist(doc: &mut serde_json::Map<String, serde_json::Value>) -> Vec<String> {
+            let Some(filters) = doc.get_mut("filters").and_then(|f| f.as_object_mut()) else {
+                return Vec::new();
+            };
+            match filters.remove("ignore") {
+                Some(ignored) => {
+                    filters.insert("blacklist".to_string(), ignored);
+                    vec!["\"filters.ignore\" was renamed to \"filters.blacklist\"".to_string()]
+                }
+                None => Vec::new(),
+            }
         }
-        std::fs::write(path, contents).unwrap();
-        panic!("some file was not up to date and has been updated. Please re-run the tests.");
+        let migrations = [Migration {
+            from: DOCUMENT_VERSION - 1,
+            apply: rename_blacklist,
+        }];
+        let doc = serde_json::json!({
+            "package": "wasmer/python",
+            "filters": { "ignore": ["wasmer/*"] },
+        });
+
+        let (doc, changes) = migrate(doc, DOCUMENT_VERSION - 1, &migrations).unwrap();
+
+        assert_eq!(
+            doc,
+            serde_json::json!({
+                "version": DOCUMENT_VERSION,
+                "package": "wasmer/python",
+                "filters": { "blacklist": ["wasmer/*"] },
+            })
+        );
+        assert_eq!(changes.len(), 1);
     }
 
-    fn normalize_newlines(s: &str) -> String {
-        s.replace("\r\n", "\n")
+    #[test]
+    fn parse_wasmer_versions_from_the_command_line() {
+        let inputs = [
+            ("latest", WasmerVersion::Latest),
+            ("4.2.0", WasmerVersion::Release("4.2.0".parse().unwrap())),
+            (
+                ">=4.0, <4.3",
+                WasmerVersion::Range(">=4.0, <4.3".parse().unwrap()),
+            ),
+            ("nightly", WasmerVersion::Channel(Channel::Nightly)),
+            (
+                "./target/release/wasmer",
+                WasmerVersion::Local {
+                    path: "./target/release/wasmer".into(),
+                },
+            ),
+        ];
+
+        for (input, expected) in inputs {
+            let version: WasmerVersion = input.parse().unwrap();
+            assert_eq!(version, expected);
+        }
     }
 }
@@ -0,0 +1,267 @@
+use std::{path::Path, process::Stdio, time::Duration};
+
+use anyhow::{Context, Error};
+use tokio::io::AsyncReadExt;
+
+use crate::{
+    config::Analyzer,
+    experiment::{
+        hooks,
+        process::{self, ProcessTree},
+        Analysis, Outcome, TestCase,
+    },
+};
+
+/// How long an analyzer may run for when the experiment doesn't have a
+/// timeout.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// The most an analyzer may write to stdout before we stop reading it.
+const MAX_OUTPUT: u64 = 1024 * 1024;
+
+/// Run each [`Analyzer`] against a test case that has finished running.
+///
+/// Analyzers are only run when the package was actually executed (i.e. it
+/// completed or timed out), and a failing analyzer is recorded in its
+/// [`Analysis`] rather than affecting the test case's outcome.
+///
+/// Each analyzer is killed if it takes longer than `timeout` (the
+/// experiment's timeout, if it has one).
+pub(crate) async fn analyze(
+    analyzers: &[Analyzer],
+    test_case: &TestCase,
+    outcome: &Outcome,
+    timeout: Option<Duration>,
+) -> Vec<Analysis> {
+    let timeout = timeout.unwrap_or(DEFAULT_TIMEOUT);
+    let (base_dir, exit_code) = match outcome {
+        Outcome::Completed {
+            base_dir, status, ..
+        } => (base_dir, Some(status.code)),
+        Outcome::TimedOut { base_dir, .. } | Outcome::OutputLimitExceeded { base_dir, .. } => {
+            (base_dir, None)
+        }
+        _ => return Vec::new(),
+    };
+
+    let mut analyses = Vec::new();
+
+    for analyzer in analyzers {
+        let analysis = match run(analyzer, test_case, outcome, base_dir, exit_code, timeout).await {
+            Ok(analysis) => analysis,
+            Err(e) => {
+                tracing::warn!(
+                    analyzer = analyzer.name.as_str(),
+                    error = &*e,
+                    "Unable to run the analyzer",
+                );
+                Analysis {
+                    name: analyzer.name.clone(),
+                    exit_code: None,
+                    output: None,
+                    error: Some(format!("{e:#}")),
+                }
+            }
+        };
+        analyses.push(analysis);
+    }
+
+    analyses
+}
+
+#[tracing::instrument(skip_all, fields(analyzer = analyzer.name.as_str()))]
+async fn run(
+    analyzer: &Analyzer,
+    test_case: &TestCase,
+    outcome: &Outcome,
+    base_dir: &Path,
+    exit_code: Option<i32>,
+    timeout: Duration,
+) -> Result<Analysis, Error> {
+    let Analyzer { name, command } = analyzer;
+
+    let outcome = serde_json::to_value(outcome)?;
+    let mut cmd = tokio::process::Command::from(hooks::shell(command));
+    cmd.current_dir(base_dir)
+        .env("BOREALIS_TEST_DIR", base_dir)
+        .env(
+            "BOREALIS_OUTCOME",
+            outcome["outcome"].as_str().unwrap_or_default(),
+        )
+        .env(
+            "BOREALIS_EXIT_CODE",
+            exit_code.map(|c| c.to_string()).unwrap_or_default(),
+        )
+        .env("PKG_NAMESPACE", &test_case.namespace)
+        .env("PKG_NAME", &test_case.package_name)
+        .env("PKG_VERSION", test_case.version())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .kill_on_drop(true);
+    process::isolate(&mut cmd);
+
+    tracing::debug!(command, "Running analyzer");
+    let mut child = cmd
+        .spawn()
+        .with_context(|| format!("Unable to run \"{command}\""))?;
+    // Takes out the analyzer and anything it started if we bail early
+    let mut tree = ProcessTree::new(child.id());
+    let mut stdout = child.stdout.take().expect("stdout is piped");
+
+    let finished = tokio::time::timeout(timeout, async {
+        let mut output = Vec::new();
+        (&mut stdout)
+            .take(MAX_OUTPUT + 1)
+            .read_to_end(&mut output)
+            .await?;
+        // Stop reading so an analyzer with too much to say can't block on
+        // a full pipe
+        drop(stdout);
+        let status = child.wait().await?;
+        Ok::<_, std::io::Error>((status, output))
+    })
+    .await;
+
+    let Ok(finished) = finished else {
+        tree.kill().context("Unable to kill the analyzer")?;
+        anyhow::bail!("The analyzer timed out after {timeout:?}");
+    };
+    let (status, output) = finished.with_context(|| format!("Unable to run \"{command}\""))?;
+    tree.disarm();
+
+    let stdout = String::from_utf8_lossy(&output);
+    let (output_json, error) = if output.len() as u64 > MAX_OUTPUT {
+        (
+            None,
+            Some(format!(
+                "The output was larger than {}",
+                crate::render::filesize(MAX_OUTPUT)
+            )),
+        )
+    } else if stdout.trim().is_empty() {
+        (None, None)
+    } else {
+        match serde_json::from_str(&stdout) {
+            Ok(value) => (Some(value), None),
+            Err(e) => (
+                None,
+                Some(format!("Unable to parse the output as JSON: {e}")),
+            ),
+        }
+    };
+
+    Ok(Analysis {
+        name: name.clone(),
+        exit_code: status.code(),
+        output: output_json,
+        error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{experiment::results::ExitStatus, test_utils::test_case};
+
+    #[tokio::test]
+    async fn analyzer_output_is_attached_to_the_report() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join("stderr.txt"), "ENOSYS").unwrap();
+        let analyzers = vec![
+            Analyzer {
+                name: "syscalls".to_string(),
+                command: r#"grep -q ENOSYS stderr.txt && echo "{\"pkg\": \"$PKG_NAME\"}"; exit 2"#
+                    .to_string(),
+            },
+            Analyzer {
+                name: "broken".to_string(),
+                command: "echo not json".to_string(),
+            },
+        ];
+        let test_case = test_case("python", "1.0.0");
+        let outcome = Outcome::Completed {
+            status: ExitStatus {
+                success: false,
+                code: 1,
+            },
+            run_time: Duration::from_secs(1),
+            base_dir: temp.path().to_path_buf(),
+        };
+
+        let analyses = analyze(&analyzers, &test_case, &outcome, None).await;
+
+        assert_eq!(analyses.len(), 2);
+        assert_eq!(analyses[0].exit_code, Some(2));
+        assert_eq!(
+            analyses[0].output,
+            Some(serde_json::json!({"pkg": "python"}))
+        );
+        assert_eq!(analyses[0].error, None);
+        assert_eq!(analyses[1].output, None);
+        assert!(analyses[1].error.is_some());
+    }
+
+    #[tokio::test]
+    async fn slow_analyzers_are_killed() {
+        let temp = tempfile::tempdir().unwrap();
+        let analyzers = vec![Analyzer {
+            name: "slow".to_string(),
+            command: "sleep 30".to_string(),
+        }];
+        let outcome = Outcome::Completed {
+            status: ExitStatus {
+                success: true,
+                code: 0,
+            },
+            run_time: Duration::from_secs(1),
+            base_dir: temp.path().to_path_buf(),
+        };
+        let start = std::time::Instant::now();
+
+        let analyses = analyze(
+            &analyzers,
+            &test_case("python", "1.0.0"),
+            &outcome,
+            Some(Duration::from_millis(100)),
+        )
+        .await;
+
+        assert!(start.elapsed() < Duration::from_secs(10));
+        assert_eq!(analyses[0].exit_code, None);
+        assert!(analyses[0].error.as_ref().unwrap().contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn analyzer_output_is_capped() {
+        let temp = tempfile::tempdir().unwrap();
+        let analyzers = vec![Analyzer {
+            name: "chatty".to_string(),
+            command: "yes".to_string(),
+        }];
+        let outcome = Outcome::Completed {
+            status: ExitStatus {
+                success: true,
+                code: 0,
+            },
+            run_time: Duration::from_secs(1),
+            base_dir: temp.path().to_path_buf(),
+        };
+
+        let analyses = analyze(
+            &analyzers,
+            &test_case("python", "1.0.0"),
+            &outcome,
+            Some(Duration::from_secs(30)),
+        )
+        .await;
+
+        assert_eq!(analyses[0].output, None);
+        assert_eq!(
+            analyses[0].error.as_deref(),
+            Some("The output was larger than 1.0 MiB")
+        );
+    }
+}
@@ -0,0 +1,187 @@
+use std::time::Duration;
+
+use crate::experiment::{diff::by_test_case, Results};
+
+/// A benchmark must be at least this much slower than its baseline before
+/// it is reported as a regression, even if the difference is statistically
+/// significant.
+const MIN_SLOWDOWN: f64 = 0.05;
+
+/// One-sided critical values of Student's t-distribution at the 95%
+/// confidence level, indexed by degrees of freedom minus one.
+const T_CRITICAL: [f64; 30] = [
+    6.314, 2.920, 2.353, 2.132, 2.015, 1.943, 1.895, 1.860, 1.833, 1.812, 1.796, 1.782, 1.771,
+    1.761, 1.753, 1.746, 1.740, 1.734, 1.729, 1.725, 1.721, 1.717, 1.714, 1.711, 1.708, 1.706,
+    1.703, 1.701, 1.699, 1.697,
+];
+
+/// Statistics about how long a test case took to run when it was run
+/// multiple times.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct BenchmarkStats {
+    /// The run time of each measured iteration, in the order they were run.
+    pub samples: Vec<Duration>,
+    pub mean: Duration,
+    pub median: Duration,
+    /// The sample standard deviation.
+    pub stddev: Duration,
+}
+
+impl BenchmarkStats {
+    /// Calculate statistics for a set of run times, returning `None` if there
+    /// are no samples.
+    pub fn from_samples(samples: Vec<Duration>) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let secs: Vec<f64> = samples.iter().map(Duration::as_secs_f64).collect();
+        let mean = mean(&secs);
+
+        let mut sorted = secs.clone();
+        sorted.sort_by(f64::total_cmp);
+        let mid = sorted.len() / 2;
+        let median = if sorted.len() % 2 == 0 {
+            (sorted[mid - 1] + sorted[mid]) / 2.0
+        } else {
+            sorted[mid]
+        };
+
+        Some(BenchmarkStats {
+            mean: Duration::from_secs_f64(mean),
+            median: Duration::from_secs_f64(median),
+            stddev: Duration::from_secs_f64(variance(&secs).sqrt()),
+            samples,
+        })
+    }
+
+    /// Use Welch's t-test to check whether these run times are significantly
+    /// slower than the `baseline`'s.
+    pub fn is_slower_than(&self, baseline: &BenchmarkStats) -> bool {
+        let after: Vec<f64> = self.samples.iter().map(Duration::as_secs_f64).collect();
+        let before: Vec<f64> = baseline.samples.iter().map(Duration::as_secs_f64).collect();
+
+        if after.len() < 2 || before.len() < 2 {
+            return false;
+        }
+
+        let (mean_after, mean_before) = (mean(&after), mean(&before));
+        if mean_after < mean_before * (1.0 + MIN_SLOWDOWN) {
+            return false;
+        }
+
+        let var_after = variance(&after) / after.len() as f64;
+        let var_before = variance(&before) / before.len() as f64;
+        let standard_error = (var_after + var_before).sqrt();
+
+        if standard_error == 0.0 {
+            // Both runs were perfectly consistent, so any difference is real
+            return true;
+        }
+
+        let t = (mean_after - mean_before) / standard_error;
+        let degrees_of_freedom = (var_after + var_before).powi(2)
+            / (var_after.powi(2) / (after.len() - 1) as f64
+                + var_before.powi(2) / (before.len() - 1) as f64);
+
+        t > t_critical(degrees_of_freedom)
+    }
+}
+
+/// A test case which got significantly slower compared to a baseline run.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PerformanceRegression {
+    pub display_name: String,
+    pub version: String,
+    pub before: BenchmarkStats,
+    pub after: BenchmarkStats,
+}
+
+impl PerformanceRegression {
+    /// How much slower the test case got, as a fraction of the baseline's
+    /// mean run time.
+    pub fn slowdown(&self) -> f64 {
+        self.after.mean.as_secs_f64() / self.before.mean.as_secs_f64() - 1.0
+    }
+}
+
+impl Results {
+    /// Find the benchmarked test cases which are significantly slower than
+    /// they were in the `baseline` (e.g. the same experiment run with a
+    /// different version of `wasmer`).
+    pub fn performance_regressions(&self, baseline: &Results) -> Vec<PerformanceRegression> {
+        let before = by_test_case(baseline);
+
+        by_test_case(self)
+            .into_iter()
+            .filter_map(|(key, new)| {
+                let after = new.benchmark.as_ref()?;
+                let before = before.get(&key)?.benchmark.as_ref()?;
+                after.is_slower_than(before).then(|| PerformanceRegression {
+                    display_name: key.0.to_string(),
+                    version: key.1.to_string(),
+                    before: before.clone(),
+                    after: after.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+fn mean(values: &[f64]) -> f64 {
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+fn variance(values: &[f64]) -> f64 {
+    if values.len() < 2 {
+        return 0.0;
+    }
+
+    let mean = mean(values);
+    values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / (values.len() - 1) as f64
+}
+
+fn t_critical(degrees_of_freedom: f64) -> f64 {
+    // Rounding down is conservative, because the critical value shrinks as
+    // the degrees of freedom grow
+    match degrees_of_freedom.floor() as usize {
+        0 => T_CRITICAL[0],
+        df if df <= T_CRITICAL.len() => T_CRITICAL[df - 1],
+        _ => 1.645,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(millis: &[u64]) -> BenchmarkStats {
+        BenchmarkStats::from_samples(millis.iter().copied().map(Duration::from_millis).collect())
+            .unwrap()
+    }
+
+    #[test]
+    fn calculate_statistics() {
+        let stats = stats(&[100, 300, 200, 400]);
+
+        assert_eq!(stats.mean, Duration::from_millis(250));
+        assert_eq!(stats.median, Duration::from_millis(250));
+        assert_eq!(stats.stddev.as_millis(), 129);
+        assert_eq!(BenchmarkStats::from_samples(Vec::new()), None);
+    }
+
+    #[test]
+    fn only_significant_slowdowns_are_regressions() {
+        let baseline = stats(&[100, 102, 98, 101, 99]);
+
+        assert!(stats(&[150, 152, 148, 151, 149]).is_slower_than(&baseline));
+        // Faster isn't a regression
+        assert!(!stats(&[50, 52, 48, 51, 49]).is_slower_than(&baseline));
+        // Too noisy to tell
+        assert!(!stats(&[60, 200, 90, 150, 80]).is_slower_than(&baseline));
+        // Significant, but not by enough to care
+        assert!(!stats(&[103, 103, 103, 103, 103]).is_slower_than(&baseline));
+    }
+}
@@ -1,20 +1,47 @@
-use std::{fmt::Debug, path::PathBuf, sync::Arc};
+use std::{
+    fmt::Debug,
+    fs::File,
+    io::{BufWriter, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
 
-use actix::{Actor, System};
-use anyhow::Error;
-use reqwest::Client;
+use anyhow::{Context, Error};
+use reqwest::{
+    header::{HeaderMap, HeaderValue},
+    Client,
+};
+use semver::Version;
 use tokio::runtime::Runtime;
 use tracing::Instrument;
 use url::Url;
 
 use crate::{
-    config::Experiment,
+    config::{Experiment, WasmerVersion},
     experiment::{
-        cache::Cache,
+        cache::{self, Cache, CacheError, RegistryAuth},
+        coredumps::Coredumps,
+        cpu::Scheduling,
+        discovery::{DiscoveryCache, DEFAULT_DISCOVERY_TTL},
+        disk::{DiskGuard, LowDiskSpace, DEFAULT_MIN_FREE_SPACE},
+        events::EventSocket,
+        hooks, local,
+        lock::{DirLock, LockError},
+        lockfile::LockFile,
         orchestrator::{BeginExperiment, Orchestrator},
+        package_list::PackageSpec,
         progress::{Progress, ProgressMonitor},
-        Results,
+        redact::Redactor,
+        schedule::RunTimes,
+        selection::{Sampling, Shard},
+        storage::{FileSystemStorage, Storage},
+        summary, ResultsSummary,
     },
+    registry::RetryPolicy,
+    render::RenderError,
+    toolchain::{self, Toolchains},
 };
 
 const PRODUCTION_ENDPOINT: &str = "https://registry.wasmer.io/graphql";
@@ -22,12 +49,31 @@ const PRODUCTION_ENDPOINT: &str = "https://registry.wasmer.io/graphql";
 #[must_use = "An ExperimentBuilder won't do anything unless you call run()"]
 pub struct ExperimentBuilder {
     experiment: Arc<Experiment>,
-    runtime: Option<Box<dyn Fn() -> Runtime>>,
+    runtime: Option<Box<dyn Fn() -> Runtime + Send>>,
     progress: Box<dyn Progress>,
     cache_dir: Option<PathBuf>,
     client: Option<Client>,
+    http: HttpOptions,
     endpoint: Url,
     experiment_dir: Option<PathBuf>,
+    max_packages: Option<usize>,
+    seed: Option<u64>,
+    shard: Option<Shard>,
+    retry: RetryPolicy,
+    event_socket: Option<PathBuf>,
+    min_free_space: u64,
+    mirrors: Vec<Url>,
+    storage: Option<Arc<dyn Storage>>,
+    secrets: Vec<String>,
+    token: Option<String>,
+    locked: Option<LockFile>,
+    packages: Option<Vec<PackageSpec>>,
+    discovery_ttl: Duration,
+    run_times: Option<RunTimes>,
+    parallelism: Option<NonZeroUsize>,
+    tee_logs: bool,
+    scheduling: Scheduling,
+    allow_unverified: bool,
 }
 
 impl ExperimentBuilder {
@@ -38,12 +84,34 @@ impl ExperimentBuilder {
             progress: Box::new(Noop),
             cache_dir: None,
             client: None,
+            http: HttpOptions::default(),
             endpoint: PRODUCTION_ENDPOINT.parse().unwrap(),
             experiment_dir: None,
+            max_packages: None,
+            seed: None,
+            shard: None,
+            retry: RetryPolicy::default(),
+            event_socket: None,
+            min_free_space: DEFAULT_MIN_FREE_SPACE,
+            mirrors: Vec::new(),
+            storage: None,
+            secrets: Vec::new(),
+            token: None,
+            locked: None,
+            packages: None,
+            discovery_ttl: DEFAULT_DISCOVERY_TTL,
+            run_times: None,
+            parallelism: None,
+            tee_logs: false,
+            scheduling: Scheduling::default(),
+            allow_unverified: false,
         }
     }
 
-    pub fn with_runtime(self, runtime: impl Fn() -> Runtime + 'static) -> Self {
+    /// The runtime [`ExperimentBuilder::run()`] should use.
+    ///
+    /// Defaults to a single-threaded runtime.
+    pub fn with_runtime(self, runtime: impl Fn() -> Runtime + Send + 'static) -> Self {
         ExperimentBuilder {
             runtime: Some(Box::new(runtime)),
             ..self
@@ -64,6 +132,43 @@ impl ExperimentBuilder {
         }
     }
 
+    /// Give up on connecting to a server after this long.
+    ///
+    /// This is ignored when a custom client is provided with
+    /// [`ExperimentBuilder::with_client()`].
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.http.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Give up on a request (including reading the response body) after this
+    /// long.
+    ///
+    /// This is ignored when a custom client is provided with
+    /// [`ExperimentBuilder::with_client()`].
+    pub fn with_request_timeout(mut self, timeout: Duration) -> Self {
+        self.http.request_timeout = Some(timeout);
+        self
+    }
+
+    /// The maximum number of idle connections kept alive for each host.
+    ///
+    /// This is ignored when a custom client is provided with
+    /// [`ExperimentBuilder::with_client()`].
+    pub fn with_max_idle_connections(mut self, max_idle_per_host: usize) -> Self {
+        self.http.max_idle_per_host = Some(max_idle_per_host);
+        self
+    }
+
+    /// Only speak HTTP/2, skipping the HTTP/1.1 negotiation.
+    ///
+    /// This is ignored when a custom client is provided with
+    /// [`ExperimentBuilder::with_client()`].
+    pub fn with_http2_only(mut self, http2_only: bool) -> Self {
+        self.http.http2_only = http2_only;
+        self
+    }
+
     pub fn with_endpoint(self, endpoint: impl AsRef<str>) -> Result<Self, url::ParseError> {
         let endpoint = endpoint.as_ref().parse()?;
         Ok(ExperimentBuilder { endpoint, ..self })
@@ -76,18 +181,310 @@ impl ExperimentBuilder {
         }
     }
 
-    pub fn run(self) -> Result<Results, Error> {
+    /// Set the directory downloaded packages will be cached in.
+    ///
+    /// Defaults to the user's cache directory.
+    pub fn with_cache_dir(self, cache_dir: impl Into<PathBuf>) -> Self {
+        ExperimentBuilder {
+            cache_dir: Some(cache_dir.into()),
+            ..self
+        }
+    }
+
+    /// Only run the experiment against a random sample of at most
+    /// `max_packages` test cases.
+    ///
+    /// This is handy for doing a quick smoke test before committing to a run
+    /// against the entire registry.
+    pub fn with_max_packages(self, max_packages: usize) -> Self {
+        ExperimentBuilder {
+            max_packages: Some(max_packages),
+            ..self
+        }
+    }
+
+    /// Set the seed used when sampling test cases.
+    ///
+    /// If no seed is provided, a random one will be chosen. This has no effect
+    /// unless [`ExperimentBuilder::with_max_packages()`] is also used.
+    pub fn with_seed(self, seed: u64) -> Self {
+        ExperimentBuilder {
+            seed: Some(seed),
+            ..self
+        }
+    }
+
+    /// Only run the test cases that belong to a particular [`Shard`].
+    pub fn with_shard(self, shard: Shard) -> Self {
+        ExperimentBuilder {
+            shard: Some(shard),
+            ..self
+        }
+    }
+
+    /// Set how requests to the registry are retried when it is overloaded or
+    /// temporarily unavailable.
+    pub fn with_retry_policy(self, retry: RetryPolicy) -> Self {
+        ExperimentBuilder { retry, ..self }
+    }
+
+    /// Publish progress events on a Unix socket at this path while the
+    /// experiment is running.
+    ///
+    /// Each event is written to every connected listener as a line of JSON,
+    /// and listeners may connect or disconnect at any time.
+    pub fn with_event_socket(self, path: impl Into<PathBuf>) -> Self {
+        ExperimentBuilder {
+            event_socket: Some(path.into()),
+            ..self
+        }
+    }
+
+    /// Stop starting new test cases when there are fewer than this many bytes
+    /// free in the cache or experiment directories.
+    ///
+    /// Defaults to [`DEFAULT_MIN_FREE_SPACE`]. Use `0` to disable the check.
+    pub fn with_min_free_space(self, min_free_space: u64) -> Self {
+        ExperimentBuilder {
+            min_free_space,
+            ..self
+        }
+    }
+
+    /// Fall back to these mirrors, in order, when a package can't be
+    /// downloaded from its original URL.
+    ///
+    /// Each mirror is a base URL which the original URL's path is appended
+    /// to (e.g. `https://mirror.example.com/wasmer/` turns
+    /// `https://cdn.wasmer.io/packages/x.tar.gz` into
+    /// `https://mirror.example.com/wasmer/packages/x.tar.gz`).
+    pub fn with_mirrors(self, mirrors: impl IntoIterator<Item = Url>) -> Self {
+        ExperimentBuilder {
+            mirrors: mirrors.into_iter().collect(),
+            ..self
+        }
+    }
+
+    /// Store downloaded packages somewhere other than the cache directory.
+    ///
+    /// Defaults to a [`FileSystemStorage`] in the cache directory.
+    pub fn with_storage(self, storage: impl Storage + 'static) -> Self {
+        ExperimentBuilder {
+            storage: Some(Arc::new(storage)),
+            ..self
+        }
+    }
+
+    /// Values (e.g. the registry token) which should be scrubbed from
+    /// reports and logs, on top of the experiment's `redact` variables.
+    pub fn with_secrets(self, secrets: impl IntoIterator<Item = String>) -> Self {
+        ExperimentBuilder {
+            secrets: secrets.into_iter().collect(),
+            ..self
+        }
+    }
+
+    /// Authenticate with the registry using this token, so experiments can
+    /// include private packages.
+    ///
+    /// The token is attached to GraphQL queries and to artifact downloads
+    /// from the registry's own domain, but never sent to mirrors or anywhere
+    /// else. It is also scrubbed from reports and logs.
+    ///
+    /// When a custom client is provided with
+    /// [`ExperimentBuilder::with_client()`], the token is only attached to
+    /// downloads, so the client needs to authenticate GraphQL queries itself.
+    pub fn with_token(self, token: impl Into<String>) -> Self {
+        ExperimentBuilder {
+            token: Some(token.into()),
+            ..self
+        }
+    }
+
+    /// Replay exactly the test cases from a previous run's `experiment.lock`
+    /// instead of discovering them from the registry.
+    ///
+    /// Test cases whose artifacts no longer match the lock file's checksums
+    /// will fail rather than silently running against something different.
+    pub fn with_locked(self, locked: LockFile) -> Self {
+        ExperimentBuilder {
+            locked: Some(locked),
+            ..self
+        }
+    }
+
+    /// Run exactly these packages instead of discovering test cases using
+    /// the experiment's filters.
+    ///
+    /// Packages without a version are run against their latest version.
+    pub fn with_packages(self, packages: impl IntoIterator<Item = PackageSpec>) -> Self {
+        ExperimentBuilder {
+            packages: Some(packages.into_iter().collect()),
+            ..self
+        }
+    }
+
+    /// Reuse the test cases discovered by an earlier run with the same
+    /// registry and filters if they were discovered less than `ttl` ago.
+    ///
+    /// Defaults to [`DEFAULT_DISCOVERY_TTL`]. Use [`Duration::ZERO`] to always
+    /// query the registry again. Freshly discovered test cases are saved for
+    /// next time either way.
+    pub fn with_discovery_ttl(self, ttl: Duration) -> Self {
+        ExperimentBuilder {
+            discovery_ttl: ttl,
+            ..self
+        }
+    }
+
+    /// Start the test cases expected to take longest first, so the end of
+    /// the run isn't spent waiting on a single slow package.
+    ///
+    /// Test cases are ordered by how long they took in an earlier run,
+    /// falling back to the size of their artifacts. This means waiting for
+    /// discovery to finish before any test cases are started.
+    pub fn with_longest_first(self, run_times: RunTimes) -> Self {
+        ExperimentBuilder {
+            run_times: Some(run_times),
+            ..self
+        }
+    }
+
+    /// The maximum number of test cases to run at the same time.
+    ///
+    /// Defaults to the number of CPUs.
+    pub fn with_parallelism(self, parallelism: NonZeroUsize) -> Self {
+        ExperimentBuilder {
+            parallelism: Some(parallelism),
+            ..self
+        }
+    }
+
+    /// Echo each test case's stdout and stderr to the console (prefixed with
+    /// the package's name and version) as well as writing them to its logs.
+    ///
+    /// This is handy when watching a small experiment interactively, but the
+    /// output from test cases running in parallel will be interleaved.
+    pub fn with_tee_logs(self, tee_logs: bool) -> Self {
+        ExperimentBuilder { tee_logs, ..self }
+    }
+
+    /// Pin the test cases to these CPU cores, keeping borealis' own threads
+    /// (downloads, orchestration, etc.) on the remaining cores so they don't
+    /// skew benchmarks and other timing measurements.
+    ///
+    /// Borealis' threads are only moved when the experiment is started with
+    /// [`ExperimentBuilder::run()`]. This is only supported on Linux.
+    pub fn with_test_cores(mut self, cores: impl IntoIterator<Item = usize>) -> Self {
+        self.scheduling = self.scheduling.with_test_cores(cores.into_iter().collect());
+        self
+    }
+
+    /// Run borealis' own threads at this niceness (e.g. `10`), so test cases
+    /// get priority over downloads and orchestration.
+    ///
+    /// Lowering the niceness of test cases back down again requires
+    /// `CAP_SYS_NICE`, so without it they share borealis' niceness. Like
+    /// [`ExperimentBuilder::with_test_cores()`], this only takes effect with
+    /// [`ExperimentBuilder::run()`] on Linux.
+    pub fn with_niceness(mut self, niceness: i32) -> Self {
+        self.scheduling = self.scheduling.with_niceness(niceness);
+        self
+    }
+
+    /// Install `wasmer` releases even if they don't publish a SHA-256 sum to
+    /// verify the downloaded archive against.
+    pub fn with_allow_unverified(self, allow_unverified: bool) -> Self {
+        ExperimentBuilder {
+            allow_unverified,
+            ..self
+        }
+    }
+
+    /// Run the experiment to completion, blocking the current thread.
+    ///
+    /// Pressing Ctrl-C cancels the experiment.
+    pub fn run(mut self) -> Result<ResultsSummary, RunError> {
+        // Any runtime threads started from here on inherit the scheduling
+        self.scheduling.apply().map_err(RunError::Scheduling)?;
+
+        let rt = match self.runtime.take() {
+            Some(rt) => rt(),
+            None => tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .map_err(RunError::Runtime)?,
+        };
+
+        rt.block_on(async {
+            // Dropping the run kills any test cases which are still going,
+            // along with everything they started
+            tokio::select! {
+                results = self.run_async() => results,
+                Ok(()) = tokio::signal::ctrl_c() => Err(RunError::Cancelled),
+            }
+        })
+    }
+
+    /// Run the experiment on the current tokio runtime.
+    ///
+    /// Unlike [`ExperimentBuilder::run()`], this leaves Ctrl-C alone and
+    /// ignores [`ExperimentBuilder::with_runtime()`]. To cancel the
+    /// experiment, drop the future.
+    pub async fn run_async(self) -> Result<ResultsSummary, RunError> {
         let ExperimentBuilder {
-            experiment,
-            runtime,
+            mut experiment,
+            runtime: _,
             progress,
             cache_dir,
             client,
+            http,
             endpoint,
             experiment_dir,
+            max_packages,
+            seed,
+            shard,
+            retry,
+            event_socket,
+            min_free_space,
+            mirrors,
+            storage,
+            secrets,
+            token,
+            locked,
+            packages,
+            discovery_ttl,
+            run_times,
+            parallelism,
+            tee_logs,
+            scheduling,
+            allow_unverified,
         } = self;
 
-        let client = client.unwrap_or_default();
+        let parallelism = parallelism.unwrap_or_else(|| {
+            std::thread::available_parallelism().unwrap_or(NonZeroUsize::new(4).unwrap())
+        });
+        let redactor = Redactor::new(&experiment, secrets.into_iter().chain(token.clone()));
+
+        let sampling = max_packages.map(|max_packages| Sampling {
+            max_packages,
+            seed: seed.unwrap_or_else(|| uuid::Uuid::new_v4().as_u64_pair().0),
+        });
+
+        let auth = token
+            .as_deref()
+            .map(|token| RegistryAuth::new(endpoint.host_str().unwrap_or_default(), token))
+            .transpose()
+            .map_err(RunError::InvalidToken)?;
+        // Only the registry client sends the token with every request
+        let (client, registry_client) = match client {
+            Some(client) => (client.clone(), client),
+            None => (
+                http.client(None).map_err(RunError::HttpClient)?,
+                http.client(token.as_deref())
+                    .map_err(RunError::HttpClient)?,
+            ),
+        };
         let cache_dir = cache_dir.unwrap_or_else(|| crate::DIRS.cache_dir().to_path_buf());
         let experiment_dir = experiment_dir.unwrap_or_else(|| {
             crate::DIRS
@@ -95,39 +492,217 @@ impl ExperimentBuilder {
                 .join(uuid::Uuid::new_v4().to_string())
         });
 
-        let system = match runtime {
-            Some(rt) => System::with_tokio_rt(rt),
-            None => System::new(),
-        };
+        std::fs::create_dir_all(&experiment_dir).map_err(|error| RunError::ExperimentDir {
+            path: experiment_dir.clone(),
+            error,
+        })?;
+        // Held until the results have been saved and the post-run hooks are
+        // done
+        let _lock = DirLock::acquire(&experiment_dir)?;
 
-        let results = system.block_on(
-            async {
-                let progress = ProgressMonitor::new(progress).start();
-                let cache = Cache::new(cache_dir, client.clone(), progress.recipient()).start();
-                let orchestrator = Orchestrator::new(cache, client, endpoint).start();
-
-                orchestrator
-                    .send(BeginExperiment {
-                        experiment,
-                        base_dir: experiment_dir.clone(),
-                    })
-                    .await
+        hooks::pre_run(&experiment.hooks.pre_run, &experiment_dir).map_err(RunError::PreRunHook)?;
+
+        let local = match &experiment.local {
+            Some(package) => {
+                let test_cases = local::test_cases(package, &experiment_dir.join("local"))
+                    .map_err(RunError::LocalPackage)?;
+                if experiment.package.is_empty() {
+                    // Nothing else to run, so run the package itself
+                    let path = local::package_path(package).map_err(RunError::LocalPackage)?;
+                    Arc::make_mut(&mut experiment).package = path.display().to_string();
+                }
+                Some(test_cases)
             }
-            .in_current_span(),
-        )?;
+            None => None,
+        };
+        cache::check_layout(&cache_dir)?;
+        let discovery =
+            DiscoveryCache::new(&cache_dir, &endpoint, &experiment.filters, discovery_ttl);
+
+        let storage =
+            storage.unwrap_or_else(|| Arc::new(FileSystemStorage::new(cache_dir.clone())));
+
+        let disk = DiskGuard::new([cache_dir.clone(), experiment_dir.clone()], min_free_space);
+        disk.check()?;
+
+        let progress: Box<dyn Progress> = match event_socket {
+            Some(path) => match EventSocket::bind(&path, progress) {
+                Ok(socket) => Box::new(socket),
+                Err(error) => return Err(RunError::EventSocket { path, error }),
+            },
+            None => progress,
+        };
+
+        let results = async {
+            let toolchains = Toolchains::in_cache_dir(&cache_dir)
+                .map_err(RunError::Toolchain)?
+                .with_allow_unverified(allow_unverified);
+            let wasmer = toolchains
+                .resolve(&experiment.wasmer.version)
+                .await
+                .map_err(RunError::Toolchain)?;
+            check_features(&experiment, &wasmer).await?;
+            let coredumps = if experiment.wasmer.coredumps {
+                Some(Coredumps::detect(&wasmer).await)
+            } else {
+                None
+            };
+
+            let (senders, monitor) = ProgressMonitor::new(progress).start();
+            let cache = Cache::new(cache_dir, client, storage, mirrors, auth, senders.cache);
+            let orchestrator =
+                Orchestrator::new(cache, registry_client, endpoint, retry, senders.tests);
+
+            let experiment = orchestrator.run(BeginExperiment {
+                experiment,
+                base_dir: experiment_dir.clone(),
+                sampling,
+                shard,
+                wasmer,
+                disk,
+                redactor,
+                local,
+                locked,
+                packages,
+                discovery,
+                run_times,
+                coredumps,
+                parallelism,
+                tee_logs,
+                scheduling,
+            });
+            // The monitor finishes once the orchestrator is done and every
+            // sender has been dropped
+            let (completed, cache) = futures::join!(experiment, monitor);
+
+            Ok::<_, RunError>((completed?, cache))
+        }
+        .in_current_span()
+        .await?;
+
+        // The reports have only been written to the log so far, so now the
+        // experiment is over we can copy them into results.json and render
+        // the report, reading them back one at a time
+        let (results, cache) = results;
+        let results = ResultsSummary { cache, ..results };
+        summary::save(&results).map_err(RunError::Save)?;
 
-        let report = crate::render::html(&results)?;
         let reports_html = experiment_dir.join("report.html");
-        std::fs::write(reports_html, report)?;
+        let file = File::create(&reports_html)
+            .with_context(|| format!("Unable to create \"{}\"", reports_html.display()))
+            .map_err(RunError::Save)?;
+        let mut writer = BufWriter::new(file);
+        crate::render::write_html(&results, &[], &mut writer)?;
+        writer
+            .flush()
+            .with_context(|| format!("Unable to save to \"{}\"", reports_html.display()))
+            .map_err(RunError::Save)?;
 
-        let reports_json = experiment_dir.join("results.json");
-        let json = serde_json::to_string_pretty(&results)?;
-        std::fs::write(reports_json, json)?;
+        hooks::post_run(
+            &results.experiment.hooks.post_run,
+            &experiment_dir,
+            &results,
+        );
 
         Ok(results)
     }
 }
 
+/// Make sure the `wasmer` CLI supports the experiment's features, so we can
+/// skip the experiment instead of every test case failing obscurely.
+async fn check_features(experiment: &Experiment, wasmer: &Path) -> Result<(), RunError> {
+    let features = &experiment.wasmer.features;
+    if features.flags().is_empty() {
+        return Ok(());
+    }
+
+    let version = match &experiment.wasmer.version {
+        WasmerVersion::Release(version) => version.clone(),
+        _ => match toolchain::version_of(wasmer).await {
+            Ok(version) => version,
+            Err(e) => {
+                tracing::warn!(
+                    error = &*e,
+                    "Unable to determine the wasmer version, so its features can't be checked",
+                );
+                return Ok(());
+            }
+        },
+    };
+
+    let unsupported = features.unsupported_by(&version);
+    if unsupported.is_empty() {
+        Ok(())
+    } else {
+        Err(RunError::UnsupportedFeatures {
+            version,
+            features: unsupported,
+        })
+    }
+}
+
+/// An error that stopped an experiment from running.
+#[derive(Debug, thiserror::Error)]
+pub enum RunError {
+    #[error("Unable to create the HTTP client")]
+    HttpClient(#[source] reqwest::Error),
+    #[error("The registry token can't be used in a HTTP header")]
+    InvalidToken(#[source] reqwest::header::InvalidHeaderValue),
+    #[error("Unable to create \"{}\"", path.display())]
+    ExperimentDir {
+        path: PathBuf,
+        #[source]
+        error: std::io::Error,
+    },
+    /// Another run is already writing to the experiment directory.
+    #[error(transparent)]
+    Lock(#[from] LockError),
+    /// One of the experiment's `pre-run` hooks failed.
+    #[error(transparent)]
+    PreRunHook(Error),
+    #[error(transparent)]
+    Cache(#[from] CacheError),
+    /// The experiment was stopped (or never started) because the cache or
+    /// experiment directory is running out of space.
+    ///
+    /// Reports for test cases which completed before this happened are still
+    /// written to `reports.jsonl`.
+    #[error(transparent)]
+    LowDiskSpace(#[from] LowDiskSpace),
+    #[error("Unable to publish events on \"{}\"", path.display())]
+    EventSocket {
+        path: PathBuf,
+        #[source]
+        error: Error,
+    },
+    /// The experiment's `local` package couldn't be loaded.
+    #[error("Unable to load the local package")]
+    LocalPackage(#[source] Error),
+    /// The `wasmer` CLI doesn't support some of the experiment's
+    /// `wasmer.features`, so the experiment was skipped.
+    #[error("wasmer {version} doesn't support {}", features.join(", "))]
+    UnsupportedFeatures {
+        version: Version,
+        features: Vec<&'static str>,
+    },
+    #[error("Unable to resolve the wasmer CLI")]
+    Toolchain(#[source] Error),
+    /// The user pressed Ctrl-C.
+    #[error("The experiment was cancelled")]
+    Cancelled,
+    #[error("Unable to start the async runtime")]
+    Runtime(#[source] std::io::Error),
+    /// The test cores or niceness couldn't be applied.
+    #[error("Unable to change which CPUs borealis runs on")]
+    Scheduling(#[source] std::io::Error),
+    #[error("Unable to render the report")]
+    Render(#[from] RenderError),
+    /// The report or results couldn't be written to the experiment
+    /// directory.
+    #[error("Unable to save the experiment's results")]
+    Save(#[source] Error),
+}
+
 impl Debug for ExperimentBuilder {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let ExperimentBuilder {
@@ -137,7 +712,27 @@ impl Debug for ExperimentBuilder {
             cache_dir,
             experiment_dir,
             client,
+            http,
             endpoint,
+            max_packages,
+            seed,
+            shard,
+            retry,
+            event_socket,
+            min_free_space,
+            mirrors,
+            storage,
+            // Secrets shouldn't end up in the logs
+            secrets: _,
+            token,
+            locked,
+            packages,
+            discovery_ttl,
+            run_times,
+            parallelism,
+            tee_logs,
+            scheduling,
+            allow_unverified,
         } = self;
 
         f.debug_struct("ExperimentBuilder")
@@ -146,11 +741,82 @@ impl Debug for ExperimentBuilder {
             .field("cache_dir", cache_dir)
             .field("experiment_dir", experiment_dir)
             .field("client", client)
+            .field("http", http)
             .field("endpoint", endpoint)
+            .field("max_packages", max_packages)
+            .field("seed", seed)
+            .field("shard", shard)
+            .field("retry", retry)
+            .field("event_socket", event_socket)
+            .field("min_free_space", min_free_space)
+            .field("mirrors", mirrors)
+            .field("storage", storage)
+            .field("token", &token.is_some())
+            .field("locked", &locked.as_ref().map(|l| l.test_cases.len()))
+            .field("packages", packages)
+            .field("discovery_ttl", discovery_ttl)
+            .field("run_times", &run_times.is_some())
+            .field("parallelism", parallelism)
+            .field("tee_logs", tee_logs)
+            .field("scheduling", scheduling)
+            .field("allow_unverified", allow_unverified)
             .finish_non_exhaustive()
     }
 }
 
+/// Settings used when constructing the default HTTP client.
+#[derive(Debug, Default, Clone, Copy)]
+struct HttpOptions {
+    connect_timeout: Option<Duration>,
+    request_timeout: Option<Duration>,
+    max_idle_per_host: Option<usize>,
+    http2_only: bool,
+}
+
+impl HttpOptions {
+    /// Create a client, optionally sending the registry `token` with every
+    /// request.
+    fn client(self, token: Option<&str>) -> Result<Client, reqwest::Error> {
+        let HttpOptions {
+            connect_timeout,
+            request_timeout,
+            max_idle_per_host,
+            http2_only,
+        } = self;
+
+        let mut builder = Client::builder().user_agent(concat!(
+            env!("CARGO_PKG_NAME"),
+            "/",
+            env!("CARGO_PKG_VERSION")
+        ));
+
+        if let Some(token) = token {
+            let mut headers = HeaderMap::new();
+            // The token was already checked by RegistryAuth::new()
+            if let Ok(mut header) = HeaderValue::from_str(&format!("bearer {token}")) {
+                header.set_sensitive(true);
+                headers.insert(reqwest::header::AUTHORIZATION, header);
+            }
+            builder = builder.default_headers(headers);
+        }
+
+        if let Some(timeout) = connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        if let Some(timeout) = request_timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(max_idle_per_host) = max_idle_per_host {
+            builder = builder.pool_max_idle_per_host(max_idle_per_host);
+        }
+        if http2_only {
+            builder = builder.http2_prior_knowledge();
+        }
+
+        builder.build()
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 struct Noop;
 
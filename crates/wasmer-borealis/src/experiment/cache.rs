@@ -1,17 +1,32 @@
 use std::{
+    collections::{BTreeMap, HashMap},
     path::{Path, PathBuf},
-    sync::Arc,
+    sync::{Arc, Mutex},
     time::{Duration, Instant},
 };
 
-use actix::{Actor, Context, Handler, Recipient};
 use anyhow::{Context as _, Error};
-use reqwest::Client;
+use futures::{
+    future::{BoxFuture, Shared},
+    FutureExt,
+};
+use reqwest::{
+    header::{HeaderValue, InvalidHeaderValue},
+    Client,
+};
+use sha2::{Digest, Sha256};
 use tempfile::TempDir;
-use tokio::sync::Semaphore;
+use tokio::{
+    io::AsyncWriteExt,
+    sync::{mpsc::UnboundedSender, Semaphore},
+};
 use url::Url;
 
-use crate::experiment::wapm::TestCase;
+use crate::experiment::{
+    discovery, hex,
+    storage::{self, Assets, Storage},
+    TestCase,
+};
 
 const DEFAULT_CONCURRENT_DOWNLOADS: usize = 16;
 
@@ -19,80 +34,183 @@ const DEFAULT_CONCURRENT_DOWNLOADS: usize = 16;
 pub(crate) struct Cache {
     dir: PathBuf,
     client: Client,
-    progress: Recipient<CacheStatusMessage>,
+    storage: Arc<dyn Storage>,
+    /// Base URLs to fall back to when downloading from the primary URL
+    /// fails.
+    mirrors: Arc<[Url]>,
+    auth: Option<RegistryAuth>,
+    progress: UnboundedSender<CacheStatusMessage>,
     download_limiter: Arc<Semaphore>,
+    /// Assets which are currently being fetched, keyed by their cache
+    /// directory.
+    in_flight: Arc<Mutex<HashMap<PathBuf, InFlight>>>,
 }
 
+type InFlight = Shared<BoxFuture<'static, Result<Assets, SharedError>>>;
+
 impl Cache {
     pub(crate) fn new(
         dir: PathBuf,
         client: Client,
-        progress: Recipient<CacheStatusMessage>,
+        storage: Arc<dyn Storage>,
+        mirrors: Vec<Url>,
+        auth: Option<RegistryAuth>,
+        progress: UnboundedSender<CacheStatusMessage>,
     ) -> Self {
         Cache {
             dir,
             client,
+            storage,
+            mirrors: mirrors.into(),
+            auth,
             progress,
             download_limiter: Arc::new(Semaphore::new(
                 std::thread::available_parallelism()
                     .map(|p| p.get())
                     .unwrap_or(DEFAULT_CONCURRENT_DOWNLOADS),
             )),
+            in_flight: Arc::default(),
         }
     }
+
+    /// Make sure a test case's assets are in the cache, downloading them if
+    /// necessary.
+    pub(crate) async fn fetch(&self, test_case: TestCase) -> Result<AssetsFetched, Error> {
+        let key = package_version_dir(&self.dir, &test_case);
+
+        // If the same package version is requested multiple times
+        // concurrently, make sure we only download it once.
+        let fetch = self
+            .in_flight
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| {
+                let progress = self.progress.clone();
+                let dir = self.dir.clone();
+                let client = self.client.clone();
+                let storage = self.storage.clone();
+                let mirrors = self.mirrors.clone();
+                let auth = self.auth.clone();
+                let semaphore = self.download_limiter.clone();
+                let in_flight = self.in_flight.clone();
+                let test_case = test_case.clone();
+
+                async move {
+                    let result = async {
+                        let _guard = semaphore.acquire().await?;
+                        prepare_assets(
+                            &client,
+                            &dir,
+                            &*storage,
+                            &mirrors,
+                            auth.as_ref(),
+                            &test_case,
+                            |msg| {
+                                // Nobody is listening once the experiment is over
+                                let _ = progress.send(msg);
+                            },
+                        )
+                        .await
+                    }
+                    .await;
+
+                    in_flight.lock().unwrap().remove(&key);
+
+                    result.map_err(|e| SharedError(Arc::new(e)))
+                }
+                .boxed()
+                .shared()
+            })
+            .clone();
+
+        let assets = fetch.await?;
+        Ok(AssetsFetched { test_case, assets })
+    }
 }
 
-impl Actor for Cache {
-    type Context = Context<Self>;
+/// The registry token, which is attached to artifact downloads from the
+/// registry and its CDN (private packages can't be downloaded without it)
+/// but never sent anywhere else.
+#[derive(Clone)]
+pub(crate) struct RegistryAuth {
+    hosts: Vec<String>,
+    header: HeaderValue,
 }
 
-#[derive(Debug, Clone, actix::Message)]
-#[rtype(result = "Result<AssetsFetched, Error>")]
-pub(crate) struct FetchAssets {
-    pub test_case: TestCase,
+impl RegistryAuth {
+    /// Scope a token to the registry at `host` (e.g. `registry.wasmer.io`).
+    ///
+    /// The token is also sent to the registry's CDN (e.g. `cdn.wasmer.io`),
+    /// because that's where download URLs point. Every other host, including
+    /// the rest of the registry's domain, is left alone.
+    pub(crate) fn new(host: &str, token: &str) -> Result<Self, InvalidHeaderValue> {
+        let mut header = HeaderValue::from_str(&format!("bearer {token}"))?;
+        header.set_sensitive(true);
+        let host = host.to_ascii_lowercase();
+        let cdn = host
+            .strip_prefix("registry.")
+            .filter(|domain| domain.contains('.'))
+            .map(|domain| format!("cdn.{domain}"));
+        let hosts = std::iter::once(host).chain(cdn).collect();
+
+        Ok(RegistryAuth { hosts, header })
+    }
+
+    /// Should the token be sent with a request to this URL?
+    fn applies_to(&self, url: &Url) -> bool {
+        let Some(host) = url.host_str() else {
+            return false;
+        };
+
+        self.hosts.iter().any(|h| h.eq_ignore_ascii_case(host))
+    }
 }
 
-impl Handler<FetchAssets> for Cache {
-    type Result = actix::ResponseFuture<Result<AssetsFetched, Error>>;
-
-    fn handle(
-        &mut self,
-        msg: FetchAssets,
-        _ctx: &mut Self::Context,
-    ) -> actix::ResponseFuture<Result<AssetsFetched, Error>> {
-        let FetchAssets { test_case } = msg;
-        let progress = self.progress.clone();
-        let dir = self.dir.clone();
-        let client = self.client.clone();
-        let semaphore = self.download_limiter.clone();
-
-        Box::pin(async move {
-            let _guard = semaphore.acquire().await?;
-            let assets = prepare_assets(&client, &dir, &test_case, progress).await?;
-            Ok(AssetsFetched { test_case, assets })
-        })
+impl std::fmt::Debug for RegistryAuth {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Don't leak the token into the logs
+        f.debug_struct("RegistryAuth")
+            .field("hosts", &self.hosts)
+            .finish_non_exhaustive()
     }
 }
 
+/// An [`Error`] which can be shared between everyone waiting on the same
+/// download.
 #[derive(Debug, Clone)]
-pub(crate) struct AssetsFetched {
-    pub test_case: TestCase,
-    pub assets: Assets,
+struct SharedError(Arc<Error>);
+
+impl std::fmt::Display for SharedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl std::error::Error for SharedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
 }
 
 #[derive(Debug, Clone)]
-pub(crate) struct Assets {
-    pub tarball: PathBuf,
-    pub webc: Option<PathBuf>,
-    /// The total size of the assets on disk.
-    pub total_size: u64,
+pub(crate) struct AssetsFetched {
+    pub test_case: TestCase,
+    pub assets: Assets,
 }
 
 /// Messages emitted by the [`Cache`] as it downloads a packages.
-#[derive(Debug, actix::Message)]
-#[rtype(result = "()")]
+#[derive(Debug)]
 pub(crate) enum CacheStatusMessage {
     Fetching(TestCase),
+    /// Part of one of the test case's files has been downloaded.
+    Downloading {
+        test_case: TestCase,
+        filename: String,
+        bytes_received: u64,
+        /// The file's size, if the server told us.
+        total: Option<u64>,
+    },
     CacheHit(TestCase),
     CacheMiss {
         test_case: TestCase,
@@ -108,189 +226,696 @@ pub(crate) enum CacheStatusMessage {
         pkg.name=test_case.package_name.as_str(),
         pkg.version=test_case.version(),
     ))]
-async fn prepare_assets(
+pub(crate) async fn prepare_assets(
     client: &Client,
     dir: &Path,
+    storage: &dyn Storage,
+    mirrors: &[Url],
+    auth: Option<&RegistryAuth>,
     test_case: &TestCase,
-    progress: Recipient<CacheStatusMessage>,
+    progress: impl Fn(CacheStatusMessage),
 ) -> Result<Assets, Error> {
-    let _ = progress
-        .send(CacheStatusMessage::Fetching(test_case.clone()))
-        .await;
+    progress(CacheStatusMessage::Fetching(test_case.clone()));
 
-    let cache_dir = package_version_dir(dir, test_case);
-    let tarball_path = cache_dir
-        .join(&test_case.package_name)
-        .with_extension("tar.gz");
-    let webc_path = cache_dir
-        .join(&test_case.package_name)
-        .with_extension("webc");
-
-    if cache_dir.exists() && tarball_path.exists() {
-        let tarball_size = std::fs::metadata(&tarball_path)?.len();
-
-        let assets = match std::fs::metadata(&webc_path) {
-            Ok(webc_meta) => Assets {
-                tarball: tarball_path,
-                webc: Some(webc_path),
-                total_size: tarball_size + webc_meta.len(),
-            },
-            Err(_) => Assets {
-                tarball: tarball_path,
-                webc: None,
-                total_size: tarball_size,
-            },
-        };
-
-        tracing::debug!(cache_dir=%cache_dir.display(), "Cache hit!");
-        let _ = progress
-            .send(CacheStatusMessage::CacheHit(test_case.clone()))
-            .await;
-
-        return Ok(assets);
+    match storage.get(test_case).await {
+        Ok(Some(assets)) => {
+            progress(CacheStatusMessage::CacheHit(test_case.clone()));
+            return Ok(assets);
+        }
+        Ok(None) => {}
+        Err(e) => {
+            tracing::warn!(
+                error = &*e,
+                "Unable to load the cached artifacts, downloading them again",
+            );
+            storage.evict(test_case).await?;
+        }
     }
 
-    tracing::debug!(
-        cache_dir.path = %cache_dir.display(),
-        cache_dir.exists = cache_dir.exists(),
-        tarball.path = %tarball_path.display(),
-        tarball.exists = tarball_path.exists(),
-        "Cache miss",
-    );
+    tracing::debug!("Cache miss");
 
     let start = Instant::now();
-    let result = do_download(client, dir, &cache_dir, tarball_path, webc_path, test_case).await;
-
-    if let Ok(assets) = &result {
-        let duration = start.elapsed();
-        let _ = progress
-            .send(CacheStatusMessage::CacheMiss {
-                test_case: test_case.clone(),
-                duration,
-                bytes_downloaded: assets.total_size,
-            })
-            .await;
+    let on_progress = |filename: &str, bytes_received, total| {
+        progress(CacheStatusMessage::Downloading {
+            test_case: test_case.clone(),
+            filename: filename.to_string(),
+            bytes_received,
+            total,
+        });
+    };
+
+    let (tarball, webc) = storage::artifact_names(test_case);
+    let mut artifacts = vec![(tarball, test_case.tarball_url().to_string())];
+    if let Some(url) = test_case.webc_url() {
+        artifacts.push((webc, url.to_string()));
     }
 
-    result
+    let staging = download_artifacts(client, dir, mirrors, auth, artifacts, on_progress).await?;
+    let assets = storage.put(test_case, staging.path()).await?;
+
+    progress(CacheStatusMessage::CacheMiss {
+        test_case: test_case.clone(),
+        duration: start.elapsed(),
+        bytes_downloaded: assets.total_size,
+    });
+
+    Ok(assets)
 }
 
-async fn do_download(
+/// Download a set of artifacts (as `(filename, url)` pairs) into a temporary
+/// directory inside `dir`, along with a [`Manifest`] that can be used to
+/// verify them later.
+///
+/// If downloading from an artifact's URL fails, each of the `mirrors` will be
+/// tried in turn. The manifest always records the original URL. The `auth`
+/// token is only sent to the registry's own hosts.
+///
+/// The `on_progress` callback is periodically called with the name of the
+/// file being downloaded, the number of bytes received so far, and the file's
+/// total size (if known).
+async fn download_artifacts(
     client: &Client,
     dir: &Path,
-    cache_dir: &Path,
-    tarball_path: PathBuf,
-    webc_path: PathBuf,
-    test_case: &TestCase,
-) -> Result<Assets, Error> {
+    mirrors: &[Url],
+    auth: Option<&RegistryAuth>,
+    artifacts: Vec<(String, String)>,
+    mut on_progress: impl FnMut(&str, u64, Option<u64>),
+) -> Result<TempDir, Error> {
     tokio::fs::create_dir_all(dir)
         .await
         .with_context(|| format!("Unable to create \"{}\"", dir.display()))?;
     let temp = TempDir::new_in(dir).context("Unable to create a temporary directory")?;
 
-    // Download our files to a temporary directory
-    let mut bytes_downloaded = download_file(
-        client,
-        test_case.tarball_url(),
-        temp.path().join(tarball_path.file_name().unwrap()),
-    )
-    .await
-    .with_context(|| format!("Downloading \"{}\" failed", test_case.tarball_url()))?;
-    if let Some(url) = test_case.webc_url() {
-        bytes_downloaded += download_file(
-            client,
-            url,
-            temp.path().join(webc_path.file_name().unwrap()),
-        )
-        .await
-        .with_context(|| format!("Downloading \"{url}\" failed"))?;
-    }
+    let mut manifest = Manifest::default();
 
-    tracing::debug!(
-        from=%temp.path().display(),
-        to=%cache_dir.display(),
-        "Persisting downloaded artifacts",
-    );
-
-    // Before persisting the downloaded directory, make sure we remove
-    // any existing stuff
-    if let Err(e) = tokio::fs::remove_dir_all(&cache_dir).await {
-        if e.kind() != std::io::ErrorKind::NotFound {
-            let error =
-                Error::new(e).context(format!("Unable to remove \"{}\"", cache_dir.display()));
-            return Err(error);
-        }
+    for (filename, url) in artifacts {
+        let dest = temp.path().join(&filename);
+        let (size, sha256) =
+            download_with_mirrors(client, &url, mirrors, auth, &dest, |received, total| {
+                on_progress(&filename, received, total)
+            })
+            .await?;
+        manifest
+            .artifacts
+            .insert(filename, ArtifactDigest { url, size, sha256 });
     }
 
-    if let Some(parent) = cache_dir.parent() {
-        if let Err(err) = tokio::fs::create_dir_all(parent).await {
-            let err = Error::new(err).context(format!("Unable to create \"{}\"", parent.display()));
-            return Err(err);
-        }
-    }
+    manifest.save(temp.path()).await?;
 
-    let temp = temp.into_path();
+    Ok(temp)
+}
 
-    // Now we can (mostly atomically) move the cached assets into place
-    if let Err(e) = tokio::fs::rename(&temp, &cache_dir).await {
-        if let Err(e) = tokio::fs::remove_dir_all(&temp).await {
-            tracing::warn!(
-                temp_dir=%temp.display(),
-                dest=%cache_dir.display(),
-                error=&e as &dyn std::error::Error,
-                "Unable to clean up the temporary folder after failing to persist it",
-            );
+/// Download a file, falling back to each of the `mirrors` in turn if the
+/// primary URL fails.
+async fn download_with_mirrors(
+    client: &Client,
+    url: &str,
+    mirrors: &[Url],
+    auth: Option<&RegistryAuth>,
+    dest: &Path,
+    mut on_progress: impl FnMut(u64, Option<u64>),
+) -> Result<(u64, String), Error> {
+    let error = match download_file(client, url, auth, dest, &mut on_progress).await {
+        Ok(downloaded) => return Ok(downloaded),
+        Err(e) => e.context(format!("Downloading \"{url}\" failed")),
+    };
+
+    let Ok(original) = Url::parse(url) else {
+        return Err(error);
+    };
+
+    for mirror in mirrors {
+        let candidate = mirror_url(mirror, &original);
+        tracing::warn!(
+            %url,
+            mirror=%candidate,
+            error=&*error,
+            "Download failed, trying a mirror",
+        );
+
+        match download_file(client, candidate.as_str(), auth, dest, &mut on_progress).await {
+            Ok(downloaded) => return Ok(downloaded),
+            Err(e) => {
+                tracing::warn!(
+                    mirror=%candidate,
+                    error=&*e,
+                    "Downloading from the mirror failed",
+                );
+            }
         }
+    }
 
-        let error = Error::new(e).context(format!(
-            "Unable to persist \"{}\" to \"{}\"",
-            temp.display(),
-            cache_dir.display()
-        ));
-        return Err(error);
+    if mirrors.is_empty() {
+        Err(error)
+    } else {
+        Err(error.context(format!("All {} mirrors also failed", mirrors.len())))
     }
+}
 
-    Ok(Assets {
-        tarball: tarball_path,
-        webc: test_case
-            .package_version
-            .distribution
-            .pirita_download_url
-            .is_some()
-            .then_some(webc_path),
-        total_size: bytes_downloaded,
-    })
+/// Rewrite a URL so it points at a mirror, keeping the original path and
+/// query (e.g. `https://cdn.wasmer.io/packages/x.tar.gz` with the mirror
+/// `https://mirror.example.com/wasmer/` becomes
+/// `https://mirror.example.com/wasmer/packages/x.tar.gz`).
+fn mirror_url(mirror: &Url, original: &Url) -> Url {
+    let mut url = mirror.clone();
+    let path = format!("{}{}", mirror.path().trim_end_matches('/'), original.path());
+    url.set_path(&path);
+    url.set_query(original.query());
+    url
 }
 
+/// How often download progress is reported.
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(100);
+
 #[tracing::instrument(skip_all, fields(url=tracing::field::Empty, bytes_read=tracing::field::Empty))]
-async fn download_file(client: &Client, url: &str, dest: impl AsRef<Path>) -> Result<u64, Error> {
+async fn download_file(
+    client: &Client,
+    url: &str,
+    auth: Option<&RegistryAuth>,
+    dest: impl AsRef<Path>,
+    mut on_progress: impl FnMut(u64, Option<u64>),
+) -> Result<(u64, String), Error> {
     let url = Url::parse(url)?;
     tracing::Span::current().record("url", url.path());
 
     let dest = dest.as_ref();
     tracing::debug!(dest=%dest.display(), "Downloading");
 
-    let payload = client
-        .get(url)
-        .send()
-        .await?
-        .error_for_status()?
-        .bytes()
-        .await?;
+    let mut request = client.get(url.clone());
+    if let Some(auth) = auth.filter(|a| a.applies_to(&url)) {
+        request = request.header(reqwest::header::AUTHORIZATION, auth.header.clone());
+    }
+    let mut response = request.send().await?.error_for_status()?;
+    let total = response.content_length();
 
-    tracing::Span::current().record("bytes_read", payload.len());
-    tracing::debug!("Download complete");
+    let mut file = tokio::fs::File::create(dest)
+        .await
+        .with_context(|| format!("Unable to create \"{}\"", dest.display()))?;
+    let mut hasher = Sha256::new();
+    let mut bytes_read = 0;
+    let mut last_update = Instant::now();
+    on_progress(0, total);
+
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk)
+            .await
+            .with_context(|| format!("Unable to save to \"{}\"", dest.display()))?;
+        hasher.update(&chunk);
+        bytes_read += chunk.len() as u64;
+
+        if last_update.elapsed() >= PROGRESS_INTERVAL {
+            on_progress(bytes_read, total);
+            last_update = Instant::now();
+        }
+    }
 
-    tokio::fs::write(dest, &payload)
+    file.flush()
         .await
         .with_context(|| format!("Unable to save to \"{}\"", dest.display()))?;
+    on_progress(bytes_read, total);
+
+    tracing::Span::current().record("bytes_read", bytes_read);
+    tracing::debug!("Download complete");
+
+    Ok((bytes_read, hex(&hasher.finalize())))
+}
+
+pub(crate) fn sha256(data: &[u8]) -> String {
+    hex(&Sha256::digest(data))
+}
+
+pub(crate) const MANIFEST_FILENAME: &str = "manifest.json";
+
+/// A record of the artifacts downloaded into a cache entry.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct Manifest {
+    /// The digests for each artifact, keyed by filename.
+    pub artifacts: BTreeMap<String, ArtifactDigest>,
+}
+
+impl Manifest {
+    fn load(entry: &Path) -> Result<Option<Self>, Error> {
+        let path = entry.join(MANIFEST_FILENAME);
+
+        match std::fs::read_to_string(&path) {
+            Ok(json) => serde_json::from_str(&json)
+                .map(Some)
+                .with_context(|| format!("Unable to parse \"{}\"", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::new(e).context(format!("Unable to read \"{}\"", path.display()))),
+        }
+    }
+
+    async fn save(&self, entry: &Path) -> Result<(), Error> {
+        let path = entry.join(MANIFEST_FILENAME);
+        let json = serde_json::to_string_pretty(self)?;
+        tokio::fs::write(&path, json)
+            .await
+            .with_context(|| format!("Unable to save to \"{}\"", path.display()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct ArtifactDigest {
+    /// Where the artifact was downloaded from.
+    pub url: String,
+    pub size: u64,
+    /// The artifact's SHA-256 hash, as a hex string.
+    pub sha256: String,
+}
+
+/// The result of checking a single entry in the package cache.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheEntry {
+    /// The directory containing the entry's artifacts.
+    pub dir: PathBuf,
+    pub status: EntryStatus,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum EntryStatus {
+    /// Every artifact matches its recorded digest.
+    Ok,
+    /// The entry was created before digests were recorded, so it can't be
+    /// checked.
+    Unverified,
+    /// Some artifacts are missing, truncated, or have been modified.
+    Corrupted {
+        /// A human-readable description of each problem.
+        problems: Vec<String>,
+    },
+}
+
+/// Check every package in the cache against the digests recorded when it was
+/// downloaded.
+pub fn verify_cache(cache_dir: &Path) -> Result<Vec<CacheEntry>, Error> {
+    check_layout(cache_dir)?;
+    let mut entries = Vec::new();
+
+    for dir in cache_entries(cache_dir)? {
+        let status = verify_entry(&dir)?;
+        entries.push(CacheEntry { dir, status });
+    }
+
+    Ok(entries)
+}
+
+/// Find every package version directory (`registry/namespace/name/version`)
+/// in the cache.
+fn cache_entries(cache_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut dirs = vec![cache_dir.to_path_buf()];
+
+    for depth in 0..4 {
+        let mut children = Vec::new();
+
+        for dir in dirs {
+            for entry in read_dir(&dir)? {
+                let name = entry.file_name();
+                let name = name.to_string_lossy();
+                let skip =
+                    name.starts_with('.') || (depth == 0 && NON_PACKAGE_DIRS.contains(&&*name));
+
+                if !skip && entry.file_type()?.is_dir() {
+                    children.push(entry.path());
+                }
+            }
+        }
+
+        dirs = children;
+    }
+
+    dirs.sort();
+    Ok(dirs)
+}
+
+/// The version of the on-disk cache layout (currently
+/// `{registry}/{namespace}/{name}/{version}`).
+///
+/// Bump this and add a migration to [`check_layout()`] whenever
+/// [`package_version_dir()`] changes, so existing downloads aren't silently
+/// orphaned.
+const LAYOUT_VERSION: u32 = 1;
+const LAYOUT_VERSION_FILENAME: &str = ".layout-version";
+
+/// Make sure the cache directory uses the layout this version of borealis
+/// expects, stamping it with the current [`LAYOUT_VERSION`] if it hasn't been
+/// stamped yet.
+pub(crate) fn check_layout(cache_dir: &Path) -> Result<(), CacheError> {
+    let path = cache_dir.join(LAYOUT_VERSION_FILENAME);
+
+    let found = match std::fs::read_to_string(&path) {
+        Ok(raw) => raw
+            .trim()
+            .parse::<u32>()
+            .map_err(|error| CacheError::InvalidLayoutVersion {
+                path: path.clone(),
+                error,
+            })?,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            // Caches created before the layout was versioned all used the
+            // current layout.
+            std::fs::create_dir_all(cache_dir)
+                .and_then(|_| std::fs::write(&path, LAYOUT_VERSION.to_string()))
+                .map_err(|error| CacheError::Io {
+                    path: path.clone(),
+                    error,
+                })?;
+            LAYOUT_VERSION
+        }
+        Err(error) => return Err(CacheError::Io { path, error }),
+    };
+
+    if found > LAYOUT_VERSION {
+        return Err(CacheError::UnsupportedLayout {
+            cache_dir: cache_dir.to_path_buf(),
+            found,
+            supported: LAYOUT_VERSION,
+        });
+    }
+
+    Ok(())
+}
+
+/// An error that occurred while checking the package cache.
+#[derive(Debug, thiserror::Error)]
+pub enum CacheError {
+    /// The cache was created by a newer version of borealis.
+    #[error(
+        "The cache at \"{}\" uses layout version {found}, but this version of borealis only \
+         understands version {supported}. Please upgrade or use a different cache directory.",
+        cache_dir.display()
+    )]
+    UnsupportedLayout {
+        cache_dir: PathBuf,
+        found: u32,
+        supported: u32,
+    },
+    #[error("Unable to parse \"{}\"", path.display())]
+    InvalidLayoutVersion {
+        path: PathBuf,
+        #[source]
+        error: std::num::ParseIntError,
+    },
+    #[error("Unable to access \"{}\"", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        error: std::io::Error,
+    },
+}
+
+/// Top-level directories in the cache which don't contain packages.
+const NON_PACKAGE_DIRS: &[&str] = &["toolchains", discovery::DISCOVERY_DIR];
+
+fn read_dir(dir: &Path) -> Result<Vec<std::fs::DirEntry>, Error> {
+    std::fs::read_dir(dir)
+        .and_then(|entries| entries.collect())
+        .with_context(|| format!("Unable to read \"{}\"", dir.display()))
+}
+
+fn verify_entry(dir: &Path) -> Result<EntryStatus, Error> {
+    let Some(manifest) = Manifest::load(dir)? else {
+        return Ok(EntryStatus::Unverified);
+    };
+
+    let mut problems = Vec::new();
+
+    for (filename, expected) in &manifest.artifacts {
+        let path = dir.join(filename);
+
+        let data = match std::fs::read(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                problems.push(format!("{filename} is missing"));
+                continue;
+            }
+            Err(e) => {
+                return Err(Error::new(e).context(format!("Unable to read \"{}\"", path.display())))
+            }
+        };
+
+        if data.len() as u64 != expected.size {
+            problems.push(format!(
+                "{filename} is {} bytes, but {} bytes were downloaded",
+                data.len(),
+                expected.size
+            ));
+        } else if sha256(&data) != expected.sha256 {
+            problems.push(format!("{filename} doesn't match its SHA-256 digest"));
+        }
+    }
 
-    Ok(payload.len().try_into().unwrap())
+    if problems.is_empty() {
+        Ok(EntryStatus::Ok)
+    } else {
+        Ok(EntryStatus::Corrupted { problems })
+    }
+}
+
+/// Re-download every artifact in a cache entry using the URLs recorded in its
+/// manifest.
+pub async fn repair_entry(client: &Client, cache_dir: &Path, entry: &Path) -> Result<(), Error> {
+    let manifest = Manifest::load(entry)?.with_context(|| {
+        format!(
+            "\"{}\" has no manifest, so it can't be re-downloaded",
+            entry.display()
+        )
+    })?;
+
+    let artifacts = manifest
+        .artifacts
+        .into_iter()
+        .map(|(filename, digest)| (filename, digest.url))
+        .collect();
+    let staging = download_artifacts(client, cache_dir, &[], None, artifacts, |_, _, _| {}).await?;
+    storage::persist(staging.path(), entry).await
 }
 
-pub fn package_version_dir(dir: &Path, test_case: &TestCase) -> PathBuf {
+pub(crate) fn package_version_dir(dir: &Path, test_case: &TestCase) -> PathBuf {
     dir.join(&test_case.registry)
         .join(&test_case.namespace)
         .join(&test_case.package_name)
         .join(test_case.version())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Response,
+    };
+
+    use super::*;
+    use crate::{experiment::FileSystemStorage, test_utils::test_case};
+
+    /// Serve `/python.tar.gz` (and fail everything else) from a local server,
+    /// returning its base URL and a count of the requests it received.
+    async fn artifact_server() -> (String, Arc<AtomicUsize>) {
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&requests);
+        let make_service = make_service_fn(move |_| {
+            let counter = Arc::clone(&counter);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    let response = match req.uri().path() {
+                        "/python.tar.gz" => Response::new(Body::from("tarball")),
+                        _ => Response::builder().status(500).body(Body::empty()).unwrap(),
+                    };
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let base_url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        (base_url, requests)
+    }
+
+    fn cache(dir: &Path) -> Cache {
+        let (progress, _) = tokio::sync::mpsc::unbounded_channel();
+        Cache::new(
+            dir.to_path_buf(),
+            Client::new(),
+            Arc::new(FileSystemStorage::new(dir)),
+            Vec::new(),
+            None,
+            progress,
+        )
+    }
+
+    fn served_from(base_url: &str, name: &str) -> TestCase {
+        let mut test_case = test_case(name, "1.0.0");
+        test_case.package_version.distribution.download_url = format!("{base_url}/{name}.tar.gz");
+        test_case
+    }
+
+    #[tokio::test]
+    async fn concurrent_fetches_share_a_download() {
+        let temp = tempfile::tempdir().unwrap();
+        let (base_url, requests) = artifact_server().await;
+        let cache = cache(temp.path());
+        let test_case = served_from(&base_url, "python");
+
+        let (first, second) = futures::join!(
+            cache.fetch(test_case.clone()),
+            cache.fetch(test_case.clone()),
+        );
+
+        let (first, second) = (first.unwrap().assets, second.unwrap().assets);
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert_eq!(first.tarball, second.tarball);
+        assert_eq!(first.webc, second.webc);
+        assert_eq!(first.total_size, second.total_size);
+        assert_eq!(
+            std::fs::read_to_string(first.tarball.unwrap()).unwrap(),
+            "tarball"
+        );
+        assert!(cache.in_flight.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_downloads_are_reported_to_every_waiter() {
+        let temp = tempfile::tempdir().unwrap();
+        let (base_url, requests) = artifact_server().await;
+        let cache = cache(temp.path());
+        let test_case = served_from(&base_url, "broken");
+
+        let (first, second) = futures::join!(
+            cache.fetch(test_case.clone()),
+            cache.fetch(test_case.clone()),
+        );
+
+        let (first, second) = (first.unwrap_err(), second.unwrap_err());
+        assert_eq!(first.to_string(), second.to_string());
+        assert!(first.to_string().contains("broken.tar.gz"));
+        assert_eq!(requests.load(Ordering::SeqCst), 1);
+        assert!(cache.in_flight.lock().unwrap().is_empty());
+
+        // The failure isn't cached, so the next fetch tries again
+        cache.fetch(test_case).await.unwrap_err();
+        assert_eq!(requests.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn caches_from_newer_versions_are_rejected() {
+        let temp = tempfile::tempdir().unwrap();
+
+        check_layout(temp.path()).unwrap();
+        let stamp = temp.path().join(LAYOUT_VERSION_FILENAME);
+        assert_eq!(std::fs::read_to_string(&stamp).unwrap(), "1");
+
+        std::fs::write(&stamp, (LAYOUT_VERSION + 1).to_string()).unwrap();
+        assert!(matches!(
+            check_layout(temp.path()),
+            Err(CacheError::UnsupportedLayout { found: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn registry_tokens_are_only_sent_to_the_registry() {
+        let auth = RegistryAuth::new("registry.wasmer.io", "secret").unwrap();
+        let applies = |url: &str| auth.applies_to(&url.parse().unwrap());
+
+        assert!(applies("https://registry.wasmer.io/graphql"));
+        assert!(applies("https://cdn.wasmer.io/packages/python.tar.gz"));
+        assert!(applies("https://CDN.wasmer.io/packages/python.tar.gz"));
+        assert!(!applies("https://wasmer.io/python.webc"));
+        assert!(!applies("https://user-content.wasmer.io/python.tar.gz"));
+        assert!(!applies("https://cdn.registry.wasmer.io/python.tar.gz"));
+        assert!(!applies(
+            "https://storage.googleapis.com/wasmer/python.tar.gz"
+        ));
+        assert!(!applies("https://notwasmer.io/python.tar.gz"));
+        assert!(!applies("https://wasmer.io.example.com/python.tar.gz"));
+
+        let local = RegistryAuth::new("localhost", "secret").unwrap();
+        assert!(local.applies_to(&"http://localhost:8080/python.tar.gz".parse().unwrap()));
+        assert!(!format!("{local:?}").contains("secret"));
+    }
+
+    #[test]
+    fn rewrite_urls_to_point_at_a_mirror() {
+        let original =
+            Url::parse("https://cdn.wasmer.io/packages/wasmer/python.tar.gz?v=1").unwrap();
+
+        let mirrors = [
+            "https://mirror.example.com",
+            "https://mirror.example.com/",
+            "https://mirror.example.com/wasmer/",
+        ];
+        let rewritten: Vec<_> = mirrors
+            .iter()
+            .map(|m| mirror_url(&Url::parse(m).unwrap(), &original).to_string())
+            .collect();
+
+        assert_eq!(
+            rewritten,
+            [
+                "https://mirror.example.com/packages/wasmer/python.tar.gz?v=1",
+                "https://mirror.example.com/packages/wasmer/python.tar.gz?v=1",
+                "https://mirror.example.com/wasmer/packages/wasmer/python.tar.gz?v=1",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn verify_detects_corrupted_entries() {
+        let temp = tempfile::tempdir().unwrap();
+        let cache_dir = temp.path();
+        let mut entries = Vec::new();
+
+        for name in ["good", "truncated", "old"] {
+            let entry = cache_dir
+                .join("registry.wasmer.io")
+                .join("wasmer")
+                .join(name)
+                .join("1.0.0");
+            std::fs::create_dir_all(&entry).unwrap();
+            std::fs::write(entry.join("pkg.tar.gz"), "tarball").unwrap();
+            entries.push(entry);
+        }
+        let manifest = Manifest {
+            artifacts: [(
+                "pkg.tar.gz".to_string(),
+                ArtifactDigest {
+                    url: "https://example.com/pkg.tar.gz".to_string(),
+                    size: 7,
+                    sha256: sha256(b"tarball"),
+                },
+            )]
+            .into_iter()
+            .collect(),
+        };
+        manifest.save(&entries[0]).await.unwrap();
+        manifest.save(&entries[1]).await.unwrap();
+        std::fs::write(entries[1].join("pkg.tar.gz"), "tar").unwrap();
+        // Toolchains are stored in the cache directory, but aren't packages
+        std::fs::create_dir_all(cache_dir.join("toolchains/v4.2.0/linux-amd64/bin")).unwrap();
+
+        let results = verify_cache(cache_dir).unwrap();
+
+        let statuses: Vec<_> = results.iter().map(|e| (&e.dir, &e.status)).collect();
+        assert_eq!(
+            statuses,
+            [
+                (&entries[0], &EntryStatus::Ok),
+                (&entries[2], &EntryStatus::Unverified),
+                (
+                    &entries[1],
+                    &EntryStatus::Corrupted {
+                        problems: vec![
+                            "pkg.tar.gz is 3 bytes, but 7 bytes were downloaded".to_string()
+                        ]
+                    }
+                ),
+            ]
+        );
+    }
+}
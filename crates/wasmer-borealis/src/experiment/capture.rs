@@ -0,0 +1,307 @@
+//! Capturing a test case's output when it needs more than a plain log file,
+//! either because it is echoed to the console or because it is limited in
+//! size.
+
+use std::{collections::VecDeque, io::Write, sync::Arc, time::Duration};
+
+use tokio::{
+    fs::File,
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
+    sync::Notify,
+    task::JoinHandle,
+};
+
+use crate::{
+    config::{OutputLimit, OutputLimitAction},
+    experiment::redact::{RedactStream, Redactor},
+};
+
+/// How long to keep copying output after the process exits, in case
+/// something it started is still holding the pipe open.
+pub(crate) const GRACE_PERIOD: Duration = Duration::from_secs(1);
+
+/// Lines longer than this are echoed to the console in pieces.
+const MAX_LINE: usize = 8 * 1024;
+
+/// How a test case's stdout and stderr are captured.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Capture {
+    /// Echo each line to the console with this prefix.
+    pub tee: Option<String>,
+    /// The most output to keep from each stream.
+    pub limit: Option<OutputLimit>,
+    /// Scrub secrets from the output before it is written to the logs or
+    /// echoed.
+    pub redactor: Option<Redactor>,
+}
+
+impl Capture {
+    /// Does the output need to go through us, or can the process write
+    /// straight to its log files?
+    pub(crate) fn is_piped(&self) -> bool {
+        self.tee.is_some() || self.limit.is_some() || self.redacts()
+    }
+
+    /// Are there any secrets to scrub from the output?
+    pub(crate) fn redacts(&self) -> bool {
+        self.redactor.as_ref().map_or(false, Redactor::has_secrets)
+    }
+
+    /// Should the process be killed when it goes over the limit?
+    pub(crate) fn kills(&self) -> bool {
+        matches!(
+            self.limit,
+            Some(OutputLimit {
+                action: OutputLimitAction::Kill,
+                ..
+            })
+        )
+    }
+
+    /// Copy everything from `src` into the log `file`, applying the limit and
+    /// echoing to the `console` as necessary.
+    ///
+    /// The `exceeded` notification fires if `src` goes over the limit.
+    pub(crate) fn spawn(
+        &self,
+        src: impl AsyncRead + Send + Unpin + 'static,
+        file: File,
+        console: Console,
+        exceeded: Arc<Notify>,
+    ) -> JoinHandle<std::io::Result<()>> {
+        let tee = self.tee.clone().map(|prefix| (prefix, console));
+        let truncate = self.limit.as_ref().map(|l| Truncate::new(l.max_bytes));
+        let redact = self
+            .redactor
+            .as_ref()
+            .filter(|r| r.has_secrets())
+            .map(Redactor::stream);
+
+        let sink = Sink {
+            file,
+            tee,
+            truncate,
+            exceeded,
+            notified: false,
+            line: Vec::new(),
+        };
+
+        tokio::spawn(copy(src, redact, sink))
+    }
+}
+
+/// Where output is echoed to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Console {
+    Stdout,
+    Stderr,
+}
+
+async fn copy(
+    mut src: impl AsyncRead + Unpin,
+    mut redact: Option<RedactStream>,
+    mut sink: Sink,
+) -> std::io::Result<()> {
+    let mut buffer = vec![0; 8 * 1024];
+
+    loop {
+        let bytes_read = src.read(&mut buffer).await?;
+        if bytes_read == 0 {
+            break;
+        }
+        let data = &buffer[..bytes_read];
+
+        // Secrets need to be scrubbed before anything else sees the output
+        match &mut redact {
+            Some(redact) => sink.write(&redact.push(data)).await?,
+            None => sink.write(data).await?,
+        }
+    }
+
+    if let Some(redact) = redact {
+        sink.write(&redact.finish()).await?;
+    }
+
+    sink.finish().await
+}
+
+/// Where a stream's output ends up.
+struct Sink {
+    file: File,
+    tee: Option<(String, Console)>,
+    truncate: Option<Truncate>,
+    exceeded: Arc<Notify>,
+    notified: bool,
+    /// A partial line waiting to be echoed.
+    line: Vec<u8>,
+}
+
+impl Sink {
+    async fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        match &mut self.truncate {
+            Some(truncate) => {
+                self.file.write_all(truncate.push(data)).await?;
+                if truncate.exceeded() && !self.notified {
+                    self.exceeded.notify_one();
+                    self.notified = true;
+                }
+            }
+            None => self.file.write_all(data).await?,
+        }
+
+        if let Some((prefix, console)) = &self.tee {
+            self.line.extend_from_slice(data);
+            while let Some(end) = self.line.iter().position(|&b| b == b'\n') {
+                let rest = self.line.split_off(end + 1);
+                echo(*console, prefix, &self.line);
+                self.line = rest;
+            }
+            if self.line.len() > MAX_LINE {
+                echo(*console, prefix, &self.line);
+                self.line.clear();
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn finish(mut self) -> std::io::Result<()> {
+        if let Some(truncate) = self.truncate {
+            self.file.write_all(&truncate.finish()).await?;
+        }
+        if let (Some((prefix, console)), false) = (&self.tee, self.line.is_empty()) {
+            echo(*console, prefix, &self.line);
+        }
+
+        self.file.flush().await
+    }
+}
+
+fn echo(console: Console, prefix: &str, line: &[u8]) {
+    // Don't let a closed terminal stop the logs from being written
+    let _ = match console {
+        Console::Stdout => write_prefixed(std::io::stdout().lock(), prefix, line),
+        Console::Stderr => write_prefixed(std::io::stderr().lock(), prefix, line),
+    };
+}
+
+fn write_prefixed(mut dest: impl Write, prefix: &str, line: &[u8]) -> std::io::Result<()> {
+    write!(dest, "[{prefix}] ")?;
+    dest.write_all(line)?;
+    if !line.ends_with(b"\n") {
+        dest.write_all(b"\n")?;
+    }
+    dest.flush()
+}
+
+/// Keeps the first and last halves of a stream once it goes over the limit,
+/// dropping everything in between.
+#[derive(Debug)]
+struct Truncate {
+    /// How much more can be written before we start dropping things.
+    head: u64,
+    /// The most recent output, which is only written at the end.
+    tail: VecDeque<u8>,
+    tail_capacity: usize,
+    dropped: u64,
+}
+
+impl Truncate {
+    fn new(limit: u64) -> Self {
+        let head = limit / 2;
+
+        Truncate {
+            head,
+            tail: VecDeque::new(),
+            tail_capacity: usize::try_from(limit - head).unwrap_or(usize::MAX),
+            dropped: 0,
+        }
+    }
+
+    /// Returns the part of `data` that can be written straight away,
+    /// holding on to the rest.
+    fn push<'a>(&mut self, data: &'a [u8]) -> &'a [u8] {
+        let head = usize::try_from(self.head).map_or(data.len(), |h| h.min(data.len()));
+        let (now, rest) = data.split_at(head);
+        self.head -= head as u64;
+
+        self.tail.extend(rest);
+        if self.tail.len() > self.tail_capacity {
+            let excess = self.tail.len() - self.tail_capacity;
+            self.tail.drain(..excess);
+            self.dropped += excess as u64;
+        }
+
+        now
+    }
+
+    /// Has the stream gone over the limit?
+    fn exceeded(&self) -> bool {
+        self.dropped > 0
+    }
+
+    /// Everything that should be written once the stream has ended.
+    fn finish(self) -> Vec<u8> {
+        let mut remaining = Vec::new();
+
+        if self.dropped > 0 {
+            remaining.extend_from_slice(
+                format!("\n[... {} bytes truncated ...]\n", self.dropped).as_bytes(),
+            );
+        }
+        remaining.extend(self.tail);
+
+        remaining
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_line_gets_its_own_prefix() {
+        let mut console = Vec::new();
+
+        write_prefixed(&mut console, "wasmer/python@1.0.0", b"Hello\n").unwrap();
+        write_prefixed(&mut console, "wasmer/python@1.0.0", b"no newline").unwrap();
+
+        assert_eq!(
+            String::from_utf8(console).unwrap(),
+            "[wasmer/python@1.0.0] Hello\n[wasmer/python@1.0.0] no newline\n"
+        );
+    }
+
+    fn truncated(limit: u64, chunks: &[&str]) -> (String, bool) {
+        let mut truncate = Truncate::new(limit);
+        let mut written = Vec::new();
+
+        for chunk in chunks {
+            written.extend_from_slice(truncate.push(chunk.as_bytes()));
+        }
+        let exceeded = truncate.exceeded();
+        written.extend(truncate.finish());
+
+        (String::from_utf8(written).unwrap(), exceeded)
+    }
+
+    #[test]
+    fn output_under_the_limit_is_kept() {
+        assert_eq!(
+            truncated(11, &["hello", " world"]),
+            ("hello world".to_string(), false)
+        );
+        assert_eq!(
+            truncated(100, &["hello", " world"]),
+            ("hello world".to_string(), false)
+        );
+    }
+
+    #[test]
+    fn the_head_and_tail_are_kept_when_over_the_limit() {
+        let (written, exceeded) = truncated(8, &["aaaa", "bbbb", "cccc", "dddd"]);
+
+        assert!(exceeded);
+        assert_eq!(written, "aaaa\n[... 8 bytes truncated ...]\ndddd");
+    }
+}
@@ -0,0 +1,142 @@
+//! Collecting core dumps from test cases that crash, so engine bugs come
+//! with something that can actually be debugged.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error};
+
+use crate::toolchain;
+
+/// The directory inside a test case's directory that core dumps are moved
+/// to.
+pub(crate) const COREDUMP_DIR: &str = "coredumps";
+
+/// The file `wasmer` writes its core dump to when a guest traps.
+pub(crate) const TRAP_COREDUMP: &str = "coredump.wasm";
+
+/// How core dumps are collected for each test case.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) enum Coredumps {
+    /// Ask `wasmer run` to write a WebAssembly core dump when the guest
+    /// traps.
+    OnTrap,
+    /// Let the operating system write a core file if the `wasmer` process
+    /// itself crashes.
+    ///
+    /// Where the core file ends up is decided by the OS (e.g.
+    /// `/proc/sys/kernel/core_pattern` on Linux), so it is only collected
+    /// when written to the test case's working directory.
+    Os,
+}
+
+impl Coredumps {
+    /// Figure out the best way to collect core dumps with this `wasmer` CLI.
+    pub(crate) async fn detect(wasmer: &Path) -> Coredumps {
+        match toolchain::run_supports(wasmer, "--coredump-on-trap").await {
+            Ok(true) => Coredumps::OnTrap,
+            Ok(false) => {
+                tracing::info!(
+                    "This wasmer doesn't support --coredump-on-trap, falling back to OS core dumps"
+                );
+                Coredumps::Os
+            }
+            Err(e) => {
+                tracing::warn!(
+                    error = &*e,
+                    "Unable to check whether wasmer supports --coredump-on-trap",
+                );
+                Coredumps::Os
+            }
+        }
+    }
+}
+
+/// Let the command write core files by raising its core file size limit.
+pub(crate) fn enable_os_coredumps(cmd: &mut tokio::process::Command) {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            // Safety: setrlimit() is async-signal-safe.
+            unsafe {
+                cmd.pre_exec(|| {
+                    let limit = libc::rlimit {
+                        rlim_cur: libc::RLIM_INFINITY,
+                        rlim_max: libc::RLIM_INFINITY,
+                    };
+                    // Not being allowed to raise the limit shouldn't stop the
+                    // test case from running.
+                    libc::setrlimit(libc::RLIMIT_CORE, &limit);
+                    Ok(())
+                });
+            }
+        } else {
+            let _ = cmd;
+        }
+    }
+}
+
+/// Move any core dumps in a test case's directory into its
+/// [`COREDUMP_DIR`], returning their paths relative to `base_dir`.
+pub(crate) fn collect(base_dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let entries = std::fs::read_dir(base_dir)
+        .with_context(|| format!("Unable to read \"{}\"", base_dir.display()))?;
+    let mut collected = Vec::new();
+
+    for entry in entries {
+        let entry = entry?;
+        let filename = entry.file_name();
+        let Some(name) = filename.to_str() else {
+            continue;
+        };
+        if !entry.file_type()?.is_file() || !is_coredump(name) {
+            continue;
+        }
+
+        let dest_dir = base_dir.join(COREDUMP_DIR);
+        std::fs::create_dir_all(&dest_dir)
+            .with_context(|| format!("Unable to create \"{}\"", dest_dir.display()))?;
+        std::fs::rename(entry.path(), dest_dir.join(name))
+            .with_context(|| format!("Unable to move \"{}\"", entry.path().display()))?;
+        collected.push(Path::new(COREDUMP_DIR).join(name));
+    }
+
+    collected.sort();
+    Ok(collected)
+}
+
+/// Does this look like a file written by `--coredump-on-trap` or the
+/// operating system (`core` or `core.<pid>`)?
+fn is_coredump(filename: &str) -> bool {
+    if filename == TRAP_COREDUMP || filename == "core" {
+        return true;
+    }
+
+    filename
+        .strip_prefix("core.")
+        .map_or(false, |pid| pid.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn coredumps_are_moved_into_their_own_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        for filename in ["coredump.wasm", "core.1234", "core.txt", "stderr.txt"] {
+            std::fs::write(temp.path().join(filename), "").unwrap();
+        }
+
+        let collected = collect(temp.path()).unwrap();
+
+        assert_eq!(
+            collected,
+            [
+                Path::new("coredumps/core.1234"),
+                Path::new("coredumps/coredump.wasm")
+            ]
+        );
+        assert!(temp.path().join("coredumps/coredump.wasm").exists());
+        assert!(!temp.path().join("coredump.wasm").exists());
+        assert!(temp.path().join("core.txt").exists());
+    }
+}
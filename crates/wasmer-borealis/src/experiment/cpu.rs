@@ -0,0 +1,216 @@
+//! Keeping borealis' own work (downloads, orchestration, etc.) away from the
+//! CPUs test cases are measured on.
+
+use std::io::Error;
+
+/// Which CPUs test cases run on, and how much priority borealis' own threads
+/// get.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Scheduling {
+    /// The cores test processes are pinned to (any core when empty).
+    test_cores: Vec<usize>,
+    /// The niceness borealis' own threads run at.
+    niceness: Option<i32>,
+    /// The niceness to give test processes, if borealis is allowed to lower
+    /// it back down after raising its own.
+    restore_niceness: Option<i32>,
+}
+
+impl Scheduling {
+    pub fn with_test_cores(self, test_cores: Vec<usize>) -> Self {
+        Scheduling { test_cores, ..self }
+    }
+
+    pub fn with_niceness(self, niceness: i32) -> Self {
+        Scheduling {
+            niceness: Some(niceness),
+            ..self
+        }
+    }
+
+    /// Move the current thread (and any threads it starts afterwards) off
+    /// the test cores and lower its priority.
+    pub fn apply(&mut self) -> Result<(), Error> {
+        if self.test_cores.is_empty() && self.niceness.is_none() {
+            return Ok(());
+        }
+
+        imp::apply(self)
+    }
+
+    /// Pin a test process to the test cores and give it back the priority
+    /// borealis had before [`Scheduling::apply()`] lowered it.
+    pub fn configure(&self, cmd: &mut tokio::process::Command) {
+        if self.test_cores.is_empty() && self.restore_niceness.is_none() {
+            return;
+        }
+
+        imp::configure(self, cmd);
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use std::{io::Error, mem::MaybeUninit};
+
+    use super::Scheduling;
+
+    pub(super) fn apply(scheduling: &mut Scheduling) -> Result<(), Error> {
+        if !scheduling.test_cores.is_empty() {
+            let mut current = current_affinity()?;
+            for &core in &scheduling.test_cores {
+                check_core(core)?;
+                unsafe { libc::CPU_CLR(core, &mut current) };
+            }
+
+            if unsafe { libc::CPU_COUNT(&current) } > 0 {
+                set_affinity(&current)?;
+            } else {
+                tracing::warn!(
+                    cores = ?scheduling.test_cores,
+                    "Every core is reserved for test cases, so borealis will share them",
+                );
+            }
+        }
+
+        if let Some(niceness) = scheduling.niceness {
+            let original = priority()?;
+            set_priority(niceness)?;
+
+            // Only privileged processes can lower their niceness, so check
+            // whether test processes can be given the original priority back
+            if set_priority(original).is_ok() {
+                set_priority(niceness)?;
+                scheduling.restore_niceness = Some(original);
+            } else {
+                tracing::warn!(
+                    niceness,
+                    "Test cases will run at the same niceness as borealis because restoring it requires CAP_SYS_NICE",
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn configure(scheduling: &Scheduling, cmd: &mut tokio::process::Command) {
+        let cores = (!scheduling.test_cores.is_empty()).then(|| {
+            let mut set = empty_set();
+            for &core in &scheduling.test_cores {
+                // Cores were checked when the scheduling was applied
+                if core < libc::CPU_SETSIZE as usize {
+                    unsafe { libc::CPU_SET(core, &mut set) };
+                }
+            }
+            set
+        });
+        let niceness = scheduling.restore_niceness;
+
+        // Safety: sched_setaffinity() and setpriority() are plain syscalls,
+        // and nothing is allocated in the closure.
+        unsafe {
+            cmd.pre_exec(move || {
+                if let Some(cores) = &cores {
+                    set_affinity(cores)?;
+                }
+                if let Some(niceness) = niceness {
+                    set_priority(niceness)?;
+                }
+                Ok(())
+            });
+        }
+    }
+
+    fn check_core(core: usize) -> Result<(), Error> {
+        if core < libc::CPU_SETSIZE as usize {
+            Ok(())
+        } else {
+            Err(Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("There is no core {core}"),
+            ))
+        }
+    }
+
+    fn empty_set() -> libc::cpu_set_t {
+        // Safety: an all-zeroes cpu_set_t is the empty set.
+        unsafe { MaybeUninit::zeroed().assume_init() }
+    }
+
+    fn current_affinity() -> Result<libc::cpu_set_t, Error> {
+        let mut set = empty_set();
+        let size = std::mem::size_of::<libc::cpu_set_t>();
+
+        if unsafe { libc::sched_getaffinity(0, size, &mut set) } == 0 {
+            Ok(set)
+        } else {
+            Err(Error::last_os_error())
+        }
+    }
+
+    fn set_affinity(set: &libc::cpu_set_t) -> Result<(), Error> {
+        let size = std::mem::size_of::<libc::cpu_set_t>();
+
+        if unsafe { libc::sched_setaffinity(0, size, set) } == 0 {
+            Ok(())
+        } else {
+            Err(Error::last_os_error())
+        }
+    }
+
+    /// The current thread's niceness.
+    fn priority() -> Result<i32, Error> {
+        // -1 is a valid niceness, so errors can only be detected via errno
+        unsafe { *libc::__errno_location() = 0 };
+        let niceness = unsafe { libc::getpriority(libc::PRIO_PROCESS, 0) };
+
+        match Error::last_os_error() {
+            e if niceness == -1 && e.raw_os_error() != Some(0) => Err(e),
+            _ => Ok(niceness),
+        }
+    }
+
+    /// Set the current thread's niceness.
+    fn set_priority(niceness: i32) -> Result<(), Error> {
+        if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, niceness) } == 0 {
+            Ok(())
+        } else {
+            Err(Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use std::io::{Error, ErrorKind};
+
+    use super::Scheduling;
+
+    pub(super) fn apply(_scheduling: &mut Scheduling) -> Result<(), Error> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "Pinning test cases to cores and changing niceness is only supported on Linux",
+        ))
+    }
+
+    pub(super) fn configure(_scheduling: &Scheduling, _cmd: &mut tokio::process::Command) {}
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_processes_are_pinned_to_the_test_cores() {
+        let scheduling = Scheduling::default().with_test_cores(vec![0]);
+        let mut cmd = tokio::process::Command::new("grep");
+        cmd.args(["Cpus_allowed_list", "/proc/self/status"]);
+
+        scheduling.configure(&mut cmd);
+        let output = cmd.output().await.unwrap();
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout.split_whitespace().last(), Some("0"));
+    }
+}
@@ -0,0 +1,305 @@
+use std::{cmp::Ordering, collections::BTreeMap, time::Duration};
+
+use crate::experiment::{Category, Outcome, Report, Results};
+
+/// A test case's run time must change by at least this fraction before it is
+/// reported in [`ResultsDiff::runtime_changed`].
+const RELATIVE_RUNTIME_THRESHOLD: f64 = 0.5;
+/// The minimum change in run time that is reported, so programs which only
+/// take a few milliseconds don't generate noise.
+const ABSOLUTE_RUNTIME_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// How an experiment's [`Results`] changed compared to a baseline run.
+///
+/// Test cases are matched up using the package's name and version. Each list
+/// is sorted by package name, then version.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ResultsDiff {
+    /// Test cases whose outcome got worse (e.g. a success became a failure).
+    pub regressed: Vec<OutcomeChange>,
+    /// Test cases whose outcome improved.
+    pub fixed: Vec<OutcomeChange>,
+    /// Test cases that weren't part of the baseline.
+    pub added: Vec<Entry>,
+    /// Test cases from the baseline that weren't run this time.
+    pub removed: Vec<Entry>,
+    /// Test cases that completed both times, but whose run time changed
+    /// significantly.
+    pub runtime_changed: Vec<RuntimeChange>,
+    /// Test cases that exited the same way both times, but whose stdout was
+    /// different.
+    pub output_changed: Vec<OutputChange>,
+}
+
+impl ResultsDiff {
+    /// Did anything change?
+    pub fn is_empty(&self) -> bool {
+        let ResultsDiff {
+            regressed,
+            fixed,
+            added,
+            removed,
+            runtime_changed,
+            output_changed,
+        } = self;
+
+        regressed.is_empty()
+            && fixed.is_empty()
+            && added.is_empty()
+            && removed.is_empty()
+            && runtime_changed.is_empty()
+            && output_changed.is_empty()
+    }
+}
+
+/// A test case whose [`Category`] changed.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OutcomeChange {
+    pub display_name: String,
+    pub version: String,
+    pub before: Category,
+    pub after: Category,
+}
+
+/// A test case that only appears in one of the two runs.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Entry {
+    pub display_name: String,
+    pub version: String,
+    pub category: Category,
+}
+
+/// A test case whose run time changed.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RuntimeChange {
+    pub display_name: String,
+    pub version: String,
+    pub before: Duration,
+    pub after: Duration,
+}
+
+/// A test case which exited the same way, but wrote something different to
+/// stdout.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OutputChange {
+    pub display_name: String,
+    pub version: String,
+    /// The SHA-256 hash of the baseline's stdout.
+    pub before: String,
+    /// The SHA-256 hash of the new stdout.
+    pub after: String,
+}
+
+impl Results {
+    /// Compare these results against a `baseline` (typically an earlier run
+    /// of the same experiment).
+    pub fn diff(&self, baseline: &Results) -> ResultsDiff {
+        let before = by_test_case(baseline);
+        let after = by_test_case(self);
+        let mut diff = ResultsDiff::default();
+
+        for (&(display_name, version), old) in &before {
+            let Some(new) = after.get(&(display_name, version)) else {
+                diff.removed.push(Entry {
+                    display_name: display_name.to_string(),
+                    version: version.to_string(),
+                    category: old.category(),
+                });
+                continue;
+            };
+
+            let change = OutcomeChange {
+                display_name: display_name.to_string(),
+                version: version.to_string(),
+                before: old.category(),
+                after: new.category(),
+            };
+
+            match change.after.cmp(&change.before) {
+                Ordering::Greater => diff.regressed.push(change),
+                Ordering::Less => diff.fixed.push(change),
+                Ordering::Equal => {
+                    if let (Some(before), Some(after)) = (run_time(old), run_time(new)) {
+                        if runtime_changed(before, after) {
+                            diff.runtime_changed.push(RuntimeChange {
+                                display_name: display_name.to_string(),
+                                version: version.to_string(),
+                                before,
+                                after,
+                            });
+                        }
+                    }
+
+                    if let (true, Some(before), Some(after)) = (
+                        new.output_diverges_from(old),
+                        &old.stdout_digest,
+                        &new.stdout_digest,
+                    ) {
+                        diff.output_changed.push(OutputChange {
+                            display_name: display_name.to_string(),
+                            version: version.to_string(),
+                            before: before.clone(),
+                            after: after.clone(),
+                        });
+                    }
+                }
+            }
+        }
+
+        for (&(display_name, version), new) in &after {
+            if !before.contains_key(&(display_name, version)) {
+                diff.added.push(Entry {
+                    display_name: display_name.to_string(),
+                    version: version.to_string(),
+                    category: new.category(),
+                });
+            }
+        }
+
+        diff
+    }
+}
+
+/// Find the test cases which exited the same way in several runs of an
+/// experiment (e.g. one run per `wasmer` version) but wrote something
+/// different to stdout.
+///
+/// Returns the package name and version of each test case, sorted.
+pub fn diverging_outputs<'a>(
+    runs: impl IntoIterator<Item = &'a Results>,
+) -> Vec<(&'a str, &'a str)> {
+    let mut reports: BTreeMap<(&str, &str), Vec<&Report>> = BTreeMap::new();
+
+    for results in runs {
+        for (key, report) in by_test_case(results) {
+            reports.entry(key).or_default().push(report);
+        }
+    }
+
+    reports
+        .into_iter()
+        .filter(|(_, reports)| {
+            reports
+                .iter()
+                .enumerate()
+                .any(|(i, a)| reports[i + 1..].iter().any(|b| a.output_diverges_from(b)))
+        })
+        .map(|(key, _)| key)
+        .collect()
+}
+
+pub(super) fn by_test_case(results: &Results) -> BTreeMap<(&str, &str), &Report> {
+    results
+        .reports
+        .iter()
+        .map(|r| ((r.display_name.as_str(), r.version()), r))
+        .collect()
+}
+
+fn run_time(report: &Report) -> Option<Duration> {
+    match report.outcome {
+        Outcome::Completed { run_time, .. } => Some(run_time),
+        _ => None,
+    }
+}
+
+fn runtime_changed(before: Duration, after: Duration) -> bool {
+    let difference = if after > before {
+        after - before
+    } else {
+        before - after
+    };
+
+    difference >= ABSOLUTE_RUNTIME_THRESHOLD
+        && difference.as_secs_f64() >= before.as_secs_f64() * RELATIVE_RUNTIME_THRESHOLD
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{completed, fetch_failed, report, results};
+
+    fn with_stdout(report: Report, digest: &str) -> Report {
+        Report {
+            stdout_digest: Some(digest.to_string()),
+            ..report
+        }
+    }
+
+    #[test]
+    fn compare_two_runs() {
+        let baseline = results(vec![
+            report("python", "1.0.0", completed(true, 1)),
+            report("cowsay", "1.0.0", fetch_failed()),
+            report("slow", "1.0.0", completed(true, 2)),
+            report("gone", "1.0.0", completed(true, 1)),
+            with_stdout(report("chatty", "1.0.0", completed(true, 1)), "aaa"),
+            report("unhashed", "1.0.0", completed(true, 1)),
+        ]);
+        let current = results(vec![
+            report("python", "1.0.0", completed(false, 1)),
+            report("cowsay", "1.0.0", completed(true, 1)),
+            report("slow", "1.0.0", completed(true, 10)),
+            report("new", "1.0.0", completed(true, 1)),
+            with_stdout(report("chatty", "1.0.0", completed(true, 1)), "bbb"),
+            with_stdout(report("unhashed", "1.0.0", completed(true, 1)), "bbb"),
+        ]);
+
+        let diff = current.diff(&baseline);
+
+        assert_eq!(
+            diff.regressed
+                .iter()
+                .map(|c| &c.display_name)
+                .collect::<Vec<_>>(),
+            ["wasmer/python"]
+        );
+        assert_eq!(diff.regressed[0].before, Category::Success);
+        assert_eq!(diff.regressed[0].after, Category::Failure);
+        assert_eq!(
+            diff.fixed
+                .iter()
+                .map(|c| &c.display_name)
+                .collect::<Vec<_>>(),
+            ["wasmer/cowsay"]
+        );
+        assert_eq!(
+            diff.added
+                .iter()
+                .map(|e| &e.display_name)
+                .collect::<Vec<_>>(),
+            ["wasmer/new"]
+        );
+        assert_eq!(
+            diff.removed
+                .iter()
+                .map(|e| &e.display_name)
+                .collect::<Vec<_>>(),
+            ["wasmer/gone"]
+        );
+        assert_eq!(
+            diff.runtime_changed,
+            [RuntimeChange {
+                display_name: "wasmer/slow".to_string(),
+                version: "1.0.0".to_string(),
+                before: Duration::from_secs(2),
+                after: Duration::from_secs(10),
+            }]
+        );
+        assert_eq!(
+            diff.output_changed,
+            [OutputChange {
+                display_name: "wasmer/chatty".to_string(),
+                version: "1.0.0".to_string(),
+                before: "aaa".to_string(),
+                after: "bbb".to_string(),
+            }]
+        );
+        assert!(current.diff(&current).is_empty());
+    }
+}
@@ -0,0 +1,188 @@
+//! Remembering which test cases were discovered, so re-running an experiment
+//! with the same filters doesn't need to walk the entire registry again.
+
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::{
+    config::Filters,
+    experiment::{hex, TestCase},
+};
+
+/// How long discovered test cases are reused unless told otherwise.
+pub const DEFAULT_DISCOVERY_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// The directory (relative to the cache directory) discovery results are
+/// saved in.
+pub(crate) const DISCOVERY_DIR: &str = "discovery";
+
+/// The test cases discovered for a particular registry and set of filters.
+#[derive(Debug, Clone)]
+pub(crate) struct DiscoveryCache {
+    path: PathBuf,
+    ttl: Duration,
+}
+
+impl DiscoveryCache {
+    pub fn new(cache_dir: &Path, endpoint: &Url, filters: &Filters, ttl: Duration) -> Self {
+        let filters = serde_json::to_vec(filters).expect("Filters are always serializable");
+        let key = Sha256::new()
+            .chain_update(endpoint.as_str().as_bytes())
+            .chain_update(filters)
+            .finalize();
+        let path = cache_dir
+            .join(DISCOVERY_DIR)
+            .join(hex(&key))
+            .with_extension("json");
+
+        DiscoveryCache { path, ttl }
+    }
+
+    /// Get the previously discovered test cases, if they haven't expired.
+    pub fn load(&self) -> Option<Vec<TestCase>> {
+        self.load_at(Utc::now())
+    }
+
+    fn load_at(&self, now: DateTime<Utc>) -> Option<Vec<TestCase>> {
+        let raw = match std::fs::read(&self.path) {
+            Ok(raw) => raw,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+            Err(e) => {
+                tracing::warn!(
+                    error = &e as &dyn std::error::Error,
+                    path = %self.path.display(),
+                    "Unable to read the discovered test cases",
+                );
+                return None;
+            }
+        };
+
+        let entry: Entry = match serde_json::from_slice(&raw) {
+            Ok(entry) => entry,
+            Err(e) => {
+                tracing::warn!(
+                    error = &e as &dyn std::error::Error,
+                    path = %self.path.display(),
+                    "Ignoring discovered test cases which couldn't be parsed",
+                );
+                return None;
+            }
+        };
+
+        let age = now
+            .signed_duration_since(entry.discovered_at)
+            .to_std()
+            .unwrap_or_default();
+        if age >= self.ttl {
+            tracing::debug!(
+                age_secs = age.as_secs(),
+                "The discovered test cases have expired",
+            );
+            return None;
+        }
+
+        tracing::info!(
+            test_cases = entry.test_cases.len(),
+            discovered_at = %entry.discovered_at,
+            "Reusing previously discovered test cases",
+        );
+
+        Some(entry.test_cases)
+    }
+
+    /// Remember the test cases that were discovered, replacing anything saved
+    /// earlier.
+    pub fn save(&self, test_cases: Vec<TestCase>) {
+        self.save_at(test_cases, Utc::now());
+    }
+
+    fn save_at(&self, test_cases: Vec<TestCase>, discovered_at: DateTime<Utc>) {
+        let entry = Entry {
+            discovered_at,
+            test_cases,
+        };
+
+        if let Err(e) = write_atomically(&self.path, &entry) {
+            tracing::warn!(
+                error = &*e,
+                path = %self.path.display(),
+                "Unable to save the discovered test cases",
+            );
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct Entry {
+    discovered_at: DateTime<Utc>,
+    test_cases: Vec<TestCase>,
+}
+
+fn write_atomically(path: &Path, entry: &Entry) -> Result<(), anyhow::Error> {
+    let dir = path
+        .parent()
+        .expect("Always inside the discovery directory");
+    std::fs::create_dir_all(dir)?;
+
+    let mut temp = tempfile::NamedTempFile::new_in(dir)?;
+    serde_json::to_writer(&mut temp, entry)?;
+    temp.persist(path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_case;
+
+    #[test]
+    fn discovered_test_cases_are_reused_until_they_expire() {
+        let temp = tempfile::tempdir().unwrap();
+        let endpoint: Url = "https://registry.wasmer.io/graphql".parse().unwrap();
+        let cache = DiscoveryCache::new(
+            temp.path(),
+            &endpoint,
+            &Filters::default(),
+            Duration::from_secs(60),
+        );
+        let now = Utc::now();
+
+        assert!(cache.load_at(now).is_none());
+        cache.save_at(vec![test_case("python", "1.0.0")], now);
+
+        let reused = cache.load_at(now + chrono::Duration::seconds(30)).unwrap();
+        assert_eq!(reused.len(), 1);
+        assert_eq!(reused[0].version(), "1.0.0");
+        assert!(cache.load_at(now + chrono::Duration::seconds(60)).is_none());
+    }
+
+    #[test]
+    fn different_filters_are_cached_separately() {
+        let temp = tempfile::tempdir().unwrap();
+        let endpoint: Url = "https://registry.wasmer.io/graphql".parse().unwrap();
+        let filters = Filters {
+            namespaces: vec!["wasmer".to_string()],
+            ..Default::default()
+        };
+        let everything = DiscoveryCache::new(
+            temp.path(),
+            &endpoint,
+            &Filters::default(),
+            DEFAULT_DISCOVERY_TTL,
+        );
+        let namespaced =
+            DiscoveryCache::new(temp.path(), &endpoint, &filters, DEFAULT_DISCOVERY_TTL);
+
+        everything.save(vec![test_case("python", "1.0.0")]);
+
+        assert!(everything.load().is_some());
+        assert!(namespaced.load().is_none());
+    }
+}
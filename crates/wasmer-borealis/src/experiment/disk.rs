@@ -0,0 +1,181 @@
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Error};
+
+/// How often free space is checked while an experiment is running.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The free space an experiment needs unless told otherwise.
+pub const DEFAULT_MIN_FREE_SPACE: u64 = 1024 * 1024 * 1024;
+
+/// One of the directories an experiment writes to is running out of space.
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error(
+    "Only {} free in \"{}\", but at least {} is required",
+    crate::render::filesize(*available),
+    path.display(),
+    crate::render::filesize(*required),
+)]
+pub struct LowDiskSpace {
+    pub path: PathBuf,
+    pub available: u64,
+    pub required: u64,
+}
+
+/// Keeps an eye on the volumes an experiment writes to so it can stop before
+/// they fill up and downloads are left half-written.
+#[derive(Debug, Clone)]
+pub(crate) struct DiskGuard {
+    dirs: Arc<[PathBuf]>,
+    min_free_space: u64,
+    tripped: Arc<Mutex<Option<LowDiskSpace>>>,
+}
+
+impl DiskGuard {
+    /// Create a guard which requires at least `min_free_space` bytes to be
+    /// free in each of `dirs`. A threshold of `0` disables the guard.
+    pub fn new(dirs: impl IntoIterator<Item = PathBuf>, min_free_space: u64) -> Self {
+        DiskGuard {
+            dirs: dirs.into_iter().collect(),
+            min_free_space,
+            tripped: Arc::default(),
+        }
+    }
+
+    /// Check each directory, remembering the first one found to be running
+    /// out of space.
+    ///
+    /// Directories we can't check are logged and skipped, rather than
+    /// stopping the experiment.
+    pub fn check(&self) -> Result<(), LowDiskSpace> {
+        if let Some(e) = self.tripped() {
+            return Err(e);
+        }
+        if self.min_free_space == 0 {
+            return Ok(());
+        }
+
+        for dir in self.dirs.iter() {
+            match available_space(dir) {
+                Ok(available) if available < self.min_free_space => {
+                    let error = LowDiskSpace {
+                        path: dir.clone(),
+                        available,
+                        required: self.min_free_space,
+                    };
+                    *self.tripped.lock().unwrap() = Some(error.clone());
+                    return Err(error);
+                }
+                Ok(_) => {}
+                Err(e) => {
+                    tracing::warn!(
+                        dir=%dir.display(),
+                        error=&*e,
+                        "Unable to check the free disk space",
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The reason the guard was tripped, if it has been.
+    pub fn tripped(&self) -> Option<LowDiskSpace> {
+        self.tripped.lock().unwrap().clone()
+    }
+
+    /// Periodically re-check the free space until the guard is tripped.
+    pub async fn monitor(self) {
+        if self.min_free_space == 0 {
+            return;
+        }
+
+        loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+
+            let guard = self.clone();
+            if let Ok(Err(e)) = tokio::task::spawn_blocking(move || guard.check()).await {
+                tracing::error!(
+                    error = &e as &dyn std::error::Error,
+                    "Running low on disk space, no more test cases will be started",
+                );
+                return;
+            }
+        }
+    }
+}
+
+/// Get the number of bytes available on the filesystem containing `dir`.
+pub fn available_space(dir: &Path) -> Result<u64, Error> {
+    if cfg!(windows) {
+        return available_space_windows(dir);
+    }
+
+    let output = Command::new("df")
+        .arg("-Pk")
+        .arg(dir)
+        .output()
+        .context("Unable to run \"df\"")?;
+    anyhow::ensure!(output.status.success(), "\"df\" failed ({})", output.status);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let kib: u64 = stdout
+        .lines()
+        .nth(1)
+        .and_then(|line| line.split_whitespace().nth(3))
+        .and_then(|available| available.parse().ok())
+        .context("Unable to parse the output from \"df\"")?;
+
+    Ok(kib * 1024)
+}
+
+/// Windows doesn't have `df`, so ask PowerShell for the drive's free space.
+fn available_space_windows(dir: &Path) -> Result<u64, Error> {
+    let script = format!(
+        "(Get-Item -LiteralPath '{}').PSDrive.Free",
+        dir.display().to_string().replace('\'', "''")
+    );
+    let output = Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command"])
+        .arg(script)
+        .output()
+        .context("Unable to run \"powershell\"")?;
+    anyhow::ensure!(
+        output.status.success(),
+        "\"powershell\" failed ({})",
+        output.status
+    );
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .parse()
+        .context("Unable to parse the output from \"powershell\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn guard_trips_when_there_is_not_enough_space() {
+        let temp = tempfile::tempdir().unwrap();
+
+        let disabled = DiskGuard::new([temp.path().to_path_buf()], 0);
+        assert_eq!(disabled.check(), Ok(()));
+
+        let guard = DiskGuard::new([temp.path().to_path_buf()], u64::MAX);
+        let error = guard.check().unwrap_err();
+
+        assert_eq!(error.path, temp.path());
+        assert_eq!(error.required, u64::MAX);
+        assert_eq!(guard.tripped(), Some(error.clone()));
+        // Once tripped, the guard stays tripped
+        assert_eq!(guard.clone().check(), Err(error));
+    }
+}
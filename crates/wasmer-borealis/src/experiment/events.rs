@@ -0,0 +1,386 @@
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+use anyhow::Error;
+
+use crate::experiment::{Category, Progress, Report, TestCase};
+
+/// How long we'll wait for a slow listener before disconnecting it.
+const WRITE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Something that happened while an experiment was running, as published on
+/// the event socket.
+///
+/// Each event is written as a single line of JSON, with an `"event"` field
+/// containing the variant's name in kebab-case.
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+enum Event<'a> {
+    Discovered {
+        test_case: &'a TestCase,
+    },
+    Finished {
+        test_case: &'a TestCase,
+        category: Category,
+    },
+    Report {
+        report: &'a Report,
+    },
+    Downloading {
+        test_case: &'a TestCase,
+    },
+    DownloadProgress {
+        test_case: &'a TestCase,
+        filename: &'a str,
+        bytes_received: u64,
+        total: Option<u64>,
+    },
+    CacheHit {
+        test_case: &'a TestCase,
+    },
+    CacheMiss {
+        test_case: &'a TestCase,
+        duration: Duration,
+        bytes_downloaded: u64,
+    },
+}
+
+/// A [`Progress`] implementation which publishes every event to anyone
+/// connected to a local socket before passing it on to another [`Progress`].
+///
+/// Listeners can connect and disconnect at any time, and a listener which
+/// stops reading will be disconnected rather than holding up the experiment.
+#[derive(Debug)]
+pub(crate) struct EventSocket {
+    inner: Box<dyn Progress>,
+    path: PathBuf,
+    listeners: Arc<Mutex<Vec<imp::Stream>>>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl EventSocket {
+    /// Start listening on `path`, replacing any stale socket left behind by a
+    /// previous run.
+    ///
+    /// Binding fails if `path` is something other than a socket, or another
+    /// process is still listening on it.
+    pub(crate) fn bind(path: impl Into<PathBuf>, inner: Box<dyn Progress>) -> Result<Self, Error> {
+        let path = path.into();
+        let listeners = Arc::new(Mutex::new(Vec::new()));
+        let stopped = Arc::new(AtomicBool::new(false));
+        imp::listen(&path, Arc::clone(&listeners), Arc::clone(&stopped))?;
+        tracing::info!(path = %path.display(), "Publishing events");
+
+        Ok(EventSocket {
+            inner,
+            path,
+            listeners,
+            stopped,
+        })
+    }
+
+    fn publish(&self, event: Event<'_>) {
+        let mut line = match serde_json::to_vec(&event) {
+            Ok(line) => line,
+            Err(e) => {
+                tracing::warn!(
+                    error = &e as &dyn std::error::Error,
+                    "Unable to serialize an event"
+                );
+                return;
+            }
+        };
+        line.push(b'\n');
+
+        let mut listeners = self.listeners.lock().unwrap();
+        listeners.retain_mut(|stream| match std::io::Write::write_all(stream, &line) {
+            Ok(_) => true,
+            Err(e) => {
+                tracing::debug!(
+                    error = &e as &dyn std::error::Error,
+                    "Listener disconnected"
+                );
+                false
+            }
+        });
+    }
+}
+
+impl Drop for EventSocket {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        imp::stop(&self.path);
+    }
+}
+
+impl Progress for EventSocket {
+    fn discovered(&mut self, test_case: TestCase) {
+        self.publish(Event::Discovered {
+            test_case: &test_case,
+        });
+        self.inner.discovered(test_case);
+    }
+
+    fn finished(&mut self, test_case: TestCase, category: Category) {
+        self.publish(Event::Finished {
+            test_case: &test_case,
+            category,
+        });
+        self.inner.finished(test_case, category);
+    }
+
+    fn report(&mut self, report: &Report) {
+        self.publish(Event::Report { report });
+        self.inner.report(report);
+    }
+
+    fn downloading(&mut self, test_case: TestCase) {
+        self.publish(Event::Downloading {
+            test_case: &test_case,
+        });
+        self.inner.downloading(test_case);
+    }
+
+    fn download_progress(
+        &mut self,
+        test_case: TestCase,
+        filename: &str,
+        bytes_received: u64,
+        total: Option<u64>,
+    ) {
+        self.publish(Event::DownloadProgress {
+            test_case: &test_case,
+            filename,
+            bytes_received,
+            total,
+        });
+        self.inner
+            .download_progress(test_case, filename, bytes_received, total);
+    }
+
+    fn cache_hit(&mut self, test_case: TestCase) {
+        self.publish(Event::CacheHit {
+            test_case: &test_case,
+        });
+        self.inner.cache_hit(test_case);
+    }
+
+    fn cache_miss(&mut self, test_case: TestCase, duration: Duration, bytes_downloaded: u64) {
+        self.publish(Event::CacheMiss {
+            test_case: &test_case,
+            duration,
+            bytes_downloaded,
+        });
+        self.inner.cache_miss(test_case, duration, bytes_downloaded);
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{
+        os::unix::{
+            fs::FileTypeExt,
+            net::{UnixListener, UnixStream},
+        },
+        path::Path,
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc, Mutex,
+        },
+    };
+
+    use anyhow::{Context, Error};
+
+    pub(super) type Stream = UnixStream;
+
+    pub(super) fn listen(
+        path: &Path,
+        listeners: Arc<Mutex<Vec<Stream>>>,
+        stopped: Arc<AtomicBool>,
+    ) -> Result<(), Error> {
+        remove_stale_socket(path)?;
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("Unable to bind to \"{}\"", path.display()))?;
+
+        std::thread::Builder::new()
+            .name("event-socket".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    if stopped.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    match stream.and_then(|s| {
+                        s.set_write_timeout(Some(super::WRITE_TIMEOUT))?;
+                        Ok(s)
+                    }) {
+                        Ok(stream) => {
+                            tracing::debug!("Listener connected");
+                            listeners.lock().unwrap().push(stream);
+                        }
+                        Err(e) => {
+                            tracing::warn!(
+                                error = &e as &dyn std::error::Error,
+                                "Unable to accept a connection",
+                            );
+                        }
+                    }
+                }
+            })
+            .context("Unable to start the event socket thread")?;
+
+        Ok(())
+    }
+
+    /// Remove a socket left behind by a previous run, making sure we never
+    /// delete a regular file or steal a socket that is still in use.
+    fn remove_stale_socket(path: &Path) -> Result<(), Error> {
+        let Ok(metadata) = std::fs::symlink_metadata(path) else {
+            return Ok(());
+        };
+
+        if !metadata.file_type().is_socket() {
+            anyhow::bail!("\"{}\" already exists and isn't a socket", path.display());
+        }
+        if UnixStream::connect(path).is_ok() {
+            anyhow::bail!("Something is already listening on \"{}\"", path.display());
+        }
+
+        std::fs::remove_file(path).with_context(|| {
+            format!(
+                "Unable to remove the stale socket at \"{}\"",
+                path.display()
+            )
+        })
+    }
+
+    /// Wake the listener thread up so it notices it has been stopped, then
+    /// remove the socket.
+    pub(super) fn stop(path: &Path) {
+        let _ = UnixStream::connect(path);
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::{
+        path::Path,
+        sync::{atomic::AtomicBool, Arc, Mutex},
+    };
+
+    use anyhow::Error;
+
+    pub(super) type Stream = std::fs::File;
+
+    pub(super) fn listen(
+        _path: &Path,
+        _listeners: Arc<Mutex<Vec<Stream>>>,
+        _stopped: Arc<AtomicBool>,
+    ) -> Result<(), Error> {
+        anyhow::bail!("Event sockets are only supported on Unix platforms")
+    }
+
+    pub(super) fn stop(_path: &Path) {}
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader},
+        os::unix::net::UnixStream,
+    };
+
+    use super::*;
+    use crate::test_utils::{self, test_case};
+
+    #[derive(Debug)]
+    struct Noop;
+
+    impl Progress for Noop {}
+
+    #[test]
+    fn listeners_receive_events_as_json_lines() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("events.sock");
+        let mut socket = EventSocket::bind(&path, Box::new(Noop)).unwrap();
+        let client = UnixStream::connect(&path).unwrap();
+        // Wait for the background thread to register the listener
+        while socket.listeners.lock().unwrap().is_empty() {
+            std::thread::yield_now();
+        }
+        let test_case = test_case("python", "1.0.0");
+
+        socket.finished(test_case, Category::Success);
+
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line).unwrap();
+        let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(event["event"], "finished");
+        assert_eq!(event["category"], "success");
+        assert_eq!(event["test_case"]["package_name"], "python");
+
+        drop(socket);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn reports_are_published() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("events.sock");
+        let mut socket = EventSocket::bind(&path, Box::new(Noop)).unwrap();
+        let client = UnixStream::connect(&path).unwrap();
+        while socket.listeners.lock().unwrap().is_empty() {
+            std::thread::yield_now();
+        }
+        let report = test_utils::report("python", "1.0.0", test_utils::fetch_failed());
+
+        socket.report(&report);
+
+        let mut line = String::new();
+        BufReader::new(client).read_line(&mut line).unwrap();
+        let event: serde_json::Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(event["event"], "report");
+        assert_eq!(event["report"]["display_name"], "wasmer/python");
+        assert_eq!(event["report"]["outcome"]["outcome"], "fetch-failed");
+    }
+
+    #[test]
+    fn stale_sockets_are_replaced() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("events.sock");
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+
+        let socket = EventSocket::bind(&path, Box::new(Noop)).unwrap();
+
+        assert!(UnixStream::connect(&path).is_ok());
+        drop(socket);
+    }
+
+    #[test]
+    fn never_replace_something_that_isnt_a_socket() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("results.json");
+        std::fs::write(&path, "{}").unwrap();
+
+        assert!(EventSocket::bind(&path, Box::new(Noop)).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}");
+    }
+
+    #[test]
+    fn never_replace_a_socket_that_is_still_in_use() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("events.sock");
+        let _first = EventSocket::bind(&path, Box::new(Noop)).unwrap();
+
+        assert!(EventSocket::bind(&path, Box::new(Noop)).is_err());
+        assert!(UnixStream::connect(&path).is_ok());
+    }
+}
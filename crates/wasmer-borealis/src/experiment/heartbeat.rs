@@ -0,0 +1,242 @@
+use std::{
+    io::Write,
+    sync::{Arc, Condvar, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use crate::experiment::{Category, Progress, TestCase};
+
+/// A [`Progress`] implementation which prints a one-line summary to stderr
+/// every so often, so CI logs show an experiment is still alive without
+/// logging every package or drawing progress bars.
+pub struct Heartbeat {
+    state: Arc<State>,
+    thread: Option<JoinHandle<()>>,
+}
+
+struct State {
+    started: Instant,
+    counts: Mutex<Counts>,
+    stopped: Mutex<bool>,
+    wake: Condvar,
+    output: Mutex<Box<dyn Write + Send>>,
+}
+
+impl Heartbeat {
+    /// Start printing a summary every `interval`.
+    ///
+    /// A final summary is printed when the [`Heartbeat`] is dropped.
+    pub fn start(interval: Duration) -> std::io::Result<Self> {
+        Heartbeat::with_output(interval, Box::new(std::io::stderr()))
+    }
+
+    /// Start writing a summary to `output` every `interval`, instead of to
+    /// stderr.
+    pub fn with_output(interval: Duration, output: Box<dyn Write + Send>) -> std::io::Result<Self> {
+        let state = Arc::new(State {
+            started: Instant::now(),
+            counts: Mutex::default(),
+            stopped: Mutex::new(false),
+            wake: Condvar::new(),
+            output: Mutex::new(output),
+        });
+
+        let thread = std::thread::Builder::new()
+            .name("heartbeat".to_string())
+            .spawn({
+                let state = Arc::clone(&state);
+                move || {
+                    let mut stopped = state.stopped.lock().unwrap();
+                    loop {
+                        stopped = state
+                            .wake
+                            .wait_timeout_while(stopped, interval, |stopped| !*stopped)
+                            .unwrap()
+                            .0;
+                        if *stopped {
+                            break;
+                        }
+                        state.print();
+                    }
+                }
+            })?;
+
+        Ok(Heartbeat {
+            state,
+            thread: Some(thread),
+        })
+    }
+
+    fn update(&self, update: impl FnOnce(&mut Counts)) {
+        update(&mut self.state.counts.lock().unwrap());
+    }
+}
+
+impl State {
+    fn print(&self) {
+        let counts = *self.counts.lock().unwrap();
+        let line = counts.summary(self.started.elapsed());
+        // Don't let a closed stderr stop the experiment
+        let mut output = self.output.lock().unwrap();
+        let _ = writeln!(output, "{line}").and_then(|_| output.flush());
+    }
+}
+
+impl std::fmt::Debug for Heartbeat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Heartbeat")
+            .field("started", &self.state.started)
+            .field("counts", &self.state.counts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Heartbeat {
+    fn drop(&mut self) {
+        *self.state.stopped.lock().unwrap() = true;
+        self.state.wake.notify_all();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+
+        self.state.print();
+    }
+}
+
+impl Progress for Heartbeat {
+    fn discovered(&mut self, _test_case: TestCase) {
+        self.update(|c| c.discovered += 1);
+    }
+
+    fn finished(&mut self, _test_case: TestCase, category: Category) {
+        self.update(|c| {
+            c.completed += 1;
+            if category != Category::Success {
+                c.failed += 1;
+            }
+        });
+    }
+
+    fn cache_hit(&mut self, _test_case: TestCase) {
+        self.update(|c| c.downloaded += 1);
+    }
+
+    fn cache_miss(&mut self, _test_case: TestCase, _duration: Duration, _bytes_downloaded: u64) {
+        self.update(|c| c.downloaded += 1);
+    }
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq)]
+struct Counts {
+    discovered: usize,
+    /// Test cases whose assets are ready, whether they were downloaded or
+    /// already cached.
+    downloaded: usize,
+    completed: usize,
+    /// Completed test cases which failed or hit a bug.
+    failed: usize,
+}
+
+impl Counts {
+    fn summary(&self, elapsed: Duration) -> String {
+        let Counts {
+            discovered,
+            downloaded,
+            completed,
+            failed,
+        } = *self;
+
+        let mut line = format!(
+            "[heartbeat] discovered: {discovered}, downloaded: {downloaded}, completed: {completed}, failed: {failed}, elapsed: {}",
+            format_duration(elapsed),
+        );
+
+        // Assume the remaining test cases take as long as the ones so far
+        if completed > 0 && completed < discovered {
+            let remaining = (discovered - completed) as u32;
+            let eta = elapsed / completed as u32 * remaining;
+            line.push_str(&format!(", eta: {}", format_duration(eta)));
+        }
+
+        line
+    }
+}
+
+/// Format a duration as something like "1h02m03s", to the nearest second.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    if hours > 0 {
+        format!("{hours}h{minutes:02}m{seconds:02}s")
+    } else if minutes > 0 {
+        format!("{minutes}m{seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_case;
+
+    /// A writer that can be inspected after the [`Heartbeat`] is dropped.
+    #[derive(Debug, Default, Clone)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn summarise_the_experiment_so_far() {
+        let output = SharedBuffer::default();
+        let mut heartbeat =
+            Heartbeat::with_output(Duration::from_secs(3600), Box::new(output.clone())).unwrap();
+        for name in ["python", "php", "ruby", "cowsay"] {
+            heartbeat.discovered(test_case(name, "1.0.0"));
+        }
+        heartbeat.cache_hit(test_case("python", "1.0.0"));
+        heartbeat.cache_miss(test_case("php", "1.0.0"), Duration::ZERO, 42);
+        heartbeat.finished(test_case("python", "1.0.0"), Category::Success);
+        heartbeat.finished(test_case("php", "1.0.0"), Category::Bug);
+
+        let counts = *heartbeat.state.counts.lock().unwrap();
+
+        assert_eq!(
+            counts,
+            Counts {
+                discovered: 4,
+                downloaded: 2,
+                completed: 2,
+                failed: 1,
+            }
+        );
+        assert_eq!(
+            counts.summary(Duration::from_secs(150)),
+            "[heartbeat] discovered: 4, downloaded: 2, completed: 2, failed: 1, elapsed: 2m30s, eta: 2m30s"
+        );
+        assert_eq!(
+            Counts::default().summary(Duration::from_secs(3723)),
+            "[heartbeat] discovered: 0, downloaded: 0, completed: 0, failed: 0, elapsed: 1h02m03s"
+        );
+
+        // Nothing is printed until the interval elapses, then the final
+        // summary is printed exactly once when the heartbeat stops
+        assert!(output.0.lock().unwrap().is_empty());
+        drop(heartbeat);
+        let printed = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            printed,
+            "[heartbeat] discovered: 4, downloaded: 2, completed: 2, failed: 1, elapsed: 0s, eta: 0s\n"
+        );
+    }
+}
@@ -0,0 +1,150 @@
+use std::{
+    path::Path,
+    process::{Command, ExitStatus},
+};
+
+use anyhow::{Context, Error};
+
+use crate::experiment::ResultsSummary;
+
+/// Run the `hooks.pre-run` commands before any test cases are discovered,
+/// stopping at the first command that fails.
+pub(crate) fn pre_run(commands: &[String], experiment_dir: &Path) -> Result<(), Error> {
+    let env = [(
+        "BOREALIS_EXPERIMENT_DIR",
+        experiment_dir.display().to_string(),
+    )];
+
+    for command in commands {
+        let status = run(command, experiment_dir, &env)?;
+        anyhow::ensure!(
+            status.success(),
+            "The pre-run hook, \"{command}\", failed ({status})"
+        );
+    }
+
+    Ok(())
+}
+
+/// Run the `hooks.post-run` commands after an experiment's results have been
+/// saved.
+///
+/// Hooks are run in order, with a failing hook being logged rather than
+/// aborting the remaining hooks.
+pub(crate) fn post_run(commands: &[String], experiment_dir: &Path, results: &ResultsSummary) {
+    if commands.is_empty() {
+        return;
+    }
+
+    let env = [
+        (
+            "BOREALIS_RESULTS",
+            experiment_dir.join("results.json").display().to_string(),
+        ),
+        (
+            "BOREALIS_REPORT",
+            experiment_dir.join("report.html").display().to_string(),
+        ),
+        (
+            "BOREALIS_EXPERIMENT_DIR",
+            experiment_dir.display().to_string(),
+        ),
+        ("BOREALIS_TOTAL", results.reports.len().to_string()),
+        ("BOREALIS_SUCCESS", results.successes().count().to_string()),
+        ("BOREALIS_FAILURES", results.failures().count().to_string()),
+        ("BOREALIS_BUGS", results.bugs().count().to_string()),
+    ];
+
+    for command in commands {
+        match run(command, experiment_dir, &env) {
+            Ok(status) if status.success() => {}
+            Ok(status) => tracing::warn!(command, %status, "Post-run hook failed"),
+            Err(e) => tracing::warn!(command, error = &*e, "Unable to run the post-run hook"),
+        }
+    }
+}
+
+#[tracing::instrument(skip(env))]
+fn run(command: &str, current_dir: &Path, env: &[(&str, String)]) -> Result<ExitStatus, Error> {
+    tracing::debug!("Running hook");
+
+    shell(command)
+        .current_dir(current_dir)
+        .envs(env.iter().map(|(k, v)| (k, v)))
+        .status()
+        .with_context(|| format!("Unable to run \"{command}\""))
+}
+
+pub(crate) fn shell(command: &str) -> Command {
+    cfg_if::cfg_if! {
+        if #[cfg(windows)] {
+            let mut cmd = Command::new("cmd");
+            cmd.arg("/C").arg(command);
+        } else {
+            let mut cmd = Command::new("sh");
+            cmd.arg("-c").arg(command);
+        }
+    }
+
+    cmd
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failing_pre_run_hooks_abort_the_experiment() {
+        let temp = tempfile::tempdir().unwrap();
+        let commands = vec!["echo hello > first.txt".to_string(), "exit 3".to_string()];
+
+        let err = pre_run(&commands, temp.path()).unwrap_err();
+
+        assert!(err.to_string().contains("exit 3"), "{err}");
+        assert!(temp.path().join("first.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn post_run_hooks_are_told_about_the_results() {
+        use crate::experiment::{Category, ReportSummary};
+
+        let temp = tempfile::tempdir().unwrap();
+        let summary = |name: &str, category| ReportSummary {
+            display_name: name.to_string(),
+            version: "1.0.0".to_string(),
+            category,
+            offset: 0,
+        };
+        let results = ResultsSummary {
+            experiment: serde_json::from_value(serde_json::json!({ "package": "wasmer/python" }))
+                .unwrap(),
+            reports: vec![
+                summary("python", Category::Success),
+                summary("php", Category::Success),
+                summary("ruby", Category::Failure),
+                summary("cowsay", Category::Bug),
+            ],
+            total_time: std::time::Duration::from_secs(1),
+            experiment_dir: temp.path().to_path_buf(),
+            cache: Default::default(),
+        };
+        let commands = vec![
+            "exit 1".to_string(),
+            "echo $BOREALIS_TOTAL $BOREALIS_SUCCESS $BOREALIS_FAILURES $BOREALIS_BUGS > counts.txt"
+                .to_string(),
+            "echo $BOREALIS_RESULTS > results.txt".to_string(),
+        ];
+
+        post_run(&commands, temp.path(), &results);
+
+        // A failing hook doesn't stop the ones after it
+        let counts = std::fs::read_to_string(temp.path().join("counts.txt")).unwrap();
+        assert_eq!(counts, "4 2 1 1\n");
+        let results_path = std::fs::read_to_string(temp.path().join("results.txt")).unwrap();
+        assert_eq!(
+            results_path.trim(),
+            temp.path().join("results.json").display().to_string()
+        );
+    }
+}
@@ -0,0 +1,242 @@
+//! Testing a package from the local filesystem, so package authors can check
+//! it works before publishing.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Error};
+use url::Url;
+
+use crate::{
+    config::LocalPackage,
+    experiment::{storage::Assets, TestCase},
+    registry::queries::{PackageDistribution, PackageVersion},
+};
+
+/// The [`TestCase::registry`] used for local packages.
+pub(crate) const LOCAL_REGISTRY: &str = "local";
+
+const DEFAULT_NAMESPACE: &str = "local";
+const DEFAULT_VERSION: &str = "0.0.0";
+
+/// Create a [`TestCase`] for each of the local package's variants.
+///
+/// A package directory is also bundled into a `*.tar.gz` inside `dir`, so it
+/// looks just like a package downloaded from the registry.
+pub(crate) fn test_cases(local: &LocalPackage, dir: &Path) -> Result<Vec<TestCase>, Error> {
+    let path = absolute(&local.path)?;
+
+    let (name, version, distribution) = if path.is_dir() {
+        let manifest = path.join("wasmer.toml");
+        let manifest = std::fs::read_to_string(&manifest)
+            .with_context(|| format!("Unable to read \"{}\"", manifest.display()))?;
+        let (name, version) = parse_manifest(&manifest);
+        let name = name.or_else(|| file_name(&path));
+
+        let tarball = dir.join("package.tar.gz");
+        create_tarball(&path, &tarball)?;
+
+        let distribution = PackageDistribution {
+            download_url: file_url(&tarball)?,
+            pirita_download_url: None,
+            size: file_size(&tarball),
+            pirita_size: None,
+        };
+        (name, version, distribution)
+    } else {
+        anyhow::ensure!(
+            path.is_file(),
+            "\"{}\" is neither a *.webc file nor a package directory",
+            path.display()
+        );
+
+        let distribution = PackageDistribution {
+            download_url: String::new(),
+            pirita_download_url: Some(file_url(&path)?),
+            size: None,
+            pirita_size: file_size(&path),
+        };
+        (file_name(&path), None, distribution)
+    };
+
+    let name = name.unwrap_or_else(|| "package".to_string());
+    let (namespace, package_name) = match name.split_once('/') {
+        Some((namespace, name)) => (namespace.to_string(), name.to_string()),
+        None => (DEFAULT_NAMESPACE.to_string(), name),
+    };
+    let package_version = PackageVersion {
+        id: cynic::Id::new(format!("local:{}", path.display())),
+        version: version.unwrap_or_else(|| DEFAULT_VERSION.to_string()),
+        distribution,
+        created_at: None,
+    };
+
+    let test_case = |variant: Option<&String>| TestCase {
+        registry: LOCAL_REGISTRY.to_string(),
+        namespace: namespace.clone(),
+        package_name: package_name.clone(),
+        package_version: package_version.clone(),
+        variant: variant.cloned(),
+    };
+
+    if local.variants.is_empty() {
+        Ok(vec![test_case(None)])
+    } else {
+        Ok(local.variants.keys().map(Some).map(test_case).collect())
+    }
+}
+
+/// Get the files for a local [`TestCase`], which are used in place instead
+/// of going through the cache.
+pub(crate) fn assets(test_case: &TestCase) -> Result<Assets, Error> {
+    let tarball = match test_case.tarball_url() {
+        "" => None,
+        url => Some(file_path(url)?),
+    };
+    let webc = test_case.webc_url().map(file_path).transpose()?;
+
+    let total_size = tarball
+        .iter()
+        .chain(&webc)
+        .filter_map(|path| path.metadata().ok())
+        .map(|meta| meta.len())
+        .sum();
+
+    Ok(Assets {
+        tarball,
+        webc,
+        total_size,
+    })
+}
+
+/// Get the path that should be passed to `wasmer run` to run the local
+/// package itself.
+pub(crate) fn package_path(local: &LocalPackage) -> Result<PathBuf, Error> {
+    absolute(&local.path)
+}
+
+fn absolute(path: &Path) -> Result<PathBuf, Error> {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .context("Unable to determine the current directory")?
+            .join(path)
+    };
+
+    anyhow::ensure!(path.exists(), "\"{}\" doesn't exist", path.display());
+    Ok(path)
+}
+
+fn file_name(path: &Path) -> Option<String> {
+    path.file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+}
+
+fn file_size(path: &Path) -> Option<i32> {
+    let len = path.metadata().ok()?.len();
+    i32::try_from(len).ok()
+}
+
+fn file_url(path: &Path) -> Result<String, Error> {
+    Url::from_file_path(path)
+        .map(String::from)
+        .map_err(|_| anyhow::anyhow!("Unable to turn \"{}\" into a URL", path.display()))
+}
+
+fn file_path(url: &str) -> Result<PathBuf, Error> {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.to_file_path().ok())
+        .with_context(|| format!("\"{url}\" isn't a local file"))
+}
+
+fn create_tarball(package_dir: &Path, tarball: &Path) -> Result<(), Error> {
+    if let Some(parent) = tarball.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Unable to create \"{}\"", parent.display()))?;
+    }
+
+    let status = Command::new("tar")
+        .arg("-czf")
+        .arg(tarball)
+        .arg("-C")
+        .arg(package_dir)
+        .arg(".")
+        .status()
+        .context("Unable to run \"tar\"")?;
+    anyhow::ensure!(status.success(), "\"tar\" failed ({status})");
+
+    Ok(())
+}
+
+/// Pull the package's name and version out of its `wasmer.toml`.
+///
+/// We only need two keys from the `[package]` table, so this doesn't bother
+/// with a full TOML parser.
+fn parse_manifest(manifest: &str) -> (Option<String>, Option<String>) {
+    let mut in_package = false;
+    let mut name = None;
+    let mut version = None;
+
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+            continue;
+        }
+        if !in_package {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+        let value = value.trim().trim_matches(|c| c == '"' || c == '\'');
+
+        match key.trim() {
+            "name" => name = Some(value.to_string()),
+            "version" => version = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    (name, version)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_variant_of_a_package_directory_is_a_test_case() {
+        let temp = tempfile::tempdir().unwrap();
+        let package_dir = temp.path().join("my-package");
+        std::fs::create_dir_all(&package_dir).unwrap();
+        std::fs::write(
+            package_dir.join("wasmer.toml"),
+            "[package]\nname = \"me/hello\"\nversion = \"0.2.0\"\n\n[[command]]\nname = \"hello\"\n",
+        )
+        .unwrap();
+        let local: LocalPackage = serde_json::from_value(serde_json::json!({
+            "path": package_dir,
+            "variants": {
+                "default": {},
+                "verbose": { "args": ["--verbose"] },
+            },
+        }))
+        .unwrap();
+
+        let test_cases = test_cases(&local, &temp.path().join("out")).unwrap();
+
+        let names: Vec<_> = test_cases.iter().map(|tc| tc.display_name()).collect();
+        assert_eq!(names, ["me/hello#default", "me/hello#verbose"]);
+        assert!(test_cases.iter().all(|tc| tc.version() == "0.2.0"));
+
+        let assets = assets(&test_cases[0]).unwrap();
+        assert_eq!(assets.tarball, Some(temp.path().join("out/package.tar.gz")));
+        assert!(assets.tarball.unwrap().exists());
+        assert_eq!(assets.webc, None);
+    }
+}
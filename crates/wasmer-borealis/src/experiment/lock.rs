@@ -0,0 +1,248 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+const LOCK_FILENAME: &str = ".borealis.lock";
+
+/// How many times we'll try to lock the file before giving up, in case it
+/// keeps being removed out from under us.
+const MAX_ATTEMPTS: usize = 3;
+
+/// An error that occurred while locking an experiment directory.
+#[derive(Debug, thiserror::Error)]
+pub enum LockError {
+    /// Another run is already using the directory.
+    #[error(
+        "\"{}\" is already being used by another run ({})",
+        dir.display(),
+        match pid {
+            Some(pid) => format!("PID {pid}"),
+            None => "unknown PID".to_string(),
+        },
+    )]
+    Locked { dir: PathBuf, pid: Option<u32> },
+    #[error("Unable to lock \"{}\"", path.display())]
+    Io {
+        path: PathBuf,
+        #[source]
+        error: std::io::Error,
+    },
+}
+
+/// An exclusive lock on an experiment directory, so two runs writing to the
+/// same place fail fast instead of overwriting each other's results.
+///
+/// The lock is a file containing the owner's PID, which is held open (and
+/// locked using `flock()` on Unix, or without sharing write access on
+/// Windows) for as long as the lock is held. The operating system releases it
+/// if the process dies, so a lock file left behind by a crash is simply
+/// reused.
+#[derive(Debug)]
+pub(crate) struct DirLock {
+    path: PathBuf,
+    file: Option<File>,
+}
+
+impl DirLock {
+    pub fn acquire(dir: &Path) -> Result<Self, LockError> {
+        let path = dir.join(LOCK_FILENAME);
+        let io_error = |error| LockError::Io {
+            path: path.clone(),
+            error,
+        };
+
+        for _ in 0..MAX_ATTEMPTS {
+            let mut file = match open_exclusive(&path) {
+                Ok(Some(file)) => file,
+                Ok(None) => {
+                    let pid = std::fs::read_to_string(&path)
+                        .ok()
+                        .and_then(|contents| contents.trim().parse::<u32>().ok());
+                    return Err(LockError::Locked {
+                        dir: dir.to_path_buf(),
+                        pid,
+                    });
+                }
+                Err(e) => return Err(io_error(e)),
+            };
+
+            if !is_current(&file, &path).map_err(io_error)? {
+                // The previous owner removed the file while we were waiting
+                // for it, so someone else may have already created a new one
+                continue;
+            }
+
+            file.set_len(0)
+                .and_then(|_| write!(file, "{}", std::process::id()))
+                .and_then(|_| file.flush())
+                .map_err(io_error)?;
+
+            return Ok(DirLock {
+                path,
+                file: Some(file),
+            });
+        }
+
+        Err(LockError::Locked {
+            dir: dir.to_path_buf(),
+            pid: None,
+        })
+    }
+}
+
+impl Drop for DirLock {
+    fn drop(&mut self) {
+        // On Unix, the file is removed before it is unlocked so nobody can
+        // lock it in between. Windows won't remove a file that is still open.
+        if cfg!(windows) {
+            self.file.take();
+        }
+
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            tracing::warn!(
+                path = %self.path.display(),
+                error = &e as &dyn std::error::Error,
+                "Unable to release the lock",
+            );
+        }
+    }
+}
+
+/// Open the lock file, returning `None` if another process holds it.
+#[cfg(unix)]
+fn open_exclusive(path: &Path) -> std::io::Result<Option<File>> {
+    use std::os::unix::io::AsRawFd;
+
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .open(path)?;
+
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(Some(file));
+    }
+
+    match std::io::Error::last_os_error() {
+        e if e.kind() == ErrorKind::WouldBlock => Ok(None),
+        e => Err(e),
+    }
+}
+
+/// Open the lock file, returning `None` if another process holds it.
+#[cfg(windows)]
+fn open_exclusive(path: &Path) -> std::io::Result<Option<File>> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    const FILE_SHARE_READ: u32 = 0x1;
+    const ERROR_SHARING_VIOLATION: i32 = 32;
+
+    // Other processes can still read the PID, but can't open the file for
+    // writing until we close it
+    let result = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .share_mode(FILE_SHARE_READ)
+        .open(path);
+
+    match result {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.raw_os_error() == Some(ERROR_SHARING_VIOLATION) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Open the lock file, returning `None` if another process holds it.
+///
+/// There's no way to lock a file here, so the lock file's existence is the
+/// lock and stale locks need to be removed by hand.
+#[cfg(not(any(unix, windows)))]
+fn open_exclusive(path: &Path) -> std::io::Result<Option<File>> {
+    match OpenOptions::new().write(true).create_new(true).open(path) {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Is the file we locked still the one at `path`?
+#[cfg(unix)]
+fn is_current(file: &File, path: &Path) -> std::io::Result<bool> {
+    use std::os::unix::fs::MetadataExt;
+
+    let locked = file.metadata()?;
+    match std::fs::metadata(path) {
+        Ok(current) => Ok(locked.dev() == current.dev() && locked.ino() == current.ino()),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e),
+    }
+}
+
+/// Is the file we locked still the one at `path`?
+///
+/// Nobody can remove the file while we have it open, so it always is.
+#[cfg(not(unix))]
+fn is_current(_file: &File, _path: &Path) -> std::io::Result<bool> {
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_one_run_can_use_a_directory() {
+        let temp = tempfile::tempdir().unwrap();
+
+        let lock = DirLock::acquire(temp.path()).unwrap();
+        let error = DirLock::acquire(temp.path()).unwrap_err();
+        assert!(
+            matches!(error, LockError::Locked { pid: Some(pid), .. } if pid == std::process::id())
+        );
+
+        drop(lock);
+        assert!(!temp.path().join(LOCK_FILENAME).exists());
+        let _lock = DirLock::acquire(temp.path()).unwrap();
+    }
+
+    #[test]
+    fn stale_locks_are_reused() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(temp.path().join(LOCK_FILENAME), u32::MAX.to_string()).unwrap();
+
+        let _lock = DirLock::acquire(temp.path()).unwrap();
+
+        let pid = std::fs::read_to_string(temp.path().join(LOCK_FILENAME)).unwrap();
+        assert_eq!(pid, std::process::id().to_string());
+    }
+
+    #[test]
+    fn only_one_run_can_take_over_a_stale_lock() {
+        for _ in 0..20 {
+            let temp = tempfile::tempdir().unwrap();
+            std::fs::write(temp.path().join(LOCK_FILENAME), u32::MAX.to_string()).unwrap();
+            let barrier = std::sync::Barrier::new(2);
+
+            let results: Vec<_> = std::thread::scope(|s| {
+                let handles: Vec<_> = (0..2)
+                    .map(|_| {
+                        s.spawn(|| {
+                            barrier.wait();
+                            DirLock::acquire(temp.path())
+                        })
+                    })
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+
+            let acquired = results.iter().filter(|r| r.is_ok()).count();
+            assert_eq!(acquired, 1, "{results:?}");
+            assert!(results
+                .iter()
+                .any(|r| matches!(r, Err(LockError::Locked { .. }))));
+        }
+    }
+}
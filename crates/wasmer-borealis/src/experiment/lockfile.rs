@@ -0,0 +1,199 @@
+//! The `experiment.lock` file, which pins the exact set of test cases an
+//! experiment ran against so the run can be replayed later.
+//!
+//! The registry changes every day, so comparing two runs which each
+//! discovered their own test cases isn't comparing like with like.
+
+use std::{collections::HashMap, fs::File, path::Path};
+
+use anyhow::{Context, Error};
+use sha2::{Digest, Sha256};
+
+use crate::experiment::{hex, storage::Assets, TestCase};
+
+/// The name of the lock file written to each experiment directory.
+pub const LOCK_FILENAME: &str = "experiment.lock";
+
+/// Every test case covered by a run, along with the checksums of the
+/// artifacts it was run against.
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LockFile {
+    pub test_cases: Vec<LockedTestCase>,
+}
+
+impl LockFile {
+    /// Load an `experiment.lock` file from disk.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read \"{}\"", path.display()))?;
+        serde_json::from_str(&raw)
+            .with_context(|| format!("Unable to deserialize \"{}\"", path.display()))
+    }
+
+    /// Save the lock file to disk as JSON.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)
+            .with_context(|| format!("Unable to save to \"{}\"", path.display()))
+    }
+
+    /// The test cases to run when replaying this lock file.
+    pub fn test_cases(&self) -> Vec<TestCase> {
+        self.test_cases
+            .iter()
+            .map(|locked| locked.test_case.clone())
+            .collect()
+    }
+
+    /// The checksums for each test case, so they can be looked up quickly
+    /// while the experiment is running.
+    pub(crate) fn checksums(&self) -> HashMap<String, Checksums> {
+        self.test_cases
+            .iter()
+            .map(|locked| (key(&locked.test_case), locked.checksums.clone()))
+            .collect()
+    }
+
+    /// Put the test cases in a stable order, so lock files from different
+    /// runs can be diffed.
+    pub(crate) fn sort(&mut self) {
+        self.test_cases
+            .sort_by_cached_key(|locked| key(&locked.test_case));
+    }
+}
+
+/// A test case and the checksums of its artifacts.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LockedTestCase {
+    #[serde(flatten)]
+    pub test_case: TestCase,
+    /// Missing if the artifacts couldn't be fetched.
+    #[serde(default, skip_serializing_if = "Checksums::is_empty")]
+    pub checksums: Checksums,
+}
+
+/// The SHA-256 hashes (as hex strings) of a test case's artifacts.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub struct Checksums {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tarball: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub webc: Option<String>,
+}
+
+impl Checksums {
+    fn is_empty(&self) -> bool {
+        self.tarball.is_none() && self.webc.is_none()
+    }
+
+    /// Hash the artifacts that were fetched for a test case.
+    pub(crate) async fn of(assets: &Assets) -> Result<Self, Error> {
+        let Assets { tarball, webc, .. } = assets.clone();
+
+        tokio::task::spawn_blocking(move || {
+            Ok(Checksums {
+                tarball: tarball.as_deref().map(sha256_file).transpose()?,
+                webc: webc.as_deref().map(sha256_file).transpose()?,
+            })
+        })
+        .await?
+    }
+
+    /// Make sure the artifacts haven't changed since the lock file was
+    /// created.
+    pub(crate) fn verify(&self, actual: &Checksums) -> Result<(), Error> {
+        let artifacts = [
+            ("tarball", &self.tarball, &actual.tarball),
+            ("webc", &self.webc, &actual.webc),
+        ];
+
+        for (name, expected, actual) in artifacts {
+            if let (Some(expected), Some(actual)) = (expected, actual) {
+                anyhow::ensure!(
+                    expected == actual,
+                    "The package's {name} has changed since the lock file was created (expected a SHA-256 of {expected}, found {actual})",
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Something that uniquely identifies a test case.
+pub(crate) fn key(test_case: &TestCase) -> String {
+    format!(
+        "{}:{}@{}",
+        test_case.registry,
+        test_case.display_name(),
+        test_case.version()
+    )
+}
+
+fn sha256_file(path: &Path) -> Result<String, Error> {
+    let mut file =
+        File::open(path).with_context(|| format!("Unable to open \"{}\"", path.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)
+        .with_context(|| format!("Unable to read \"{}\"", path.display()))?;
+
+    Ok(hex(&hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_files_round_trip() {
+        let json = serde_json::json!({
+            "test-cases": [
+                {
+                    "registry": "registry.wasmer.io",
+                    "namespace": "wasmer",
+                    "package_name": "python",
+                    "package_version": {
+                        "id": "1",
+                        "version": "3.12.0",
+                        "distribution": {
+                            "downloadUrl": "https://cdn.wasmer.io/python.tar.gz",
+                            "piritaDownloadUrl": null,
+                        },
+                    },
+                    "checksums": { "tarball": "abcd" },
+                },
+            ],
+        });
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join(LOCK_FILENAME);
+
+        let lock: LockFile = serde_json::from_value(json).unwrap();
+        lock.save(&path).unwrap();
+        let lock = LockFile::load(&path).unwrap();
+
+        let test_cases = lock.test_cases();
+        assert_eq!(test_cases.len(), 1);
+        assert_eq!(test_cases[0].display_name(), "wasmer/python");
+        let checksums = &lock.checksums()["registry.wasmer.io:wasmer/python@3.12.0"];
+        assert_eq!(checksums.tarball.as_deref(), Some("abcd"));
+    }
+
+    #[test]
+    fn changed_artifacts_are_detected() {
+        let locked = Checksums {
+            tarball: Some("abcd".to_string()),
+            webc: None,
+        };
+
+        assert!(locked.verify(&locked).is_ok());
+        let changed = Checksums {
+            tarball: Some("1234".to_string()),
+            webc: Some("5678".to_string()),
+        };
+        assert!(locked.verify(&changed).is_err());
+    }
+}
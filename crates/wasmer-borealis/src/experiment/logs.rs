@@ -0,0 +1,252 @@
+#[cfg(feature = "run")]
+use std::io::{BufWriter, Write};
+use std::{
+    fs::File,
+    io::{ErrorKind, Read},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Error};
+use flate2::read::GzDecoder;
+#[cfg(feature = "run")]
+use flate2::{write::GzEncoder, Compression};
+#[cfg(feature = "run")]
+use sha2::{Digest, Sha256};
+
+#[cfg(feature = "run")]
+use crate::experiment::hex;
+
+/// The log files written to each test case's directory.
+#[cfg(feature = "run")]
+const LOG_FILES: [&str; 3] = ["stdout.txt", "stderr.txt", WASMER_LOG];
+
+/// Where the `wasmer` CLI's own logs are saved when `wasmer.log` is set.
+#[cfg(feature = "run")]
+pub(crate) const WASMER_LOG: &str = "wasmer.log";
+
+#[cfg(feature = "run")]
+const LEVELS: [&str; 5] = ["TRACE", "DEBUG", "INFO", "WARN", "ERROR"];
+
+/// Logs larger than this are gzipped once the test case has finished.
+#[cfg(feature = "run")]
+const COMPRESSION_THRESHOLD: u64 = 1024 * 1024;
+
+/// Gzip any of a test case's logs which are larger than
+/// [`COMPRESSION_THRESHOLD`], replacing `stdout.txt` with `stdout.txt.gz` and
+/// so on.
+#[cfg(feature = "run")]
+pub(crate) fn compress_logs(base_dir: &Path) -> Result<(), Error> {
+    for filename in LOG_FILES {
+        let path = base_dir.join(filename);
+
+        let Ok(metadata) = std::fs::metadata(&path) else {
+            continue;
+        };
+        if metadata.len() <= COMPRESSION_THRESHOLD {
+            continue;
+        }
+
+        let compressed = gz_path(&path);
+        tracing::debug!(
+            path = %path.display(),
+            bytes = metadata.len(),
+            "Compressing log file",
+        );
+
+        let mut src =
+            File::open(&path).with_context(|| format!("Unable to open \"{}\"", path.display()))?;
+        let dest = File::create(&compressed)
+            .with_context(|| format!("Unable to create \"{}\"", compressed.display()))?;
+        let mut encoder = GzEncoder::new(BufWriter::new(dest), Compression::default());
+        std::io::copy(&mut src, &mut encoder)
+            .and_then(|_| encoder.finish()?.flush())
+            .with_context(|| format!("Unable to compress \"{}\"", path.display()))?;
+
+        std::fs::remove_file(&path)
+            .with_context(|| format!("Unable to remove \"{}\"", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Move the `wasmer` CLI's log messages out of `stderr.txt` and into
+/// [`WASMER_LOG`], leaving only what the guest wrote.
+///
+/// The guest and `wasmer` share the same stderr, so we pick out the lines
+/// which look like they were written by `tracing` (e.g.
+/// `2024-06-01T12:00:00.000Z  INFO wasmer_wasix: ...`).
+#[cfg(feature = "run")]
+pub(crate) fn split_wasmer_log(base_dir: &Path) -> Result<(), Error> {
+    let stderr_path = base_dir.join("stderr.txt");
+    let stderr = match std::fs::read(&stderr_path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+        Err(e) => {
+            return Err(
+                Error::new(e).context(format!("Unable to read \"{}\"", stderr_path.display()))
+            )
+        }
+    };
+
+    let mut guest = Vec::new();
+    let mut wasmer = Vec::new();
+
+    for line in stderr.split_inclusive(|&b| b == b'\n') {
+        if is_log_line(&String::from_utf8_lossy(line)) {
+            wasmer.extend_from_slice(line);
+        } else {
+            guest.extend_from_slice(line);
+        }
+    }
+
+    let log_path = base_dir.join(WASMER_LOG);
+    std::fs::write(&log_path, wasmer)
+        .with_context(|| format!("Unable to write to \"{}\"", log_path.display()))?;
+    std::fs::write(&stderr_path, guest)
+        .with_context(|| format!("Unable to write to \"{}\"", stderr_path.display()))?;
+
+    Ok(())
+}
+
+/// Does this line look like it was written by `tracing_subscriber`'s
+/// formatter (with or without a timestamp)?
+#[cfg(feature = "run")]
+fn is_log_line(line: &str) -> bool {
+    let mut words = line.split_whitespace();
+    let Some(mut word) = words.next() else {
+        return false;
+    };
+
+    if word.starts_with(|c: char| c.is_ascii_digit()) && word.contains(':') {
+        // Skip the timestamp
+        match words.next() {
+            Some(w) => word = w,
+            None => return false,
+        }
+    }
+
+    LEVELS.contains(&word)
+}
+
+/// Find one of a test case's log files (e.g. `stderr.txt`), which may have
+/// been compressed.
+pub fn log_path(base_dir: &Path, filename: &str) -> Option<PathBuf> {
+    let path = base_dir.join(filename);
+    let compressed = gz_path(&path);
+
+    [path, compressed].into_iter().find(|p| p.exists())
+}
+
+/// Read one of a test case's log files (e.g. `stderr.txt`), decompressing it
+/// if necessary.
+///
+/// Returns `None` if the log doesn't exist.
+pub fn read_log(base_dir: &Path, filename: &str) -> Result<Option<String>, Error> {
+    let path = base_dir.join(filename);
+
+    match std::fs::read(&path) {
+        Ok(bytes) => return Ok(Some(String::from_utf8_lossy(&bytes).into_owned())),
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(Error::new(e).context(format!("Unable to read \"{}\"", path.display())))
+        }
+    }
+
+    let compressed = gz_path(&path);
+
+    match File::open(&compressed) {
+        Ok(f) => {
+            let mut bytes = Vec::new();
+            GzDecoder::new(f)
+                .read_to_end(&mut bytes)
+                .with_context(|| format!("Unable to decompress \"{}\"", compressed.display()))?;
+            Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => {
+            Err(Error::new(e).context(format!("Unable to read \"{}\"", compressed.display())))
+        }
+    }
+}
+
+/// Calculate the SHA-256 hash of one of a test case's (uncompressed) log
+/// files, returning `None` if it doesn't exist.
+#[cfg(feature = "run")]
+pub(crate) fn digest_log(base_dir: &Path, filename: &str) -> Result<Option<String>, Error> {
+    let path = base_dir.join(filename);
+
+    let mut f = match File::open(&path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => {
+            return Err(Error::new(e).context(format!("Unable to open \"{}\"", path.display())))
+        }
+    };
+
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut f, &mut hasher)
+        .with_context(|| format!("Unable to read \"{}\"", path.display()))?;
+
+    Ok(Some(hex(&hasher.finalize())))
+}
+
+fn gz_path(path: &Path) -> PathBuf {
+    let mut path = path.as_os_str().to_os_string();
+    path.push(".gz");
+    PathBuf::from(path)
+}
+
+#[cfg(all(test, feature = "run"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn large_logs_are_compressed_and_read_transparently() {
+        let temp = tempfile::tempdir().unwrap();
+        let stdout = "Hello, World!\n".repeat(100_000);
+        std::fs::write(temp.path().join("stdout.txt"), &stdout).unwrap();
+        std::fs::write(temp.path().join("stderr.txt"), "oops").unwrap();
+
+        compress_logs(temp.path()).unwrap();
+
+        assert!(!temp.path().join("stdout.txt").exists());
+        assert_eq!(
+            log_path(temp.path(), "stdout.txt").unwrap(),
+            temp.path().join("stdout.txt.gz")
+        );
+        assert_eq!(
+            read_log(temp.path(), "stdout.txt").unwrap().unwrap(),
+            stdout
+        );
+        assert_eq!(
+            log_path(temp.path(), "stderr.txt").unwrap(),
+            temp.path().join("stderr.txt")
+        );
+        assert_eq!(
+            read_log(temp.path(), "stderr.txt").unwrap().unwrap(),
+            "oops"
+        );
+        assert_eq!(read_log(temp.path(), "missing.txt").unwrap(), None);
+    }
+
+    #[test]
+    fn wasmer_logs_are_split_from_the_guests_stderr() {
+        let temp = tempfile::tempdir().unwrap();
+        let stderr = "2024-06-01T12:00:00.123456Z  INFO wasmer_wasix::runners: Starting\n\
+            Traceback (most recent call last):\n\
+            \x20 WARN wasmer::cli: no timestamp\n\
+            ValueError: oops\n";
+        std::fs::write(temp.path().join("stderr.txt"), stderr).unwrap();
+
+        split_wasmer_log(temp.path()).unwrap();
+
+        assert_eq!(
+            read_log(temp.path(), "stderr.txt").unwrap().unwrap(),
+            "Traceback (most recent call last):\nValueError: oops\n"
+        );
+        assert_eq!(
+            read_log(temp.path(), WASMER_LOG).unwrap().unwrap(),
+            "2024-06-01T12:00:00.123456Z  INFO wasmer_wasix::runners: Starting\n  WARN wasmer::cli: no timestamp\n"
+        );
+    }
+}
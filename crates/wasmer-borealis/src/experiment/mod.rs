@@ -1,14 +1,89 @@
+#[cfg(feature = "run")]
+mod analyzers;
+mod benchmark;
+#[cfg(feature = "run")]
 mod builder;
+#[cfg(feature = "run")]
 mod cache;
+#[cfg(feature = "run")]
+mod capture;
+#[cfg(feature = "run")]
+mod coredumps;
+#[cfg(feature = "run")]
+mod cpu;
+mod diff;
+#[cfg(feature = "run")]
+mod discovery;
+#[cfg(feature = "run")]
+mod disk;
+#[cfg(feature = "run")]
+mod events;
+#[cfg(feature = "run")]
+mod heartbeat;
+#[cfg(feature = "run")]
+mod hooks;
+#[cfg(feature = "run")]
+mod local;
+#[cfg(feature = "run")]
+mod lock;
+#[cfg(feature = "run")]
+mod lockfile;
+mod logs;
+mod modules;
+#[cfg(feature = "run")]
 mod orchestrator;
+#[cfg(feature = "run")]
+mod package_list;
+#[cfg(feature = "run")]
+mod process;
+#[cfg(feature = "run")]
 mod progress;
+#[cfg(feature = "run")]
+mod redact;
+#[cfg(feature = "run")]
+mod reproduce;
 mod results;
+#[cfg(feature = "run")]
 mod runner;
+mod schedule;
+mod selection;
+#[cfg(feature = "run")]
+mod storage;
+mod summary;
+mod test_case;
+#[cfg(feature = "run")]
 mod wapm;
 
 pub use self::{
-    builder::ExperimentBuilder,
+    benchmark::{BenchmarkStats, PerformanceRegression},
+    diff::{diverging_outputs, Entry, OutcomeChange, OutputChange, ResultsDiff, RuntimeChange},
+    logs::{log_path, read_log},
+    modules::{MemoryLimits, ModuleInfo},
+    results::{
+        Analysis, Annotation, CacheStats, Category, CommandLine, Disposition, Download, ExitStatus,
+        Outcome, Phase, Repetitions, Report, Rerun, Results, SerializableError, StartupTimes,
+        Timeline,
+    },
+    schedule::RunTimes,
+    selection::Shard,
+    summary::{ReportReader, ReportSummary, ResultsSummary},
+    test_case::TestCase,
+};
+#[cfg(feature = "run")]
+pub use self::{
+    builder::{ExperimentBuilder, RunError},
+    cache::{repair_entry, verify_cache, CacheEntry, CacheError, EntryStatus},
+    discovery::DEFAULT_DISCOVERY_TTL,
+    disk::{available_space, LowDiskSpace, DEFAULT_MIN_FREE_SPACE},
+    heartbeat::Heartbeat,
+    lock::LockError,
+    lockfile::{Checksums, LockFile, LockedTestCase, LOCK_FILENAME},
+    package_list::PackageSpec,
     progress::Progress,
-    results::{Outcome, Report, Results},
-    wapm::TestCase,
+    reproduce::Reproduction,
+    storage::{Assets, Bucket, Credentials, FileSystemStorage, RemoteCache, Storage},
+    wapm::discover,
 };
+
+#[cfg(feature = "run")]
+pub(crate) use self::selection::{hex, stable_hash};
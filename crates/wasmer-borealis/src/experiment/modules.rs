@@ -0,0 +1,443 @@
+//! Static analysis of the WebAssembly modules inside a package, so reports
+//! can answer questions like "which failing packages import `sock_open`?"
+//! without running anything.
+
+use std::collections::BTreeMap;
+#[cfg(feature = "run")]
+use std::{
+    io::{ErrorKind, Read},
+    path::Path,
+};
+
+#[cfg(feature = "run")]
+use anyhow::{Context, Error};
+#[cfg(feature = "run")]
+use flate2::read::GzDecoder;
+
+/// What a WebAssembly module needs from (and offers to) its host.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct ModuleInfo {
+    /// Where the module lives inside the package.
+    pub path: String,
+    /// The module's size in bytes.
+    pub size: u64,
+    /// The names of the functions (and other items) imported from each
+    /// module (e.g. `wasi_snapshot_preview1`).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub imports: BTreeMap<String, Vec<String>>,
+    /// The names of everything the module exports.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exports: Vec<String>,
+    /// The limits on the module's memory, whether it is defined by the
+    /// module or imported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memory: Option<MemoryLimits>,
+}
+
+impl ModuleInfo {
+    /// Does this module import something with this name (from any module)?
+    pub fn imports(&self, name: &str) -> bool {
+        self.imports.values().flatten().any(|n| n == name)
+    }
+}
+
+/// The size limits on a linear memory, in 64 KiB pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct MemoryLimits {
+    pub minimum: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maximum: Option<u64>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub shared: bool,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub memory64: bool,
+}
+
+/// What we found out about a package's modules.
+#[cfg(feature = "run")]
+#[derive(Debug, Default)]
+pub(crate) struct Inspection {
+    pub modules: Vec<ModuleInfo>,
+    /// Anything which stopped us from inspecting the package (or one of its
+    /// modules).
+    pub errors: Vec<String>,
+}
+
+#[cfg(feature = "run")]
+impl Inspection {
+    pub(crate) fn failed(error: Error) -> Self {
+        Inspection {
+            modules: Vec::new(),
+            errors: vec![format!("{error:#}")],
+        }
+    }
+
+    /// We don't know how to find the modules inside a `*.webc` file yet.
+    pub(crate) fn unsupported(path: &Path) -> Self {
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        Inspection::failed(Error::msg(format!(
+            "Unable to inspect \"{}\" because *.webc files aren't supported",
+            name.to_string_lossy()
+        )))
+    }
+}
+
+/// Inspect every `*.wasm` file in a package's `*.tar.gz` tarball.
+#[cfg(feature = "run")]
+pub(crate) fn inspect_tarball(path: &Path) -> Result<Inspection, Error> {
+    let file = std::fs::File::open(path)
+        .with_context(|| format!("Unable to open \"{}\"", path.display()))?;
+    let mut archive = GzDecoder::new(std::io::BufReader::new(file));
+
+    let mut inspection = Inspection::default();
+    let mut long_name = None;
+    let mut header = [0_u8; 512];
+
+    loop {
+        match archive.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            Err(e) => return Err(e.into()),
+        }
+        if header.iter().all(|&b| b == 0) {
+            // The end-of-archive marker
+            break;
+        }
+
+        let size = octal(&header[124..136]).context("Invalid tar header")?;
+        let padded = (size + 511) / 512 * 512;
+        let mut entry = (&mut archive).take(padded);
+
+        match header[156] {
+            b'L' => {
+                // A GNU long name for the next entry
+                let mut name = Vec::new();
+                (&mut entry).take(size).read_to_end(&mut name)?;
+                std::io::copy(&mut entry, &mut std::io::sink())?;
+                long_name = Some(nul_terminated(&name));
+                continue;
+            }
+            b'0' | b'\0' => {}
+            _ => {
+                std::io::copy(&mut entry, &mut std::io::sink())?;
+                long_name = None;
+                continue;
+            }
+        }
+
+        let name = long_name.take().unwrap_or_else(|| entry_name(&header));
+
+        if name.ends_with(".wasm") {
+            let mut wasm = Vec::new();
+            (&mut entry).take(size).read_to_end(&mut wasm)?;
+            let name = name.trim_start_matches("./").to_string();
+            match inspect(&name, &wasm) {
+                Ok(Some(module)) => inspection.modules.push(module),
+                Ok(None) => {}
+                Err(e) => inspection
+                    .errors
+                    .push(format!("Unable to parse \"{name}\": {e:#}")),
+            }
+        }
+
+        std::io::copy(&mut entry, &mut std::io::sink())?;
+    }
+
+    Ok(inspection)
+}
+
+/// Parse a core WebAssembly module, returning `None` if it is something else
+/// (e.g. a component).
+#[cfg(feature = "run")]
+pub(crate) fn inspect(path: &str, wasm: &[u8]) -> Result<Option<ModuleInfo>, Error> {
+    let mut reader = Reader { bytes: wasm };
+
+    anyhow::ensure!(reader.take(4)? == b"\0asm", "Not a WebAssembly module");
+    if reader.take(4)? != [1, 0, 0, 0] {
+        return Ok(None);
+    }
+
+    let mut module = ModuleInfo {
+        path: path.to_string(),
+        size: wasm.len() as u64,
+        imports: BTreeMap::new(),
+        exports: Vec::new(),
+        memory: None,
+    };
+
+    while !reader.bytes.is_empty() {
+        let id = reader.byte()?;
+        let size = reader.u32()? as usize;
+        let mut section = Reader {
+            bytes: reader.take(size)?,
+        };
+
+        match id {
+            IMPORT_SECTION => {
+                for _ in 0..section.u32()? {
+                    let from = section.name()?;
+                    let name = section.name()?;
+                    match section.byte()? {
+                        // function
+                        0x00 => {
+                            section.u32()?;
+                        }
+                        // table
+                        0x01 => {
+                            section.byte()?;
+                            section.limits()?;
+                        }
+                        // memory
+                        0x02 => {
+                            let limits = section.limits()?;
+                            module.memory.get_or_insert(limits);
+                        }
+                        // global
+                        0x03 => {
+                            section.take(2)?;
+                        }
+                        // tag
+                        0x04 => {
+                            section.byte()?;
+                            section.u32()?;
+                        }
+                        other => anyhow::bail!("Unknown import kind, 0x{other:02x}"),
+                    }
+                    module.imports.entry(from).or_default().push(name);
+                }
+            }
+            MEMORY_SECTION => {
+                if section.u32()? > 0 {
+                    let limits = section.limits()?;
+                    module.memory.get_or_insert(limits);
+                }
+            }
+            EXPORT_SECTION => {
+                for _ in 0..section.u32()? {
+                    module.exports.push(section.name()?);
+                    section.byte()?;
+                    section.u32()?;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(Some(module))
+}
+
+#[cfg(feature = "run")]
+const IMPORT_SECTION: u8 = 2;
+#[cfg(feature = "run")]
+const MEMORY_SECTION: u8 = 5;
+#[cfg(feature = "run")]
+const EXPORT_SECTION: u8 = 7;
+
+#[cfg(feature = "run")]
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+#[cfg(feature = "run")]
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        anyhow::ensure!(len <= self.bytes.len(), "Unexpected end of module");
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> Result<u8, Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    /// Read an unsigned LEB128 integer.
+    fn u64(&mut self) -> Result<u64, Error> {
+        let mut value = 0_u64;
+
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+
+        anyhow::bail!("Integer too large")
+    }
+
+    fn u32(&mut self) -> Result<u32, Error> {
+        u32::try_from(self.u64()?).context("Integer too large")
+    }
+
+    fn name(&mut self) -> Result<String, Error> {
+        let len = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn limits(&mut self) -> Result<MemoryLimits, Error> {
+        let flags = self.byte()?;
+        let minimum = self.u64()?;
+        let maximum = if flags & 0x01 != 0 {
+            Some(self.u64()?)
+        } else {
+            None
+        };
+
+        Ok(MemoryLimits {
+            minimum,
+            maximum,
+            shared: flags & 0x02 != 0,
+            memory64: flags & 0x04 != 0,
+        })
+    }
+}
+
+#[cfg(feature = "run")]
+fn octal(field: &[u8]) -> Option<u64> {
+    let digits = nul_terminated(field);
+    let digits = digits.trim();
+    if digits.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(digits, 8).ok()
+}
+
+#[cfg(feature = "run")]
+fn entry_name(header: &[u8; 512]) -> String {
+    let name = nul_terminated(&header[..100]);
+    let prefix = nul_terminated(&header[345..500]);
+
+    if &header[257..262] == b"ustar" && !prefix.is_empty() {
+        format!("{prefix}/{name}")
+    } else {
+        name
+    }
+}
+
+#[cfg(feature = "run")]
+fn nul_terminated(bytes: &[u8]) -> String {
+    let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    String::from_utf8_lossy(&bytes[..end]).into_owned()
+}
+
+#[cfg(all(test, feature = "run"))]
+mod tests {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+
+    use super::*;
+
+    /// A module which imports `wasi_snapshot_preview1::fd_write` and
+    /// `wasix_32v1::sock_open`, and exports a memory (1 to 16 pages) and
+    /// `_start`.
+    fn module() -> Vec<u8> {
+        fn name(s: &str) -> Vec<u8> {
+            let mut bytes = vec![s.len() as u8];
+            bytes.extend(s.as_bytes());
+            bytes
+        }
+        fn section(id: u8, contents: Vec<u8>) -> Vec<u8> {
+            let mut bytes = vec![id, contents.len() as u8];
+            bytes.extend(contents);
+            bytes
+        }
+
+        let mut imports = vec![2];
+        for (module, field) in [
+            ("wasi_snapshot_preview1", "fd_write"),
+            ("wasix_32v1", "sock_open"),
+        ] {
+            imports.extend(name(module));
+            imports.extend(name(field));
+            imports.extend([0x00, 0x00]);
+        }
+
+        let mut exports = vec![2];
+        exports.extend(name("memory"));
+        exports.extend([0x02, 0x00]);
+        exports.extend(name("_start"));
+        exports.extend([0x00, 0x02]);
+
+        let mut wasm = b"\0asm\x01\0\0\0".to_vec();
+        // A type section we don't care about
+        wasm.extend(section(1, vec![1, 0x60, 0, 0]));
+        wasm.extend(section(IMPORT_SECTION, imports));
+        wasm.extend(section(MEMORY_SECTION, vec![1, 0x01, 1, 16]));
+        wasm.extend(section(EXPORT_SECTION, exports));
+        wasm
+    }
+
+    #[test]
+    fn inspect_a_module() {
+        let wasm = module();
+
+        let info = inspect("main.wasm", &wasm).unwrap().unwrap();
+
+        assert_eq!(info.size, wasm.len() as u64);
+        assert_eq!(info.imports["wasi_snapshot_preview1"], ["fd_write"]);
+        assert!(info.imports("sock_open"));
+        assert!(!info.imports("sock_connect"));
+        assert_eq!(info.exports, ["memory", "_start"]);
+        assert_eq!(
+            info.memory,
+            Some(MemoryLimits {
+                minimum: 1,
+                maximum: Some(16),
+                shared: false,
+                memory64: false,
+            })
+        );
+    }
+
+    #[test]
+    fn components_are_skipped() {
+        let component = b"\0asm\x0d\0\x01\0";
+
+        assert_eq!(inspect("component.wasm", component).unwrap(), None);
+        assert!(inspect("main.wasm", b"not wasm").is_err());
+    }
+
+    #[test]
+    fn find_the_modules_in_a_tarball() {
+        fn entry(name: &str, contents: &[u8]) -> Vec<u8> {
+            let mut header = [0_u8; 512];
+            header[..name.len()].copy_from_slice(name.as_bytes());
+            let size = format!("{:011o}\0", contents.len());
+            header[124..136].copy_from_slice(size.as_bytes());
+            header[156] = b'0';
+            header[257..262].copy_from_slice(b"ustar");
+
+            let mut bytes = header.to_vec();
+            bytes.extend(contents);
+            bytes.resize((bytes.len() + 511) / 512 * 512, 0);
+            bytes
+        }
+
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("package.tar.gz");
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder
+            .write_all(&entry("wasmer.toml", b"[package]"))
+            .unwrap();
+        encoder
+            .write_all(&entry("./bin/main.wasm", &module()))
+            .unwrap();
+        encoder
+            .write_all(&entry("bin/broken.wasm", b"\0asm\x01\0\0\0\x02"))
+            .unwrap();
+        encoder.write_all(&[0; 1024]).unwrap();
+        std::fs::write(&path, encoder.finish().unwrap()).unwrap();
+
+        let Inspection { modules, errors } = inspect_tarball(&path).unwrap();
+
+        assert_eq!(modules.len(), 1);
+        assert_eq!(modules[0].path, "bin/main.wasm");
+        assert!(modules[0].imports("fd_write"));
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("Unable to parse \"bin/broken.wasm\""));
+    }
+}
@@ -1,141 +1,679 @@
-use std::{path::PathBuf, sync::Arc, time::Instant};
+use std::{
+    collections::HashMap,
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
 
-use actix::{Actor, Addr, Context, Handler, ResponseFuture};
-use anyhow::Error;
-use futures::{stream::FuturesUnordered, StreamExt};
+use anyhow::{Context as _, Error};
+use chrono::Utc;
+use futures::{
+    stream::{BoxStream, FuturesUnordered},
+    FutureExt, StreamExt,
+};
 use reqwest::Client;
+use tokio::{io::AsyncWriteExt, sync::mpsc::UnboundedSender};
 use url::Url;
 
 use crate::{
     config::Experiment,
     experiment::{
-        cache::{AssetsFetched, Cache, FetchAssets},
+        builder::RunError,
+        cache::{AssetsFetched, Cache},
+        coredumps::Coredumps,
+        cpu::Scheduling,
+        discovery::DiscoveryCache,
+        disk::DiskGuard,
+        local,
+        lockfile::{self, Checksums, LockFile, LockedTestCase, LOCK_FILENAME},
+        modules::{self, Inspection},
+        package_list::PackageSpec,
+        redact::Redactor,
         runner::{BeginTest, Runner},
-        wapm::{FetchTestCases, TestCaseDiscovered, Wapm},
-        Outcome, Report, Results,
+        schedule::{self, RunTimes},
+        selection::{Sampling, Shard},
+        storage::Assets,
+        summary::REPORT_LOG,
+        wapm::{TestCaseDiscovered, Wapm},
+        CacheStats, Category, Outcome, Phase, Report, ReportSummary, ResultsSummary, TestCase,
+        Timeline,
     },
+    registry::RetryPolicy,
 };
 
 /// The top-level experiment runner.
 #[derive(Debug)]
 pub(crate) struct Orchestrator {
-    cache: Addr<Cache>,
+    cache: Cache,
     client: Client,
     endpoint: Url,
+    retry: RetryPolicy,
+    progress: UnboundedSender<TestStatusMessage>,
 }
 
 impl Orchestrator {
-    pub fn new(cache: Addr<Cache>, client: Client, endpoint: Url) -> Self {
+    pub fn new(
+        cache: Cache,
+        client: Client,
+        endpoint: Url,
+        retry: RetryPolicy,
+        progress: UnboundedSender<TestStatusMessage>,
+    ) -> Self {
         Orchestrator {
             cache,
             client,
             endpoint,
+            retry,
+            progress,
         }
     }
 }
 
-impl Actor for Orchestrator {
-    type Context = Context<Self>;
+/// Messages emitted by the [`Orchestrator`] as test cases move through the
+/// pipeline.
+#[derive(Debug)]
+pub(crate) enum TestStatusMessage {
+    /// A test case will be included in the experiment.
+    Discovered(TestCase),
+    /// A test case has finished.
+    Finished {
+        test_case: TestCase,
+        category: Category,
+    },
+    /// A finished test case's report has been written to the log.
+    Reported(Box<Report>),
 }
 
-#[derive(Debug, actix::Message)]
-#[rtype(result = "Results")]
-pub struct BeginExperiment {
+#[derive(Debug)]
+pub(crate) struct BeginExperiment {
     pub experiment: Arc<Experiment>,
     /// The directory experiment results should be saved to.
     pub base_dir: PathBuf,
+    /// Only run against a random subset of the discovered test cases.
+    pub sampling: Option<Sampling>,
+    /// Only run the test cases belonging to this shard.
+    pub shard: Option<Shard>,
+    /// The `wasmer` CLI to run each test case with.
+    pub wasmer: PathBuf,
+    /// Stops new test cases from being started when we run low on disk
+    /// space.
+    pub disk: DiskGuard,
+    /// Scrubs secrets from each test case's report and logs.
+    pub redactor: Redactor,
+    /// Run these test cases (from a local package) instead of discovering
+    /// test cases from the registry.
+    pub local: Option<Vec<TestCase>>,
+    /// Replay the test cases from a lock file instead of discovering them.
+    pub locked: Option<LockFile>,
+    /// Look up exactly these packages instead of discovering test cases
+    /// using the experiment's filters.
+    pub packages: Option<Vec<PackageSpec>>,
+    /// Reuse (and remember) the test cases discovered using the
+    /// experiment's filters.
+    pub discovery: DiscoveryCache,
+    /// Start the test cases expected to take longest first.
+    pub run_times: Option<RunTimes>,
+    /// How core dumps are collected when a test case crashes, if at all.
+    pub coredumps: Option<Coredumps>,
+    /// The maximum number of test cases to run at the same time.
+    pub parallelism: NonZeroUsize,
+    /// Echo each test case's output to the console as it runs.
+    pub tee_logs: bool,
+    /// Which CPUs test cases are pinned to.
+    pub scheduling: Scheduling,
 }
 
-impl Handler<BeginExperiment> for Orchestrator {
-    type Result = ResponseFuture<Results>;
-
-    fn handle(
-        &mut self,
-        msg: BeginExperiment,
-        _ctx: &mut Self::Context,
-    ) -> actix::ResponseFuture<Results> {
+impl Orchestrator {
+    /// Run the experiment to completion.
+    ///
+    /// Dropping the returned future stops the experiment, killing any test
+    /// cases which are still running.
+    pub(crate) async fn run(self, msg: BeginExperiment) -> Result<ResultsSummary, RunError> {
         let BeginExperiment {
             experiment,
             base_dir,
+            sampling,
+            shard,
+            wasmer,
+            disk,
+            redactor,
+            local,
+            locked,
+            packages,
+            discovery,
+            run_times,
+            coredumps,
+            parallelism,
+            tee_logs,
+            scheduling,
         } = msg;
         let start = Instant::now();
 
         tracing::info!(?base_dir, "Experiment started");
 
-        let (sender, receiver) = futures::channel::mpsc::channel(1);
+        let runner = Arc::new(
+            Runner::new(
+                experiment.clone(),
+                base_dir.join("experiments"),
+                wasmer,
+                base_dir.join("module-cache"),
+                redactor.clone(),
+                coredumps,
+                parallelism,
+            )
+            .with_tee_logs(tee_logs)
+            .with_scheduling(scheduling),
+        );
 
-        let cache = self.cache.clone();
-        let wapm = Wapm::new(self.client.clone(), self.endpoint.clone()).start();
-        let runner = Runner::new(experiment.clone(), base_dir.join("experiments")).start();
+        let pins = Arc::new(Pins::new(locked.as_ref()));
 
-        wapm.do_send(FetchTestCases {
-            filters: experiment.filters.clone(),
-            recipient: sender,
+        // Keep discovering test cases in the background until we're done
+        let previously_discovered = || {
+            if packages.is_some() || !experiment.test_cases.is_empty() {
+                None
+            } else {
+                discovery.load()
+            }
+        };
+        let (discovered, _discovery): (BoxStream<'static, TestCase>, _) = match locked
+            .map(|l| l.test_cases())
+            .or(local)
+            .or_else(previously_discovered)
+        {
+            Some(test_cases) => (futures::stream::iter(test_cases).boxed(), None),
+            None => {
+                let (sender, receiver) = futures::channel::mpsc::channel(1);
+                let wapm = Wapm::new(self.client.clone(), self.endpoint.clone(), self.retry);
+                let task = match packages {
+                    Some(packages) => tokio::spawn(wapm.resolve_packages(packages, sender)),
+                    None if !experiment.test_cases.is_empty() => {
+                        tokio::spawn(wapm.resolve_pinned(experiment.test_cases.clone(), sender))
+                    }
+                    None => tokio::spawn(wapm.fetch_test_cases(
+                        experiment.filters.clone(),
+                        sender,
+                        discovery,
+                    )),
+                };
+                let discovered = receiver
+                    .map(|TestCaseDiscovered(test_case)| test_case)
+                    .boxed();
+                (discovered, Some(AbortOnDrop(task)))
+            }
+        };
+
+        let discovered = discovered.filter(move |test_case| {
+            let keep = shard.map_or(true, |shard| shard.contains(test_case));
+            futures::future::ready(keep)
         });
 
-        let mut reports = receiver.map(move |TestCaseDiscovered(test_case)| {
+        let test_cases: BoxStream<'static, TestCase> = if sampling.is_some() || run_times.is_some()
+        {
+            // We need to know about every test case before we can choose a
+            // sample or put them in order, so wait for discovery to finish.
+            discovered
+                .collect::<Vec<_>>()
+                .map(move |mut test_cases| {
+                    if let Some(sampling) = sampling {
+                        let discovered = test_cases.len();
+                        test_cases = sampling.apply(test_cases);
+                        tracing::info!(
+                            discovered,
+                            sampled = test_cases.len(),
+                            seed = sampling.seed,
+                            "Sampled the discovered test cases",
+                        );
+                    }
+                    if let Some(run_times) = &run_times {
+                        test_cases = schedule::longest_first(test_cases, run_times);
+                    }
+                    futures::stream::iter(test_cases)
+                })
+                .flatten_stream()
+                .boxed()
+        } else {
+            discovered.boxed()
+        };
+
+        let _monitor = AbortOnDrop(tokio::spawn(disk.clone().monitor()));
+
+        let cache = self.cache.clone();
+        let progress = self.progress.clone();
+        let guard = disk.clone();
+        let recorder = pins.clone();
+        let mut reports = test_cases.fuse().map(move |test_case| {
             let cache = cache.clone();
             let runner = runner.clone();
+            let progress = progress.clone();
+            let disk = guard.clone();
+            let pins = recorder.clone();
 
-            async move {
-                let result = cache
-                    .send(FetchAssets {
-                        test_case: test_case.clone(),
-                    })
-                    .await
-                    .map_err(Error::from)
-                    .and_then(|r| r);
-
-                let begin_test = match result {
-                    Ok(AssetsFetched { test_case, assets }) => BeginTest { test_case, assets },
-                    Err(error) => {
-                        return Report {
-                            display_name: test_case.display_name(),
-                            package_version: test_case.package_version,
-                            outcome: Outcome::FetchFailed {
-                                error: error.into(),
-                            },
-                        };
-                    }
-                };
+            let _ = progress.send(TestStatusMessage::Discovered(test_case.clone()));
 
-                runner.send(begin_test).await.unwrap()
+            async move {
+                let report =
+                    run_test_case(&cache, &runner, &disk, &pins, test_case.clone()).await?;
+                let _ = progress.send(TestStatusMessage::Finished {
+                    test_case,
+                    category: report.category(),
+                });
+                Some(report)
             }
         });
 
-        Box::pin(async move {
-            let mut futures = FuturesUnordered::new();
-            let mut completed = Vec::new();
-
-            // Note: for maximum throughput, poll the reports while still
-            // fetching test cases.
-            loop {
-                futures::select! {
-                    fut = reports.next() => {
-                        match fut {
-                            Some(fut) => futures.push(fut),
-                            None => {
-                                break;
-                            },
-                        }
+        let mut futures = FuturesUnordered::new();
+        let mut completed = Vec::new();
+        let mut log = ReportLog::create(base_dir.join(REPORT_LOG))
+            .await
+            .map_err(RunError::Save)?;
+
+        // Note: for maximum throughput, poll the reports while still
+        // fetching test cases.
+        loop {
+            if disk.tripped().is_some() {
+                // Stop discovering test cases and let the ones in
+                // progress wind down.
+                break;
+            }
+
+            futures::select! {
+                fut = reports.next() => {
+                    match fut {
+                        Some(fut) => futures.push(fut),
+                        None => {
+                            break;
+                        },
                     }
-                    report = futures.next() => {
-                        if let Some(report) = report {
-                            completed.push(report);
-                        }
+                }
+                report = futures.next() => {
+                    if let Some(Some(report)) = report {
+                        completed.push(log.append(&report).await.map_err(RunError::Save)?);
+                        let _ = self.progress.send(TestStatusMessage::Reported(Box::new(report)));
                     }
                 }
             }
+        }
+
+        while let Some(report) = futures.next().await {
+            if let Some(report) = report {
+                completed.push(log.append(&report).await.map_err(RunError::Save)?);
+                let _ = self
+                    .progress
+                    .send(TestStatusMessage::Reported(Box::new(report)));
+            }
+        }
+
+        log.finish().await.map_err(RunError::Save)?;
+
+        let lock_file = pins.lock_file();
+        if let Err(e) = lock_file.save(base_dir.join(LOCK_FILENAME)) {
+            tracing::warn!(error = &*e, "Unable to save the lock file");
+        }
+
+        if let Some(error) = disk.tripped() {
+            tracing::error!(
+                error = &error as &dyn std::error::Error,
+                completed = completed.len(),
+                "Stopped the experiment early because we are running low on disk space",
+            );
+            return Err(error.into());
+        }
+
+        let count = |category| completed.iter().filter(|r| r.category == category).count();
+        tracing::info!(
+            successes = count(Category::Success),
+            failures = count(Category::Failure),
+            bugs = count(Category::Bug),
+            "Experiment finished",
+        );
+
+        Ok(ResultsSummary {
+            experiment: redactor.redact_experiment(&experiment),
+            reports: completed,
+            total_time: start.elapsed(),
+            experiment_dir: base_dir,
+            // Filled in by the caller once the cache monitor has finished
+            cache: CacheStats::default(),
+        })
+    }
+}
+
+/// A background task which is stopped when the experiment finishes (or is
+/// cancelled).
+#[derive(Debug)]
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+/// Keeps track of the artifacts each test case was run against, checking
+/// them against the lock file being replayed (if there is one).
+#[derive(Debug, Default)]
+struct Pins {
+    expected: HashMap<String, Checksums>,
+    recorded: Mutex<Vec<LockedTestCase>>,
+}
+
+impl Pins {
+    fn new(locked: Option<&LockFile>) -> Self {
+        Pins {
+            expected: locked.map(LockFile::checksums).unwrap_or_default(),
+            recorded: Mutex::default(),
+        }
+    }
+
+    /// Record the checksums of a test case's artifacts, failing if they
+    /// don't match the lock file.
+    async fn pin(&self, fetched: &AssetsFetched) -> Result<(), Error> {
+        let AssetsFetched { test_case, assets } = fetched;
+
+        let checksums = match Checksums::of(assets).await {
+            Ok(checksums) => checksums,
+            Err(e) => {
+                tracing::warn!(error = &*e, "Unable to calculate the artifacts' checksums");
+                Checksums::default()
+            }
+        };
+        self.record(test_case, checksums.clone());
+
+        match self.expected.get(&lockfile::key(test_case)) {
+            Some(expected) => expected.verify(&checksums),
+            None => Ok(()),
+        }
+    }
 
-            let remaining_reports: Vec<_> = futures.collect().await;
-            completed.extend(remaining_reports);
+    fn record(&self, test_case: &TestCase, checksums: Checksums) {
+        self.recorded.lock().unwrap().push(LockedTestCase {
+            test_case: test_case.clone(),
+            checksums,
+        });
+    }
+
+    fn lock_file(&self) -> LockFile {
+        let mut lock_file = LockFile {
+            test_cases: self.recorded.lock().unwrap().clone(),
+        };
+        lock_file.sort();
+        lock_file
+    }
+}
+
+/// An append-only log of [`Report`]s, written as each test case completes so
+/// they aren't lost if the experiment is interrupted and don't need to be
+/// kept in memory.
+///
+/// The log is the only copy of each report until `results.json` is written,
+/// so failing to write to it stops the experiment.
+#[derive(Debug)]
+struct ReportLog {
+    path: PathBuf,
+    file: tokio::fs::File,
+    offset: u64,
+}
+
+impl ReportLog {
+    async fn create(path: PathBuf) -> Result<Self, Error> {
+        let file = create_file(&path).await?;
+
+        Ok(ReportLog {
+            path,
+            file,
+            offset: 0,
+        })
+    }
+
+    async fn append(&mut self, report: &Report) -> Result<ReportSummary, Error> {
+        let mut line = serde_json::to_vec(report).expect("Serialization should never fail");
+        line.push(b'\n');
+
+        self.file
+            .write_all(&line)
+            .await
+            .with_context(|| format!("Unable to write to \"{}\"", self.path.display()))?;
 
-            Results {
-                experiment: Experiment::clone(&experiment),
-                reports: completed,
-                total_time: start.elapsed(),
-                experiment_dir: base_dir,
+        let summary = ReportSummary::new(report, self.offset);
+        self.offset += line.len() as u64;
+
+        Ok(summary)
+    }
+
+    /// Make sure everything has been written to disk so the log can be read
+    /// back.
+    async fn finish(mut self) -> Result<(), Error> {
+        self.file
+            .flush()
+            .await
+            .with_context(|| format!("Unable to write to \"{}\"", self.path.display()))
+    }
+}
+
+async fn create_file(path: &Path) -> Result<tokio::fs::File, Error> {
+    if let Some(parent) = path.parent() {
+        tokio::fs::create_dir_all(parent)
+            .await
+            .with_context(|| format!("Unable to create \"{}\"", parent.display()))?;
+    }
+
+    tokio::fs::File::create(path)
+        .await
+        .with_context(|| format!("Unable to create \"{}\"", path.display()))
+}
+
+/// Fetch and run a test case, returning `None` if it was skipped because we
+/// are running low on disk space.
+async fn run_test_case(
+    cache: &Cache,
+    runner: &Runner,
+    disk: &DiskGuard,
+    pins: &Pins,
+    test_case: TestCase,
+) -> Option<Report> {
+    if disk.tripped().is_some() {
+        return None;
+    }
+
+    if runner.runs_by_name(&test_case) {
+        // The wasmer CLI downloads the package itself, so there is nothing
+        // for us to pin, but we still want to know what is inside it
+        pins.record(&test_case, Checksums::default());
+        let fetch = cache.fetch(test_case.clone());
+        let inspection = async {
+            match fetch.await {
+                Ok(fetched) => inspect_modules(fetched.assets).await,
+                Err(e) => Inspection::failed(e.context("Unable to fetch the package to inspect")),
             }
+        };
+        let begin_test = BeginTest {
+            test_case,
+            assets: Assets::default(),
+        };
+        let (mut report, inspection) = futures::join!(runner.run(begin_test), inspection);
+        report.modules = inspection.modules;
+        report.module_errors = inspection.errors;
+        return Some(report);
+    }
+
+    let download_start = Utc::now();
+    let result = if test_case.registry == local::LOCAL_REGISTRY {
+        // Local packages are used in place rather than being cached
+        local::assets(&test_case).map(|assets| AssetsFetched {
+            test_case: test_case.clone(),
+            assets,
         })
+    } else {
+        cache.fetch(test_case.clone()).await
+    };
+
+    let download = Phase::since(download_start);
+
+    let result = match result {
+        Ok(fetched) => pins.pin(&fetched).await.map(|_| fetched),
+        Err(error) => {
+            pins.record(&test_case, Checksums::default());
+            Err(error)
+        }
+    };
+
+    let begin_test = match result {
+        Ok(AssetsFetched { test_case, assets }) => BeginTest { test_case, assets },
+        Err(error) => {
+            return Some(Report {
+                display_name: test_case.display_name(),
+                package_version: test_case.package_version,
+                outcome: Outcome::FetchFailed {
+                    error: error.into(),
+                },
+                analyses: Vec::new(),
+                annotation: None,
+                command: None,
+                benchmark: None,
+                startup: None,
+                repetitions: None,
+                stdout_digest: None,
+                rerun: None,
+                coredumps: Vec::new(),
+                logs_discarded: false,
+                modules: Vec::new(),
+                module_errors: Vec::new(),
+                timeline: Some(Timeline {
+                    download: Some(download),
+                    execution: None,
+                }),
+            });
+        }
+    };
+
+    if disk.tripped().is_some() {
+        return None;
+    }
+
+    // Inspect the package while it runs instead of making it wait
+    let inspection = inspect_modules(begin_test.assets.clone());
+    let (mut report, inspection) = futures::join!(runner.run(begin_test), inspection);
+    report
+        .timeline
+        .get_or_insert_with(Timeline::default)
+        .download = Some(download);
+    report.modules = inspection.modules;
+    report.module_errors = inspection.errors;
+
+    Some(report)
+}
+
+/// Find out which WASI functions (and other imports) a package's modules use.
+async fn inspect_modules(assets: Assets) -> Inspection {
+    let tarball = match (assets.tarball, assets.webc) {
+        (Some(tarball), _) => tarball,
+        (None, Some(webc)) => return Inspection::unsupported(&webc),
+        (None, None) => return Inspection::default(),
+    };
+
+    let result = tokio::task::spawn_blocking(move || modules::inspect_tarball(&tarball))
+        .await
+        .map_err(Error::from)
+        .and_then(|result| result);
+
+    result.unwrap_or_else(|e| {
+        Inspection::failed(e.context("Unable to inspect the package's modules"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::{
+        experiment::{summary, Results},
+        test_utils,
+    };
+
+    fn report(name: &str, version: &str, stdout_digest: &str) -> Report {
+        Report {
+            stdout_digest: Some(stdout_digest.to_string()),
+            ..test_utils::report(
+                name,
+                version,
+                Outcome::FetchFailed {
+                    error: Error::msg("404").into(),
+                },
+            )
+        }
+    }
+
+    #[tokio::test]
+    async fn results_are_copied_from_the_report_log() {
+        let temp = tempfile::tempdir().unwrap();
+        let reports = vec![
+            report("python", "1.0.0", "a"),
+            report("python", "2.0.0", "b"),
+            report("qjs", "0.1.0", "c"),
+        ];
+        let mut log = ReportLog::create(temp.path().join(REPORT_LOG))
+            .await
+            .unwrap();
+        let mut summaries = Vec::new();
+        for report in &reports {
+            summaries.push(log.append(report).await.unwrap());
+        }
+        log.finish().await.unwrap();
+        let experiment: Experiment =
+            serde_json::from_value(serde_json::json!({ "package": "wasmer/wasi" })).unwrap();
+        let summary = ResultsSummary {
+            experiment: experiment.clone(),
+            reports: summaries,
+            total_time: Duration::from_secs(42),
+            experiment_dir: temp.path().to_path_buf(),
+            cache: CacheStats::default(),
+        };
+        let expected = Results {
+            experiment,
+            reports,
+            total_time: Duration::from_secs(42),
+            experiment_dir: temp.path().to_path_buf(),
+            cache: CacheStats::default(),
+        };
+
+        summary::save(&summary).unwrap();
+        expected.save(temp.path().join("expected.json")).unwrap();
+
+        assert_eq!(summary.reports[1].version, "2.0.0");
+        assert_eq!(summary.reports[1].category, Category::Bug);
+        let digests = |reports: Vec<Report>| -> Vec<_> {
+            reports
+                .into_iter()
+                .map(|r| r.stdout_digest.unwrap())
+                .collect()
+        };
+        assert_eq!(digests(summary.load().unwrap().reports), ["a", "b", "c"]);
+        let streamed = summary.reports().unwrap().map(Result::unwrap).collect();
+        assert_eq!(digests(streamed), ["a", "b", "c"]);
+        let qjs = summary.reader().unwrap().read(&summary.reports[2]).unwrap();
+        assert_eq!(qjs.display_name, "wasmer/qjs");
+        assert_eq!(
+            std::fs::read_to_string(temp.path().join("results.json")).unwrap(),
+            std::fs::read_to_string(temp.path().join("expected.json")).unwrap(),
+        );
+    }
+
+    #[tokio::test]
+    async fn webc_only_packages_are_marked_as_unsupported() {
+        let assets = Assets {
+            tarball: None,
+            webc: Some(PathBuf::from("/packages/python.webc")),
+            total_size: 0,
+        };
+
+        let inspection = inspect_modules(assets).await;
+
+        assert!(inspection.modules.is_empty());
+        assert_eq!(
+            inspection.errors,
+            ["Unable to inspect \"python.webc\" because *.webc files aren't supported"]
+        );
     }
 }
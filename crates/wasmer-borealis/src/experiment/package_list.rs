@@ -0,0 +1,297 @@
+//! An explicit list of packages to test, so another tool can decide what
+//! goes into an experiment instead of walking the registry's namespaces.
+
+use std::{fmt::Display, io::BufRead, str::FromStr};
+
+use anyhow::{Context, Error};
+use futures::{channel::mpsc::Sender, SinkExt};
+use reqwest::Client;
+use url::Url;
+
+use crate::{
+    config::PinnedTestCase,
+    experiment::{wapm::TestCaseDiscovered, TestCase},
+    registry::{format_graphql, RetryPolicy},
+};
+
+/// A package from a package list, written as `namespace/name[@version]`.
+///
+/// When the version is left out, the package's latest version is used.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PackageSpec {
+    pub namespace: String,
+    pub name: String,
+    pub version: Option<String>,
+}
+
+impl PackageSpec {
+    /// Read a package list, with one [`PackageSpec`] per line.
+    ///
+    /// Blank lines and lines starting with `#` are ignored.
+    pub fn read_list(reader: impl BufRead) -> Result<Vec<PackageSpec>, Error> {
+        let mut specs = Vec::new();
+
+        for (i, line) in reader.lines().enumerate() {
+            let line = line.context("Unable to read the package list")?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let spec = line
+                .parse()
+                .with_context(|| format!("Invalid package on line {}", i + 1))?;
+            specs.push(spec);
+        }
+
+        Ok(specs)
+    }
+
+    /// The package's full name (e.g. `wasmer/python`).
+    pub fn full_name(&self) -> String {
+        format!("{}/{}", self.namespace, self.name)
+    }
+}
+
+impl Display for PackageSpec {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.namespace, self.name)?;
+        if let Some(version) = &self.version {
+            write!(f, "@{version}")?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PackageSpec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, version) = match s.split_once('@') {
+            Some((name, version)) => (name, Some(version.trim())),
+            None => (s, None),
+        };
+        let (namespace, name) = name.split_once('/').with_context(|| {
+            format!("Packages should be in the form \"namespace/name[@version]\", found \"{s}\"")
+        })?;
+        let (namespace, name) = (namespace.trim(), name.trim());
+
+        anyhow::ensure!(
+            !namespace.is_empty() && !name.is_empty() && !name.contains('/'),
+            "\"{s}\" isn't a valid package name"
+        );
+        anyhow::ensure!(
+            version.map_or(true, |v| !v.is_empty()),
+            "\"{s}\" has an empty version"
+        );
+
+        Ok(PackageSpec {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            version: version.map(str::to_string),
+        })
+    }
+}
+
+/// Look up each package in the list, sending a [`TestCase`] for every one
+/// which exists.
+///
+/// Packages which can't be found are logged and skipped rather than failing
+/// the whole experiment.
+pub(crate) async fn resolve(
+    client: Client,
+    endpoint: Url,
+    retry: RetryPolicy,
+    packages: Vec<PackageSpec>,
+    mut recipient: Sender<TestCaseDiscovered>,
+) {
+    let hostname = endpoint.host_str().unwrap_or("unknown").to_string();
+
+    for spec in packages {
+        let name = spec.full_name();
+        let result = match &spec.version {
+            Some(version) => crate::registry::get_package_version(
+                &client,
+                endpoint.as_str(),
+                &retry,
+                &name,
+                version,
+            )
+            .await
+            .map(|v| v.map(|v| vec![v])),
+            None => crate::registry::get_package(&client, endpoint.as_str(), &retry, &name)
+                .await
+                .map(|pkg| pkg.map(|pkg| pkg.last_version.into_iter().collect())),
+        };
+
+        let versions = match result {
+            Ok(Some(versions)) => versions,
+            Ok(None) => {
+                tracing::warn!(package = %spec, "Package not found");
+                continue;
+            }
+            Err(e) => {
+                tracing::error!(
+                    error = &e as &dyn std::error::Error,
+                    package = %spec,
+                    "Unable to look up a package",
+                );
+                continue;
+            }
+        };
+
+        for package_version in versions {
+            let test_case = TestCase {
+                registry: hostname.clone(),
+                namespace: spec.namespace.clone(),
+                package_name: spec.name.clone(),
+                package_version,
+                variant: None,
+            };
+            if recipient.send(TestCaseDiscovered(test_case)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Look up each of an experiment's inline `test-cases`, using the registry
+/// each one names or `endpoint` when it doesn't name one.
+pub(crate) async fn resolve_pinned(
+    client: Client,
+    endpoint: Url,
+    retry: RetryPolicy,
+    test_cases: Vec<PinnedTestCase>,
+    recipient: Sender<TestCaseDiscovered>,
+) {
+    for (endpoint, packages) in group_by_registry(&endpoint, test_cases) {
+        resolve(client.clone(), endpoint, retry, packages, recipient.clone()).await;
+    }
+}
+
+/// Group test cases by the GraphQL endpoint of their registry, keeping them
+/// in the order they were listed.
+fn group_by_registry(
+    endpoint: &Url,
+    test_cases: Vec<PinnedTestCase>,
+) -> Vec<(Url, Vec<PackageSpec>)> {
+    let mut groups: Vec<(Url, Vec<PackageSpec>)> = Vec::new();
+
+    for test_case in test_cases {
+        let PinnedTestCase {
+            registry,
+            namespace,
+            name,
+            version,
+        } = test_case;
+
+        let endpoint = match registry {
+            Some(registry) => match format_graphql(&registry).parse() {
+                Ok(endpoint) => endpoint,
+                Err(e) => {
+                    tracing::error!(
+                        error = &e as &dyn std::error::Error,
+                        %registry,
+                        package = %format!("{namespace}/{name}@{version}"),
+                        "Invalid registry",
+                    );
+                    continue;
+                }
+            },
+            None => endpoint.clone(),
+        };
+        let spec = PackageSpec {
+            namespace,
+            name,
+            version: Some(version),
+        };
+
+        match groups.iter_mut().find(|(url, _)| *url == endpoint) {
+            Some((_, specs)) => specs.push(spec),
+            None => groups.push((endpoint, vec![spec])),
+        }
+    }
+
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_a_package_list() {
+        let src = "# packages to test\nwasmer/python@3.12.0\n\n  syrusakbary/cowsay \n";
+
+        let specs = PackageSpec::read_list(src.as_bytes()).unwrap();
+
+        assert_eq!(
+            specs,
+            [
+                PackageSpec {
+                    namespace: "wasmer".to_string(),
+                    name: "python".to_string(),
+                    version: Some("3.12.0".to_string()),
+                },
+                PackageSpec {
+                    namespace: "syrusakbary".to_string(),
+                    name: "cowsay".to_string(),
+                    version: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_package_specs_are_rejected() {
+        for spec in ["python", "/python", "wasmer/", "wasmer/python@", "a/b/c"] {
+            assert!(spec.parse::<PackageSpec>().is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn errors_mention_the_line_number() {
+        let err = PackageSpec::read_list("wasmer/python\nnonsense\n".as_bytes()).unwrap_err();
+
+        assert_eq!(err.to_string(), "Invalid package on line 2");
+    }
+
+    #[test]
+    fn inline_test_cases_are_grouped_by_registry() {
+        let endpoint: Url = "https://registry.wasmer.io/graphql".parse().unwrap();
+        let pinned = |registry: Option<&str>, name: &str| PinnedTestCase {
+            registry: registry.map(str::to_string),
+            namespace: "wasmer".to_string(),
+            name: name.to_string(),
+            version: "1.0.0".to_string(),
+        };
+        let test_cases = vec![
+            pinned(None, "python"),
+            pinned(Some("https://registry.wasmer.wtf/graphql"), "php"),
+            pinned(Some("https://registry.wasmer.io/graphql"), "ruby"),
+        ];
+
+        let groups = group_by_registry(&endpoint, test_cases);
+
+        let names: Vec<(&str, Vec<String>)> = groups
+            .iter()
+            .map(|(url, specs)| (url.as_str(), specs.iter().map(|s| s.to_string()).collect()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (
+                    "https://registry.wasmer.io/graphql",
+                    vec![
+                        "wasmer/python@1.0.0".to_string(),
+                        "wasmer/ruby@1.0.0".to_string()
+                    ]
+                ),
+                (
+                    "https://registry.wasmer.wtf/graphql",
+                    vec!["wasmer/php@1.0.0".to_string()]
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Making sure that when a test case is killed, anything it started goes with
+//! it.
+//!
+//! Packages like web servers often spawn their own children, and killing just
+//! the `wasmer` process leaves them running in the background where they hold
+//! onto ports and CPU time and skew the tests that come after.
+
+use std::io::Error;
+
+/// Start the command in its own process group so the whole tree can be
+/// killed with [`ProcessTree::kill()`].
+pub(crate) fn isolate(cmd: &mut tokio::process::Command) {
+    cfg_if::cfg_if! {
+        if #[cfg(unix)] {
+            // Note: tokio only exposes process_group() with tokio_unstable.
+            // Safety: setpgid() is async-signal-safe.
+            unsafe {
+                cmd.pre_exec(|| {
+                    if libc::setpgid(0, 0) == 0 {
+                        Ok(())
+                    } else {
+                        Err(Error::last_os_error())
+                    }
+                });
+            }
+        } else if #[cfg(windows)] {
+            const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+            cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+        }
+    }
+}
+
+/// A guard which kills a process and all of its descendants, unless
+/// [`ProcessTree::disarm()`] is called first.
+///
+/// This is what cleans up after a test case when its future is dropped (e.g.
+/// because the user hit Ctrl-C or the experiment's deadline passed).
+#[derive(Debug)]
+pub(crate) struct ProcessTree {
+    pid: Option<u32>,
+}
+
+impl ProcessTree {
+    /// Guard a process that was started with [`isolate()`].
+    pub fn new(pid: Option<u32>) -> Self {
+        ProcessTree { pid }
+    }
+
+    /// Kill every process in the tree.
+    pub fn kill(&mut self) -> Result<(), Error> {
+        match self.pid.take() {
+            Some(pid) => kill_tree(pid),
+            None => Ok(()),
+        }
+    }
+
+    /// The process exited by itself, so there is nothing to clean up.
+    ///
+    /// Once the process has been reaped its PID may be reused by something
+    /// else, so the guard mustn't be left armed afterwards.
+    pub fn disarm(&mut self) {
+        self.pid = None;
+    }
+}
+
+impl Drop for ProcessTree {
+    fn drop(&mut self) {
+        if let Err(e) = self.kill() {
+            tracing::warn!(
+                error = &e as &dyn std::error::Error,
+                "Unable to kill the test case's processes",
+            );
+        }
+    }
+}
+
+#[cfg(unix)]
+fn kill_tree(pid: u32) -> Result<(), Error> {
+    // The child is the leader of its own process group, so the group ID is
+    // its PID.
+    let pgid = pid as libc::pid_t;
+
+    if unsafe { libc::killpg(pgid, libc::SIGKILL) } == 0 {
+        return Ok(());
+    }
+
+    match Error::last_os_error() {
+        // Everything has already exited
+        e if e.raw_os_error() == Some(libc::ESRCH) => Ok(()),
+        e => Err(e),
+    }
+}
+
+#[cfg(windows)]
+fn kill_tree(pid: u32) -> Result<(), Error> {
+    let status = std::process::Command::new("taskkill")
+        .args(["/T", "/F", "/PID", &pid.to_string()])
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .status()?;
+
+    if status.success() {
+        Ok(())
+    } else {
+        Err(Error::new(
+            std::io::ErrorKind::Other,
+            format!("\"taskkill\" failed ({status})"),
+        ))
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::{path::Path, process::Stdio, time::Duration};
+
+    use tokio::io::{AsyncBufReadExt, BufReader};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn killing_the_tree_also_kills_grandchildren() {
+        let mut cmd = tokio::process::Command::new("sh");
+        cmd.arg("-c")
+            .arg("sleep 60 & echo $!; wait")
+            .stdout(Stdio::piped())
+            .kill_on_drop(true);
+        isolate(&mut cmd);
+        let mut child = cmd.spawn().unwrap();
+        let mut tree = ProcessTree::new(child.id());
+
+        let mut stdout = BufReader::new(child.stdout.take().unwrap());
+        let mut line = String::new();
+        stdout.read_line(&mut line).await.unwrap();
+        let grandchild = Path::new("/proc").join(line.trim()).join("stat");
+
+        tree.kill().unwrap();
+        child.wait().await.unwrap();
+
+        // Killed processes may linger as zombies until init gets around to
+        // reaping them
+        let is_alive = || match std::fs::read_to_string(&grandchild) {
+            Ok(stat) => !stat.contains(") Z "),
+            Err(_) => false,
+        };
+        for _ in 0..50 {
+            if !is_alive() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("{} is still running", grandchild.display());
+    }
+}
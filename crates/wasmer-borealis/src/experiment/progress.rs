@@ -1,40 +1,143 @@
 use std::{fmt::Debug, time::Duration};
 
-use actix::{Actor, Context, Handler};
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
-use crate::experiment::{cache::CacheStatusMessage, wapm::TestCase};
+use crate::experiment::{
+    cache::CacheStatusMessage,
+    orchestrator::TestStatusMessage,
+    results::{CacheStats, Download},
+    Category, Report, TestCase,
+};
 
+/// Forwards status messages from the rest of the pipeline to a [`Progress`]
+/// implementation, keeping track of [`CacheStats`] along the way.
 #[derive(Debug)]
-pub(crate) struct ProgressMonitor(Box<dyn Progress>);
+pub(crate) struct ProgressMonitor {
+    progress: Box<dyn Progress>,
+    cache_stats: CacheStats,
+}
+
+/// The ends of the channels used to send messages to a [`ProgressMonitor`].
+#[derive(Debug, Clone)]
+pub(crate) struct ProgressSenders {
+    pub cache: UnboundedSender<CacheStatusMessage>,
+    pub tests: UnboundedSender<TestStatusMessage>,
+}
 
 impl ProgressMonitor {
     pub fn new(progress: Box<dyn Progress>) -> Self {
-        ProgressMonitor(progress)
+        ProgressMonitor {
+            progress,
+            cache_stats: CacheStats::default(),
+        }
     }
-}
 
-pub trait Progress: Debug {
-    fn downloading(&mut self, _test_case: TestCase) {}
-    fn cache_hit(&mut self, _test_case: TestCase) {}
-    fn cache_miss(&mut self, _test_case: TestCase, _duration: Duration, _bytes_downloaded: u64) {}
-}
+    /// Start listening for status messages.
+    ///
+    /// The returned future completes with the [`CacheStats`] once every
+    /// [`ProgressSenders`] has been dropped.
+    pub fn start(
+        self,
+    ) -> (
+        ProgressSenders,
+        impl std::future::Future<Output = CacheStats> + Send,
+    ) {
+        let (cache, cache_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (tests, tests_rx) = tokio::sync::mpsc::unbounded_channel();
 
-impl Actor for ProgressMonitor {
-    type Context = Context<Self>;
-}
+        (
+            ProgressSenders { cache, tests },
+            self.run(cache_rx, tests_rx),
+        )
+    }
 
-impl Handler<CacheStatusMessage> for ProgressMonitor {
-    type Result = ();
+    async fn run(
+        mut self,
+        mut cache: UnboundedReceiver<CacheStatusMessage>,
+        mut tests: UnboundedReceiver<TestStatusMessage>,
+    ) -> CacheStats {
+        let mut cache_open = true;
+        let mut tests_open = true;
 
-    fn handle(&mut self, msg: CacheStatusMessage, _ctx: &mut Self::Context) {
+        while cache_open || tests_open {
+            tokio::select! {
+                msg = cache.recv(), if cache_open => match msg {
+                    Some(msg) => self.on_cache_status(msg),
+                    None => cache_open = false,
+                },
+                msg = tests.recv(), if tests_open => match msg {
+                    Some(msg) => self.on_test_status(msg),
+                    None => tests_open = false,
+                },
+            }
+        }
+
+        self.cache_stats
+    }
+
+    fn on_cache_status(&mut self, msg: CacheStatusMessage) {
         match msg {
-            CacheStatusMessage::Fetching(test_case) => self.0.downloading(test_case),
-            CacheStatusMessage::CacheHit(test_case) => self.0.cache_hit(test_case),
+            CacheStatusMessage::Fetching(test_case) => self.progress.downloading(test_case),
+            CacheStatusMessage::Downloading {
+                test_case,
+                filename,
+                bytes_received,
+                total,
+            } => self
+                .progress
+                .download_progress(test_case, &filename, bytes_received, total),
+            CacheStatusMessage::CacheHit(test_case) => {
+                self.cache_stats.record_hit();
+                self.progress.cache_hit(test_case);
+            }
             CacheStatusMessage::CacheMiss {
                 test_case,
                 duration,
                 bytes_downloaded,
-            } => self.0.cache_miss(test_case, duration, bytes_downloaded),
+            } => {
+                let download = Download {
+                    display_name: test_case.display_name(),
+                    version: test_case.version().to_string(),
+                    bytes: bytes_downloaded,
+                };
+                self.cache_stats.record_miss(download, duration);
+                self.progress
+                    .cache_miss(test_case, duration, bytes_downloaded);
+            }
+        }
+    }
+
+    fn on_test_status(&mut self, msg: TestStatusMessage) {
+        match msg {
+            TestStatusMessage::Discovered(test_case) => self.progress.discovered(test_case),
+            TestStatusMessage::Finished {
+                test_case,
+                category,
+            } => self.progress.finished(test_case, category),
+            TestStatusMessage::Reported(report) => self.progress.report(&report),
         }
     }
 }
+
+pub trait Progress: Debug + Send {
+    /// A test case will be included in the experiment.
+    fn discovered(&mut self, _test_case: TestCase) {}
+    /// A test case has finished running.
+    fn finished(&mut self, _test_case: TestCase, _category: Category) {}
+    /// The full report for a test case which has just finished.
+    fn report(&mut self, _report: &Report) {}
+    fn downloading(&mut self, _test_case: TestCase) {}
+    /// Part of one of a test case's files has been downloaded.
+    ///
+    /// The `total` is the file's size, if the server provided one.
+    fn download_progress(
+        &mut self,
+        _test_case: TestCase,
+        _filename: &str,
+        _bytes_received: u64,
+        _total: Option<u64>,
+    ) {
+    }
+    fn cache_hit(&mut self, _test_case: TestCase) {}
+    fn cache_miss(&mut self, _test_case: TestCase, _duration: Duration, _bytes_downloaded: u64) {}
+}
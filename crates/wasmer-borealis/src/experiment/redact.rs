@@ -0,0 +1,303 @@
+use std::{borrow::Cow, ffi::OsStr, sync::Arc};
+
+use indexmap::IndexMap;
+
+use crate::{
+    config::{Experiment, TemplatedString},
+    experiment::{CommandLine, Outcome, Report},
+};
+
+pub(crate) const REDACTED: &str = "<redacted>";
+
+/// Secrets shorter than this are ignored, because scrubbing something like
+/// `"1"` would mangle every log it appears in.
+const MIN_SECRET_LEN: usize = 4;
+
+/// Does this environment variable or flag name look like it holds a secret?
+pub(crate) fn is_secret(name: &str) -> bool {
+    const SECRETS: &[&str] = &[
+        "TOKEN",
+        "SECRET",
+        "PASSWORD",
+        "PASSWD",
+        "API_KEY",
+        "APIKEY",
+        "CREDENTIAL",
+        "AUTH",
+    ];
+
+    let name = name.to_ascii_uppercase().replace('-', "_");
+    SECRETS.iter().any(|secret| name.contains(secret))
+}
+
+/// Scrubs secret values from everything recorded about an experiment, so
+/// reports and logs are safe to share.
+#[derive(Debug, Clone)]
+pub(crate) struct Redactor {
+    /// The names of environment variables whose values are secret, on top of
+    /// anything [`is_secret()`] flags.
+    names: Arc<[String]>,
+    /// The secret values, longest first so a secret containing another secret
+    /// is replaced in full.
+    secrets: Vec<String>,
+}
+
+impl Redactor {
+    /// Create a redactor which scrubs the `secrets` (e.g. the registry token)
+    /// and the host's value for any of the experiment's `redact` variables.
+    pub fn new(experiment: &Experiment, secrets: impl IntoIterator<Item = String>) -> Self {
+        let host_values = experiment
+            .redact
+            .iter()
+            .filter_map(|name| std::env::var(name).ok());
+
+        Redactor {
+            names: experiment.redact.iter().cloned().collect(),
+            secrets: Vec::new(),
+        }
+        .with_secrets(secrets.into_iter().chain(host_values))
+    }
+
+    /// Also scrub the value of each environment variable that is secret.
+    pub fn with_env<'a>(&self, env: impl IntoIterator<Item = (&'a str, &'a OsStr)>) -> Self {
+        let values: Vec<String> = env
+            .into_iter()
+            .filter(|(name, _)| self.is_secret(name))
+            .map(|(_, value)| value.to_string_lossy().into_owned())
+            .collect();
+
+        self.clone().with_secrets(values)
+    }
+
+    fn with_secrets(mut self, secrets: impl IntoIterator<Item = String>) -> Self {
+        self.secrets.extend(
+            secrets
+                .into_iter()
+                .filter(|s| s.len() >= MIN_SECRET_LEN && !s.contains(REDACTED)),
+        );
+        self.secrets
+            .sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        self.secrets.dedup();
+        self
+    }
+
+    fn is_secret(&self, name: &str) -> bool {
+        is_secret(name) || self.names.iter().any(|n| n == name)
+    }
+
+    pub fn redact<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+
+        for secret in &self.secrets {
+            if text.contains(secret.as_str()) {
+                text = Cow::Owned(text.replace(secret.as_str(), REDACTED));
+            }
+        }
+
+        text
+    }
+
+    fn redact_in_place(&self, text: &mut String) {
+        if let Cow::Owned(redacted) = self.redact(text) {
+            *text = redacted;
+        }
+    }
+
+    /// Does this redactor have any secret values to scrub?
+    pub fn has_secrets(&self) -> bool {
+        !self.secrets.is_empty()
+    }
+
+    /// Start scrubbing secrets from a stream of output (e.g. a test case's
+    /// stdout) before it is written anywhere.
+    pub fn stream(&self) -> RedactStream {
+        RedactStream {
+            secrets: self.secrets.clone(),
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn redact_command(&self, command: &mut CommandLine) {
+        for arg in &mut command.args {
+            self.redact_in_place(arg);
+        }
+        for (name, value) in &mut command.env {
+            if self.is_secret(name) {
+                *value = REDACTED.to_string();
+            } else {
+                self.redact_in_place(value);
+            }
+        }
+    }
+
+    pub fn redact_report(&self, report: &mut Report) {
+        if let Some(command) = &mut report.command {
+            self.redact_command(command);
+        }
+
+        if let Outcome::FetchFailed { error }
+        | Outcome::SetupFailed { error, .. }
+        | Outcome::SpawnFailed { error, .. } = &mut report.outcome
+        {
+            self.redact_in_place(&mut error.error);
+            self.redact_in_place(&mut error.detailed_error);
+            error
+                .causes
+                .iter_mut()
+                .for_each(|cause| self.redact_in_place(cause));
+        }
+
+        for analysis in &mut report.analyses {
+            if let Some(output) = &mut analysis.output {
+                self.redact_json(output);
+            }
+            if let Some(error) = &mut analysis.error {
+                self.redact_in_place(error);
+            }
+        }
+    }
+
+    fn redact_json(&self, value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::String(s) => self.redact_in_place(s),
+            serde_json::Value::Array(items) => {
+                items.iter_mut().for_each(|item| self.redact_json(item))
+            }
+            serde_json::Value::Object(fields) => {
+                fields.values_mut().for_each(|v| self.redact_json(v))
+            }
+            _ => {}
+        }
+    }
+
+    /// Get a copy of the experiment that is safe to save alongside its
+    /// results, with any secrets written directly into the config removed.
+    pub fn redact_experiment(&self, experiment: &Experiment) -> Experiment {
+        let mut experiment = experiment.clone();
+
+        self.redact_env(&mut experiment.env);
+        self.redact_env(&mut experiment.wasmer.env);
+        for overrides in experiment.overrides.values_mut() {
+            self.redact_env(&mut overrides.env);
+        }
+
+        for arg in experiment
+            .args
+            .iter_mut()
+            .chain(&mut experiment.wasmer.args)
+        {
+            if let Cow::Owned(redacted) = self.redact(arg.as_str()) {
+                *arg = TemplatedString::new(redacted);
+            }
+        }
+
+        experiment
+    }
+
+    fn redact_env(&self, env: &mut IndexMap<String, TemplatedString>) {
+        for (name, value) in env.iter_mut() {
+            // Values like "$WASMER_TOKEN" are only references to a secret
+            let is_reference = value.as_str().starts_with('$');
+
+            if self.is_secret(name) && !is_reference {
+                *value = TemplatedString::new(REDACTED);
+            } else if let Cow::Owned(redacted) = self.redact(value.as_str()) {
+                *value = TemplatedString::new(redacted);
+            }
+        }
+    }
+}
+
+/// Scrubs secrets from a stream of bytes as they arrive, holding back just
+/// enough to catch a secret that is split across two chunks.
+#[derive(Debug)]
+pub(crate) struct RedactStream {
+    /// Longest first, like [`Redactor::secrets`].
+    secrets: Vec<String>,
+    pending: Vec<u8>,
+}
+
+impl RedactStream {
+    /// Returns the part of `data` (plus anything held back previously) which
+    /// is safe to write.
+    pub fn push(&mut self, data: &[u8]) -> Vec<u8> {
+        self.pending.extend_from_slice(data);
+        self.scrub(false)
+    }
+
+    /// Everything that was held back, once the stream has ended.
+    pub fn finish(mut self) -> Vec<u8> {
+        self.scrub(true)
+    }
+
+    fn scrub(&mut self, last: bool) -> Vec<u8> {
+        let longest = self.secrets.first().map_or(0, String::len);
+        let mut scrubbed = Vec::with_capacity(self.pending.len());
+        let mut i = 0;
+
+        while i < self.pending.len() {
+            let rest = &self.pending[i..];
+            if !last && rest.len() < longest {
+                // A secret might start here, but we won't know until we
+                // get more data
+                break;
+            }
+
+            match self.secrets.iter().find(|s| rest.starts_with(s.as_bytes())) {
+                Some(secret) => {
+                    scrubbed.extend_from_slice(REDACTED.as_bytes());
+                    i += secret.len();
+                }
+                None => {
+                    scrubbed.push(rest[0]);
+                    i += 1;
+                }
+            }
+        }
+
+        self.pending.drain(..i);
+        scrubbed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn secrets_are_scrubbed_everywhere() {
+        let experiment: Experiment = serde_json::from_value(serde_json::json!({
+            "package": "wasmer/python",
+            "env": {
+                "API_KEY": "literal-key",
+                "GITHUB_TOKEN": "$GITHUB_TOKEN",
+                "GREETING": "hello",
+            },
+            "redact": ["DATABASE_URL"],
+        }))
+        .unwrap();
+        let redactor = Redactor::new(&experiment, ["registry-token".to_string(), "abc".into()])
+            .with_env([
+                ("DATABASE_URL", OsStr::new("postgres://db")),
+                ("HOME", OsStr::new("/home/me")),
+            ]);
+
+        assert_eq!(
+            redactor.redact("token=registry-token url=postgres://db home=/home/me abc"),
+            "token=<redacted> url=<redacted> home=/home/me abc"
+        );
+
+        let redacted = redactor.redact_experiment(&experiment);
+        assert_eq!(redacted.env["API_KEY"].as_str(), REDACTED);
+        assert_eq!(redacted.env["GITHUB_TOKEN"].as_str(), "$GITHUB_TOKEN");
+        assert_eq!(redacted.env["GREETING"].as_str(), "hello");
+
+        let mut stream = redactor.stream();
+        let mut stdout = Vec::new();
+        for chunk in [&b"connecting to post"[..], b"gres:/", b"/db\n\xff"] {
+            stdout.extend(stream.push(chunk));
+        }
+        stdout.extend(stream.finish());
+        assert_eq!(stdout, b"connecting to <redacted>\n\xff");
+    }
+}
@@ -0,0 +1,147 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error};
+use reqwest::Client;
+
+use crate::{
+    config::ModuleCache,
+    experiment::{
+        cache::{self, RegistryAuth},
+        cpu::Scheduling,
+        local,
+        runner::{self, Invocation, WasmerCli},
+        storage::{Assets, FileSystemStorage},
+        CommandLine, Report, Results, TestCase,
+    },
+};
+
+/// A test case from a previous run whose working directory has been
+/// re-created so it can be investigated by hand.
+#[derive(Debug, Clone)]
+pub struct Reproduction {
+    invocation: Invocation,
+}
+
+impl Reproduction {
+    /// Re-create the working directory for one of the [`Report`]s in an
+    /// experiment's [`Results`], downloading the package again if it is no
+    /// longer in the cache.
+    ///
+    /// The `registry` hostname is used when the test case's `test_case.json`
+    /// has been deleted, and to scope the `token` (if any) so it is only sent
+    /// when downloading from the registry.
+    pub async fn prepare(
+        client: &Client,
+        cache_dir: &Path,
+        wasmer: &Path,
+        results: &Results,
+        report: &Report,
+        registry: &str,
+        token: Option<&str>,
+    ) -> Result<Self, Error> {
+        let base_dir = match report.outcome.base_dir() {
+            Some(dir) => dir.to_path_buf(),
+            None => default_base_dir(results, report),
+        };
+
+        let test_case = match std::fs::read_to_string(base_dir.join("test_case.json")) {
+            Ok(json) => serde_json::from_str(&json).with_context(|| {
+                format!(
+                    "Unable to parse \"{}\"",
+                    base_dir.join("test_case.json").display()
+                )
+            })?,
+            Err(_) => test_case(report, registry),
+        };
+
+        let experiment = runner::settings(&results.experiment, &test_case);
+        let assets = if test_case.registry == local::LOCAL_REGISTRY {
+            local::assets(&test_case)?
+        } else if runner::runs_by_name(&experiment, &test_case) {
+            // wasmer will fetch the package itself
+            Assets::default()
+        } else {
+            cache::check_layout(cache_dir)?;
+            let auth = token
+                .map(|token| RegistryAuth::new(registry, token))
+                .transpose()
+                .context("The registry token can't be used in a HTTP header")?;
+            cache::prepare_assets(
+                client,
+                cache_dir,
+                &FileSystemStorage::new(cache_dir),
+                &[],
+                auth.as_ref(),
+                &test_case,
+                |_| {},
+            )
+            .await?
+        };
+
+        let module_cache = match experiment.wasmer.module_cache {
+            ModuleCache::Default => None,
+            ModuleCache::Shared | ModuleCache::Warm => {
+                Some(results.experiment_dir.join("module-cache"))
+            }
+            ModuleCache::ColdAndWarm => Some(runner::test_case_dir(
+                &results.experiment_dir.join("module-cache"),
+                &test_case,
+            )),
+        };
+        let dirs = directories::BaseDirs::new().context("Unable to find the home directory")?;
+
+        let cli = WasmerCli {
+            program: wasmer,
+            module_cache: module_cache.as_deref(),
+            coredumps: None,
+            tee_logs: false,
+            scheduling: &Scheduling::default(),
+        };
+        let invocation = runner::setup(
+            &experiment,
+            cli,
+            &test_case,
+            &assets,
+            &base_dir,
+            dirs.home_dir(),
+        )
+        .await?;
+
+        Ok(Reproduction { invocation })
+    }
+
+    /// The command that will be run, with any secrets redacted.
+    pub fn command_line(&self) -> CommandLine {
+        self.invocation.command_line()
+    }
+
+    /// Create a [`std::process::Command`] which runs the test case with
+    /// stdin, stdout, and stderr attached to the current process.
+    pub fn command(&self) -> std::process::Command {
+        self.invocation.interactive_command()
+    }
+}
+
+/// Where the runner would have put a test case's working directory.
+fn default_base_dir(results: &Results, report: &Report) -> PathBuf {
+    results
+        .experiment_dir
+        .join("experiments")
+        .join(&report.display_name)
+        .join(report.version())
+}
+
+fn test_case(report: &Report, registry: &str) -> TestCase {
+    let (namespace, package_name) = report
+        .display_name
+        .split_once('/')
+        .unwrap_or(("_", report.display_name.as_str()));
+
+    TestCase {
+        registry: registry.to_string(),
+        namespace: namespace.to_string(),
+        package_name: package_name.to_string(),
+        package_version: report.package_version.clone(),
+        variant: None,
+    }
+}
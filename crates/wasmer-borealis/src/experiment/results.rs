@@ -1,49 +1,768 @@
-use std::{path::PathBuf, time::Duration};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use anyhow::Error;
+use anyhow::{Context, Error};
+use chrono::{DateTime, Utc};
 
-use crate::{config::Experiment, registry::queries::PackageVersion};
+use crate::{
+    config::Experiment,
+    experiment::{BenchmarkStats, ModuleInfo},
+    registry::queries::PackageVersion,
+};
 
+/// The results of running an [`Experiment`], as saved to `results.json`.
+///
+/// The serialized form of this type (and everything it contains) is described
+/// by the `results.schema.json` file in the repository. Fields may be added in
+/// the future, but existing fields won't be renamed or removed.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
 pub struct Results {
+    /// The experiment that was run.
     pub experiment: Experiment,
+    /// A report for each test case.
     pub reports: Vec<Report>,
+    /// How long the entire experiment took.
     pub total_time: Duration,
+    /// The directory all experiment-related files were written to.
     pub experiment_dir: PathBuf,
+    /// How effective the package cache was.
+    #[serde(default)]
+    pub cache: CacheStats,
 }
 
+impl Results {
+    /// Load a `results.json` file from disk.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let raw = std::fs::read_to_string(path)
+            .with_context(|| format!("Unable to read \"{}\"", path.display()))?;
+        let results = serde_json::from_str(&raw)
+            .with_context(|| format!("Unable to deserialize \"{}\"", path.display()))?;
+
+        Ok(results)
+    }
+
+    /// Save these results to disk as JSON.
+    ///
+    /// The results are written to a temporary file which then replaces
+    /// `path`, so an interrupted save never leaves a truncated file behind.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(self)?;
+        let dir = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        let mut temp = tempfile::NamedTempFile::new_in(dir).with_context(|| {
+            format!("Unable to create a temporary file in \"{}\"", dir.display())
+        })?;
+        temp.write_all(json.as_bytes())
+            .with_context(|| format!("Unable to save to \"{}\"", path.display()))?;
+        temp.persist(path)
+            .with_context(|| format!("Unable to save to \"{}\"", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Point these results at a copy of the experiment directory (e.g. one
+    /// unpacked from a bundle on another machine), so each test case's logs
+    /// can still be found.
+    pub fn relocate(&mut self, experiment_dir: impl Into<PathBuf>) {
+        let experiment_dir = experiment_dir.into();
+
+        for report in &mut self.reports {
+            if let Some(base_dir) = report.outcome.base_dir_mut() {
+                if let Ok(relative) = base_dir.strip_prefix(&self.experiment_dir) {
+                    *base_dir = experiment_dir.join(relative);
+                }
+            }
+        }
+
+        self.experiment_dir = experiment_dir;
+    }
+
+    /// All reports that ran to completion and met the experiment's
+    /// expectations.
+    pub fn successes(&self) -> impl Iterator<Item = &Report> + '_ {
+        self.in_category(Category::Success)
+    }
+
+    /// All reports where the package ran, but didn't meet the experiment's
+    /// expectations.
+    pub fn failures(&self) -> impl Iterator<Item = &Report> + '_ {
+        self.in_category(Category::Failure)
+    }
+
+    /// All reports where something went wrong with borealis itself (e.g. a
+    /// package couldn't be downloaded).
+    pub fn bugs(&self) -> impl Iterator<Item = &Report> + '_ {
+        self.in_category(Category::Bug)
+    }
+
+    /// All reports whose exit code or output changed when they were run a
+    /// second time.
+    pub fn nondeterministic(&self) -> impl Iterator<Item = &Report> + '_ {
+        self.reports.iter().filter(|r| r.is_nondeterministic())
+    }
+
+    /// All reports which passed some, but not all, of their repetitions.
+    pub fn flaky(&self) -> impl Iterator<Item = &Report> + '_ {
+        self.reports.iter().filter(|r| r.is_flaky())
+    }
+
+    /// The median cold and warm start times across every test case that was
+    /// run in `cold-and-warm` mode.
+    pub fn median_startup(&self) -> Option<StartupTimes> {
+        StartupTimes::median(self.reports.iter().filter_map(|r| r.startup))
+    }
+
+    fn in_category(&self, category: Category) -> impl Iterator<Item = &Report> + '_ {
+        self.reports
+            .iter()
+            .filter(move |r| r.category() == category)
+    }
+
+    /// The fraction of reports (between `0.0` and `1.0`) that were successful.
+    ///
+    /// An empty set of results has a success rate of `0.0`.
+    pub fn success_rate(&self) -> f64 {
+        if self.reports.is_empty() {
+            return 0.0;
+        }
+
+        self.successes().count() as f64 / self.reports.len() as f64
+    }
+
+    /// Group the reports by the namespace that owns each package.
+    pub fn by_namespace(&self) -> BTreeMap<&str, Vec<&Report>> {
+        let mut namespaces: BTreeMap<&str, Vec<&Report>> = BTreeMap::new();
+
+        for report in &self.reports {
+            namespaces
+                .entry(report.namespace())
+                .or_default()
+                .push(report);
+        }
+
+        namespaces
+    }
+
+    /// Find every report for a package (e.g. `"wasmer/python"`).
+    pub fn find<'a>(&'a self, package: &'a str) -> impl Iterator<Item = &'a Report> + 'a {
+        self.reports
+            .iter()
+            .filter(move |r| r.display_name == package)
+    }
+
+    /// Get the report for a specific version of a package.
+    pub fn get(&self, package: &str, version: &str) -> Option<&Report> {
+        self.reports
+            .iter()
+            .find(|r| r.display_name == package && r.version() == version)
+    }
+
+    /// Merge the [`Results`] from several runs of the same experiment (e.g.
+    /// each [`crate::experiment::Shard`]) into one.
+    ///
+    /// The total time is the longest of the individual runs because shards are
+    /// typically executed in parallel.
+    pub fn merge(results: impl IntoIterator<Item = Results>) -> Result<Results, Error> {
+        let mut results = results.into_iter();
+        let mut merged = results
+            .next()
+            .context("At least one set of results is required")?;
+
+        for other in results {
+            anyhow::ensure!(
+                other.experiment == merged.experiment,
+                "Unable to merge results from different experiments"
+            );
+
+            merged.reports.extend(other.reports);
+            merged.total_time = merged.total_time.max(other.total_time);
+            merged.cache.merge(other.cache);
+        }
+
+        Ok(merged)
+    }
+}
+
+/// Statistics about the package cache, collected while an experiment runs.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct CacheStats {
+    /// The number of test cases whose files were already cached.
+    pub hits: usize,
+    /// The number of test cases whose files needed to be downloaded.
+    pub misses: usize,
+    /// The total number of bytes downloaded.
+    pub bytes_downloaded: u64,
+    /// The total time spent downloading.
+    pub download_time: Duration,
+    /// The largest downloads, biggest first.
+    pub largest_downloads: Vec<Download>,
+}
+
+impl CacheStats {
+    /// The number of entries kept in [`CacheStats::largest_downloads`].
+    pub const MAX_LARGEST_DOWNLOADS: usize = 5;
+
+    #[cfg(feature = "run")]
+    pub(crate) fn record_hit(&mut self) {
+        self.hits += 1;
+    }
+
+    #[cfg(feature = "run")]
+    pub(crate) fn record_miss(&mut self, download: Download, duration: Duration) {
+        self.misses += 1;
+        self.bytes_downloaded += download.bytes;
+        self.download_time += duration;
+        self.add_largest([download]);
+    }
+
+    /// Combine the statistics from another run.
+    pub fn merge(&mut self, other: CacheStats) {
+        let CacheStats {
+            hits,
+            misses,
+            bytes_downloaded,
+            download_time,
+            largest_downloads,
+        } = other;
+
+        self.hits += hits;
+        self.misses += misses;
+        self.bytes_downloaded += bytes_downloaded;
+        self.download_time += download_time;
+        self.add_largest(largest_downloads);
+    }
+
+    fn add_largest(&mut self, downloads: impl IntoIterator<Item = Download>) {
+        self.largest_downloads.extend(downloads);
+        self.largest_downloads.sort_by(|a, b| b.bytes.cmp(&a.bytes));
+        self.largest_downloads
+            .truncate(CacheStats::MAX_LARGEST_DOWNLOADS);
+    }
+}
+
+/// A package version that was downloaded.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct Download {
+    /// The package's name (e.g. `wasmer/python`).
+    pub display_name: String,
+    /// The version that was downloaded.
+    pub version: String,
+    /// The number of bytes downloaded.
+    pub bytes: u64,
+}
+
+/// The result of running a single test case.
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
 pub struct Report {
+    /// The package's name (e.g. `wasmer/python`).
     pub display_name: String,
+    /// The version of the package that was tested.
+    #[serde(deserialize_with = "crate::registry::deserialize_package_version")]
     pub package_version: PackageVersion,
+    /// What happened when the test case was run.
     pub outcome: Outcome,
+    /// The findings from each of the experiment's analyzers.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub analyses: Vec<Analysis>,
+    /// A human's verdict on this report, recorded while triaging.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotation: Option<Annotation>,
+    /// Exactly how the `wasmer` CLI was invoked (if the test case got that
+    /// far).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub command: Option<CommandLine>,
+    /// Run time statistics, if the experiment was run in benchmark mode.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub benchmark: Option<BenchmarkStats>,
+    /// How long the test case took with an empty and a populated module
+    /// cache, if the experiment used the `cold-and-warm` module cache.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub startup: Option<StartupTimes>,
+    /// How often the test case passed, if it was run more than once.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repetitions: Option<Repetitions>,
+    /// The SHA-256 hash of everything the package wrote to stdout, if it ran
+    /// to completion.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_digest: Option<String>,
+    /// What happened when the test case was run a second time, if the
+    /// experiment checks for non-determinism.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rerun: Option<Rerun>,
+    /// Core dumps written when the test case crashed, relative to its
+    /// working directory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub coredumps: Vec<PathBuf>,
+    /// When the test case was downloaded and run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeline: Option<Timeline>,
+    /// The WebAssembly modules found in the package's tarball.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub modules: Vec<ModuleInfo>,
+    /// Why some (or all) of the package's modules couldn't be inspected
+    /// (e.g. the package only has a `*.webc` file).
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub module_errors: Vec<String>,
+    /// The test case's logs were emptied because secrets couldn't be scrubbed
+    /// from all of its output.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub logs_discarded: bool,
+}
+
+impl Report {
+    /// The namespace (or user) that owns this package.
+    pub fn namespace(&self) -> &str {
+        self.display_name
+            .split_once('/')
+            .map_or(self.display_name.as_str(), |(ns, _)| ns)
+    }
+
+    pub fn version(&self) -> &str {
+        &self.package_version.version
+    }
+
+    /// The combined size of the package version's artifacts in bytes, as
+    /// reported by the registry.
+    pub fn artifact_size(&self) -> Option<u64> {
+        self.package_version.distribution.total_size()
+    }
+
+    /// When the package version was published.
+    pub fn published_at(&self) -> Option<DateTime<Utc>> {
+        self.package_version.created_at
+    }
+
+    /// Is this report a success, failure, or bug?
+    pub fn category(&self) -> Category {
+        self.outcome.category()
+    }
+
+    /// The names of everything imported by the package's modules (e.g.
+    /// `fd_write` or `sock_open`).
+    pub fn imports(&self) -> BTreeSet<&str> {
+        self.modules
+            .iter()
+            .flat_map(|m| m.imports.values().flatten())
+            .map(|name| name.as_str())
+            .collect()
+    }
+
+    /// Did this test case exit the same way as the `other` report, but write
+    /// something different to stdout?
+    ///
+    /// This catches silent behavioural changes (e.g. between two versions of
+    /// `wasmer`) that wouldn't show up as a change in outcome.
+    pub fn output_diverges_from(&self, other: &Report) -> bool {
+        let (
+            Outcome::Completed { status: a, .. },
+            Outcome::Completed { status: b, .. },
+        ) = (&self.outcome, &other.outcome)
+        else {
+            return false;
+        };
+
+        match (&self.stdout_digest, &other.stdout_digest) {
+            (Some(x), Some(y)) => a.code == b.code && x != y,
+            _ => false,
+        }
+    }
+
+    /// Did running the test case a second time give a different exit code or
+    /// output?
+    pub fn is_nondeterministic(&self) -> bool {
+        let Some(rerun) = &self.rerun else {
+            return false;
+        };
+
+        match &self.outcome {
+            Outcome::Completed { status, .. } => {
+                // Discarded logs have nothing to compare
+                let compare_output = !self.logs_discarded && !rerun.logs_discarded;
+                rerun.status.map(|s| s.code) != Some(status.code)
+                    || (compare_output && rerun.stdout_digest != self.stdout_digest)
+            }
+            Outcome::TimedOut { .. } => rerun.status.is_some(),
+            _ => false,
+        }
+    }
+
+    /// Did this test case pass some of its repetitions, but not all of them?
+    pub fn is_flaky(&self) -> bool {
+        self.repetitions.map_or(false, |r| r.is_flaky())
+    }
+}
+
+/// The fully resolved command used to run a test case, after templates were
+/// expanded and with any secrets redacted.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct CommandLine {
+    /// The program that was run.
+    pub program: PathBuf,
+    /// The arguments passed to the program.
+    pub args: Vec<String>,
+    /// Every environment variable that was set. Nothing else is inherited
+    /// from the host.
+    pub env: BTreeMap<String, String>,
+    /// The directory the program was run from.
+    pub current_dir: PathBuf,
+}
+
+impl CommandLine {
+    /// Render the command so it can be pasted into a POSIX shell.
+    pub fn shell(&self) -> String {
+        let mut command = format!(
+            "cd {} && env -i",
+            shell_quote(&self.current_dir.to_string_lossy())
+        );
+
+        for (name, value) in &self.env {
+            command.push_str(&format!(" {name}={}", shell_quote(value)));
+        }
+
+        command.push(' ');
+        command.push_str(&shell_quote(&self.program.to_string_lossy()));
+
+        for arg in &self.args {
+            command.push(' ');
+            command.push_str(&shell_quote(arg));
+        }
+
+        command
+    }
+}
+
+fn shell_quote(s: &str) -> Cow<'_, str> {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "-_./=:,+@%".contains(c);
+
+    if !s.is_empty() && s.chars().all(is_safe) {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(format!("'{}'", s.replace('\'', r"'\''")))
+    }
+}
+
+/// A note attached to a [`Report`] while triaging the results.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct Annotation {
+    /// What caused the failure.
+    pub disposition: Disposition,
+    /// Free-form notes (e.g. a link to the relevant issue).
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+/// The cause of a failing [`Report`], as decided by a human.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum Disposition {
+    /// The failure is already being tracked somewhere.
+    KnownIssue,
+    /// The package itself is broken.
+    PackageBug,
+    /// The failure was caused by a bug in Wasmer.
+    WasmerBug,
+    /// The failure isn't interesting.
+    Ignore,
+}
+
+impl Disposition {
+    /// A human-friendly description of this disposition.
+    pub fn description(self) -> &'static str {
+        match self {
+            Disposition::KnownIssue => "known issue",
+            Disposition::PackageBug => "package bug",
+            Disposition::WasmerBug => "wasmer bug",
+            Disposition::Ignore => "ignore",
+        }
+    }
 }
 
+/// The result of running a [`crate::config::Analyzer`] against a test case.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct Analysis {
+    /// The analyzer's name.
+    pub name: String,
+    /// The analyzer's exit code, if it ran to completion.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>,
+    /// The JSON the analyzer wrote to stdout, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<serde_json::Value>,
+    /// Why the analyzer couldn't be run or its output couldn't be parsed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A broad classification of an [`Outcome`].
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, serde::Serialize, serde::Deserialize,
+)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum Category {
+    /// The package ran and met the experiment's expectations.
+    Success,
+    /// The package ran, but didn't meet the experiment's expectations.
+    Failure,
+    /// Something went wrong before the package could be run.
+    Bug,
+}
+
+/// How long a test case took to run before and after its modules were
+/// compiled and cached.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct StartupTimes {
+    /// The run time with an empty module cache.
+    pub cold: Duration,
+    /// The run time once the module cache was populated.
+    pub warm: Duration,
+}
+
+impl StartupTimes {
+    /// The median cold and warm start times.
+    pub(crate) fn median(samples: impl IntoIterator<Item = StartupTimes>) -> Option<Self> {
+        let (cold, warm): (Vec<_>, Vec<_>) = samples.into_iter().map(|s| (s.cold, s.warm)).unzip();
+
+        Some(StartupTimes {
+            cold: BenchmarkStats::from_samples(cold)?.median,
+            warm: BenchmarkStats::from_samples(warm)?.median,
+        })
+    }
+
+    /// How many times faster the warm run was than the cold one.
+    pub fn speedup(&self) -> f64 {
+        if self.warm.is_zero() {
+            return 0.0;
+        }
+
+        self.cold.as_secs_f64() / self.warm.as_secs_f64()
+    }
+}
+
+/// How often a test case passed when it was run multiple times.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct Repetitions {
+    /// The number of times the test case was run.
+    pub runs: usize,
+    /// The number of runs which met the experiment's expectations.
+    pub passed: usize,
+}
+
+impl Repetitions {
+    /// The fraction of runs (between `0.0` and `1.0`) that passed.
+    pub fn pass_rate(&self) -> f64 {
+        if self.runs == 0 {
+            return 0.0;
+        }
+
+        self.passed as f64 / self.runs as f64
+    }
+
+    /// Did the test case pass sometimes, but not always?
+    pub fn is_flaky(&self) -> bool {
+        self.passed > 0 && self.passed < self.runs
+    }
+}
+
+/// The result of running a test case a second time under the same
+/// conditions.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct Rerun {
+    /// How the second run exited, or `None` if it timed out or couldn't be
+    /// started.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<ExitStatus>,
+    /// The SHA-256 hash of everything the second run wrote to stdout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stdout_digest: Option<String>,
+    /// The second run's logs were emptied because secrets couldn't be
+    /// scrubbed from all of its output.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub logs_discarded: bool,
+}
+
+/// When each phase of a test case happened, in wall-clock time.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct Timeline {
+    /// Fetching the package's assets (from the cache or the registry).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub download: Option<Phase>,
+    /// Setting up, running, and analyzing the test case, starting once a
+    /// runner slot became free.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub execution: Option<Phase>,
+}
+
+impl Timeline {
+    /// When work on the test case started.
+    pub fn start(&self) -> Option<DateTime<Utc>> {
+        self.download.or(self.execution).map(|p| p.start)
+    }
+
+    /// When work on the test case finished.
+    pub fn end(&self) -> Option<DateTime<Utc>> {
+        self.execution.or(self.download).map(|p| p.end)
+    }
+}
+
+/// A span of wall-clock time.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
+pub struct Phase {
+    #[cfg_attr(test, schemars(with = "String"))]
+    pub start: DateTime<Utc>,
+    #[cfg_attr(test, schemars(with = "String"))]
+    pub end: DateTime<Utc>,
+}
+
+impl Phase {
+    /// A phase that started at `start` and has just finished.
+    #[cfg(feature = "run")]
+    pub(crate) fn since(start: DateTime<Utc>) -> Phase {
+        Phase {
+            start,
+            end: Utc::now(),
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        (self.end - self.start).to_std().unwrap_or_default()
+    }
+}
+
+/// What happened when a test case was run.
+///
+/// This is serialized as an object with an `"outcome"` field containing the
+/// variant's name in kebab-case (e.g. `"fetch-failed"`).
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
 #[serde(tag = "outcome", rename_all = "kebab-case")]
 pub enum Outcome {
+    /// The package ran to completion.
     Completed {
+        /// How the process exited.
         status: ExitStatus,
+        /// How long the process ran for.
         run_time: Duration,
+        /// The test case's working directory.
         base_dir: PathBuf,
     },
-    FetchFailed {
-        error: SerializableError,
+    /// The package was killed because it ran for too long.
+    TimedOut {
+        /// The test case's working directory.
+        base_dir: PathBuf,
+        /// The timeout the package was run with.
+        timeout: Duration,
+        /// How long the package ran for before it was killed.
+        #[serde(default)]
+        run_time: Duration,
     },
+    /// The package was killed because it wrote more than the experiment's
+    /// output limit.
+    OutputLimitExceeded {
+        /// The test case's working directory.
+        base_dir: PathBuf,
+        /// The most bytes that could be written to stdout or stderr.
+        limit: u64,
+        /// How long the package ran for before it was killed.
+        run_time: Duration,
+    },
+    /// The package's files couldn't be downloaded.
+    FetchFailed { error: SerializableError },
+    /// The test case's working directory couldn't be prepared.
     SetupFailed {
+        /// The test case's working directory.
         base_dir: PathBuf,
         error: SerializableError,
     },
+    /// The `wasmer` CLI couldn't be started.
     SpawnFailed {
+        /// The test case's working directory.
         base_dir: PathBuf,
         error: SerializableError,
     },
 }
 
+impl Outcome {
+    /// The test case's working directory, if one was created.
+    pub fn base_dir(&self) -> Option<&Path> {
+        match self {
+            Outcome::Completed { base_dir, .. }
+            | Outcome::TimedOut { base_dir, .. }
+            | Outcome::OutputLimitExceeded { base_dir, .. }
+            | Outcome::SetupFailed { base_dir, .. }
+            | Outcome::SpawnFailed { base_dir, .. } => Some(base_dir),
+            Outcome::FetchFailed { .. } => None,
+        }
+    }
+
+    fn base_dir_mut(&mut self) -> Option<&mut PathBuf> {
+        match self {
+            Outcome::Completed { base_dir, .. }
+            | Outcome::TimedOut { base_dir, .. }
+            | Outcome::OutputLimitExceeded { base_dir, .. }
+            | Outcome::SetupFailed { base_dir, .. }
+            | Outcome::SpawnFailed { base_dir, .. } => Some(base_dir),
+            Outcome::FetchFailed { .. } => None,
+        }
+    }
+
+    /// The error that stopped the test case from running, if any.
+    pub fn error(&self) -> Option<&SerializableError> {
+        match self {
+            Outcome::FetchFailed { error }
+            | Outcome::SetupFailed { error, .. }
+            | Outcome::SpawnFailed { error, .. } => Some(error),
+            Outcome::Completed { .. }
+            | Outcome::TimedOut { .. }
+            | Outcome::OutputLimitExceeded { .. } => None,
+        }
+    }
+
+    pub fn category(&self) -> Category {
+        match self {
+            Outcome::Completed { status, .. } if status.success => Category::Success,
+            Outcome::Completed { .. }
+            | Outcome::TimedOut { .. }
+            | Outcome::OutputLimitExceeded { .. } => Category::Failure,
+            Outcome::FetchFailed { .. }
+            | Outcome::SetupFailed { .. }
+            | Outcome::SpawnFailed { .. } => Category::Bug,
+        }
+    }
+}
+
+/// An error that has been converted to a serializable form.
 #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
 pub struct SerializableError {
+    /// The top-level error message.
     pub error: String,
+    /// The error's full debug representation, including any backtraces.
     pub detailed_error: String,
+    /// The messages for each error in the chain of causes.
     pub causes: Vec<String>,
 }
 
@@ -67,9 +786,13 @@ impl From<Error> for SerializableError {
     }
 }
 
+/// How a process exited.
 #[derive(Debug, Copy, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[cfg_attr(test, derive(schemars::JsonSchema))]
 pub struct ExitStatus {
+    /// Did the process meet the experiment's expectations?
     pub success: bool,
+    /// The exit code (`1` if the process was killed by a signal).
     pub code: i32,
 }
 
@@ -81,3 +804,257 @@ impl From<std::process::ExitStatus> for ExitStatus {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        completed, ensure_file_contents, fetch_failed, project_root, report, results,
+    };
+
+    fn owned_by(display_name: &str, version: &str) -> Report {
+        let name = display_name.rsplit('/').next().unwrap();
+        Report {
+            display_name: display_name.to_string(),
+            ..report(name, version, completed(true, 1))
+        }
+    }
+
+    fn names<'a>(reports: impl IntoIterator<Item = &'a Report>) -> Vec<String> {
+        reports
+            .into_iter()
+            .map(|r| format!("{}@{}", r.display_name, r.version()))
+            .collect()
+    }
+
+    #[test]
+    fn reports_are_grouped_by_namespace() {
+        let results = results(vec![
+            owned_by("wasmer/python", "1.0.0"),
+            owned_by("syrusakbary/cowsay", "0.1.0"),
+            owned_by("wasmer/python", "2.0.0"),
+            owned_by("wasmer/qjs", "1.0.0"),
+            owned_by("no-namespace", "1.0.0"),
+        ]);
+
+        let namespaces: BTreeMap<_, _> = results
+            .by_namespace()
+            .into_iter()
+            .map(|(namespace, reports)| (namespace, names(reports)))
+            .collect();
+
+        assert_eq!(
+            namespaces,
+            BTreeMap::from([
+                ("no-namespace", vec!["no-namespace@1.0.0".to_string()]),
+                ("syrusakbary", vec!["syrusakbary/cowsay@0.1.0".to_string()]),
+                (
+                    "wasmer",
+                    vec![
+                        "wasmer/python@1.0.0".to_string(),
+                        "wasmer/python@2.0.0".to_string(),
+                        "wasmer/qjs@1.0.0".to_string(),
+                    ]
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn find_every_version_of_a_package() {
+        let results = results(vec![
+            owned_by("wasmer/python", "1.0.0"),
+            owned_by("wasmer/python-3", "1.0.0"),
+            report("python", "2.0.0", fetch_failed()),
+            owned_by("syrusakbary/python", "1.0.0"),
+        ]);
+
+        assert_eq!(
+            names(results.find("wasmer/python")),
+            ["wasmer/python@1.0.0", "wasmer/python@2.0.0"]
+        );
+        assert_eq!(results.find("python").count(), 0);
+        assert_eq!(results.find("wasmer/missing").count(), 0);
+    }
+
+    #[test]
+    fn saving_replaces_the_previous_results() {
+        let temp = tempfile::tempdir().unwrap();
+        let path = temp.path().join("results.json");
+        let mut results = results(vec![report("python", "1.0.0", fetch_failed())]);
+        results.save(&path).unwrap();
+
+        results
+            .reports
+            .push(report("qjs", "1.0.0", completed(true, 1)));
+        results.save(&path).unwrap();
+
+        assert_eq!(Results::load(&path).unwrap().reports.len(), 2);
+        let files: Vec<_> = std::fs::read_dir(temp.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(files, ["results.json"]);
+    }
+
+    #[test]
+    fn commands_can_be_pasted_into_a_shell() {
+        let command = CommandLine {
+            program: "/usr/bin/wasmer".into(),
+            args: vec![
+                "run".to_string(),
+                "wasmer/python".to_string(),
+                "--".to_string(),
+                "-c".to_string(),
+                "print('Hello, World!')".to_string(),
+            ],
+            env: [("PATH".to_string(), "/usr/bin:/bin".to_string())].into(),
+            current_dir: "/tmp/python".into(),
+        };
+
+        assert_eq!(
+            command.shell(),
+            r#"cd /tmp/python && env -i PATH=/usr/bin:/bin /usr/bin/wasmer run wasmer/python -- -c 'print('\''Hello, World!'\'')'"#
+        );
+    }
+
+    #[test]
+    fn only_some_passing_repetitions_are_flaky() {
+        let flaky = Repetitions {
+            runs: 10,
+            passed: 7,
+        };
+        assert!(flaky.is_flaky());
+        assert_eq!(flaky.pass_rate(), 0.7);
+
+        assert!(!Repetitions {
+            runs: 10,
+            passed: 10
+        }
+        .is_flaky());
+        assert!(!Repetitions {
+            runs: 10,
+            passed: 0
+        }
+        .is_flaky());
+    }
+
+    #[test]
+    fn warm_starts_are_compared_to_cold_starts() {
+        let startup = StartupTimes {
+            cold: Duration::from_millis(1500),
+            warm: Duration::from_millis(300),
+        };
+        assert_eq!(startup.speedup(), 5.0);
+
+        let instant = StartupTimes {
+            cold: Duration::from_millis(1500),
+            warm: Duration::ZERO,
+        };
+        assert_eq!(instant.speedup(), 0.0);
+    }
+
+    #[cfg(feature = "run")]
+    #[test]
+    fn cache_stats_only_keep_the_largest_downloads() {
+        let mut stats = CacheStats::default();
+        stats.record_hit();
+
+        for bytes in 1..=10 {
+            let download = Download {
+                display_name: "wasmer/python".to_string(),
+                version: format!("0.{bytes}.0"),
+                bytes,
+            };
+            stats.record_miss(download, Duration::from_secs(1));
+        }
+
+        assert_eq!(stats.hits, 1);
+        assert_eq!(stats.misses, 10);
+        assert_eq!(stats.bytes_downloaded, 55);
+        assert_eq!(stats.download_time, Duration::from_secs(10));
+        let sizes: Vec<_> = stats.largest_downloads.iter().map(|d| d.bytes).collect();
+        assert_eq!(sizes, [10, 9, 8, 7, 6]);
+    }
+
+    #[test]
+    fn reports_without_publish_dates_can_be_loaded() {
+        let json = serde_json::json!({
+            "display_name": "wasmer/python",
+            "package_version": {
+                "id": "1",
+                "version": "1.0.0",
+                "distribution": {
+                    "downloadUrl": "https://example.com/python.tar.gz",
+                    "piritaDownloadUrl": null,
+                },
+            },
+            "outcome": {
+                "outcome": "fetch-failed",
+                "error": { "error": "", "detailed_error": "", "causes": [] },
+            },
+        });
+
+        let report: Report = serde_json::from_value(json).unwrap();
+
+        assert_eq!(report.package_version.created_at, None);
+    }
+
+    #[test]
+    fn reruns_with_different_output_are_nondeterministic() {
+        let json = serde_json::json!({
+            "display_name": "wasmer/python",
+            "package_version": {
+                "id": "1",
+                "version": "1.0.0",
+                "distribution": {
+                    "downloadUrl": "https://example.com/python.tar.gz",
+                    "piritaDownloadUrl": null,
+                },
+            },
+            "outcome": {
+                "outcome": "completed",
+                "base_dir": "/tmp/python",
+                "status": { "success": true, "code": 0 },
+                "run_time": { "secs": 1, "nanos": 0 },
+            },
+            "stdout_digest": "aaa",
+        });
+        let mut report: Report = serde_json::from_value(json).unwrap();
+        assert!(!report.is_nondeterministic());
+
+        let status = ExitStatus {
+            success: true,
+            code: 0,
+        };
+        report.rerun = Some(Rerun {
+            status: Some(status),
+            stdout_digest: Some("aaa".to_string()),
+            logs_discarded: false,
+        });
+        assert!(!report.is_nondeterministic());
+
+        report.rerun = Some(Rerun {
+            status: Some(status),
+            stdout_digest: Some("bbb".to_string()),
+            logs_discarded: false,
+        });
+        assert!(report.is_nondeterministic());
+
+        report.rerun = Some(Rerun {
+            status: None,
+            stdout_digest: Some("aaa".to_string()),
+            logs_discarded: false,
+        });
+        assert!(report.is_nondeterministic());
+    }
+
+    #[test]
+    fn results_schema_is_up_to_date() {
+        let dest = project_root().join("results.schema.json");
+        let schema = schemars::schema_for!(Results);
+        let schema = serde_json::to_string_pretty(&schema).unwrap();
+
+        ensure_file_contents(dest, schema);
+    }
+}
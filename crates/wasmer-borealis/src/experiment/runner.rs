@@ -1,18 +1,36 @@
 use std::{
+    borrow::Cow,
     collections::HashMap,
+    ffi::{OsStr, OsString},
+    future::Future,
     num::NonZeroUsize,
     path::{Path, PathBuf},
+    process::Stdio,
     sync::Arc,
-    time::Instant,
+    time::{Duration, Instant},
 };
 
-use actix::{Actor, Context, Handler};
 use anyhow::{Context as _, Error};
-use tokio::sync::Semaphore;
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use tokio::sync::{Notify, Semaphore};
 
 use crate::{
-    config::Experiment,
-    experiment::{cache::Assets, Outcome, Report, TestCase},
+    config::{Experiment, ModuleCache, WasmerDir},
+    experiment::{
+        analyzers,
+        capture::{self, Capture, Console},
+        coredumps::{self, Coredumps, TRAP_COREDUMP},
+        cpu::Scheduling,
+        local, logs,
+        process::{self, ProcessTree},
+        redact::{is_secret, Redactor, REDACTED},
+        results::ExitStatus,
+        storage::Assets,
+        BenchmarkStats, Category, CommandLine, Outcome, Phase, Repetitions, Report, Rerun,
+        StartupTimes, TestCase, Timeline,
+    },
+    registry::format_graphql,
 };
 
 #[derive(Debug, Clone)]
@@ -20,53 +38,130 @@ pub(crate) struct Runner {
     experiment: Arc<Experiment>,
     semaphore: Arc<Semaphore>,
     base_dir: PathBuf,
+    /// The `wasmer` CLI to run.
+    wasmer: PathBuf,
+    /// The module cache shared by every test case.
+    module_cache: PathBuf,
+    redactor: Redactor,
+    /// How core dumps are collected, if at all.
+    coredumps: Option<Coredumps>,
+    tee_logs: bool,
+    scheduling: Scheduling,
 }
 
 impl Runner {
-    pub(crate) fn new(experiment: Arc<Experiment>, base_dir: PathBuf) -> Self {
+    pub(crate) fn new(
+        experiment: Arc<Experiment>,
+        base_dir: PathBuf,
+        wasmer: PathBuf,
+        module_cache: PathBuf,
+        redactor: Redactor,
+        coredumps: Option<Coredumps>,
+        parallelism: NonZeroUsize,
+    ) -> Self {
         Runner {
             experiment,
             base_dir,
-            semaphore: Arc::new(Semaphore::new(
-                std::thread::available_parallelism()
-                    .unwrap_or(NonZeroUsize::new(4).unwrap())
-                    .get(),
-            )),
+            wasmer,
+            module_cache,
+            redactor,
+            coredumps,
+            tee_logs: false,
+            scheduling: Scheduling::default(),
+            semaphore: Arc::new(Semaphore::new(parallelism.get())),
         }
     }
-}
 
-impl Actor for Runner {
-    type Context = Context<Self>;
+    /// Echo each test case's output to the console, prefixed with the
+    /// package's name, as well as writing it to the logs.
+    pub(crate) fn with_tee_logs(self, tee_logs: bool) -> Self {
+        Runner { tee_logs, ..self }
+    }
+
+    /// Pin test cases to particular CPUs.
+    pub(crate) fn with_scheduling(self, scheduling: Scheduling) -> Self {
+        Runner { scheduling, ..self }
+    }
 }
 
-#[derive(Debug, Clone, actix::Message)]
-#[rtype(result = "Report")]
+#[derive(Debug, Clone)]
 pub(crate) struct BeginTest {
     pub test_case: TestCase,
     pub assets: Assets,
 }
 
-impl Handler<BeginTest> for Runner {
-    type Result = actix::ResponseFuture<Report>;
+impl Runner {
+    /// Will the `wasmer` CLI fetch this test case from the registry itself?
+    pub(crate) fn runs_by_name(&self, test_case: &TestCase) -> bool {
+        runs_by_name(&settings(&self.experiment, test_case), test_case)
+    }
 
-    fn handle(&mut self, msg: BeginTest, _ctx: &mut Self::Context) -> Self::Result {
+    /// Run a test case, waiting until fewer than `parallelism` test cases are
+    /// running.
+    pub(crate) async fn run(&self, msg: BeginTest) -> Report {
         let BeginTest { test_case, assets } = msg;
 
-        let base_dir = self
-            .base_dir
-            .join(&test_case.namespace)
-            .join(&test_case.package_name)
-            .join(test_case.version());
+        let base_dir = test_case_dir(&self.base_dir, &test_case);
+        let module_cache = match self.experiment.wasmer.module_cache {
+            ModuleCache::Default => None,
+            ModuleCache::Shared | ModuleCache::Warm => Some(self.module_cache.clone()),
+            ModuleCache::ColdAndWarm => Some(test_case_dir(&self.module_cache, &test_case)),
+        };
 
-        let experiment = self.experiment.clone();
-        let semaphore = self.semaphore.clone();
+        let _guard = self.semaphore.acquire().await.unwrap();
+        let start = Utc::now();
+        let cli = WasmerCli {
+            program: &self.wasmer,
+            module_cache: module_cache.as_deref(),
+            coredumps: self.coredumps,
+            tee_logs: self.tee_logs,
+            scheduling: &self.scheduling,
+        };
+        let mut report = run_experiment(
+            &self.experiment,
+            cli,
+            &test_case,
+            &assets,
+            base_dir,
+            &self.redactor,
+        )
+        .await;
+        report
+            .timeline
+            .get_or_insert_with(Timeline::default)
+            .execution = Some(Phase::since(start));
+        report
+    }
+}
 
-        Box::pin(async move {
-            let _guard = semaphore.acquire().await.unwrap();
-            run_experiment(&experiment, &test_case, &assets, base_dir).await
-        })
+/// How the `wasmer` CLI is invoked for a test case.
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct WasmerCli<'a> {
+    pub program: &'a Path,
+    /// Where compiled modules are cached, if not wasmer's default location.
+    pub module_cache: Option<&'a Path>,
+    /// How core dumps are collected, if at all.
+    pub coredumps: Option<Coredumps>,
+    /// Echo the test case's output to the console as well as its logs.
+    pub tee_logs: bool,
+    /// Which CPUs the test case runs on.
+    pub scheduling: &'a Scheduling,
+}
+
+/// The directory (relative to a test case's base directory) that the logs
+/// from checking for non-determinism are written to.
+const RERUN_DIR: &str = "rerun";
+
+/// The directory inside `parent` that belongs to a particular test case.
+pub(crate) fn test_case_dir(parent: &Path, test_case: &TestCase) -> PathBuf {
+    let mut dir = parent
+        .join(&test_case.namespace)
+        .join(&test_case.package_name)
+        .join(test_case.version());
+    if let Some(variant) = &test_case.variant {
+        dir = dir.join(variant);
     }
+    dir
 }
 
 #[tracing::instrument(
@@ -80,39 +175,115 @@ impl Handler<BeginTest> for Runner {
 )]
 async fn run_experiment(
     experiment: &Experiment,
+    cli: WasmerCli<'_>,
     test_case: &TestCase,
     assets: &Assets,
     base_dir: PathBuf,
+    redactor: &Redactor,
 ) -> Report {
     let dirs = directories::BaseDirs::new().unwrap();
+    let experiment = settings(experiment, test_case);
+    let cold_and_warm = experiment.wasmer.module_cache == ModuleCache::ColdAndWarm;
 
-    let mut cmd = match setup(experiment, test_case, assets, &base_dir, dirs.home_dir()).await {
-        Ok(cmd) => cmd,
-        Err(error) => {
-            return Report {
-                display_name: test_case.display_name(),
-                package_version: test_case.package_version.clone(),
-                outcome: Outcome::SetupFailed {
-                    base_dir,
-                    error: error.into(),
-                },
+    if let (true, Some(module_cache)) = (cold_and_warm, cli.module_cache) {
+        // Leftovers from a previous run would make the cold start warm
+        if let Err(e) = tokio::fs::remove_dir_all(module_cache).await {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                tracing::warn!(
+                    error = &e as &dyn std::error::Error,
+                    dir = %module_cache.display(),
+                    "Unable to clear the module cache",
+                );
             }
         }
+    }
+
+    let invocation = match setup(
+        &experiment,
+        cli,
+        test_case,
+        assets,
+        &base_dir,
+        dirs.home_dir(),
+    )
+    .await
+    {
+        Ok(invocation) => invocation,
+        Err(error) => return setup_failed(test_case, base_dir, error, None, redactor),
+    };
+    let redactor = redactor.with_env(
+        invocation
+            .env
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_os_str())),
+    );
+    let mut command = invocation.command_line();
+    redactor.redact_command(&mut command);
+
+    let mut warm_ups = experiment.benchmark.as_ref().map_or(0, |b| b.warm_up);
+    if experiment.wasmer.module_cache == ModuleCache::Warm {
+        // Run the test case once without recording anything, so the module
+        // is already compiled when we do the real run.
+        warm_ups = warm_ups.max(1);
+    }
+    if cold_and_warm {
+        // The recorded run needs to be the cold one
+        warm_ups = 0;
+    }
+
+    for _ in 0..warm_ups {
+        tracing::debug!("Warming up");
+        let _ = run_with_timeout(&mut invocation.command(), experiment.timeout).await;
+    }
+
+    let capture = Capture {
+        tee: cli
+            .tee_logs
+            .then(|| format!("{}@{}", test_case.display_name(), test_case.version())),
+        limit: experiment.output_limit.clone(),
+        redactor: Some(redactor.clone()),
     };
 
-    tracing::debug!(cmd=?cmd.as_std(), "Invoking wasmer CLI");
+    let cmd = match invocation.command_with_logs(&base_dir, &capture).await {
+        Ok(cmd) => cmd,
+        Err(error) => return setup_failed(test_case, base_dir, error, Some(command), &redactor),
+    };
+
+    tracing::debug!(cmd=?cmd.cmd.as_std(), "Invoking wasmer CLI");
     let start = Instant::now();
 
-    let outcome = match cmd.status().await {
-        Ok(status) => Outcome::Completed {
+    let mut logs_discarded = false;
+    let finished = cmd.run(experiment.timeout).await.map(|logged| {
+        logs_discarded = logged.discarded;
+        logged.finished
+    });
+
+    let outcome = match finished {
+        Ok(Finished::Exited(status)) => {
+            let mut status = ExitStatus::from(status);
+            status.success = passed(&experiment, status);
+
+            Outcome::Completed {
+                base_dir,
+                status,
+                run_time: start.elapsed(),
+            }
+        }
+        Ok(Finished::TimedOut) => Outcome::TimedOut {
+            base_dir,
+            // Only runs with a timeout can time out
+            timeout: experiment.timeout.unwrap_or_default(),
+            run_time: start.elapsed(),
+        },
+        Ok(Finished::OutputLimitExceeded) => Outcome::OutputLimitExceeded {
             base_dir,
-            status: status.into(),
+            limit: capture.limit.as_ref().map_or(0, |l| l.max_bytes),
             run_time: start.elapsed(),
         },
         Err(error) => {
             let error = Error::new(error).context(format!(
                 "Unable to start \"{}\", is it installed?",
-                cmd.as_std().get_program().to_string_lossy()
+                invocation.program.display()
             ));
             Outcome::SetupFailed {
                 error: error.into(),
@@ -121,21 +292,503 @@ async fn run_experiment(
         }
     };
 
-    Report {
+    if let (Some(_), Some(base_dir)) = (&experiment.wasmer.log, outcome.base_dir()) {
+        let base_dir = base_dir.to_path_buf();
+        let result = tokio::task::spawn_blocking(move || logs::split_wasmer_log(&base_dir)).await;
+        if let Ok(Err(e)) = result {
+            tracing::warn!(error = &*e, "Unable to separate wasmer's logs from stderr");
+        }
+    }
+
+    let startup = match &outcome {
+        Outcome::Completed {
+            status, run_time, ..
+        } if cold_and_warm && status.success => {
+            run_warm(&experiment, &invocation)
+                .await
+                .map(|warm| StartupTimes {
+                    cold: *run_time,
+                    warm,
+                })
+        }
+        _ => None,
+    };
+
+    let benchmark = match (&experiment.benchmark, &outcome) {
+        (
+            Some(benchmark),
+            Outcome::Completed {
+                status, run_time, ..
+            },
+        ) if status.success => {
+            // Compilation would skew the measurements, so start from the
+            // warm run when we have one
+            let first = startup.map_or(*run_time, |s| s.warm);
+            Some(run_benchmark(&experiment, &invocation, benchmark.iterations, first).await)
+        }
+        _ => None,
+    };
+
+    let repetitions = match experiment.repetitions {
+        Some(n) if n.get() > 1 => {
+            let first_passed = outcome.category() == Category::Success;
+            Some(run_repetitions(&experiment, &invocation, n.get(), first_passed).await)
+        }
+        _ => None,
+    };
+
+    let rerun = match &outcome {
+        Outcome::Completed { base_dir, .. } | Outcome::TimedOut { base_dir, .. }
+            if experiment.check_determinism =>
+        {
+            Some(run_again(&experiment, &invocation, base_dir, &capture).await)
+        }
+        _ => None,
+    };
+
+    let coredumps = match (cli.coredumps, outcome.base_dir()) {
+        (Some(_), Some(base_dir)) => {
+            let base_dir = base_dir.to_path_buf();
+            match tokio::task::spawn_blocking(move || coredumps::collect(&base_dir)).await {
+                Ok(Ok(collected)) => collected,
+                Ok(Err(e)) => {
+                    tracing::warn!(error = &*e, "Unable to collect core dumps");
+                    Vec::new()
+                }
+                Err(_) => Vec::new(),
+            }
+        }
+        _ => Vec::new(),
+    };
+
+    let analyses = analyzers::analyze(
+        &experiment.analyzers,
+        test_case,
+        &outcome,
+        experiment.timeout,
+    )
+    .await;
+
+    let stdout_digest = match &outcome {
+        Outcome::Completed { base_dir, .. } if !logs_discarded => {
+            let base_dir = base_dir.clone();
+            let result =
+                tokio::task::spawn_blocking(move || logs::digest_log(&base_dir, "stdout.txt"))
+                    .await;
+            match result {
+                Ok(Ok(digest)) => digest,
+                Ok(Err(e)) => {
+                    tracing::warn!(error = &*e, "Unable to hash stdout");
+                    None
+                }
+                Err(_) => None,
+            }
+        }
+        _ => None,
+    };
+
+    // Only compress the logs once analyzers have had a chance to look at them
+    if let Some(base_dir) = outcome.base_dir() {
+        let base_dir = base_dir.to_path_buf();
+        let result = tokio::task::spawn_blocking(move || logs::compress_logs(&base_dir)).await;
+        if let Ok(Err(e)) = result {
+            tracing::warn!(error = &*e, "Unable to compress the logs");
+        }
+    }
+
+    let mut report = Report {
         display_name: test_case.display_name(),
         package_version: test_case.package_version.clone(),
         outcome,
+        analyses,
+        annotation: None,
+        command: Some(command),
+        benchmark,
+        startup,
+        repetitions,
+        stdout_digest,
+        rerun,
+        coredumps,
+        timeline: None,
+        modules: Vec::new(),
+        module_errors: Vec::new(),
+        logs_discarded,
+    };
+    redactor.redact_report(&mut report);
+
+    report
+}
+
+/// The [`Report`] for a test case which couldn't be set up.
+fn setup_failed(
+    test_case: &TestCase,
+    base_dir: PathBuf,
+    error: Error,
+    command: Option<CommandLine>,
+    redactor: &Redactor,
+) -> Report {
+    let mut report = Report {
+        display_name: test_case.display_name(),
+        package_version: test_case.package_version.clone(),
+        outcome: Outcome::SetupFailed {
+            base_dir,
+            error: error.into(),
+        },
+        analyses: Vec::new(),
+        annotation: None,
+        command,
+        benchmark: None,
+        startup: None,
+        repetitions: None,
+        stdout_digest: None,
+        rerun: None,
+        coredumps: Vec::new(),
+        modules: Vec::new(),
+        module_errors: Vec::new(),
+        timeline: None,
+        logs_discarded: false,
+    };
+    redactor.redact_report(&mut report);
+
+    report
+}
+
+/// Did a run meet the experiment's expectations?
+fn passed(experiment: &Experiment, status: ExitStatus) -> bool {
+    status.code == experiment.expectations.exit_code
+}
+
+/// Run a test case until it has been run `runs` times in total, counting how
+/// many of those runs passed.
+///
+/// Unlike benchmarking, this keeps going after a failure because the whole
+/// point is to see how often the test case fails.
+async fn run_repetitions(
+    experiment: &Experiment,
+    invocation: &Invocation,
+    runs: usize,
+    first_passed: bool,
+) -> Repetitions {
+    let mut passes = usize::from(first_passed);
+
+    for run in 2..=runs {
+        match run_with_timeout(&mut invocation.command(), experiment.timeout).await {
+            Ok(Some(status)) if passed(experiment, status.into()) => passes += 1,
+            other => tracing::debug!(run, result = ?other, "Repetition failed"),
+        }
+    }
+
+    Repetitions {
+        runs,
+        passed: passes,
+    }
+}
+
+/// Run a test case a second time with the same command, environment, and
+/// working directory, writing its logs to the [`RERUN_DIR`].
+async fn run_again(
+    experiment: &Experiment,
+    invocation: &Invocation,
+    base_dir: &Path,
+    capture: &Capture,
+) -> Rerun {
+    let logs_dir = base_dir.join(RERUN_DIR);
+    let mut logs_discarded = false;
+
+    let status = match tokio::fs::create_dir_all(&logs_dir).await {
+        Ok(()) => match invocation.command_with_logs(&logs_dir, capture).await {
+            Ok(cmd) => match cmd.run(experiment.timeout).await {
+                Ok(Logged {
+                    finished: Finished::Exited(s),
+                    discarded,
+                }) => {
+                    logs_discarded = discarded;
+                    let mut status = ExitStatus::from(s);
+                    status.success = passed(experiment, status);
+                    Some(status)
+                }
+                Ok(Logged { discarded, .. }) => {
+                    logs_discarded = discarded;
+                    None
+                }
+                Err(e) => {
+                    tracing::warn!(
+                        error = &e as &dyn std::error::Error,
+                        "Unable to run the test case a second time",
+                    );
+                    None
+                }
+            },
+            Err(e) => {
+                tracing::warn!(error = &*e, "Unable to run the test case a second time");
+                None
+            }
+        },
+        Err(e) => {
+            tracing::warn!(
+                error = &e as &dyn std::error::Error,
+                dir = %logs_dir.display(),
+                "Unable to create the directory for the second run's logs",
+            );
+            None
+        }
+    };
+
+    let result = tokio::task::spawn_blocking(move || {
+        let digest = logs::digest_log(&logs_dir, "stdout.txt")?;
+        logs::compress_logs(&logs_dir)?;
+        Ok::<_, Error>(digest)
+    })
+    .await;
+
+    let stdout_digest = match result {
+        Ok(Ok(_)) if logs_discarded => None,
+        Ok(Ok(digest)) => digest,
+        Ok(Err(e)) => {
+            tracing::warn!(error = &*e, "Unable to hash the second run's stdout");
+            None
+        }
+        Err(_) => None,
+    };
+
+    Rerun {
+        status,
+        stdout_digest,
+        logs_discarded,
+    }
+}
+
+/// Run a test case again now its modules have been compiled, returning how
+/// long the warm run took if it passed.
+async fn run_warm(experiment: &Experiment, invocation: &Invocation) -> Option<Duration> {
+    let start = Instant::now();
+
+    match run_with_timeout(&mut invocation.command(), experiment.timeout).await {
+        Ok(Some(status)) if passed(experiment, status.into()) => Some(start.elapsed()),
+        other => {
+            tracing::warn!(result = ?other, "The warm run didn't succeed");
+            None
+        }
+    }
+}
+
+/// Keep re-running a test case until we have `iterations` measurements,
+/// starting with the `first` run's time.
+///
+/// Measurement stops early if a run fails or times out, because its run time
+/// wouldn't be comparable.
+async fn run_benchmark(
+    experiment: &Experiment,
+    invocation: &Invocation,
+    iterations: usize,
+    first: Duration,
+) -> BenchmarkStats {
+    let mut samples = vec![first];
+
+    while samples.len() < iterations {
+        let start = Instant::now();
+
+        match run_with_timeout(&mut invocation.command(), experiment.timeout).await {
+            Ok(Some(status)) if passed(experiment, status.into()) => {
+                samples.push(start.elapsed());
+            }
+            other => {
+                tracing::warn!(
+                    iteration = samples.len() + 1,
+                    result = ?other,
+                    "Benchmark iteration didn't succeed, stopping early",
+                );
+                break;
+            }
+        }
+    }
+
+    BenchmarkStats::from_samples(samples).expect("There is always at least one sample")
+}
+
+/// How a process finished.
+#[derive(Debug)]
+enum Finished {
+    Exited(std::process::ExitStatus),
+    TimedOut,
+    OutputLimitExceeded,
+}
+
+/// Run a command to completion, returning `None` if it was killed because it
+/// took longer than the `timeout`.
+async fn run_with_timeout(
+    cmd: &mut tokio::process::Command,
+    timeout: Option<Duration>,
+) -> Result<Option<std::process::ExitStatus>, std::io::Error> {
+    let child = spawn(cmd)?;
+
+    match wait_or_kill(child, timed_out(timeout)).await? {
+        Finished::Exited(status) => Ok(Some(status)),
+        Finished::TimedOut | Finished::OutputLimitExceeded => Ok(None),
+    }
+}
+
+fn spawn(cmd: &mut tokio::process::Command) -> Result<tokio::process::Child, std::io::Error> {
+    process::isolate(cmd);
+    cmd.kill_on_drop(true).spawn()
+}
+
+/// Resolves once the `timeout` has elapsed, or never if there isn't one.
+async fn timed_out(timeout: Option<Duration>) -> Finished {
+    match timeout {
+        Some(timeout) => {
+            tokio::time::sleep(timeout).await;
+            tracing::warn!(?timeout, "Test case timed out");
+            Finished::TimedOut
+        }
+        None => std::future::pending().await,
+    }
+}
+
+/// Wait for a process to exit, killing it (and anything it started) if
+/// `stop` resolves first.
+async fn wait_or_kill(
+    mut child: tokio::process::Child,
+    stop: impl Future<Output = Finished>,
+) -> Result<Finished, std::io::Error> {
+    // If we get cancelled, this takes out any processes wasmer started, too
+    let mut tree = ProcessTree::new(child.id());
+
+    tokio::select! {
+        status = child.wait() => {
+            tree.disarm();
+            status.map(Finished::Exited)
+        }
+        reason = stop => {
+            tree.kill()?;
+            child.kill().await?;
+            Ok(reason)
+        }
     }
 }
 
+/// A command which writes its output to `stdout.txt` and `stderr.txt`.
+struct LoggedCommand {
+    cmd: tokio::process::Command,
+    /// The directory `stdout.txt` and `stderr.txt` are in.
+    logs_dir: PathBuf,
+    /// How the output is captured and the log files it goes to, when it
+    /// doesn't go straight into the files.
+    capture: Option<(Capture, tokio::fs::File, tokio::fs::File)>,
+}
+
+impl LoggedCommand {
+    /// Run the command to completion, killing it if it takes longer than the
+    /// `timeout`.
+    async fn run(self, timeout: Option<Duration>) -> Result<Logged, std::io::Error> {
+        let LoggedCommand {
+            mut cmd,
+            logs_dir,
+            capture,
+        } = self;
+
+        let Some((capture, stdout, stderr)) = capture else {
+            let child = spawn(&mut cmd)?;
+            let finished = wait_or_kill(child, timed_out(timeout)).await?;
+            return Ok(Logged {
+                finished,
+                discarded: false,
+            });
+        };
+
+        let mut child = spawn(&mut cmd)?;
+        let exceeded = Arc::new(Notify::new());
+        let copies = [
+            capture.spawn(
+                child.stdout.take().expect("stdout is piped"),
+                stdout,
+                Console::Stdout,
+                Arc::clone(&exceeded),
+            ),
+            capture.spawn(
+                child.stderr.take().expect("stderr is piped"),
+                stderr,
+                Console::Stderr,
+                Arc::clone(&exceeded),
+            ),
+        ];
+
+        let output_limit = async {
+            if capture.kills() {
+                exceeded.notified().await;
+                tracing::warn!("Test case exceeded the output limit");
+                Finished::OutputLimitExceeded
+            } else {
+                std::future::pending().await
+            }
+        };
+        let stop = async {
+            tokio::select! {
+                reason = timed_out(timeout) => reason,
+                reason = output_limit => reason,
+            }
+        };
+        let finished = wait_or_kill(child, stop).await?;
+
+        let mut failed = false;
+        match tokio::time::timeout(capture::GRACE_PERIOD, futures::future::join_all(copies)).await {
+            Ok(results) => {
+                for result in results {
+                    let e = match result {
+                        Ok(Ok(())) => continue,
+                        Ok(Err(e)) => e,
+                        Err(e) => std::io::Error::new(std::io::ErrorKind::Other, e),
+                    };
+                    tracing::warn!(
+                        error = &e as &dyn std::error::Error,
+                        "Unable to copy the test case's output to its logs",
+                    );
+                    failed = true;
+                }
+            }
+            Err(_) => tracing::debug!("Stopped waiting for the test case's output"),
+        }
+
+        // We can't vouch for a log that was only partly scrubbed, so it's
+        // safer to throw the logs away
+        let discarded = failed && capture.redacts();
+        if discarded {
+            for filename in ["stdout.txt", "stderr.txt"] {
+                if let Err(e) = tokio::fs::write(logs_dir.join(filename), b"").await {
+                    tracing::warn!(
+                        error = &e as &dyn std::error::Error,
+                        filename,
+                        "Unable to discard a log which may contain secrets",
+                    );
+                }
+            }
+        }
+
+        Ok(Logged {
+            finished,
+            discarded,
+        })
+    }
+}
+
+/// How a [`LoggedCommand`] finished.
+#[derive(Debug)]
+struct Logged {
+    finished: Finished,
+    /// The logs were emptied because secrets couldn't be scrubbed from all
+    /// of the output.
+    discarded: bool,
+}
+
 #[tracing::instrument(skip_all)]
-async fn setup(
+pub(crate) async fn setup(
     experiment: &Experiment,
+    cli: WasmerCli<'_>,
     test_case: &TestCase,
     assets: &Assets,
     base_dir: &Path,
     home_dir: &Path,
-) -> Result<tokio::process::Command, Error> {
+) -> Result<Invocation, Error> {
     if base_dir.exists() {
         tokio::fs::remove_dir_all(base_dir)
             .await
@@ -161,67 +814,391 @@ async fn setup(
         .context("Unable to create the working dir")?;
 
     let tarball_path = fixtures_dir.join("package.tar.gz");
-    tokio::fs::copy(&assets.tarball, &tarball_path)
-        .await
-        .context("Unable to copy the tarball into place")?;
+    if let Some(tarball) = &assets.tarball {
+        provision(tarball, &tarball_path)
+            .await
+            .context("Unable to put the tarball into place")?;
+    }
 
     let webc_path = fixtures_dir.join("package.webc");
     if let Some(webc) = &assets.webc {
-        tokio::fs::copy(webc, &webc_path)
+        provision(webc, &webc_path)
             .await
-            .context("Unable to copy the webc into place")?;
+            .context("Unable to put the webc into place")?;
     }
 
-    let env = Env::new(fixtures_dir, out_dir, test_case);
+    let env = Env::new(fixtures_dir, out_dir, test_case, assets);
 
-    let mut cmd = tokio::process::Command::new("wasmer");
+    let mut invocation = Invocation {
+        program: cli.program.to_path_buf(),
+        args: Vec::new(),
+        env: Vec::new(),
+        current_dir: base_dir.to_path_buf(),
+        os_coredumps: cli.coredumps == Some(Coredumps::Os),
+        scheduling: cli.scheduling.clone(),
+    };
 
-    let stdout = tokio::fs::File::create(base_dir.join("stdout.txt"))
-        .await
-        .context("Unable to open stdout.txt")?;
-    let stderr = tokio::fs::File::create(base_dir.join("stderr.txt"))
-        .await
-        .context("Unable to open stderr.txt")?;
+    for var in INHERITED_VARS {
+        if let Some(value) = std::env::var_os(var) {
+            invocation.env.push((var.to_string(), value));
+        }
+    }
 
-    cmd.current_dir(base_dir)
-        .stdout(stdout.into_std().await)
-        .stderr(stderr.into_std().await)
-        .stdin(std::process::Stdio::null())
-        .env_clear();
+    if experiment.wasmer.wasmer_dir == WasmerDir::Isolated {
+        let wasmer_dir = base_dir.join("wasmer");
+        tokio::fs::create_dir_all(&wasmer_dir)
+            .await
+            .context("Unable to create the isolated $WASMER_DIR")?;
+        invocation.set_env("WASMER_DIR", wasmer_dir);
+    }
 
-    let whitelisted_vars = ["PATH", "WASMER_DIR"];
+    if let Some(module_cache) = cli.module_cache {
+        invocation.set_env("WASMER_CACHE_DIR", module_cache);
+    }
 
-    for var in whitelisted_vars {
-        if let Some(value) = std::env::var_os(var) {
-            cmd.env(var, value);
-        }
+    if let Some(filter) = &experiment.wasmer.log {
+        invocation.set_env("RUST_LOG", filter);
+        // Colour codes would stop us from recognising the log lines
+        invocation.set_env("NO_COLOR", "1");
+    }
+
+    let by_name = runs_by_name(experiment, test_case);
+    if by_name {
+        // Make sure wasmer looks the package up in the same registry we found
+        // it in, unless the experiment says otherwise
+        invocation.set_env("WASMER_REGISTRY", format_graphql(&test_case.registry));
     }
 
     for (name, value) in &experiment.wasmer.env {
         let value = value.resolve(home_dir, |var| env.get_host(var));
-        cmd.env(name, value.as_ref());
+        invocation.set_env(name, value.as_ref());
+    }
+
+    if by_name {
+        invocation.arg("run").arg(format!(
+            "{}/{}@{}",
+            test_case.namespace,
+            test_case.package_name,
+            test_case.version()
+        ));
+    } else {
+        invocation.arg("run").arg(&experiment.package);
     }
 
-    cmd.arg("run").arg(&experiment.package);
+    if let Some(command) = &experiment.command {
+        invocation.arg(format!("--command-name={command}"));
+    }
 
     for arg in &experiment.wasmer.args {
         let arg = arg.resolve(home_dir, |var| env.get_host(var));
-        cmd.arg(arg.as_ref());
+        invocation.arg(arg.as_ref());
+    }
+
+    for flag in experiment.wasmer.features.flags() {
+        invocation.arg(flag);
+    }
+
+    if cli.coredumps == Some(Coredumps::OnTrap) {
+        let mut flag = OsString::from("--coredump-on-trap=");
+        flag.push(base_dir.join(TRAP_COREDUMP));
+        invocation.arg(flag);
+    }
+
+    match experiment.capabilities.network {
+        Some(true) => {
+            invocation.arg("--net");
+        }
+        Some(false) => {
+            anyhow::ensure!(
+                !invocation.args.iter().any(|arg| is_network_flag(arg)),
+                "The wasmer args enable networking, but the experiment's capabilities don't allow it",
+            );
+        }
+        None => {}
     }
 
     for (name, value) in &experiment.env {
         let value = value.resolve(home_dir, |var| env.get_guest(var));
-        cmd.arg(format!("--env={name}={value}"));
+        invocation.arg(format!("--env={name}={value}"));
     }
 
-    cmd.arg("--");
+    invocation.arg("--");
 
     for arg in &experiment.args {
         let arg = arg.resolve(home_dir, |var| env.get_guest(var));
-        cmd.arg(arg.as_ref());
+        invocation.arg(arg.as_ref());
+    }
+
+    // Packages from the registry are downloaded when wasmer starts, so we
+    // can only cut local packages off from the network at the host level.
+    if experiment.capabilities.network == Some(false)
+        && !by_name
+        && is_local_package(&experiment.package, base_dir)
+    {
+        if let Some(unshare) = network_sandbox() {
+            invocation.wrap(unshare);
+        }
+    }
+
+    Ok(invocation)
+}
+
+/// Is the experiment's package a file or directory on disk, rather than
+/// something wasmer will look up in the registry?
+///
+/// Relative paths are resolved against the test case's directory, because
+/// that's where `wasmer run` is started.
+fn is_local_package(package: &str, base_dir: &Path) -> bool {
+    !package.is_empty() && base_dir.join(package).exists()
+}
+
+fn is_network_flag(arg: &OsStr) -> bool {
+    let arg = arg.to_string_lossy();
+    arg == "--net" || arg.starts_with("--net=") || arg == "--enable-networking"
+}
+
+/// The command used to run a process without network access, if this machine
+/// supports it.
+///
+/// This is a fallback for when we can't rely on wasmer's own sandbox (e.g.
+/// older releases which always give packages network access).
+fn network_sandbox() -> Option<&'static [&'static str]> {
+    const UNSHARE: &[&str] = &["unshare", "--net", "--map-root-user", "--"];
+
+    static AVAILABLE: Lazy<bool> = Lazy::new(|| {
+        cfg!(target_os = "linux")
+            && std::process::Command::new(UNSHARE[0])
+                .args(&UNSHARE[1..])
+                .arg("true")
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .map_or(false, |status| status.success())
+    });
+
+    if *AVAILABLE {
+        Some(UNSHARE)
+    } else {
+        tracing::debug!("Network namespaces aren't available, relying on wasmer's sandbox");
+        None
+    }
+}
+
+/// Should the `wasmer` CLI resolve and download this test case itself (i.e.
+/// `wasmer run namespace/name@version`) instead of using the assets we
+/// fetched?
+///
+/// Local packages aren't in a registry, so they never run by name.
+pub(crate) fn runs_by_name(experiment: &Experiment, test_case: &TestCase) -> bool {
+    experiment.wasmer.by_name && test_case.registry != local::LOCAL_REGISTRY
+}
+
+/// The experiment's settings for a particular test case, with any namespace
+/// overrides or variant settings applied.
+pub(crate) fn settings<'a>(
+    experiment: &'a Experiment,
+    test_case: &TestCase,
+) -> Cow<'a, Experiment> {
+    let experiment = experiment.with_overrides(&test_case.namespace);
+
+    match &test_case.variant {
+        Some(variant) => Cow::Owned(experiment.with_variant(variant).into_owned()),
+        None => experiment,
+    }
+}
+
+/// Host environment variables that are passed through to the `wasmer` CLI.
+///
+/// Windows programs can't start without things like `SystemRoot`, so more
+/// needs to be inherited there.
+const INHERITED_VARS: &[&str] = if cfg!(windows) {
+    &[
+        "PATH",
+        "PATHEXT",
+        "WASMER_DIR",
+        "SystemRoot",
+        "SystemDrive",
+        "windir",
+        "TEMP",
+        "TMP",
+        "USERPROFILE",
+        "APPDATA",
+        "LOCALAPPDATA",
+        "ComSpec",
+    ]
+} else {
+    &["PATH", "WASMER_DIR"]
+};
+
+/// Put one of the cached assets into a test case's fixtures directory.
+///
+/// A hard link is cheap and works on every platform, but can't cross
+/// filesystems (e.g. when the cache is on another drive), so we fall back to
+/// copying.
+async fn provision(src: &Path, dest: &Path) -> Result<(), Error> {
+    match tokio::fs::hard_link(src, dest).await {
+        Ok(_) => Ok(()),
+        Err(e) => {
+            tracing::debug!(
+                src = %src.display(),
+                dest = %dest.display(),
+                error = &e as &dyn std::error::Error,
+                "Unable to hard link, falling back to a copy",
+            );
+            tokio::fs::copy(src, dest).await?;
+            Ok(())
+        }
     }
+}
 
-    Ok(cmd)
+/// Everything needed to invoke the `wasmer` CLI for a test case.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Invocation {
+    program: PathBuf,
+    args: Vec<OsString>,
+    /// The process's environment variables. Nothing is inherited from the
+    /// host.
+    env: Vec<(String, OsString)>,
+    current_dir: PathBuf,
+    /// Let the operating system write a core file if `wasmer` crashes.
+    os_coredumps: bool,
+    scheduling: Scheduling,
+}
+
+impl Invocation {
+    fn arg(&mut self, arg: impl AsRef<OsStr>) -> &mut Self {
+        self.args.push(arg.as_ref().to_os_string());
+        self
+    }
+
+    /// Run the current command via another program (e.g. `unshare`), where
+    /// `wrapper` is that program and its arguments.
+    fn wrap(&mut self, wrapper: &[&str]) {
+        let (program, wrapper_args) = wrapper.split_first().expect("The wrapper is never empty");
+
+        let mut args: Vec<OsString> = wrapper_args.iter().map(OsString::from).collect();
+        args.push(std::mem::replace(&mut self.program, PathBuf::from(program)).into_os_string());
+        args.append(&mut self.args);
+        self.args = args;
+    }
+
+    fn set_env(&mut self, name: &str, value: impl AsRef<OsStr>) {
+        let value = value.as_ref().to_os_string();
+
+        match self.env.iter_mut().find(|(n, _)| n == name) {
+            Some((_, v)) => *v = value,
+            None => self.env.push((name.to_string(), value)),
+        }
+    }
+
+    /// The [`CommandLine`] recorded in the test case's [`Report`], with the
+    /// values of anything that looks like a secret redacted.
+    pub(crate) fn command_line(&self) -> CommandLine {
+        let env = self
+            .env
+            .iter()
+            .map(|(name, value)| {
+                let value = if is_secret(name) {
+                    REDACTED.to_string()
+                } else {
+                    value.to_string_lossy().into_owned()
+                };
+                (name.clone(), value)
+            })
+            .collect();
+
+        let mut args = Vec::new();
+        let mut redact_next = false;
+
+        for arg in &self.args {
+            let arg = arg.to_string_lossy().into_owned();
+
+            if std::mem::take(&mut redact_next) {
+                args.push(REDACTED.to_string());
+                continue;
+            }
+
+            let arg = match arg.split_once('=') {
+                Some(("--env", var)) => match var.split_once('=') {
+                    Some((name, _)) if is_secret(name) => format!("--env={name}={REDACTED}"),
+                    _ => arg,
+                },
+                Some((flag, _)) if flag.starts_with("--") && is_secret(flag) => {
+                    format!("{flag}={REDACTED}")
+                }
+                None if arg.starts_with("--") && is_secret(&arg) => {
+                    redact_next = true;
+                    arg
+                }
+                _ => arg,
+            };
+            args.push(arg);
+        }
+
+        CommandLine {
+            program: self.program.clone(),
+            args,
+            env,
+            current_dir: self.current_dir.clone(),
+        }
+    }
+
+    /// Create a new [`LoggedCommand`] which will write its output to
+    /// `stdout.txt` and `stderr.txt` in the `base_dir`.
+    async fn command_with_logs(
+        &self,
+        base_dir: &Path,
+        capture: &Capture,
+    ) -> Result<LoggedCommand, Error> {
+        let stdout = tokio::fs::File::create(base_dir.join("stdout.txt"))
+            .await
+            .context("Unable to open stdout.txt")?;
+        let stderr = tokio::fs::File::create(base_dir.join("stderr.txt"))
+            .await
+            .context("Unable to open stderr.txt")?;
+
+        let mut cmd = self.command();
+
+        let capture = if capture.is_piped() {
+            cmd.stdout(Stdio::piped()).stderr(Stdio::piped());
+            Some((capture.clone(), stdout, stderr))
+        } else {
+            cmd.stdout(stdout.into_std().await)
+                .stderr(stderr.into_std().await);
+            None
+        };
+
+        Ok(LoggedCommand {
+            cmd,
+            logs_dir: base_dir.to_path_buf(),
+            capture,
+        })
+    }
+
+    /// Create a new [`tokio::process::Command`] with stdin, stdout, and stderr
+    /// all set to null.
+    fn command(&self) -> tokio::process::Command {
+        let mut cmd = tokio::process::Command::from(self.interactive_command());
+        cmd.stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null());
+        if self.os_coredumps {
+            coredumps::enable_os_coredumps(&mut cmd);
+        }
+        self.scheduling.configure(&mut cmd);
+        cmd
+    }
+
+    /// Create a new [`std::process::Command`] which inherits stdin, stdout,
+    /// and stderr from the current process.
+    pub(crate) fn interactive_command(&self) -> std::process::Command {
+        let mut cmd = std::process::Command::new(&self.program);
+        cmd.args(&self.args)
+            .current_dir(&self.current_dir)
+            .env_clear()
+            .envs(self.env.iter().map(|(k, v)| (k, v)));
+        cmd
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -231,22 +1208,24 @@ struct Env {
 }
 
 impl Env {
-    fn new(fixtures_dir: PathBuf, out_dir: PathBuf, test_case: &TestCase) -> Self {
+    fn new(fixtures_dir: PathBuf, out_dir: PathBuf, test_case: &TestCase, assets: &Assets) -> Self {
         let mut common: HashMap<&str, String> = HashMap::new();
 
         common.insert("PKG_NAMESPACE", test_case.namespace.clone());
         common.insert("PKG_NAME", test_case.package_name.clone());
         common.insert("PKG_VERSION", test_case.version().to_string());
-        common.insert("TARBALL_FILENAME", "package.tar.gz".to_string());
 
         let mut host: HashMap<&str, String> = HashMap::new();
 
-        host.insert(
-            "TARBALL_PATH",
-            fixtures_dir.join("package.tar.gz").display().to_string(),
-        );
+        if assets.tarball.is_some() {
+            host.insert(
+                "TARBALL_PATH",
+                fixtures_dir.join("package.tar.gz").display().to_string(),
+            );
+            common.insert("TARBALL_FILENAME", "package.tar.gz".to_string());
+        }
 
-        if test_case.webc_url().is_some() {
+        if assets.webc.is_some() {
             host.insert(
                 "WEBC_PATH",
                 fixtures_dir.join("package.webc").display().to_string(),
@@ -268,3 +1247,304 @@ impl Env {
         self.common.get(var).cloned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{OutputLimit, OutputLimitAction},
+        test_utils::test_case,
+    };
+
+    #[tokio::test]
+    async fn test_cases_can_be_run_by_name() {
+        let temp = tempfile::tempdir().unwrap();
+        let experiment: Experiment = serde_json::from_value(serde_json::json!({
+            "package": "wasmer/wapm2pirita",
+            "args": ["--help"],
+            "wasmer": { "args": [], "by-name": true },
+        }))
+        .unwrap();
+        let test_case = test_case("python", "3.12.0");
+        let cli = WasmerCli {
+            program: Path::new("wasmer"),
+            module_cache: None,
+            coredumps: None,
+            tee_logs: false,
+            scheduling: &Scheduling::default(),
+        };
+
+        let invocation = setup(
+            &experiment,
+            cli,
+            &test_case,
+            &Assets::default(),
+            temp.path(),
+            temp.path(),
+        )
+        .await
+        .unwrap();
+
+        let command = invocation.command_line();
+        assert_eq!(
+            command.args,
+            ["run", "wasmer/python@3.12.0", "--", "--help"]
+        );
+        assert_eq!(
+            command.env["WASMER_REGISTRY"],
+            format_graphql("registry.wasmer.io")
+        );
+
+        // Local packages aren't in a registry, so they are still run from disk
+        let local = TestCase {
+            registry: local::LOCAL_REGISTRY.to_string(),
+            ..test_case
+        };
+        assert!(!runs_by_name(&experiment, &local));
+    }
+
+    #[tokio::test]
+    async fn relative_packages_are_resolved_against_the_test_case_dir() {
+        let temp = tempfile::tempdir().unwrap();
+        let webc = temp.path().join("python.webc");
+        std::fs::write(&webc, "").unwrap();
+        let base_dir = temp.path().join("python");
+        let experiment: Experiment = serde_json::from_value(serde_json::json!({
+            "package": "fixtures/package.webc",
+            "wasmer": { "args": [] },
+            "capabilities": { "network": false },
+        }))
+        .unwrap();
+        let cli = WasmerCli {
+            program: Path::new("wasmer"),
+            module_cache: None,
+            coredumps: None,
+            tee_logs: false,
+            scheduling: &Scheduling::default(),
+        };
+        let assets = Assets {
+            webc: Some(webc),
+            ..Default::default()
+        };
+
+        let invocation = setup(
+            &experiment,
+            cli,
+            &test_case("python", "1.0.0"),
+            &assets,
+            &base_dir,
+            temp.path(),
+        )
+        .await
+        .unwrap();
+
+        assert!(!Path::new(&experiment.package).exists());
+        assert!(is_local_package(&experiment.package, &base_dir));
+        assert!(!is_local_package("wasmer/python", &base_dir));
+        let command = invocation.command_line();
+        let expected = match network_sandbox() {
+            Some(unshare) => Path::new(unshare[0]),
+            None => Path::new("wasmer"),
+        };
+        assert_eq!(command.program, expected);
+    }
+
+    #[tokio::test]
+    async fn each_test_case_can_have_its_own_wasmer_dir() {
+        async fn wasmer_dir(experiment: &Experiment, base_dir: &Path) -> Option<String> {
+            let cli = WasmerCli {
+                program: Path::new("wasmer"),
+                module_cache: None,
+                coredumps: None,
+                tee_logs: false,
+                scheduling: &Scheduling::default(),
+            };
+            let invocation = setup(
+                experiment,
+                cli,
+                &test_case("python", "1.0.0"),
+                &Assets::default(),
+                base_dir,
+                base_dir,
+            )
+            .await
+            .unwrap();
+
+            invocation.command_line().env.get("WASMER_DIR").cloned()
+        }
+
+        let temp = tempfile::tempdir().unwrap();
+
+        // By default, the host's $WASMER_DIR is used
+        let inherit: Experiment = serde_json::from_value(serde_json::json!({
+            "package": "wasmer/python",
+            "wasmer": { "args": [] },
+        }))
+        .unwrap();
+        assert_eq!(inherit.wasmer.wasmer_dir, WasmerDir::Inherit);
+        assert_eq!(
+            wasmer_dir(&inherit, temp.path()).await,
+            std::env::var("WASMER_DIR").ok()
+        );
+
+        let isolated: Experiment = serde_json::from_value(serde_json::json!({
+            "package": "wasmer/python",
+            "wasmer": { "args": [], "wasmer-dir": "isolated" },
+        }))
+        .unwrap();
+        for run in ["first", "second"] {
+            let base_dir = temp.path().join(run);
+            let expected = base_dir.join("wasmer");
+
+            assert_eq!(
+                wasmer_dir(&isolated, &base_dir).await,
+                Some(expected.display().to_string())
+            );
+            assert!(expected.is_dir());
+        }
+    }
+
+    #[test]
+    fn secrets_are_redacted_from_the_recorded_command() {
+        let mut invocation = Invocation {
+            program: "wasmer".into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            current_dir: "/tmp".into(),
+            os_coredumps: false,
+            scheduling: Scheduling::default(),
+        };
+        invocation.set_env("PATH", "/usr/bin");
+        invocation.set_env("WASMER_TOKEN", "hunter2");
+        invocation
+            .arg("run")
+            .arg("--token")
+            .arg("hunter2")
+            .arg("--registry-token=hunter2")
+            .arg("--env=GITHUB_TOKEN=hunter2")
+            .arg("--env=NAME=value")
+            .arg("wasmer/python");
+
+        let command = invocation.command_line();
+
+        assert_eq!(command.env["PATH"], "/usr/bin");
+        assert_eq!(command.env["WASMER_TOKEN"], REDACTED);
+        assert_eq!(
+            command.args,
+            [
+                "run",
+                "--token",
+                REDACTED,
+                "--registry-token=<redacted>",
+                "--env=GITHUB_TOKEN=<redacted>",
+                "--env=NAME=value",
+                "wasmer/python",
+            ]
+        );
+    }
+
+    #[test]
+    fn wrapped_commands_run_wasmer_via_the_wrapper() {
+        let mut invocation = Invocation {
+            program: "/bin/wasmer".into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            current_dir: "/tmp".into(),
+            os_coredumps: false,
+            scheduling: Scheduling::default(),
+        };
+        invocation.arg("run").arg("./package.webc").arg("--net");
+        assert!(is_network_flag(&invocation.args[2]));
+
+        invocation.wrap(&["unshare", "--net", "--"]);
+
+        let command = invocation.command_line();
+        assert_eq!(command.program, Path::new("unshare"));
+        assert_eq!(
+            command.args,
+            [
+                "--net",
+                "--",
+                "/bin/wasmer",
+                "run",
+                "./package.webc",
+                "--net"
+            ]
+        );
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn noisy_test_cases_can_be_killed() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut invocation = Invocation {
+            program: "/bin/sh".into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            current_dir: temp.path().to_path_buf(),
+            os_coredumps: false,
+            scheduling: Scheduling::default(),
+        };
+        invocation.arg("-c").arg("while true; do echo spam; done");
+        let capture = Capture {
+            tee: None,
+            limit: Some(OutputLimit {
+                max_bytes: 1024,
+                action: OutputLimitAction::Kill,
+            }),
+            redactor: None,
+        };
+
+        let cmd = invocation
+            .command_with_logs(temp.path(), &capture)
+            .await
+            .unwrap();
+        let finished = cmd
+            .run(Some(Duration::from_secs(30)))
+            .await
+            .unwrap()
+            .finished;
+
+        assert!(matches!(finished, Finished::OutputLimitExceeded));
+        let stdout = std::fs::read_to_string(temp.path().join("stdout.txt")).unwrap();
+        assert!(stdout.starts_with("spam\n"));
+        assert!(stdout.contains("bytes truncated ..."));
+        assert!(stdout.len() < 1024 + 100);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn secrets_never_reach_the_logs() {
+        let temp = tempfile::tempdir().unwrap();
+        let experiment: Experiment =
+            serde_json::from_value(serde_json::json!({ "package": "wasmer/python" })).unwrap();
+        let mut invocation = Invocation {
+            program: "/bin/sh".into(),
+            args: Vec::new(),
+            env: Vec::new(),
+            current_dir: temp.path().to_path_buf(),
+            os_coredumps: false,
+            scheduling: Scheduling::default(),
+        };
+        invocation
+            .arg("-c")
+            .arg("printf 'token=hun'; sleep 0.1; printf 'ter2\\n'; echo hunter2 >&2");
+        let capture = Capture {
+            tee: None,
+            limit: None,
+            redactor: Some(Redactor::new(&experiment, ["hunter2".to_string()])),
+        };
+
+        let cmd = invocation
+            .command_with_logs(temp.path(), &capture)
+            .await
+            .unwrap();
+        let logged = cmd.run(Some(Duration::from_secs(30))).await.unwrap();
+
+        assert!(!logged.discarded);
+        let stdout = std::fs::read_to_string(temp.path().join("stdout.txt")).unwrap();
+        assert_eq!(stdout, "token=<redacted>\n");
+        let stderr = std::fs::read_to_string(temp.path().join("stderr.txt")).unwrap();
+        assert_eq!(stderr, "<redacted>\n");
+    }
+}
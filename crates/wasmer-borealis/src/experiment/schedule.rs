@@ -0,0 +1,109 @@
+//! Starting the slowest test cases first, so a parallel run doesn't end with
+//! one huge package running on its own long after everything else finished.
+
+use std::{collections::HashMap, time::Duration};
+
+use crate::experiment::{Outcome, Results, TestCase};
+
+/// How long test cases took in earlier runs, used to estimate how long they
+/// will take this time.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct RunTimes {
+    versions: HashMap<(String, String), Duration>,
+    packages: HashMap<String, Duration>,
+}
+
+impl RunTimes {
+    /// Get the run times from a previous run's results.
+    ///
+    /// Test cases which timed out are assumed to take as long as the
+    /// timeout.
+    pub fn from_results(results: &Results) -> Self {
+        let mut run_times = RunTimes::default();
+
+        for report in &results.reports {
+            let run_time = match &report.outcome {
+                Outcome::Completed { run_time, .. }
+                | Outcome::OutputLimitExceeded { run_time, .. } => *run_time,
+                Outcome::TimedOut { timeout, .. } => *timeout,
+                _ => continue,
+            };
+            run_times.insert(&report.display_name, report.version(), run_time);
+        }
+
+        run_times
+    }
+
+    /// Record how long a particular version of a package took.
+    pub fn insert(&mut self, display_name: &str, version: &str, run_time: Duration) {
+        self.versions
+            .insert((display_name.to_string(), version.to_string()), run_time);
+
+        let longest = self.packages.entry(display_name.to_string()).or_default();
+        *longest = (*longest).max(run_time);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+
+    /// Estimate how long a test case will take, falling back to other
+    /// versions of the same package if this version hasn't been run before.
+    pub fn estimate(&self, test_case: &TestCase) -> Option<Duration> {
+        let name = test_case.display_name();
+
+        self.versions
+            .get(&(name.clone(), test_case.version().to_string()))
+            .or_else(|| self.packages.get(&name))
+            .copied()
+    }
+}
+
+/// Sort test cases so the ones expected to take longest come first.
+///
+/// Test cases with a known run time go first, slowest first, followed by the
+/// rest ordered by the size of their artifacts.
+#[cfg(feature = "run")]
+pub(crate) fn longest_first(mut test_cases: Vec<TestCase>, run_times: &RunTimes) -> Vec<TestCase> {
+    test_cases
+        .sort_by_cached_key(|tc| std::cmp::Reverse((run_times.estimate(tc), tc.artifact_size())));
+    test_cases
+}
+
+#[cfg(all(test, feature = "run"))]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_case;
+
+    #[test]
+    fn slowest_test_cases_go_first_then_the_largest() {
+        let mut run_times = RunTimes::default();
+        run_times.insert("wasmer/fast", "1.0.0", Duration::from_secs(1));
+        run_times.insert("wasmer/slow", "0.9.0", Duration::from_secs(60));
+        let mut small = test_case("small", "1.0.0");
+        small.package_version.distribution.size = Some(1024);
+        let mut large = test_case("large", "1.0.0");
+        large.package_version.distribution.size = Some(1024 * 1024);
+        let test_cases = vec![
+            test_case("fast", "1.0.0"),
+            small,
+            test_case("unknown", "1.0.0"),
+            large,
+            test_case("slow", "1.0.0"),
+        ];
+
+        let scheduled = longest_first(test_cases, &run_times);
+
+        let names: Vec<_> = scheduled.iter().map(|tc| tc.display_name()).collect();
+        assert_eq!(
+            names,
+            [
+                "wasmer/slow",
+                "wasmer/fast",
+                "wasmer/large",
+                "wasmer/small",
+                "wasmer/unknown"
+            ]
+        );
+    }
+}
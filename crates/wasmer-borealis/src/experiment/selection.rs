@@ -0,0 +1,185 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{Context, Error};
+use sha2::{Digest, Sha256};
+
+use crate::experiment::TestCase;
+
+/// Settings for running an experiment against a random subset of the
+/// discovered [`TestCase`]s.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub(crate) struct Sampling {
+    /// The maximum number of test cases to run.
+    pub max_packages: usize,
+    /// The seed used when choosing test cases.
+    pub seed: u64,
+}
+
+impl Sampling {
+    /// Take a deterministic random sample of the provided test cases.
+    ///
+    /// The same seed and the same set of test cases will always result in the
+    /// same sample, regardless of the order they were discovered in.
+    #[cfg(feature = "run")]
+    pub fn apply(self, mut test_cases: Vec<TestCase>) -> Vec<TestCase> {
+        let Sampling { max_packages, seed } = self;
+
+        test_cases.sort_by_cached_key(|tc| stable_hash(seed, &tc.id()));
+        test_cases.truncate(max_packages);
+
+        test_cases
+    }
+}
+
+/// One of `count` disjoint subsets of an experiment's test cases.
+///
+/// Test cases are assigned to a shard using a stable hash of their
+/// [`TestCase::id()`], so the same test case will always end up in the same
+/// shard. This lets a large experiment be split across several machines (e.g.
+/// CI jobs) and the results merged together afterwards with
+/// [`crate::experiment::Results::merge()`].
+///
+/// Shards are written as `index/count`, where `index` starts at 1.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Shard {
+    index: u64,
+    count: u64,
+}
+
+impl Shard {
+    pub fn new(index: u64, count: u64) -> Result<Self, Error> {
+        anyhow::ensure!(count > 0, "The shard count must be non-zero");
+        anyhow::ensure!(
+            (1..=count).contains(&index),
+            "The shard index must be between 1 and {count}, found {index}"
+        );
+
+        Ok(Shard { index, count })
+    }
+
+    /// The (1-based) index of this shard.
+    pub fn index(self) -> u64 {
+        self.index
+    }
+
+    /// The total number of shards.
+    pub fn count(self) -> u64 {
+        self.count
+    }
+
+    /// Does this test case belong to this shard?
+    pub fn contains(self, test_case: &TestCase) -> bool {
+        stable_hash(0, &test_case.id()) % self.count == self.index - 1
+    }
+}
+
+impl Display for Shard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.index, self.count)
+    }
+}
+
+impl FromStr for Shard {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (index, count) = s
+            .split_once('/')
+            .context("Shards should be in the form \"index/count\" (e.g. \"1/4\")")?;
+        let index = index.trim().parse().context("Invalid shard index")?;
+        let count = count.trim().parse().context("Invalid shard count")?;
+
+        Shard::new(index, count)
+    }
+}
+
+/// A hash that is guaranteed to be the same across platforms and `rustc`
+/// versions, unlike [`std::collections::hash_map::DefaultHasher`].
+pub(crate) fn stable_hash(seed: u64, key: &str) -> u64 {
+    let digest = Sha256::new()
+        .chain_update(seed.to_le_bytes())
+        .chain_update(key.as_bytes())
+        .finalize();
+
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    u64::from_le_bytes(bytes)
+}
+
+/// Format a digest as a lowercase hex string.
+#[cfg(feature = "run")]
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_utils::test_case;
+
+    use super::*;
+
+    #[cfg(feature = "run")]
+    fn ids(test_cases: &[TestCase]) -> Vec<String> {
+        test_cases.iter().map(|tc| tc.id()).collect()
+    }
+
+    #[cfg(feature = "run")]
+    #[test]
+    fn sampling_is_deterministic_and_order_independent() {
+        let test_cases: Vec<_> = (0..20)
+            .map(|i| test_case(&format!("pkg-{i}"), "1.0.0"))
+            .collect();
+        let mut reversed = test_cases.clone();
+        reversed.reverse();
+        let sampling = Sampling {
+            max_packages: 5,
+            seed: 42,
+        };
+
+        let first = sampling.apply(test_cases.clone());
+        let second = sampling.apply(reversed);
+
+        assert_eq!(first.len(), 5);
+        assert_eq!(ids(&first), ids(&second));
+
+        let different_seed = Sampling {
+            seed: 7,
+            ..sampling
+        }
+        .apply(test_cases);
+        assert_ne!(ids(&first), ids(&different_seed));
+    }
+
+    #[test]
+    fn every_test_case_belongs_to_exactly_one_shard() {
+        let shards: Vec<Shard> = ["1/3", "2/3", "3/3"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+
+        for i in 0..50 {
+            let test_case = test_case(&format!("pkg-{i}"), "1.0.0");
+            let matches = shards.iter().filter(|s| s.contains(&test_case)).count();
+            assert_eq!(matches, 1);
+        }
+    }
+
+    #[test]
+    fn invalid_shards_are_rejected() {
+        for shard in ["0/3", "4/3", "1/0", "1", "a/b"] {
+            assert!(shard.parse::<Shard>().is_err(), "{shard}");
+        }
+    }
+
+    #[cfg(feature = "run")]
+    #[test]
+    fn sampling_keeps_everything_when_under_the_limit() {
+        let test_cases = vec![test_case("a", "1.0.0"), test_case("b", "1.0.0")];
+        let sampling = Sampling {
+            max_packages: 10,
+            seed: 0,
+        };
+
+        assert_eq!(sampling.apply(test_cases).len(), 2);
+    }
+}
@@ -0,0 +1,229 @@
+mod s3;
+
+use std::{
+    fmt::Debug,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Error};
+use futures::{future::BoxFuture, FutureExt};
+
+use crate::experiment::{cache::package_version_dir, TestCase};
+
+pub use self::s3::{Bucket, Credentials, RemoteCache};
+
+/// The files needed to run a test case, available on the local filesystem.
+#[derive(Debug, Default, Clone)]
+pub struct Assets {
+    /// The package's `*.tar.gz` tarball.
+    ///
+    /// This is always available for packages from the registry, but a local
+    /// `*.webc` file won't have one.
+    pub tarball: Option<PathBuf>,
+    /// The package's `*.webc` file, if it has one.
+    pub webc: Option<PathBuf>,
+    /// The total size of the assets on disk.
+    pub total_size: u64,
+}
+
+/// Somewhere downloaded packages are stored between experiments.
+///
+/// The default is [`FileSystemStorage`], which keeps each package in the
+/// cache directory. Embedders can implement this trait to keep packages
+/// somewhere else (e.g. a network share or an object store), as long as
+/// [`Storage::get()`] makes the artifacts available on the local filesystem.
+pub trait Storage: Debug + Send + Sync {
+    /// Look up a test case's artifacts, returning `None` if they haven't been
+    /// stored yet.
+    ///
+    /// If this fails, the entry is assumed to be corrupted and will be
+    /// [evicted](Storage::evict) and downloaded again.
+    fn get<'a>(&'a self, test_case: &'a TestCase) -> BoxFuture<'a, Result<Option<Assets>, Error>>;
+
+    /// Take ownership of a test case's freshly downloaded artifacts.
+    ///
+    /// The `staging` directory contains the package's tarball, its `*.webc`
+    /// file (if it has one), and a `manifest.json` describing where they were
+    /// downloaded from. It is deleted afterwards, so implementations should
+    /// move or copy anything they want to keep.
+    fn put<'a>(
+        &'a self,
+        test_case: &'a TestCase,
+        staging: &'a Path,
+    ) -> BoxFuture<'a, Result<Assets, Error>>;
+
+    /// Remove a test case's artifacts, if they are stored.
+    fn evict<'a>(&'a self, test_case: &'a TestCase) -> BoxFuture<'a, Result<(), Error>>;
+
+    /// Have the test case's artifacts been stored?
+    fn contains<'a>(&'a self, test_case: &'a TestCase) -> BoxFuture<'a, Result<bool, Error>> {
+        self.get(test_case)
+            .map(|assets| assets.map(|a| a.is_some()))
+            .boxed()
+    }
+}
+
+/// The default [`Storage`], which keeps packages in a
+/// `{registry}/{namespace}/{name}/{version}` directory hierarchy.
+#[derive(Debug, Clone)]
+pub struct FileSystemStorage {
+    dir: PathBuf,
+}
+
+impl FileSystemStorage {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        FileSystemStorage { dir: dir.into() }
+    }
+}
+
+impl Storage for FileSystemStorage {
+    fn get<'a>(&'a self, test_case: &'a TestCase) -> BoxFuture<'a, Result<Option<Assets>, Error>> {
+        async move {
+            let entry = package_version_dir(&self.dir, test_case);
+            let (tarball, _) = artifact_paths(&entry, test_case);
+
+            if !tarball.exists() {
+                return Ok(None);
+            }
+
+            let assets = assets_in(&entry, test_case)?;
+            tracing::debug!(entry=%entry.display(), "Cache hit!");
+
+            Ok(Some(assets))
+        }
+        .boxed()
+    }
+
+    fn put<'a>(
+        &'a self,
+        test_case: &'a TestCase,
+        staging: &'a Path,
+    ) -> BoxFuture<'a, Result<Assets, Error>> {
+        async move {
+            let entry = package_version_dir(&self.dir, test_case);
+            persist(staging, &entry).await?;
+            assets_in(&entry, test_case)
+        }
+        .boxed()
+    }
+
+    fn evict<'a>(&'a self, test_case: &'a TestCase) -> BoxFuture<'a, Result<(), Error>> {
+        async move {
+            let entry = package_version_dir(&self.dir, test_case);
+
+            match tokio::fs::remove_dir_all(&entry).await {
+                Ok(_) => Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => {
+                    Err(Error::new(e).context(format!("Unable to remove \"{}\"", entry.display())))
+                }
+            }
+        }
+        .boxed()
+    }
+}
+
+/// The names of a test case's tarball and `*.webc` file.
+pub(crate) fn artifact_names(test_case: &TestCase) -> (String, String) {
+    let (tarball, webc) = artifact_paths(Path::new(""), test_case);
+    (file_name(&tarball), file_name(&webc))
+}
+
+fn artifact_paths(entry: &Path, test_case: &TestCase) -> (PathBuf, PathBuf) {
+    let tarball = entry.join(&test_case.package_name).with_extension("tar.gz");
+    let webc = entry.join(&test_case.package_name).with_extension("webc");
+    (tarball, webc)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().unwrap().to_string_lossy().into_owned()
+}
+
+fn assets_in(entry: &Path, test_case: &TestCase) -> Result<Assets, Error> {
+    let (tarball, webc) = artifact_paths(entry, test_case);
+    let tarball_size = std::fs::metadata(&tarball)
+        .with_context(|| format!("Unable to read \"{}\"", tarball.display()))?
+        .len();
+
+    let assets = match std::fs::metadata(&webc) {
+        Ok(webc_meta) => Assets {
+            tarball: Some(tarball),
+            webc: Some(webc),
+            total_size: tarball_size + webc_meta.len(),
+        },
+        Err(_) => Assets {
+            tarball: Some(tarball),
+            webc: None,
+            total_size: tarball_size,
+        },
+    };
+
+    Ok(assets)
+}
+
+/// (Mostly atomically) replace the `dest` directory with `staging`.
+pub(crate) async fn persist(staging: &Path, dest: &Path) -> Result<(), Error> {
+    tracing::debug!(
+        from=%staging.display(),
+        to=%dest.display(),
+        "Persisting downloaded artifacts",
+    );
+
+    // Before persisting the downloaded directory, make sure we remove
+    // any existing stuff
+    if let Err(e) = tokio::fs::remove_dir_all(dest).await {
+        if e.kind() != std::io::ErrorKind::NotFound {
+            let error = Error::new(e).context(format!("Unable to remove \"{}\"", dest.display()));
+            return Err(error);
+        }
+    }
+
+    if let Some(parent) = dest.parent() {
+        if let Err(err) = tokio::fs::create_dir_all(parent).await {
+            let err = Error::new(err).context(format!("Unable to create \"{}\"", parent.display()));
+            return Err(err);
+        }
+    }
+
+    tokio::fs::rename(staging, dest).await.with_context(|| {
+        format!(
+            "Unable to persist \"{}\" to \"{}\"",
+            staging.display(),
+            dest.display()
+        )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::test_case;
+
+    #[tokio::test]
+    async fn store_and_evict_packages_on_disk() {
+        let temp = tempfile::tempdir().unwrap();
+        let storage = FileSystemStorage::new(temp.path().join("cache"));
+        let test_case = test_case("python", "3.12.0");
+        let staging = temp.path().join("staging");
+        std::fs::create_dir_all(&staging).unwrap();
+        std::fs::write(staging.join("python.tar.gz"), "tarball").unwrap();
+
+        assert!(!storage.contains(&test_case).await.unwrap());
+
+        let assets = storage.put(&test_case, &staging).await.unwrap();
+
+        assert_eq!(assets.total_size, 7);
+        assert_eq!(assets.webc, None);
+        let cached = storage.get(&test_case).await.unwrap().unwrap();
+        assert_eq!(cached.tarball, assets.tarball);
+        assert_eq!(
+            std::fs::read_to_string(cached.tarball.unwrap()).unwrap(),
+            "tarball"
+        );
+
+        storage.evict(&test_case).await.unwrap();
+
+        assert!(!storage.contains(&test_case).await.unwrap());
+        assert!(!assets.tarball.unwrap().exists());
+    }
+}
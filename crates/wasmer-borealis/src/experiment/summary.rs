@@ -0,0 +1,220 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Error;
+
+use crate::{
+    config::Experiment,
+    experiment::{CacheStats, Category, Report, Results},
+};
+
+/// The name of the file each [`Report`] is appended to as its test case
+/// completes.
+pub(crate) const REPORT_LOG: &str = "reports.jsonl";
+
+/// A finished experiment, where only a [`ReportSummary`] is kept in memory
+/// for each test case.
+///
+/// The full reports are in the experiment directory's `reports.jsonl` (see
+/// [`ResultsSummary::reports()`]) and `results.json` (see
+/// [`ResultsSummary::load()`]), so experiments with tens of thousands of test
+/// cases don't need to hold every report at once.
+#[derive(Debug, Clone)]
+pub struct ResultsSummary {
+    /// The experiment that was run, with secrets redacted.
+    pub experiment: Experiment,
+    /// A summary of each report, in the order they were written to the log.
+    pub reports: Vec<ReportSummary>,
+    /// How long the entire experiment took.
+    pub total_time: Duration,
+    /// The directory all experiment-related files were written to.
+    pub experiment_dir: PathBuf,
+    /// How effective the package cache was.
+    pub cache: CacheStats,
+}
+
+impl ResultsSummary {
+    /// The `results.json` file containing every report.
+    pub fn results_path(&self) -> PathBuf {
+        self.experiment_dir.join("results.json")
+    }
+
+    /// The `reports.jsonl` file each report was written to as its test case
+    /// completed.
+    pub fn report_log(&self) -> PathBuf {
+        self.experiment_dir.join(REPORT_LOG)
+    }
+
+    /// Load the full [`Results`] into memory.
+    pub fn load(&self) -> Result<Results, Error> {
+        Results::load(self.results_path())
+    }
+
+    /// Read each report back from the log, one at a time.
+    pub fn reports(&self) -> std::io::Result<impl Iterator<Item = std::io::Result<Report>>> {
+        let log = BufReader::new(File::open(self.report_log())?);
+
+        Ok(log.lines().map(|line| Ok(serde_json::from_str(&line?)?)))
+    }
+
+    /// Open the log so individual reports can be read back.
+    pub fn reader(&self) -> std::io::Result<ReportReader> {
+        ReportReader::open(self.report_log())
+    }
+
+    /// All reports for packages which ran successfully.
+    pub fn successes(&self) -> impl Iterator<Item = &ReportSummary> + '_ {
+        self.in_category(Category::Success)
+    }
+
+    /// All reports where the package ran, but didn't meet the experiment's
+    /// expectations.
+    pub fn failures(&self) -> impl Iterator<Item = &ReportSummary> + '_ {
+        self.in_category(Category::Failure)
+    }
+
+    /// All reports where something went wrong with borealis itself.
+    pub fn bugs(&self) -> impl Iterator<Item = &ReportSummary> + '_ {
+        self.in_category(Category::Bug)
+    }
+
+    fn in_category(&self, category: Category) -> impl Iterator<Item = &ReportSummary> + '_ {
+        self.reports.iter().filter(move |r| r.category == category)
+    }
+}
+
+/// The bits of a [`Report`] which are kept in memory after it has been
+/// written to the experiment's `reports.jsonl`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReportSummary {
+    pub display_name: String,
+    pub version: String,
+    pub category: Category,
+    /// Where the report starts in the log.
+    pub offset: u64,
+}
+
+impl ReportSummary {
+    /// Summarise a report which starts `offset` bytes into the log.
+    pub fn new(report: &Report, offset: u64) -> Self {
+        ReportSummary {
+            display_name: report.display_name.clone(),
+            version: report.version().to_string(),
+            category: report.category(),
+            offset,
+        }
+    }
+}
+
+/// Reads individual [`Report`]s from a `reports.jsonl` file.
+#[derive(Debug)]
+pub struct ReportReader {
+    log: BufReader<File>,
+}
+
+impl ReportReader {
+    pub fn open(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let log = BufReader::new(File::open(path)?);
+        Ok(ReportReader { log })
+    }
+
+    /// Read the report a [`ReportSummary`] refers to.
+    pub fn read(&mut self, summary: &ReportSummary) -> std::io::Result<Report> {
+        let mut line = String::new();
+        self.log.seek(SeekFrom::Start(summary.offset))?;
+        self.log.read_line(&mut line)?;
+
+        Ok(serde_json::from_str(&line)?)
+    }
+}
+
+#[cfg(feature = "run")]
+pub(crate) use self::save::save;
+
+#[cfg(feature = "run")]
+mod save {
+    use std::{
+        cell::RefCell,
+        io::{BufWriter, Write},
+        path::Path,
+        time::Duration,
+    };
+
+    use anyhow::{Context, Error};
+    use serde::{
+        ser::{Error as _, SerializeSeq},
+        Serialize, Serializer,
+    };
+
+    use super::{ReportReader, ReportSummary, ResultsSummary};
+    use crate::{config::Experiment, experiment::CacheStats};
+
+    /// Write a `results.json` file, copying each report across from the log
+    /// one at a time.
+    pub(crate) fn save(summary: &ResultsSummary) -> Result<(), Error> {
+        let log = summary.report_log();
+        let reader = ReportReader::open(&log)
+            .with_context(|| format!("Unable to open \"{}\"", log.display()))?;
+
+        let results = ResultsFile {
+            experiment: &summary.experiment,
+            reports: LoggedReports {
+                reader: RefCell::new(reader),
+                summaries: &summary.reports,
+            },
+            total_time: summary.total_time,
+            experiment_dir: &summary.experiment_dir,
+            cache: &summary.cache,
+        };
+
+        let path = summary.results_path();
+        let file = std::fs::File::create(&path)
+            .with_context(|| format!("Unable to create \"{}\"", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, &results)
+            .and_then(|_| writer.flush().map_err(serde_json::Error::io))
+            .with_context(|| format!("Unable to save to \"{}\"", path.display()))?;
+
+        Ok(())
+    }
+
+    /// Serializes the same way as [`Results`][crate::experiment::Results],
+    /// but reads each report from the log instead of keeping them all in
+    /// memory.
+    #[derive(Serialize)]
+    struct ResultsFile<'a> {
+        experiment: &'a Experiment,
+        reports: LoggedReports<'a>,
+        total_time: Duration,
+        experiment_dir: &'a Path,
+        cache: &'a CacheStats,
+    }
+
+    struct LoggedReports<'a> {
+        reader: RefCell<ReportReader>,
+        summaries: &'a [ReportSummary],
+    }
+
+    impl Serialize for LoggedReports<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut reader = self.reader.borrow_mut();
+            let mut seq = serializer.serialize_seq(Some(self.summaries.len()))?;
+
+            for summary in self.summaries {
+                let report = reader.read(summary).map_err(|e| {
+                    S::Error::custom(format!(
+                        "Unable to read the report for {} v{}: {e}",
+                        summary.display_name, summary.version
+                    ))
+                })?;
+                seq.serialize_element(&report)?;
+            }
+
+            seq.end()
+        }
+    }
+}
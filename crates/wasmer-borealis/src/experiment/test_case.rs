@@ -0,0 +1,83 @@
+use chrono::{DateTime, Utc};
+
+use crate::registry::queries::PackageVersion;
+
+/// A package version that will be included in the experiment.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TestCase {
+    /// The hostname of the registry this [`TestCase`] came from.
+    pub registry: String,
+    /// The namespace or user that owns the package.
+    pub namespace: String,
+    /// The package's name.
+    pub package_name: String,
+    #[serde(deserialize_with = "crate::registry::deserialize_package_version")]
+    pub package_version: PackageVersion,
+    /// Which of a local package's variants this is.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub variant: Option<String>,
+}
+
+impl TestCase {
+    #[cfg(feature = "run")]
+    pub(crate) fn new(
+        registry_hostname: &str,
+        namespace: String,
+        package_name: String,
+        package_version: PackageVersion,
+    ) -> Self {
+        TestCase {
+            registry: registry_hostname.to_string(),
+            namespace,
+            package_name,
+            package_version,
+            variant: None,
+        }
+    }
+
+    pub fn tarball_url(&self) -> &str {
+        &self.package_version.distribution.download_url
+    }
+
+    pub fn webc_url(&self) -> Option<&str> {
+        self.package_version
+            .distribution
+            .pirita_download_url
+            .as_deref()
+    }
+
+    pub fn version(&self) -> &str {
+        &self.package_version.version
+    }
+
+    /// The combined size of this package version's artifacts in bytes, as
+    /// reported by the registry.
+    pub fn artifact_size(&self) -> Option<u64> {
+        self.package_version.distribution.total_size()
+    }
+
+    /// When this package version was published.
+    pub fn published_at(&self) -> Option<DateTime<Utc>> {
+        self.package_version.created_at
+    }
+
+    /// The package's name (e.g. `wasmer/python`), followed by the variant
+    /// for local packages (e.g. `wasmer/python#verbose`).
+    pub fn display_name(&self) -> String {
+        match &self.variant {
+            Some(variant) => format!("{}/{}#{variant}", self.namespace, self.package_name),
+            None => format!("{}/{}", self.namespace, self.package_name),
+        }
+    }
+
+    /// A string which uniquely identifies this [`TestCase`] across registries
+    /// (i.e. `registry/namespace/name@version`).
+    pub fn id(&self) -> String {
+        format!(
+            "{}/{}@{}",
+            self.registry,
+            self.display_name(),
+            self.version()
+        )
+    }
+}
@@ -1,102 +1,192 @@
-use actix::{Actor, AsyncContext, Context, Handler, WrapFuture};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
 use futures::{channel::mpsc::Sender, SinkExt, Stream, StreamExt};
 use reqwest::Client;
 use tracing::Instrument;
 use url::Url;
 
 use crate::{
-    config::Filters,
-    registry::queries::{Package, PackageVersion},
+    config::{Filters, PinnedTestCase},
+    experiment::{
+        discovery::DiscoveryCache,
+        package_list::{self, PackageSpec},
+        TestCase,
+    },
+    registry::{
+        queries::{Package, PackageVersion},
+        RetryPolicy,
+    },
 };
 
 #[derive(Debug, Clone)]
 pub(crate) struct Wapm {
     client: Client,
     endpoint: Url,
+    retry: RetryPolicy,
 }
 
 impl Wapm {
-    /// Initialize the [`Wapm`] actor.
+    /// Initialize the [`Wapm`] client.
     ///
     /// # Authentication
     ///
     /// If you want access to all packages, you will need to make sure the
     /// [`Client`] has been configured to send the right `Authorization` header.
-    pub fn new(client: Client, endpoint: Url) -> Self {
-        Wapm { client, endpoint }
+    pub fn new(client: Client, endpoint: Url, retry: RetryPolicy) -> Self {
+        Wapm {
+            client,
+            endpoint,
+            retry,
+        }
     }
 }
 
-impl Actor for Wapm {
-    type Context = Context<Self>;
-}
-
-/// Tell [`Wapm`] to start looking for all [`TestCase`]s that should be
-/// included in the experiment.
-#[derive(Debug, Clone, actix::Message)]
-#[rtype(result = "()")]
-pub(crate) struct FetchTestCases {
-    pub filters: Filters,
-    pub recipient: Sender<TestCaseDiscovered>,
-}
-
 /// A batch of [`TestCase`]s have been discovered from the registry.
-#[derive(Debug, Clone, actix::Message)]
-#[rtype(result = "()")]
+#[derive(Debug, Clone)]
 pub(crate) struct TestCaseDiscovered(pub TestCase);
 
-impl Handler<FetchTestCases> for Wapm {
-    type Result = ();
-
-    fn handle(&mut self, msg: FetchTestCases, ctx: &mut Self::Context) {
-        let FetchTestCases {
-            filters,
-            mut recipient,
-        } = msg;
-
-        let client = self.client.clone();
-        let endpoint = self.endpoint.clone();
+impl Wapm {
+    /// Look for all [`TestCase`]s that should be included in the experiment,
+    /// sending them to the `recipient` as they are found.
+    ///
+    /// If everything was discovered without any errors, the test cases are
+    /// also saved to the `discovery` cache.
+    pub(crate) async fn fetch_test_cases(
+        self,
+        filters: Filters,
+        mut recipient: Sender<TestCaseDiscovered>,
+        discovery: DiscoveryCache,
+    ) {
+        let Wapm {
+            client,
+            endpoint,
+            retry,
+        } = self;
 
-        ctx.spawn(
-            async move {
-                let mut responses = discover_test_cases(client, filters, endpoint);
+        async move {
+            let failed = Arc::new(AtomicBool::new(false));
+            let mut responses =
+                discover_test_cases(client, filters, endpoint, retry, failed.clone());
+            let mut discovered = Vec::new();
 
-                while let Some(test_cases) = responses.next().await {
-                    for test_case in test_cases {
-                        if recipient.send(TestCaseDiscovered(test_case)).await.is_err() {
-                            break;
-                        };
-                    }
+            while let Some(test_cases) = responses.next().await {
+                for test_case in test_cases {
+                    if recipient
+                        .send(TestCaseDiscovered(test_case.clone()))
+                        .await
+                        .is_err()
+                    {
+                        // Nobody is listening, so we won't have the full list
+                        return;
+                    };
+                    discovered.push(test_case);
                 }
             }
-            .instrument(tracing::debug_span!("discover_test_cases"))
-            .into_actor(self),
-        );
+
+            if !failed.load(Ordering::SeqCst) {
+                discovery.save(discovered);
+            }
+        }
+        .instrument(tracing::debug_span!("discover_test_cases"))
+        .await
+    }
+
+    /// Look up an explicit list of packages instead of walking the registry.
+    pub(crate) async fn resolve_packages(
+        self,
+        packages: Vec<PackageSpec>,
+        recipient: Sender<TestCaseDiscovered>,
+    ) {
+        let Wapm {
+            client,
+            endpoint,
+            retry,
+        } = self;
+
+        package_list::resolve(client, endpoint, retry, packages, recipient)
+            .instrument(tracing::debug_span!("resolve_packages"))
+            .await
+    }
+
+    /// Look up the experiment's inline test cases.
+    pub(crate) async fn resolve_pinned(
+        self,
+        test_cases: Vec<PinnedTestCase>,
+        recipient: Sender<TestCaseDiscovered>,
+    ) {
+        let Wapm {
+            client,
+            endpoint,
+            retry,
+        } = self;
+
+        package_list::resolve_pinned(client, endpoint, retry, test_cases, recipient)
+            .instrument(tracing::debug_span!("resolve_pinned"))
+            .await
     }
 }
 
+/// Find every [`TestCase`] on the registry at `endpoint` that matches the
+/// `filters`, without running anything.
+///
+/// Fails if any of the registry queries failed, because the list would be
+/// incomplete.
+pub async fn discover(
+    client: Client,
+    endpoint: Url,
+    filters: Filters,
+    retry: RetryPolicy,
+) -> Result<Vec<TestCase>, anyhow::Error> {
+    let failed = Arc::new(AtomicBool::new(false));
+    let test_cases: Vec<TestCase> =
+        discover_test_cases(client, filters, endpoint, retry, failed.clone())
+            .flat_map(futures::stream::iter)
+            .collect()
+            .instrument(tracing::debug_span!("discover"))
+            .await;
+
+    anyhow::ensure!(
+        !failed.load(Ordering::SeqCst),
+        "Unable to query every package from the registry"
+    );
+
+    Ok(test_cases)
+}
+
 /// Discover [`TestCase`]s, retrieving them page-by-page.
 fn discover_test_cases(
     client: Client,
     filters: Filters,
     endpoint: Url,
+    retry: RetryPolicy,
+    failed: Arc<AtomicBool>,
 ) -> impl Stream<Item = Vec<TestCase>> {
     let (mut sender, receiver) = futures::channel::mpsc::channel(1);
     let Filters {
         namespaces,
-        blacklist,
+        blacklist: _,
         include_every_version,
+        max_versions_per_package,
         users,
-    } = filters;
+        published_after: _,
+        published_before: _,
+    } = filters.clone();
 
     let hostname = endpoint.host_str().unwrap_or("unknown").to_string();
 
     if namespaces.is_empty() && users.is_empty() {
         tokio::spawn(async move {
             if let Err(e) =
-                crate::registry::all_packages(&client, endpoint.as_str(), &mut sender).await
+                crate::registry::all_packages(&client, endpoint.as_str(), &retry, &mut sender).await
             {
-                tracing::error!(error = &*e, "Unable to list all packages");
+                failed.store(true, Ordering::SeqCst);
+                tracing::error!(
+                    error = &e as &dyn std::error::Error,
+                    "Unable to list all packages"
+                );
             }
         });
     } else {
@@ -105,13 +195,15 @@ fn discover_test_cases(
                 if let Err(e) = crate::registry::all_packages_in_namespace(
                     &client,
                     endpoint.as_str(),
+                    &retry,
                     namespace,
                     &mut sender,
                 )
                 .await
                 {
+                    failed.store(true, Ordering::SeqCst);
                     tracing::error!(
-                        error = &*e,
+                        error = &e as &dyn std::error::Error,
                         namespace = namespace.as_str(),
                         "Unable to fetch a namespace's packages"
                     );
@@ -122,13 +214,15 @@ fn discover_test_cases(
                 if let Err(e) = crate::registry::all_packages_by_user(
                     &client,
                     endpoint.as_str(),
+                    &retry,
                     user,
                     &mut sender,
                 )
                 .await
                 {
+                    failed.store(true, Ordering::SeqCst);
                     tracing::error!(
-                        error = &*e,
+                        error = &e as &dyn std::error::Error,
                         user = user.as_str(),
                         "Unable to fetch a user's packages"
                     );
@@ -139,30 +233,21 @@ fn discover_test_cases(
 
     receiver.map(move |page| {
         page.into_iter()
-            .filter(|pkg| blacklist.is_empty() || !blacklist.contains(&pkg.display_name))
             .flat_map(|pkg| {
-                if include_every_version {
-                    TestCase::all(&hostname, pkg)
-                } else {
-                    TestCase::latest(&hostname, pkg)
+                let in_range = |v: &PackageVersion| filters.published_within_range(v.created_at);
+
+                match (include_every_version, max_versions_per_package) {
+                    (_, Some(n)) => TestCase::most_recent(&hostname, pkg, n, in_range),
+                    (true, None) => TestCase::all(&hostname, pkg),
+                    (false, None) => TestCase::latest(&hostname, pkg, in_range),
                 }
             })
+            .filter(|tc| !filters.is_blacklisted(&tc.display_name(), tc.version()))
+            .filter(|tc| filters.published_within_range(tc.package_version.created_at))
             .collect()
     })
 }
 
-/// A package version that will be included in the experiment.
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
-pub struct TestCase {
-    /// The hostname of the registry this [`TestCase`] came from.
-    pub registry: String,
-    /// The namespace or user that owns the package.
-    pub namespace: String,
-    /// The package's name.
-    pub package_name: String,
-    pub package_version: PackageVersion,
-}
-
 impl TestCase {
     fn all(registry_hostname: &str, pkg: Package) -> Vec<TestCase> {
         pkg.versions
@@ -179,49 +264,85 @@ impl TestCase {
             .collect()
     }
 
-    fn latest(registry: &str, pkg: Package) -> Vec<TestCase> {
-        if let Some(version) = pkg.last_version {
-            vec![TestCase::new(
+    /// Get up to `count` of the most recent versions of a package which match
+    /// the `filter`.
+    fn most_recent(
+        registry: &str,
+        pkg: Package,
+        count: usize,
+        filter: impl Fn(&PackageVersion) -> bool,
+    ) -> Vec<TestCase> {
+        let mut versions: Vec<_> = pkg.versions.into_iter().flatten().filter(filter).collect();
+        versions.sort_by_cached_key(|v| {
+            std::cmp::Reverse((semver::Version::parse(&v.version).ok(), v.created_at))
+        });
+        versions.truncate(count);
+
+        versions
+            .into_iter()
+            .map(|version| {
+                TestCase::new(
+                    registry,
+                    pkg.namespace.clone(),
+                    pkg.package_name.clone(),
+                    version,
+                )
+            })
+            .collect()
+    }
+
+    /// Get the most recent version of a package, falling back to the most
+    /// recently published version that matches the `filter` if the latest
+    /// version doesn't.
+    fn latest(
+        registry: &str,
+        pkg: Package,
+        filter: impl Fn(&PackageVersion) -> bool,
+    ) -> Vec<TestCase> {
+        let version = match pkg.last_version {
+            Some(version) if filter(&version) => Some(version),
+            _ => pkg
+                .versions
+                .into_iter()
+                .flatten()
+                .filter(|v| filter(v))
+                .max_by_key(|v| v.created_at),
+        };
+
+        match version {
+            Some(version) => vec![TestCase::new(
                 registry,
                 pkg.namespace,
                 pkg.package_name,
                 version,
-            )]
-        } else {
-            Vec::new()
-        }
-    }
-
-    fn new(
-        registry_hostname: &str,
-        namespace: String,
-        package_name: String,
-        package_version: PackageVersion,
-    ) -> Self {
-        TestCase {
-            registry: registry_hostname.to_string(),
-            namespace,
-            package_name,
-            package_version,
+            )],
+            None => Vec::new(),
         }
     }
+}
 
-    pub fn tarball_url(&self) -> &str {
-        &self.package_version.distribution.download_url
-    }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    pub fn webc_url(&self) -> Option<&str> {
-        self.package_version
-            .distribution
-            .pirita_download_url
-            .as_deref()
-    }
+    #[test]
+    fn most_recent_versions_are_ordered_by_semver() {
+        let versions = ["0.9.0", "1.10.0", "1.2.0", "not-semver", "1.9.0"];
+        let pkg = Package {
+            id: cynic::Id::new("python"),
+            package_name: "python".to_string(),
+            namespace: "wasmer".to_string(),
+            display_name: "wasmer/python".to_string(),
+            last_version: None,
+            versions: versions
+                .iter()
+                .map(|v| Some(crate::test_utils::test_case("python", v).package_version))
+                .collect(),
+        };
 
-    pub fn version(&self) -> &str {
-        &self.package_version.version
-    }
+        let test_cases = TestCase::most_recent("wasmer.io", pkg, 3, |v| v.version != "1.9.0");
 
-    pub fn display_name(&self) -> String {
-        format!("{}/{}", self.namespace, self.package_name)
+        let versions: Vec<_> = test_cases.iter().map(|tc| tc.version()).collect();
+        assert_eq!(versions, ["1.10.0", "1.2.0", "0.9.0"]);
     }
 }
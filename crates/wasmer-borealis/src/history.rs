@@ -0,0 +1,153 @@
+//! A persistent record of previous experiment runs.
+
+use std::{
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Error};
+use chrono::{DateTime, Utc};
+
+use crate::experiment::Results;
+
+/// A history store that keeps a [`RunRecord`] for each experiment run.
+///
+/// Records are appended to a JSON-lines file so the history can be updated
+/// without needing to re-write the entire file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct History {
+    path: PathBuf,
+}
+
+impl History {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        History { path: path.into() }
+    }
+
+    /// The history store used when no other location is specified.
+    pub fn default_location() -> PathBuf {
+        crate::DIRS.data_local_dir().join("history.jsonl")
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add a record to the history.
+    pub fn record(&self, record: &RunRecord) -> Result<(), Error> {
+        append_jsonl(&self.path, record)
+    }
+
+    /// Read every record in the history, oldest first.
+    ///
+    /// A history store that doesn't exist yet is treated as empty.
+    pub fn records(&self) -> Result<Vec<RunRecord>, Error> {
+        read_jsonl(&self.path)
+    }
+
+    /// Get the records for a particular experiment, oldest first.
+    pub fn runs_of(&self, experiment: &str) -> Result<Vec<RunRecord>, Error> {
+        let mut records = self.records()?;
+        records.retain(|r| r.experiment == experiment);
+        Ok(records)
+    }
+}
+
+/// Append a value to a JSON-lines file, creating it if necessary.
+pub(crate) fn append_jsonl(path: &Path, value: &impl serde::Serialize) -> Result<(), Error> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("Unable to create \"{}\"", parent.display()))?;
+    }
+
+    let mut line = serde_json::to_string(value)?;
+    line.push('\n');
+
+    let mut f = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Unable to open \"{}\"", path.display()))?;
+    f.write_all(line.as_bytes())
+        .with_context(|| format!("Unable to write to \"{}\"", path.display()))?;
+
+    Ok(())
+}
+
+/// Read every line of a JSON-lines file, treating a missing file as empty.
+pub(crate) fn read_jsonl<T: serde::de::DeserializeOwned>(path: &Path) -> Result<Vec<T>, Error> {
+    let f = match std::fs::File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => {
+            return Err(Error::new(e).context(format!("Unable to open \"{}\"", path.display())))
+        }
+    };
+
+    let mut values = Vec::new();
+
+    for (i, line) in BufReader::new(f).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let value = serde_json::from_str(&line)
+            .with_context(|| format!("Unable to parse line {} of \"{}\"", i + 1, path.display()))?;
+        values.push(value);
+    }
+
+    Ok(values)
+}
+
+/// A summary of a single experiment run.
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RunRecord {
+    /// The name of the experiment that was run.
+    pub experiment: String,
+    /// The package used by the experiment.
+    pub package: String,
+    /// When the run started.
+    pub started: DateTime<Utc>,
+    /// The directory the experiment's results were written to.
+    pub experiment_dir: PathBuf,
+    pub total: usize,
+    pub success: usize,
+    pub failures: usize,
+    pub bugs: usize,
+    /// How long the run took.
+    pub total_time: Duration,
+}
+
+impl RunRecord {
+    pub fn new(experiment: impl Into<String>, started: DateTime<Utc>, results: &Results) -> Self {
+        RunRecord {
+            experiment: experiment.into(),
+            package: results.experiment.package.clone(),
+            started,
+            experiment_dir: results.experiment_dir.clone(),
+            total: results.reports.len(),
+            success: results.successes().count(),
+            failures: results.failures().count(),
+            bugs: results.bugs().count(),
+            total_time: results.total_time,
+        }
+    }
+
+    /// The fraction of test cases (between `0.0` and `1.0`) that were
+    /// successful.
+    pub fn success_rate(&self) -> f64 {
+        if self.total == 0 {
+            return 0.0;
+        }
+
+        self.success as f64 / self.total as f64
+    }
+
+    /// The `results.json` file for this run.
+    pub fn results_path(&self) -> PathBuf {
+        self.experiment_dir.join("results.json")
+    }
+}
@@ -1,7 +1,19 @@
+pub mod alerts;
+pub mod bundle;
 pub mod config;
 pub mod experiment;
+pub mod history;
 pub mod registry;
+#[cfg(feature = "render")]
 pub mod render;
+pub mod runs;
+#[cfg(feature = "run")]
+pub mod server;
+#[cfg(test)]
+mod test_utils;
+#[cfg(feature = "run")]
+pub mod toolchain;
+pub mod user_config;
 
 use directories::ProjectDirs;
 use once_cell::sync::Lazy;
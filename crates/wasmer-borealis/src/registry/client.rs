@@ -0,0 +1,289 @@
+use cynic::{GraphQlError, GraphQlResponse, Operation, QueryBuilder};
+use futures::{Sink, SinkExt};
+use reqwest::Client;
+
+use crate::registry::{
+    queries::{self, Variables},
+    RetryPolicy,
+};
+
+/// An error that occurred while querying the registry.
+#[derive(Debug, thiserror::Error)]
+pub enum RegistryError {
+    /// The request couldn't be sent, or the registry responded with an error
+    /// status code.
+    #[error("The request to the registry failed")]
+    Http(#[from] reqwest::Error),
+    /// The registry returned GraphQL errors.
+    #[error("The query failed: {}", messages.join("; "))]
+    Query { messages: Vec<String> },
+    /// The response contained neither data nor errors.
+    #[error("The registry didn't return any data")]
+    MissingData,
+    #[error("Unknown user, \"{0}\"")]
+    UnknownUser(String),
+    #[error("Unknown namespace, \"{0}\"")]
+    UnknownNamespace(String),
+    /// The packages couldn't be passed to the caller's [`Sink`].
+    #[error("Unable to forward the packages")]
+    Sink(#[source] Box<dyn std::error::Error + Send + Sync>),
+}
+
+#[tracing::instrument(skip_all)]
+pub async fn all_packages<S>(
+    client: &Client,
+    graphql_endpoint: &str,
+    retry: &RetryPolicy,
+    mut dest: S,
+) -> Result<(), RegistryError>
+where
+    S: Sink<Vec<queries::Package>> + Unpin,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    let op = queries::GetAllPackages::build(());
+
+    let response: GraphQlResponse<queries::GetAllPackages> =
+        post_graphql(client, graphql_endpoint, retry, &op).await?;
+
+    if let Some(errors) = response.errors {
+        return Err(aggregate_errors(errors));
+    }
+
+    let packages: Vec<_> = response
+        .data
+        .and_then(|g| g.packages)
+        .into_iter()
+        .flat_map(|p| p.edges)
+        .flatten()
+        .flat_map(|p| p.node)
+        .collect();
+
+    tracing::debug!(count = packages.len(), "Listed all packages");
+
+    dest.send(packages).await.map_err(sink_error)?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip_all, fields(username))]
+pub async fn all_packages_by_user<S>(
+    client: &Client,
+    graphql_endpoint: &str,
+    retry: &RetryPolicy,
+    username: &str,
+    dest: S,
+) -> Result<(), RegistryError>
+where
+    S: Sink<Vec<queries::Package>> + Unpin,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    packages_query(
+        client,
+        graphql_endpoint,
+        retry,
+        dest,
+        |offset| {
+            queries::GetUserPackages::build(Variables {
+                name: username,
+                offset,
+            })
+        },
+        |result| {
+            let user = result
+                .get_user
+                .ok_or_else(|| RegistryError::UnknownUser(username.to_string()))?;
+            Ok(user.packages)
+        },
+    )
+    .await
+}
+
+#[tracing::instrument(skip_all, fields(namespace))]
+pub async fn all_packages_in_namespace<S>(
+    client: &Client,
+    graphql_endpoint: &str,
+    retry: &RetryPolicy,
+    namespace: &str,
+    dest: S,
+) -> Result<(), RegistryError>
+where
+    S: Sink<Vec<queries::Package>> + Unpin,
+    S::Error: std::error::Error + Send + Sync + 'static,
+{
+    packages_query(
+        client,
+        graphql_endpoint,
+        retry,
+        dest,
+        |offset| {
+            queries::GetNamespace::build(Variables {
+                name: namespace,
+                offset,
+            })
+        },
+        |result| {
+            let ns = result
+                .get_namespace
+                .ok_or_else(|| RegistryError::UnknownNamespace(namespace.to_string()))?;
+            Ok(ns.packages)
+        },
+    )
+    .await
+}
+
+/// Look up a single package by name (e.g. `"wasmer/python"`), returning
+/// `None` if it doesn't exist.
+#[tracing::instrument(skip_all, fields(name))]
+pub async fn get_package(
+    client: &Client,
+    graphql_endpoint: &str,
+    retry: &RetryPolicy,
+    name: &str,
+) -> Result<Option<queries::Package>, RegistryError> {
+    let op = queries::GetPackage::build(queries::GetPackageVariables { name });
+
+    let response: GraphQlResponse<queries::GetPackage> =
+        post_graphql(client, graphql_endpoint, retry, &op).await?;
+
+    if let Some(errors) = response.errors {
+        if !errors.is_empty() {
+            return Err(aggregate_errors(errors));
+        }
+    }
+
+    Ok(response.data.and_then(|d| d.get_package))
+}
+
+/// Look up a particular version of a package, returning `None` if it doesn't
+/// exist.
+#[tracing::instrument(skip_all, fields(name, version))]
+pub async fn get_package_version(
+    client: &Client,
+    graphql_endpoint: &str,
+    retry: &RetryPolicy,
+    name: &str,
+    version: &str,
+) -> Result<Option<queries::PackageVersion>, RegistryError> {
+    let op =
+        queries::GetPackageVersion::build(queries::GetPackageVersionVariables { name, version });
+
+    let response: GraphQlResponse<queries::GetPackageVersion> =
+        post_graphql(client, graphql_endpoint, retry, &op).await?;
+
+    if let Some(errors) = response.errors {
+        if !errors.is_empty() {
+            return Err(aggregate_errors(errors));
+        }
+    }
+
+    Ok(response.data.and_then(|d| d.get_package_version))
+}
+
+/// Get the username of whoever the client is authenticated as, returning
+/// `None` if the client doesn't have a (valid) token.
+#[tracing::instrument(skip_all)]
+pub async fn whoami(
+    client: &Client,
+    graphql_endpoint: &str,
+    retry: &RetryPolicy,
+) -> Result<Option<String>, RegistryError> {
+    let op = queries::GetViewer::build(());
+
+    let response: GraphQlResponse<queries::GetViewer> =
+        post_graphql(client, graphql_endpoint, retry, &op).await?;
+
+    if let Some(errors) = response.errors {
+        if !errors.is_empty() {
+            return Err(aggregate_errors(errors));
+        }
+    }
+
+    Ok(response
+        .data
+        .and_then(|d| d.viewer)
+        .map(|viewer| viewer.username))
+}
+
+#[tracing::instrument(skip_all, fields(namespace))]
+pub async fn packages_query<'a, S, Q, Build, GetPackages>(
+    client: &Client,
+    graphql_endpoint: &str,
+    retry: &RetryPolicy,
+    mut dest: S,
+    build: Build,
+    get_packages: GetPackages,
+) -> Result<(), RegistryError>
+where
+    S: Sink<Vec<queries::Package>> + Unpin,
+    S::Error: std::error::Error + Send + Sync + 'static,
+    Build: Fn(i32) -> Operation<Q, Variables<'a>>,
+    GetPackages: Fn(Q) -> Result<queries::PackageConnection, RegistryError>,
+    Q: serde::de::DeserializeOwned,
+{
+    let mut offset = 0;
+
+    loop {
+        let op = build(offset);
+
+        tracing::debug!(offset, "Fetching a page of packages");
+
+        let response: GraphQlResponse<Q> =
+            post_graphql(client, graphql_endpoint, retry, &op).await?;
+
+        if let Some(errors) = response.errors {
+            if !errors.is_empty() {
+                return Err(aggregate_errors(errors));
+            }
+        }
+
+        let query_result = response.data.ok_or(RegistryError::MissingData)?;
+        let packages: Vec<_> = get_packages(query_result)?
+            .edges
+            .into_iter()
+            .flatten()
+            .flat_map(|edge| edge.node)
+            .collect();
+
+        if packages.is_empty() {
+            break;
+        }
+
+        offset += i32::try_from(packages.len()).unwrap();
+        dest.send(packages).await.map_err(sink_error)?;
+        dest.flush().await.map_err(sink_error)?;
+    }
+
+    Ok(())
+}
+
+async fn post_graphql<R>(
+    client: &Client,
+    graphql_endpoint: &str,
+    retry: &RetryPolicy,
+    op: &impl serde::Serialize,
+) -> Result<R, RegistryError>
+where
+    R: serde::de::DeserializeOwned,
+{
+    let response = retry
+        .send(|| {
+            client
+                .post(graphql_endpoint)
+                .header("Content-Type", "application/json")
+                .json(op)
+        })
+        .await?
+        .json()
+        .await?;
+
+    Ok(response)
+}
+
+fn aggregate_errors(errors: Vec<GraphQlError>) -> RegistryError {
+    let messages = errors.into_iter().map(|e| e.message).collect();
+    RegistryError::Query { messages }
+}
+
+fn sink_error(e: impl std::error::Error + Send + Sync + 'static) -> RegistryError {
+    RegistryError::Sink(Box::new(e))
+}
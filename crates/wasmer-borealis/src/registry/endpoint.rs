@@ -0,0 +1,95 @@
+use url::Url;
+
+/// Well-known registries which can be referred to by name.
+const ALIASES: &[(&str, &str)] = &[
+    ("prod", "https://registry.wasmer.io/graphql"),
+    ("production", "https://registry.wasmer.io/graphql"),
+    ("staging", "https://registry.wasmer.wtf/graphql"),
+    ("dev", "https://registry.wasmer.wtf/graphql"),
+    ("local", "http://localhost:8080/graphql"),
+];
+
+/// Turn whatever the user passed as their registry into the URL of its
+/// GraphQL endpoint.
+///
+/// This accepts
+///
+/// - an alias (`"prod"`, `"staging"`, or `"local"`),
+/// - a domain name (`"wasmer.io"` becomes
+///   `"https://registry.wasmer.io/graphql"`), or
+/// - a URL, which is used as-is unless it has no path (`"https://example.com/"`
+///   becomes `"https://example.com/graphql"`).
+pub fn format_graphql(registry: &str) -> String {
+    let registry = registry.trim();
+
+    if let Some((_, endpoint)) = ALIASES
+        .iter()
+        .find(|(alias, _)| alias.eq_ignore_ascii_case(registry))
+    {
+        return endpoint.to_string();
+    }
+
+    if let Ok(mut url) = Url::parse(registry) {
+        // Looks like we've got a valid URL. Let's try to use it as-is.
+        if url.has_host() {
+            if url.path() == "/" {
+                // make sure we convert http://registry.wasmer.io/ to
+                // http://registry.wasmer.io/graphql
+                url.set_path("/graphql");
+            }
+
+            return url.to_string();
+        }
+    }
+
+    let domain_name = registry.trim_end_matches('/');
+    if !domain_name.contains("://") && !domain_name.contains('/') {
+        return endpoint_from_domain_name(domain_name);
+    }
+
+    // looks like we've received something we can't deal with. Just pass it
+    // through as-is and hopefully it'll either work or the end user can figure
+    // it out
+    registry.to_string()
+}
+
+/// By convention, something like `"wasmer.io"` should be converted to
+/// `"https://registry.wasmer.io/graphql"`.
+fn endpoint_from_domain_name(domain_name: &str) -> String {
+    if domain_name.contains("localhost") || domain_name.starts_with("127.0.0.1") {
+        return format!("http://{domain_name}/graphql");
+    }
+
+    format!("https://registry.{domain_name}/graphql")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registries_are_normalized_to_graphql_endpoints() {
+        let inputs = [
+            ("prod", "https://registry.wasmer.io/graphql"),
+            ("Staging", "https://registry.wasmer.wtf/graphql"),
+            ("local", "http://localhost:8080/graphql"),
+            ("wasmer.io", "https://registry.wasmer.io/graphql"),
+            (" wasmer.io/ ", "https://registry.wasmer.io/graphql"),
+            ("localhost:8080", "http://localhost:8080/graphql"),
+            ("127.0.0.1:8080", "http://127.0.0.1:8080/graphql"),
+            (
+                "https://registry.wasmer.io",
+                "https://registry.wasmer.io/graphql",
+            ),
+            (
+                "http://example.com/custom/graphql",
+                "http://example.com/custom/graphql",
+            ),
+            ("some/weird/path", "some/weird/path"),
+        ];
+
+        for (registry, expected) in inputs {
+            assert_eq!(format_graphql(registry), expected, "{registry}");
+        }
+    }
+}
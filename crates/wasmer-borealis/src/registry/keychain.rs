@@ -0,0 +1,216 @@
+//! Keeping registry tokens in the operating system's keychain, so they don't
+//! end up in shell history or plaintext env files.
+//!
+//! Tokens are keyed by the registry's GraphQL endpoint (see
+//! [`format_graphql()`][super::format_graphql]), so `prod` and `wasmer.io`
+//! share a token.
+//!
+//! This goes through each platform's command-line tool (`security` on macOS,
+//! PowerShell's access to the Credential Manager on Windows, and
+//! `secret-tool` from libsecret elsewhere) and never passes the token as a
+//! command-line argument, where other users could see it.
+
+use std::process::{Command, Stdio};
+
+use anyhow::{Context, Error};
+
+/// The service name tokens are saved under.
+#[cfg(any(unix, windows))]
+const SERVICE: &str = "wasmer-borealis";
+
+/// Loads the Credential Manager's `PasswordVault` into `$vault`. The service
+/// and registry are passed in through environment variables so they don't
+/// need quoting.
+#[cfg(windows)]
+const OPEN_VAULT: &str = "$ErrorActionPreference = 'Stop'; \
+    [void][Windows.Security.Credentials.PasswordVault,Windows.Security.Credentials,ContentType=WindowsRuntime]; \
+    $vault = New-Object Windows.Security.Credentials.PasswordVault;";
+
+/// Save the token for a registry, replacing any previous token.
+pub fn save_token(endpoint: &str, token: &str) -> Result<(), Error> {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "macos")] {
+            // "security -i" reads commands from stdin, which keeps the token
+            // out of the process list
+            let command = format!(
+                "add-generic-password -U -s {} -a {} -w {}\n",
+                quote(SERVICE),
+                quote(endpoint),
+                quote(token),
+            );
+            run_with_stdin(Command::new("security").arg("-i"), &command)
+        } else if #[cfg(unix)] {
+            let label = format!("wasmer-borealis token for {endpoint}");
+            run_with_stdin(
+                Command::new("secret-tool")
+                    .args(["store", "--label", &label])
+                    .args(["service", SERVICE, "registry", endpoint]),
+                token,
+            )
+        } else if #[cfg(windows)] {
+            // The token is read from stdin to keep it out of the process list
+            let script = format!(
+                "{OPEN_VAULT} $token = [Console]::In.ReadLine(); \
+                $vault.Add((New-Object Windows.Security.Credentials.PasswordCredential($env:BOREALIS_SERVICE, $env:BOREALIS_REGISTRY, $token)))"
+            );
+            run_with_stdin(&mut powershell(&script, endpoint), token)
+        } else {
+            let _ = (endpoint, token);
+            anyhow::bail!(
+                "Saving tokens to the keychain isn't supported on this platform, use --token or \"token-command\" instead"
+            )
+        }
+    }
+}
+
+/// Look up the token for a registry, returning `None` if there isn't one or
+/// the keychain isn't available.
+pub fn load_token(endpoint: &str) -> Result<Option<String>, Error> {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "macos")] {
+            lookup(Command::new("security").args([
+                "find-generic-password",
+                "-s",
+                SERVICE,
+                "-a",
+                endpoint,
+                "-w",
+            ]))
+        } else if #[cfg(unix)] {
+            lookup(Command::new("secret-tool").args([
+                "lookup",
+                "service",
+                SERVICE,
+                "registry",
+                endpoint,
+            ]))
+        } else if #[cfg(windows)] {
+            // Retrieve() throws when there is no token, which exits with an
+            // error
+            let script = format!(
+                "{OPEN_VAULT} $credential = $vault.Retrieve($env:BOREALIS_SERVICE, $env:BOREALIS_REGISTRY); \
+                $credential.RetrievePassword(); \
+                [Console]::Out.Write($credential.Password)"
+            );
+            lookup(&mut powershell(&script, endpoint))
+        } else {
+            let _ = endpoint;
+            Ok(None)
+        }
+    }
+}
+
+/// Remove the token for a registry, if there is one.
+pub fn delete_token(endpoint: &str) -> Result<(), Error> {
+    cfg_if::cfg_if! {
+        if #[cfg(target_os = "macos")] {
+            // A missing token isn't an error, so ignore the exit code
+            Command::new("security")
+                .args(["delete-generic-password", "-s", SERVICE, "-a", endpoint])
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .context("Unable to run \"security\"")?;
+            Ok(())
+        } else if #[cfg(unix)] {
+            let status = Command::new("secret-tool")
+                .args(["clear", "service", SERVICE, "registry", endpoint])
+                .status()
+                .context("Unable to run \"secret-tool\"")?;
+            anyhow::ensure!(status.success(), "\"secret-tool\" failed ({status})");
+            Ok(())
+        } else if #[cfg(windows)] {
+            // A missing token isn't an error
+            let script = format!(
+                "{OPEN_VAULT} try {{ $vault.Remove($vault.Retrieve($env:BOREALIS_SERVICE, $env:BOREALIS_REGISTRY)) }} catch {{ }}"
+            );
+            let status = powershell(&script, endpoint)
+                .stdout(Stdio::null())
+                .status()
+                .context("Unable to run \"powershell\"")?;
+            anyhow::ensure!(status.success(), "\"powershell\" failed ({status})");
+            Ok(())
+        } else {
+            let _ = endpoint;
+            Ok(())
+        }
+    }
+}
+
+/// Run a PowerShell script with access to the service and registry a token
+/// is saved under.
+#[cfg(windows)]
+fn powershell(script: &str, endpoint: &str) -> Command {
+    let mut cmd = Command::new("powershell");
+    cmd.args(["-NoProfile", "-NonInteractive", "-Command", script])
+        .env("BOREALIS_SERVICE", SERVICE)
+        .env("BOREALIS_REGISTRY", endpoint);
+    cmd
+}
+
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+fn run_with_stdin(cmd: &mut Command, stdin: &str) -> Result<(), Error> {
+    use std::io::Write;
+
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let mut child = cmd
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Unable to run \"{program}\", is it installed?"))?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(stdin.as_bytes())
+        .with_context(|| format!("Unable to send the token to \"{program}\""))?;
+
+    let status = child.wait()?;
+    anyhow::ensure!(status.success(), "\"{program}\" failed ({status})");
+
+    Ok(())
+}
+
+#[cfg_attr(not(any(unix, windows)), allow(dead_code))]
+fn lookup(cmd: &mut Command) -> Result<Option<String>, Error> {
+    let output = match cmd.stderr(Stdio::null()).output() {
+        Ok(output) => output,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            tracing::debug!(
+                program = %cmd.get_program().to_string_lossy(),
+                "The keychain isn't available",
+            );
+            return Ok(None);
+        }
+        Err(e) => return Err(Error::new(e).context("Unable to read from the keychain")),
+    };
+
+    // Every tool exits with an error when nothing was found
+    let token = String::from_utf8(output.stdout).context("The token wasn't valid UTF-8")?;
+    let token = token.trim();
+
+    if output.status.success() && !token.is_empty() {
+        Ok(Some(token.to_string()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// Quote an argument for `security -i`, which splits commands like a shell.
+#[cfg(unix)]
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn quote(arg: &str) -> String {
+    format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_arguments_are_escaped() {
+        assert_eq!(quote("abc123"), r#""abc123""#);
+        assert_eq!(quote(r#"a"b\c"#), r#""a\"b\\c""#);
+    }
+}
@@ -1,174 +1,51 @@
-use anyhow::{Context, Error};
-use cynic::{GraphQlError, GraphQlResponse, Operation, QueryBuilder};
-use futures::{Sink, SinkExt};
-use reqwest::Client;
-
-use crate::registry::queries::Variables;
-
-#[tracing::instrument(skip_all)]
-pub async fn all_packages<S>(
-    client: &Client,
-    graphql_endpoint: &str,
-    mut dest: S,
-) -> Result<(), Error>
+#[cfg(feature = "run")]
+mod client;
+mod endpoint;
+mod keychain;
+#[cfg(feature = "run")]
+mod retry;
+
+#[cfg(feature = "run")]
+pub use self::{
+    client::{
+        all_packages, all_packages_by_user, all_packages_in_namespace, get_package,
+        get_package_version, packages_query, whoami, RegistryError,
+    },
+    retry::RetryPolicy,
+};
+pub use self::{
+    endpoint::format_graphql,
+    keychain::{delete_token, load_token, save_token},
+};
+
+/// Deserialize a [`queries::PackageVersion`], tolerating fields that were
+/// added after older `results.json` and `test_case.json` files were written.
+pub(crate) fn deserialize_package_version<'de, D>(
+    deserializer: D,
+) -> Result<queries::PackageVersion, D::Error>
 where
-    S: Sink<Vec<queries::Package>> + Unpin,
-    S::Error: std::error::Error + Send + Sync + 'static,
+    D: serde::Deserializer<'de>,
 {
-    let op = queries::GetAllPackages::build(());
-
-    let response: GraphQlResponse<queries::GetAllPackages> = client
-        .post(graphql_endpoint)
-        .header("Content-Type", "application/json")
-        .json(&op)
-        .send()
-        .await?
-        .error_for_status()?
-        .json()
-        .await?;
-
-    if let Some(errors) = response.errors {
-        return Err(aggregate_errors(errors));
-    }
-
-    let packages: Vec<_> = response
-        .data
-        .and_then(|g| g.packages)
-        .into_iter()
-        .flat_map(|p| p.edges)
-        .flatten()
-        .flat_map(|p| p.node)
-        .collect();
-
-    tracing::debug!(count = packages.len(), "Listed all packages");
-
-    dest.send(packages).await?;
-
-    Ok(())
-}
-
-#[tracing::instrument(skip_all, fields(username))]
-pub async fn all_packages_by_user<S>(
-    client: &Client,
-    graphql_endpoint: &str,
-    username: &str,
-    dest: S,
-) -> Result<(), Error>
-where
-    S: Sink<Vec<queries::Package>> + Unpin,
-    S::Error: std::error::Error + Send + Sync + 'static,
-{
-    packages_query(
-        client,
-        graphql_endpoint,
-        dest,
-        |offset| {
-            queries::GetUserPackages::build(Variables {
-                name: username,
-                offset,
-            })
-        },
-        |result| {
-            let user = result
-                .get_user
-                .with_context(|| format!("Unknown user, \"{username}\""))?;
-            Ok(user.packages)
-        },
-    )
-    .await
-}
-
-#[tracing::instrument(skip_all, fields(namespace))]
-pub async fn all_packages_in_namespace<S>(
-    client: &Client,
-    graphql_endpoint: &str,
-    namespace: &str,
-    dest: S,
-) -> Result<(), Error>
-where
-    S: Sink<Vec<queries::Package>> + Unpin,
-    S::Error: std::error::Error + Send + Sync + 'static,
-{
-    packages_query(
-        client,
-        graphql_endpoint,
-        dest,
-        |offset| {
-            queries::GetNamespace::build(Variables {
-                name: namespace,
-                offset,
-            })
-        },
-        |result| {
-            let ns = result
-                .get_namespace
-                .with_context(|| format!("Unknown namespace, \"{namespace}\""))?;
-            Ok(ns.packages)
-        },
-    )
-    .await
-}
-
-#[tracing::instrument(skip_all, fields(namespace))]
-pub async fn packages_query<'a, S, Q, Build, GetPackages>(
-    client: &Client,
-    graphql_endpoint: &str,
-    mut dest: S,
-    build: Build,
-    get_packages: GetPackages,
-) -> Result<(), Error>
-where
-    S: Sink<Vec<queries::Package>> + Unpin,
-    S::Error: std::error::Error + Send + Sync + 'static,
-    Build: Fn(i32) -> Operation<Q, Variables<'a>>,
-    GetPackages: Fn(Q) -> Result<queries::PackageConnection, Error>,
-    Q: serde::de::DeserializeOwned,
-{
-    let mut offset = 0;
-
-    loop {
-        let op = build(offset);
-
-        tracing::debug!(offset, "Fetching a page of packages");
-
-        let response: GraphQlResponse<Q> = client
-            .post(graphql_endpoint)
-            .header("Content-Type", "application/json")
-            .json(&op)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
-
-        if let Some(errors) = response.errors {
-            if !errors.is_empty() {
-                return Err(aggregate_errors(errors));
-            }
-        }
-
-        let query_result = response.data.context("Invalid query")?;
-        let packages: Vec<_> = get_packages(query_result)?
-            .edges
-            .into_iter()
-            .flatten()
-            .flat_map(|edge| edge.node)
-            .collect();
-
-        if packages.is_empty() {
-            break;
+    use serde::{de::Error as _, Deserialize};
+
+    let mut value = serde_json::Value::deserialize(deserializer)?;
+    if let Some(object) = value.as_object_mut() {
+        object.entry("createdAt").or_insert(serde_json::Value::Null);
+
+        if let Some(distribution) = object
+            .get_mut("distribution")
+            .and_then(|d| d.as_object_mut())
+        {
+            distribution
+                .entry("size")
+                .or_insert(serde_json::Value::Null);
+            distribution
+                .entry("piritaSize")
+                .or_insert(serde_json::Value::Null);
         }
-
-        offset += i32::try_from(packages.len()).unwrap();
-        dest.send(packages).await?;
-        dest.flush().await?;
     }
 
-    Ok(())
-}
-
-fn aggregate_errors(_errors: Vec<GraphQlError>) -> Error {
-    todo!()
+    serde_json::from_value(value).map_err(D::Error::custom)
 }
 
 #[cynic::schema_for_derives(
@@ -232,19 +109,99 @@ pub mod queries {
         pub versions: Vec<Option<PackageVersion>>,
     }
 
+    /// A particular version of a package.
     #[derive(cynic::QueryFragment, Debug, Clone, serde::Serialize)]
+    #[cfg_attr(test, derive(schemars::JsonSchema))]
     #[serde(rename_all = "camelCase")]
     pub struct PackageVersion {
+        /// The registry's ID for this package version.
+        #[cfg_attr(test, schemars(with = "String"))]
         pub id: cynic::Id,
         pub version: String,
         pub distribution: PackageDistribution,
+        /// When this version was published.
+        ///
+        /// This may be missing from results recorded by older versions of
+        /// borealis.
+        #[cfg_attr(test, schemars(with = "Option<String>"))]
+        pub created_at: Option<chrono::DateTime<chrono::Utc>>,
     }
 
+    /// Where a package version can be downloaded from.
     #[derive(cynic::QueryFragment, Debug, Clone, serde::Serialize)]
+    #[cfg_attr(test, derive(schemars::JsonSchema))]
     #[serde(rename_all = "camelCase")]
     pub struct PackageDistribution {
+        /// The URL for the package's `*.tar.gz` file.
         pub download_url: String,
+        /// The URL for the package's `*.webc` file, if one is available.
         pub pirita_download_url: Option<String>,
+        /// The size of the `*.tar.gz` file in bytes.
+        ///
+        /// This may be missing from results recorded by older versions of
+        /// borealis.
+        pub size: Option<i32>,
+        /// The size of the `*.webc` file in bytes.
+        ///
+        /// This may be missing from results recorded by older versions of
+        /// borealis.
+        pub pirita_size: Option<i32>,
+    }
+
+    impl PackageDistribution {
+        /// The combined size of the package's artifacts in bytes, if the
+        /// registry told us.
+        pub fn total_size(&self) -> Option<u64> {
+            let sizes = [self.size, self.pirita_size];
+            let known: Vec<u64> = sizes
+                .into_iter()
+                .flatten()
+                .filter_map(|size| u64::try_from(size).ok())
+                .collect();
+
+            if known.is_empty() {
+                None
+            } else {
+                Some(known.into_iter().sum())
+            }
+        }
+    }
+
+    #[derive(cynic::QueryVariables, Debug, Clone)]
+    pub struct GetPackageVariables<'a> {
+        pub name: &'a str,
+    }
+
+    #[derive(cynic::QueryFragment, Debug, Clone)]
+    #[cynic(graphql_type = "Query", variables = "GetPackageVariables")]
+    pub struct GetPackage {
+        #[arguments(name: $name)]
+        pub get_package: Option<Package>,
+    }
+
+    #[derive(cynic::QueryVariables, Debug, Clone)]
+    pub struct GetPackageVersionVariables<'a> {
+        pub name: &'a str,
+        pub version: &'a str,
+    }
+
+    #[derive(cynic::QueryFragment, Debug, Clone)]
+    #[cynic(graphql_type = "Query", variables = "GetPackageVersionVariables")]
+    pub struct GetPackageVersion {
+        #[arguments(name: $name, version: $version)]
+        pub get_package_version: Option<PackageVersion>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug, Clone)]
+    #[cynic(graphql_type = "Query")]
+    pub struct GetViewer {
+        pub viewer: Option<Viewer>,
+    }
+
+    #[derive(cynic::QueryFragment, Debug, Clone)]
+    #[cynic(graphql_type = "User")]
+    pub struct Viewer {
+        pub username: String,
     }
 
     #[derive(cynic::QueryFragment, Debug, Clone)]
@@ -258,3 +215,50 @@ pub mod queries {
 mod schema {
     cynic::use_schema!("src/registry/schema.graphql");
 }
+
+cynic::impl_scalar!(chrono::DateTime<chrono::Utc>, schema::DateTime);
+
+#[cfg(test)]
+mod tests {
+    use cynic::QueryBuilder;
+
+    use super::*;
+
+    #[test]
+    fn get_package_version_query() {
+        let op = queries::GetPackageVersion::build(queries::GetPackageVersionVariables {
+            name: "wasmer/python",
+            version: "3.12.0",
+        });
+
+        assert!(
+            op.query
+                .contains("getPackageVersion(name: $name, version: $version)"),
+            "{}",
+            op.query
+        );
+        let variables = serde_json::to_value(&op.variables).unwrap();
+        assert_eq!(
+            variables,
+            serde_json::json!({ "name": "wasmer/python", "version": "3.12.0" })
+        );
+    }
+
+    #[test]
+    fn package_versions_without_sizes_can_be_deserialized() {
+        let value = serde_json::json!({
+            "id": "UGFja2FnZVZlcnNpb246MQ==",
+            "version": "1.0.0",
+            "distribution": {
+                "downloadUrl": "https://example.com/python.tar.gz",
+                "piritaDownloadUrl": null,
+            },
+        });
+
+        let version = deserialize_package_version(value).unwrap();
+
+        assert_eq!(version.created_at, None);
+        assert_eq!(version.distribution.size, None);
+        assert_eq!(version.distribution.total_size(), None);
+    }
+}
@@ -0,0 +1,160 @@
+use std::time::Duration;
+
+use reqwest::{header::RETRY_AFTER, Error, RequestBuilder, Response, StatusCode};
+
+/// How requests to the registry should be retried when the server is
+/// overloaded or temporarily unavailable.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// The maximum number of times a request will be retried before giving
+    /// up.
+    pub max_retries: u32,
+    /// How long to wait before the first retry. This doubles after every
+    /// attempt, unless the server tells us how long to wait with a
+    /// `Retry-After` header.
+    pub initial_backoff: Duration,
+    /// The longest we will ever wait between attempts.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Never retry.
+    pub const NONE: RetryPolicy = RetryPolicy {
+        max_retries: 0,
+        initial_backoff: Duration::ZERO,
+        max_backoff: Duration::ZERO,
+    };
+
+    /// Send a request, retrying on `429 Too Many Requests`, `5xx` responses,
+    /// and connection errors.
+    ///
+    /// The `request` closure is called to create a fresh request for each
+    /// attempt.
+    pub(crate) async fn send(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+    ) -> Result<Response, Error> {
+        let mut backoff = self.initial_backoff;
+        let mut attempt = 0;
+
+        loop {
+            let result = request().send().await;
+
+            let delay = match &result {
+                Ok(response) if is_retryable(response.status()) => {
+                    retry_after(response).unwrap_or(backoff)
+                }
+                Err(e) if e.is_connect() || e.is_timeout() => backoff,
+                _ => return result?.error_for_status(),
+            };
+
+            if attempt >= self.max_retries {
+                return result?.error_for_status();
+            }
+
+            let delay = delay.min(self.max_backoff);
+            attempt += 1;
+            tracing::warn!(
+                attempt,
+                max_retries = self.max_retries,
+                ?delay,
+                status = result.as_ref().ok().map(|r| r.status().as_u16()),
+                "Request failed, retrying",
+            );
+
+            tokio::time::sleep(delay).await;
+            backoff = (backoff * 2).min(self.max_backoff);
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_retries: 5,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(60),
+        }
+    }
+}
+
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+/// Parse the `Retry-After` header, if it contains a number of seconds.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let seconds = response
+        .headers()
+        .get(RETRY_AFTER)?
+        .to_str()
+        .ok()?
+        .trim()
+        .parse()
+        .ok()?;
+
+    Some(Duration::from_secs(seconds))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        net::SocketAddr,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+    };
+
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Server,
+    };
+    use reqwest::Client;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn throttled_requests_are_retried() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let counter = Arc::clone(&attempts);
+        let make_svc = make_service_fn(move |_| {
+            let counter = Arc::clone(&counter);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |_req| {
+                    let attempt = counter.fetch_add(1, Ordering::SeqCst);
+                    async move {
+                        let response = if attempt < 2 {
+                            hyper::Response::builder()
+                                .status(429)
+                                .header("Retry-After", "0")
+                                .body(Body::empty())
+                        } else {
+                            hyper::Response::builder().body(Body::from("ok"))
+                        };
+                        Ok::<_, Infallible>(response.unwrap())
+                    }
+                }))
+            }
+        });
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_svc);
+        let url = format!("http://{}/", server.local_addr());
+        tokio::spawn(server);
+        let client = Client::new();
+        let policy = RetryPolicy {
+            max_retries: 1,
+            initial_backoff: Duration::from_secs(60),
+            max_backoff: Duration::from_secs(60),
+        };
+
+        // Not enough retries
+        let err = policy.send(|| client.get(&url)).await.unwrap_err();
+        assert!(err.to_string().contains("429"), "{err}");
+
+        // The Retry-After header takes precedence over our own backoff
+        let response = policy.send(|| client.get(&url)).await.unwrap();
+        assert_eq!(response.text().await.unwrap(), "ok");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+}
@@ -0,0 +1,84 @@
+//! Small SVG status badges (e.g. "registry compat: 97.4%") which can be
+//! embedded in READMEs and dashboards.
+
+use crate::render::{RenderError, Renderer};
+
+/// Horizontal padding on either side of each half of the badge.
+const PADDING: f64 = 6.0;
+
+impl Renderer<'_> {
+    /// Render a badge showing a success rate (between `0.0` and `1.0`).
+    ///
+    /// The badge is coloured from green to red depending on the rate.
+    #[tracing::instrument(skip_all)]
+    pub fn badge(&self, label: &str, success_rate: f64) -> Result<String, RenderError> {
+        let value = format!("{:.1}%", success_rate * 100.0);
+        let label_width = text_width(label) + 2.0 * PADDING;
+        let value_width = text_width(&value) + 2.0 * PADDING;
+
+        let ctx = minijinja::context! {
+            label,
+            value,
+            color => color(success_rate),
+            label_width,
+            value_width,
+            width => label_width + value_width,
+        };
+
+        let rendered = self.env.get_template("badge")?.render(ctx)?;
+        Ok(rendered)
+    }
+}
+
+fn color(success_rate: f64) -> &'static str {
+    match success_rate {
+        r if r >= 0.95 => "#4c1",
+        r if r >= 0.9 => "#97ca00",
+        r if r >= 0.75 => "#dfb317",
+        r if r >= 0.5 => "#fe7d37",
+        _ => "#e05d44",
+    }
+}
+
+/// Roughly how wide some text is in 11px Verdana, the font badges are
+/// conventionally drawn with.
+///
+/// We can't measure the text without the font, so this just needs to be
+/// close enough that the text doesn't overflow its half of the badge.
+fn text_width(text: &str) -> f64 {
+    let width: f64 = text
+        .chars()
+        .map(|c| match c {
+            'i' | 'j' | 'l' | '.' | ',' | ':' | ';' | '!' | '|' | '\'' => 3.5,
+            'f' | 'r' | 't' | ' ' | '(' | ')' | '[' | ']' | '-' | '/' => 5.0,
+            'm' | 'w' | 'M' | 'W' | '%' => 10.5,
+            c if c.is_ascii_uppercase() => 7.5,
+            _ => 7.0,
+        })
+        .sum();
+
+    width.round()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn badges_show_the_success_rate() {
+        let svg = Renderer::new().badge("registry compat", 0.97436).unwrap();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains("registry compat: 97.4%"));
+        assert!(svg.contains(r##"fill="#4c1""##));
+    }
+
+    #[test]
+    fn labels_are_escaped() {
+        let svg = Renderer::new().badge("<a & b>", 0.2).unwrap();
+
+        assert!(svg.contains("&lt;a &amp; b&gt;"));
+        assert!(!svg.contains("<a & b>"));
+        assert!(svg.contains(r##"fill="#e05d44""##));
+    }
+}
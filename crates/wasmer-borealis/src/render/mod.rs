@@ -1,119 +1,1137 @@
-use std::io::Write;
+mod badge;
+mod pdf;
+mod site;
+mod trend;
 
-use anyhow::Error;
+pub use self::{pdf::BROWSER_ENV, site::Site, trend::TREND_RUNS};
+
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, BTreeSet},
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use minijinja::{
+    filters::Filter,
+    functions::Function,
+    value::{FunctionArgs, FunctionResult, SeqObject, Value, ViaDeserialize},
+};
 use once_cell::sync::Lazy;
 
-use crate::experiment::{Report, Results};
+use crate::{
+    alerts::SuccessRateDrop,
+    config::Experiment,
+    experiment::{
+        CacheStats, Category, CommandLine, Outcome, Phase, Report, ReportReader, ReportSummary,
+        Results, ResultsSummary, StartupTimes, Timeline,
+    },
+    history::RunRecord,
+    render::trend::Trend,
+    runs::IndexedRun,
+};
 
-static TEMPLATES: Lazy<minijinja::Environment<'static>> = Lazy::new(|| {
-    let mut env = minijinja::Environment::new();
-    env.add_template("report", include_str!("report.html.jinja"))
-        .unwrap();
-    env.add_filter("file_url", file_url);
-    env
-});
+/// An error that occurred while rendering a report.
+#[derive(Debug, thiserror::Error)]
+pub enum RenderError {
+    #[error("Unable to render the template")]
+    Template(#[from] minijinja::Error),
+    #[error("Unable to write the report")]
+    Io(#[from] std::io::Error),
+    #[error(
+        "Unable to find a Chromium-based browser to print the PDF with (set ${} to its path)",
+        pdf::BROWSER_ENV
+    )]
+    BrowserNotFound,
+    #[error("The browser was unable to print the PDF ({_0})")]
+    BrowserFailed(std::process::ExitStatus),
+}
 
-fn file_url(path: String) -> String {
-    url::Url::from_file_path(&path)
-        .map(|u| u.to_string())
-        .unwrap_or(path)
+/// The name of a template which, if registered with
+/// [`Renderer::add_template()`], is included in `report.html` before the
+/// results for each test case.
+pub const REPORT_SECTIONS: &str = "report_sections";
+
+/// The name of a template which, if registered with
+/// [`Renderer::add_template()`], is included at the end of each test case's
+/// section in `report.html`, with the test case's [`Report`] available as
+/// `report`.
+pub const TEST_CASE_SECTIONS: &str = "test_case_sections";
+
+static DEFAULT: Lazy<Renderer<'static>> = Lazy::new(Renderer::new);
+
+/// The environment reports are rendered with.
+///
+/// Downstream tools can register their own filters, globals, and templates
+/// to add custom sections to reports (see [`REPORT_SECTIONS`] and
+/// [`TEST_CASE_SECTIONS`]), or replace one of the built-in templates
+/// (`report`, `print`, `site`, `summary`, `matrix`, `summary.md`,
+/// `owner.md`, and `badge`) entirely.
+#[derive(Debug, Clone)]
+pub struct Renderer<'source> {
+    env: minijinja::Environment<'source>,
+}
+
+impl<'source> Renderer<'source> {
+    pub fn new() -> Self {
+        let mut env = minijinja::Environment::new();
+        env.add_template("report", include_str!("report.html.jinja"))
+            .unwrap();
+        env.add_template("summary", include_str!("summary.html.jinja"))
+            .unwrap();
+        env.add_template("matrix", include_str!("matrix.html.jinja"))
+            .unwrap();
+        env.add_template("summary.md", include_str!("summary.md.jinja"))
+            .unwrap();
+        env.add_template("owner.md", include_str!("owner.md.jinja"))
+            .unwrap();
+        env.add_template("print", include_str!("print.html.jinja"))
+            .unwrap();
+        env.add_template("site", include_str!("site.html.jinja"))
+            .unwrap();
+        env.add_template("badge", include_str!("badge.svg.jinja"))
+            .unwrap();
+        env.add_filter("file_url", file_url);
+        env.add_filter("log_url", log_url);
+        env.add_filter("filesize", filesize);
+        env.add_filter("namespace", namespace);
+        env.add_filter(
+            "category",
+            |outcome: ViaDeserialize<Outcome>| match outcome.category() {
+                Category::Success => "success",
+                Category::Failure => "failure",
+                Category::Bug => "bug",
+            },
+        );
+        env.add_filter("shell", |command: ViaDeserialize<CommandLine>| {
+            command.shell()
+        });
+        env.add_filter("nondeterministic", |report: ViaDeserialize<Report>| {
+            report.is_nondeterministic()
+        });
+        env.add_filter("imports", |report: ViaDeserialize<Report>| {
+            report.imports().into_iter().collect::<Vec<_>>().join(" ")
+        });
+
+        Renderer { env }
+    }
+
+    /// Add a template, replacing any existing template with the same name.
+    pub fn add_template(
+        &mut self,
+        name: &'source str,
+        source: &'source str,
+    ) -> Result<(), RenderError> {
+        self.env.add_template(name, source)?;
+        Ok(())
+    }
+
+    /// Register a filter which templates can use (e.g. `{{ value | name }}`).
+    pub fn add_filter<N, F, Rv, Args>(&mut self, name: N, filter: F)
+    where
+        N: Into<Cow<'source, str>>,
+        F: Filter<Rv, Args> + for<'a> Filter<Rv, <Args as FunctionArgs<'a>>::Output>,
+        Rv: FunctionResult,
+        Args: for<'a> FunctionArgs<'a>,
+    {
+        self.env.add_filter(name, filter);
+    }
+
+    /// Register a function which templates can call (e.g. `{{ name() }}`).
+    pub fn add_function<N, F, Rv, Args>(&mut self, name: N, function: F)
+    where
+        N: Into<Cow<'source, str>>,
+        F: Function<Rv, Args> + for<'a> Function<Rv, <Args as FunctionArgs<'a>>::Output>,
+        Rv: FunctionResult,
+        Args: for<'a> FunctionArgs<'a>,
+    {
+        self.env.add_function(name, function);
+    }
+
+    /// Make a variable available to every template.
+    pub fn add_global(&mut self, name: impl Into<Cow<'source, str>>, value: impl Into<Value>) {
+        self.env.add_global(name, value);
+    }
+
+    #[tracing::instrument(skip_all)]
+    pub fn html(&self, results: &Results) -> Result<String, RenderError> {
+        let rendered = self
+            .env
+            .get_template("report")?
+            .render(report_context(results, None))?;
+        Ok(rendered)
+    }
+
+    /// Render `report.html` with a chart showing how the success rate and
+    /// bug count changed over the experiment's most recent runs (see
+    /// [`TREND_RUNS`]).
+    ///
+    /// The `history` should only contain runs of this experiment. The chart
+    /// is left out if there are fewer than two runs.
+    #[tracing::instrument(skip_all)]
+    pub fn html_with_history(
+        &self,
+        results: &Results,
+        history: &[RunRecord],
+    ) -> Result<String, RenderError> {
+        let rendered = self
+            .env
+            .get_template("report")?
+            .render(report_context(results, Trend::new(history)))?;
+        Ok(rendered)
+    }
+
+    /// Write `report.html` for an experiment whose reports are still in its
+    /// `reports.jsonl`, reading each report back as it is rendered instead
+    /// of loading them all at once.
+    #[tracing::instrument(skip_all)]
+    pub fn write_html(
+        &self,
+        results: &ResultsSummary,
+        history: &[RunRecord],
+        dest: impl Write,
+    ) -> Result<(), RenderError> {
+        let ctx = logged_report_context(results, Trend::new(history))?;
+        self.env
+            .get_template("report")?
+            .render_to_write(ctx, dest)?;
+        Ok(())
+    }
+
+    /// Render a paginated, print-friendly version of the report (e.g. to be
+    /// turned into a PDF).
+    #[tracing::instrument(skip_all)]
+    pub fn print_html(&self, results: &Results) -> Result<String, RenderError> {
+        let rendered = self
+            .env
+            .get_template("print")?
+            .render(report_context(results, None))?;
+        Ok(rendered)
+    }
+
+    /// Render a HTML overview of several experiments that were run together.
+    ///
+    /// Each experiment is identified by a name and links to its own
+    /// `report.html`.
+    #[tracing::instrument(skip_all)]
+    pub fn summary_html<'a>(
+        &self,
+        runs: impl IntoIterator<Item = (&'a str, &'a Results)>,
+    ) -> Result<String, RenderError> {
+        let runs: Vec<_> = runs
+            .into_iter()
+            .map(|(name, results)| RunSummary::new(name, results))
+            .collect();
+
+        let ctx = minijinja::context! { runs };
+
+        let rendered = self.env.get_template("summary")?.render(ctx)?;
+        Ok(rendered)
+    }
+
+    /// Render a Markdown overview of several experiments, suitable for a pull
+    /// request comment or CI job summary.
+    ///
+    /// Any `alerts` are listed as regressions, followed by the packages which
+    /// triggered a bug in each experiment.
+    #[tracing::instrument(skip_all)]
+    pub fn markdown<'a>(
+        &self,
+        runs: impl IntoIterator<Item = (&'a str, &'a Results)>,
+        alerts: &[SuccessRateDrop],
+    ) -> Result<String, RenderError> {
+        let runs: Vec<_> = runs
+            .into_iter()
+            .map(|(name, results)| {
+                let bugs: Vec<String> = results
+                    .bugs()
+                    .map(|r| format!("{}@{}", r.display_name, r.version()))
+                    .collect();
+                minijinja::context! { summary => RunSummary::new(name, results), bugs }
+            })
+            .collect();
+
+        let ctx = minijinja::context! { runs, alerts };
+
+        let rendered = self.env.get_template("summary.md")?.render(ctx)?;
+        Ok(rendered)
+    }
+
+    /// Render a Markdown summary for each namespace with failing packages,
+    /// keyed by namespace, so the packages' maintainers can be told what no
+    /// longer runs.
+    ///
+    /// Bugs are left out because they are borealis' problem, not the
+    /// maintainer's.
+    #[tracing::instrument(skip_all)]
+    pub fn owner_summaries(
+        &self,
+        results: &Results,
+    ) -> Result<BTreeMap<String, String>, RenderError> {
+        let template = self.env.get_template("owner.md")?;
+        let mut summaries = BTreeMap::new();
+
+        for (namespace, reports) in results.by_namespace() {
+            let failures: Vec<_> = reports
+                .into_iter()
+                .filter(|r| r.category() == Category::Failure)
+                .map(OwnerFailure::new)
+                .collect();
+            if failures.is_empty() {
+                continue;
+            }
+
+            let ctx = minijinja::context! {
+                namespace,
+                experiment => results.experiment,
+                failures,
+            };
+            summaries.insert(namespace.to_string(), template.render(ctx)?);
+        }
+
+        Ok(summaries)
+    }
+
+    /// Render a table showing the outcome for each package across several
+    /// runs of the same experiment (e.g. one run per `wasmer` version).
+    ///
+    /// Each column is identified by a name and links to its own
+    /// `report.html`. Test cases in `diverged` (see
+    /// [`crate::experiment::diverging_outputs()`]) have their cells marked.
+    #[tracing::instrument(skip_all)]
+    pub fn matrix_html<'a>(
+        &self,
+        runs: impl IntoIterator<Item = (&'a str, &'a Results)>,
+        diverged: &[(&str, &str)],
+    ) -> Result<String, RenderError> {
+        let runs: Vec<_> = runs.into_iter().collect();
+
+        let packages: BTreeSet<(&str, &str)> = runs
+            .iter()
+            .flat_map(|(_, results)| &results.reports)
+            .map(|r| (r.display_name.as_str(), r.version()))
+            .collect();
+
+        let rows: Vec<_> = packages
+            .into_iter()
+            .map(|(package, version)| {
+                let cells: Vec<Option<Category>> = runs
+                    .iter()
+                    .map(|(_, results)| results.get(package, version).map(Report::category))
+                    .collect();
+                let changed = cells.windows(2).any(|w| w[0] != w[1]);
+                let diverged = diverged.contains(&(package, version));
+
+                minijinja::context! { package, version, cells, changed, diverged }
+            })
+            .collect();
+
+        let columns: Vec<_> = runs
+            .iter()
+            .map(|(name, results)| {
+                let startup = results
+                    .median_startup()
+                    .map(|s| format!("{:.1?} cold, {:.1?} warm", s.cold, s.warm));
+                minijinja::context! { name, experiment_dir => results.experiment_dir, startup }
+            })
+            .collect();
+
+        let ctx = minijinja::context! { columns, rows };
+
+        let rendered = self.env.get_template("matrix")?.render(ctx)?;
+        Ok(rendered)
+    }
 }
 
-#[tracing::instrument(skip_all)]
-pub fn html(results: &Results) -> Result<String, Error> {
+/// The variables available to the `report` and `print` templates.
+fn report_context(results: &Results, trend: Option<Trend>) -> Value {
     let Results {
         experiment,
         reports,
         total_time,
         experiment_dir,
+        cache,
     } = results;
 
-    let ctx = minijinja::context! {
+    let mut index = Index::new();
+    for report in reports {
+        index.add(report, report);
+    }
+
+    index.context(
         experiment,
-        reports => ReportCategories::new(reports),
-        total_time => format!("{total_time:.1?}"),
+        *total_time,
         experiment_dir,
-    };
+        cache,
+        trend,
+        |reports| Value::from_serializable(&reports),
+    )
+}
+
+/// The same variables as [`report_context()`], except each report is read
+/// from the experiment's `reports.jsonl` when the template gets to it instead
+/// of being held in memory.
+fn logged_report_context(
+    results: &ResultsSummary,
+    trend: Option<Trend>,
+) -> Result<Value, RenderError> {
+    let mut reader = results.reader()?;
+
+    let mut index = Index::new();
+    for summary in &results.reports {
+        let report = reader.read(summary)?;
+        index.add(&report, summary.clone());
+    }
+
+    let reader = Arc::new(Mutex::new(reader));
+
+    Ok(index.context(
+        &results.experiment,
+        results.total_time,
+        &results.experiment_dir,
+        &results.cache,
+        trend,
+        |summaries| {
+            Value::from_seq_object(LoggedReports {
+                reader: Arc::clone(&reader),
+                summaries,
+            })
+        },
+    ))
+}
+
+impl Default for Renderer<'_> {
+    fn default() -> Self {
+        Renderer::new()
+    }
+}
+
+fn file_url(path: String) -> String {
+    url::Url::from_file_path(&path)
+        .map(|u| u.to_string())
+        .unwrap_or(path)
+}
+
+/// The namespace part of a package's name (e.g. `wasmer` for
+/// `wasmer/python`).
+fn namespace(display_name: String) -> String {
+    match display_name.split_once('/') {
+        Some((namespace, _)) => namespace.to_string(),
+        None => display_name,
+    }
+}
 
-    let rendered = TEMPLATES.get_template("report")?.render(ctx)?;
-    Ok(rendered)
+/// Link to one of a test case's logs, which may have been compressed.
+fn log_url(base_dir: String, filename: String) -> String {
+    let base_dir = Path::new(&base_dir);
+    let path = crate::experiment::log_path(base_dir, &filename)
+        .unwrap_or_else(|| base_dir.join(&filename));
+
+    file_url(path.display().to_string())
+}
+
+pub(crate) fn filesize(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    format!("{size:.1} {}", UNITS[unit])
+}
+
+/// Render `report.html` with the default [`Renderer`].
+pub fn html(results: &Results) -> Result<String, RenderError> {
+    DEFAULT.html(results)
+}
+
+/// Render `report.html` with a trend chart using the default [`Renderer`].
+pub fn html_with_history(results: &Results, history: &[RunRecord]) -> Result<String, RenderError> {
+    DEFAULT.html_with_history(results, history)
+}
+
+/// Write `report.html` from an experiment's `reports.jsonl` using the default
+/// [`Renderer`].
+pub fn write_html(
+    results: &ResultsSummary,
+    history: &[RunRecord],
+    dest: impl Write,
+) -> Result<(), RenderError> {
+    DEFAULT.write_html(results, history, dest)
+}
+
+/// Print the report to a PDF with the default [`Renderer`].
+pub fn pdf(results: &Results, dest: &Path) -> Result<(), RenderError> {
+    DEFAULT.pdf(results, dest)
+}
+
+/// Render a success rate badge with the default [`Renderer`].
+pub fn badge(label: &str, success_rate: f64) -> Result<String, RenderError> {
+    DEFAULT.badge(label, success_rate)
+}
+
+/// Build a static site covering these runs with the default [`Renderer`].
+pub fn build_site(runs: &[IndexedRun], output: &Path) -> Result<Site, RenderError> {
+    DEFAULT.build_site(runs, output)
+}
+
+/// Render a HTML overview of several experiments with the default
+/// [`Renderer`].
+pub fn summary_html<'a>(
+    runs: impl IntoIterator<Item = (&'a str, &'a Results)>,
+) -> Result<String, RenderError> {
+    DEFAULT.summary_html(runs)
+}
+
+/// Render a Markdown overview of several experiments with the default
+/// [`Renderer`].
+pub fn markdown<'a>(
+    runs: impl IntoIterator<Item = (&'a str, &'a Results)>,
+    alerts: &[SuccessRateDrop],
+) -> Result<String, RenderError> {
+    DEFAULT.markdown(runs, alerts)
+}
+
+/// Render a Markdown summary for each namespace with failing packages using
+/// the default [`Renderer`].
+pub fn owner_summaries(results: &Results) -> Result<BTreeMap<String, String>, RenderError> {
+    DEFAULT.owner_summaries(results)
+}
+
+/// Render a version matrix with the default [`Renderer`].
+pub fn matrix_html<'a>(
+    runs: impl IntoIterator<Item = (&'a str, &'a Results)>,
+    diverged: &[(&str, &str)],
+) -> Result<String, RenderError> {
+    DEFAULT.matrix_html(runs, diverged)
 }
 
 #[derive(Debug, serde::Serialize)]
-struct ReportCategories<'a> {
-    bugs: Vec<&'a Report>,
-    success: Vec<&'a Report>,
-    failures: Vec<&'a Report>,
-    all: Vec<&'a Report>,
+struct RunSummary<'a> {
+    name: &'a str,
+    package: &'a str,
+    experiment_dir: &'a std::path::Path,
     total: usize,
+    success: usize,
+    failures: usize,
+    bugs: usize,
+    total_time: String,
+}
+
+impl<'a> RunSummary<'a> {
+    fn new(name: &'a str, results: &'a Results) -> Self {
+        RunSummary {
+            name,
+            package: &results.experiment.package,
+            experiment_dir: &results.experiment_dir,
+            total: results.reports.len(),
+            success: results.successes().count(),
+            failures: results.failures().count(),
+            bugs: results.bugs().count(),
+            total_time: format!("{:.1?}", results.total_time),
+        }
+    }
+}
+
+/// How many lines from the end of a failing package's logs are included in
+/// its owner's summary.
+const EXCERPT_LINES: usize = 20;
+
+#[derive(Debug, serde::Serialize)]
+struct OwnerFailure<'a> {
+    display_name: &'a str,
+    version: &'a str,
+    problem: String,
+    /// The log the excerpt was taken from.
+    log: &'static str,
+    excerpt: Option<String>,
 }
 
-impl<'a> ReportCategories<'a> {
-    fn new(reports: &'a [Report]) -> Self {
-        let mut bugs = Vec::new();
-        let mut success = Vec::new();
-        let mut failures = Vec::new();
-
-        for report in reports {
-            match &report.outcome {
-                crate::experiment::Outcome::Completed { status, .. } if status.success => {
-                    success.push(report);
-                }
-                crate::experiment::Outcome::Completed { .. } => failures.push(report),
-                crate::experiment::Outcome::FetchFailed { .. }
-                | crate::experiment::Outcome::SetupFailed { .. }
-                | crate::experiment::Outcome::SpawnFailed { .. } => bugs.push(report),
+impl<'a> OwnerFailure<'a> {
+    fn new(report: &'a Report) -> Self {
+        let problem = match &report.outcome {
+            Outcome::Completed { status, .. } => format!("Exited with code {}", status.code),
+            Outcome::TimedOut { timeout, .. } => format!("Timed out after {timeout:.1?}"),
+            Outcome::OutputLimitExceeded { limit, .. } => {
+                format!("Wrote more than {} of output", filesize(*limit))
             }
+            other => other.error().map(|e| e.error.clone()).unwrap_or_default(),
+        };
+
+        // stderr is usually where the interesting part is, but some packages
+        // only ever write to stdout
+        let (log, excerpt) = ["stderr.txt", "stdout.txt"]
+            .into_iter()
+            .find_map(|log| {
+                let base_dir = report.outcome.base_dir()?;
+                let excerpt = log_excerpt(base_dir, log)?;
+                Some((log, Some(excerpt)))
+            })
+            .unwrap_or(("", None));
+
+        OwnerFailure {
+            display_name: &report.display_name,
+            version: report.version(),
+            problem,
+            log,
+            excerpt,
+        }
+    }
+}
+
+/// The last few lines of a log, if it has anything in it.
+fn log_excerpt(base_dir: &Path, filename: &str) -> Option<String> {
+    let contents = crate::experiment::read_log(base_dir, filename).ok()??;
+    let lines: Vec<&str> = contents.trim_end().lines().collect();
+    let start = lines.len().saturating_sub(EXCERPT_LINES);
+    let excerpt = lines[start..].join("\n");
+
+    (!excerpt.trim().is_empty()).then_some(excerpt)
+}
+
+/// Everything the `report` template needs to know about the reports up
+/// front, where `T` is used to look up each report while it is rendered.
+struct Index<T> {
+    listings: Vec<(Listing, T)>,
+    timelines: Vec<(String, String, Timeline)>,
+    imports: BTreeMap<String, ImportUsage>,
+    startup: Vec<StartupTimes>,
+}
+
+/// How a report is sorted and categorised in the report.
+struct Listing {
+    display_name: String,
+    version: String,
+    category: Category,
+    flaky: bool,
+    nondeterministic: bool,
+}
+
+impl<T: Clone> Index<T> {
+    fn new() -> Self {
+        Index {
+            listings: Vec::new(),
+            timelines: Vec::new(),
+            imports: BTreeMap::new(),
+            startup: Vec::new(),
         }
+    }
+
+    fn add(&mut self, report: &Report, item: T) {
+        let listing = Listing {
+            display_name: report.display_name.clone(),
+            version: report.version().to_string(),
+            category: report.category(),
+            flaky: report.is_flaky(),
+            nondeterministic: report.is_nondeterministic(),
+        };
+
+        if let Some(timeline) = report.timeline {
+            self.timelines.push((
+                listing.display_name.clone(),
+                listing.version.clone(),
+                timeline,
+            ));
+        }
+
+        for name in report.imports() {
+            let usage = self
+                .imports
+                .entry(name.to_string())
+                .or_insert_with(|| ImportUsage {
+                    name: name.to_string(),
+                    failing: 0,
+                    total: 0,
+                });
+            usage.total += 1;
+            if listing.category != Category::Success {
+                usage.failing += 1;
+            }
+        }
+
+        self.startup.extend(report.startup);
+        self.listings.push((listing, item));
+    }
+
+    /// Build the template's variables, using `list` to turn each category's
+    /// reports into something the template can iterate over.
+    fn context(
+        self,
+        experiment: &Experiment,
+        total_time: Duration,
+        experiment_dir: &Path,
+        cache: &CacheStats,
+        trend: Option<Trend>,
+        list: impl Fn(Vec<T>) -> Value,
+    ) -> Value {
+        let Index {
+            mut listings,
+            timelines,
+            imports,
+            startup,
+        } = self;
 
-        let sort = |items: &mut [&Report]| {
-            items.sort_by_key(|r| {
-                (
-                    r.display_name.as_str(),
-                    std::cmp::Reverse(r.package_version.version.as_str()),
-                )
-            });
+        listings.sort_by(|(a, _), (b, _)| {
+            let key = |l: &Listing| (l.display_name.clone(), std::cmp::Reverse(l.version.clone()));
+            key(a).cmp(&key(b))
+        });
+        let select = |keep: fn(&Listing) -> bool| {
+            list(
+                listings
+                    .iter()
+                    .filter(|(listing, _)| keep(listing))
+                    .map(|(_, item)| item.clone())
+                    .collect(),
+            )
         };
 
-        let mut all: Vec<&Report> = reports.iter().collect();
-        sort(&mut bugs);
-        sort(&mut success);
-        sort(&mut failures);
-        sort(&mut all);
-
-        ReportCategories {
-            bugs,
-            success,
-            failures,
-            all,
-            total: reports.len(),
+        minijinja::context! {
+            experiment,
+            reports => minijinja::context! {
+                bugs => select(|l| l.category == Category::Bug),
+                success => select(|l| l.category == Category::Success),
+                failures => select(|l| l.category == Category::Failure),
+                flaky => select(|l| l.flaky),
+                nondeterministic => select(|l| l.nondeterministic),
+                all => select(|_| true),
+                total => listings.len(),
+            },
+            timeline => timeline(&timelines),
+            total_time => format!("{total_time:.1?}"),
+            experiment_dir,
+            cache,
+            download_time => format!("{:.1?}", cache.download_time),
+            startup => StartupTimes::median(startup).map(|s| minijinja::context! {
+                cold => format!("{:.1?}", s.cold),
+                warm => format!("{:.1?}", s.warm),
+                speedup => format!("{:.1}", s.speedup()),
+            }),
+            trend,
+            imports => failing_imports(imports),
         }
     }
 }
 
-pub fn text(results: &Results, mut dest: impl Write) -> Result<(), Error> {
-    let Results {
-        experiment: _,
-        reports,
-        total_time,
-        ..
-    } = results;
+/// A list of reports which are read from the experiment's `reports.jsonl` as
+/// the template iterates over them.
+struct LoggedReports {
+    reader: Arc<Mutex<ReportReader>>,
+    summaries: Vec<ReportSummary>,
+}
 
-    let mut success = 0;
-    let mut failures = 0;
-    let mut bugs = 0;
+impl SeqObject for LoggedReports {
+    fn get_item(&self, idx: usize) -> Option<Value> {
+        let summary = self.summaries.get(idx)?;
+        let mut reader = self.reader.lock().unwrap();
 
-    for report in reports {
-        match &report.outcome {
-            crate::experiment::Outcome::Completed { status, .. } if status.success => success += 1,
-            crate::experiment::Outcome::Completed { .. } => failures += 1,
-            crate::experiment::Outcome::FetchFailed { .. }
-            | crate::experiment::Outcome::SetupFailed { .. }
-            | crate::experiment::Outcome::SpawnFailed { .. } => bugs += 1,
+        match reader.read(summary) {
+            Ok(report) => Some(Value::from_serializable(&report)),
+            Err(e) => {
+                tracing::warn!(
+                    error = &e as &dyn std::error::Error,
+                    package = %summary.display_name,
+                    version = %summary.version,
+                    "Unable to read the report back from the log",
+                );
+                None
+            }
         }
     }
 
+    fn item_count(&self) -> usize {
+        self.summaries.len()
+    }
+}
+
+/// How many failing test cases import something, compared to how many
+/// import it in total.
+#[derive(Debug, PartialEq, serde::Serialize)]
+struct ImportUsage {
+    name: String,
+    failing: usize,
+    total: usize,
+}
+
+/// The imports most commonly used by failures and bugs, to help spot
+/// functionality (e.g. sockets) that tends to break.
+fn failing_imports(usage: BTreeMap<String, ImportUsage>) -> Vec<ImportUsage> {
+    const MAX_IMPORTS: usize = 15;
+
+    let mut usage: Vec<_> = usage.into_values().filter(|u| u.failing > 0).collect();
+    usage.sort_by(|a, b| {
+        (std::cmp::Reverse(a.failing), &a.name).cmp(&(std::cmp::Reverse(b.failing), &b.name))
+    });
+    usage.truncate(MAX_IMPORTS);
+
+    usage
+}
+
+/// A test case's row in the report's timeline.
+#[derive(Debug, PartialEq, serde::Serialize)]
+struct TimelineRow<'a> {
+    display_name: &'a str,
+    version: &'a str,
+    download: Option<TimelineBar>,
+    execution: Option<TimelineBar>,
+}
+
+/// A bar on the timeline, positioned as a percentage of the whole run.
+#[derive(Debug, PartialEq, serde::Serialize)]
+struct TimelineBar {
+    left: f64,
+    width: f64,
+    duration: String,
+}
+
+/// Lay out each test case's download and execution phases over the course
+/// of the run, earliest first.
+fn timeline(timelines: &[(String, String, Timeline)]) -> Vec<TimelineRow<'_>> {
+    let (Some(origin), Some(end)) = (
+        timelines.iter().filter_map(|(_, _, t)| t.start()).min(),
+        timelines.iter().filter_map(|(_, _, t)| t.end()).max(),
+    ) else {
+        return Vec::new();
+    };
+    let span = (end - origin).num_milliseconds().max(1) as f64;
+
+    let bar = |phase: Option<Phase>| {
+        phase.map(|p| {
+            let percent = |ms: i64| (ms as f64 / span * 1000.0).round() / 10.0;
+            TimelineBar {
+                left: percent((p.start - origin).num_milliseconds()),
+                width: percent((p.end - p.start).num_milliseconds()),
+                duration: format!("{:.1?}", p.duration()),
+            }
+        })
+    };
+
+    let mut rows: Vec<_> = timelines
+        .iter()
+        .map(|(display_name, version, t)| {
+            (
+                t.start(),
+                TimelineRow {
+                    display_name,
+                    version,
+                    download: bar(t.download),
+                    execution: bar(t.execution),
+                },
+            )
+        })
+        .collect();
+    rows.sort_by_key(|(start, _)| *start);
+
+    rows.into_iter().map(|(_, row)| row).collect()
+}
+
+pub fn text(results: &Results, mut dest: impl Write) -> Result<(), RenderError> {
+    let total_time = results.total_time;
+    let success = results.successes().count();
+    let failures = results.failures().count();
+    let bugs = results.bugs().count();
+
     writeln!(dest, "Experiment result... success: {success}, failures: {failures}, bugs: {bugs}. Finished in {total_time:?}")?;
 
+    if let Some(startup) = results.median_startup() {
+        writeln!(
+            dest,
+            "Median startup... cold: {:.1?}, warm: {:.1?} ({:.1}x faster)",
+            startup.cold,
+            startup.warm,
+            startup.speedup()
+        )?;
+    }
+
+    let flaky = results.flaky().count();
+    if flaky > 0 {
+        writeln!(
+            dest,
+            "{flaky} test case(s) passed some repetitions but not others"
+        )?;
+    }
+
+    let nondeterministic = results.nondeterministic().count();
+    if nondeterministic > 0 {
+        writeln!(
+            dest,
+            "{nondeterministic} test case(s) behaved differently when run a second time"
+        )?;
+    }
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{path::PathBuf, time::Duration};
+
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::{
+        experiment::{MemoryLimits, ModuleInfo, SerializableError, Timeline},
+        test_utils::test_case,
+    };
+
+    fn results() -> Results {
+        Results {
+            experiment: serde_json::from_value(serde_json::json!({ "package": "wasmer/wasi" }))
+                .unwrap(),
+            reports: vec![Report {
+                display_name: "wasmer/python".to_string(),
+                package_version: test_case("python", "1.0.0").package_version,
+                outcome: Outcome::FetchFailed {
+                    error: SerializableError {
+                        error: "404".to_string(),
+                        detailed_error: "404".to_string(),
+                        causes: Vec::new(),
+                    },
+                },
+                analyses: Vec::new(),
+                annotation: None,
+                command: None,
+                benchmark: None,
+                startup: None,
+                repetitions: None,
+                stdout_digest: None,
+                rerun: None,
+                coredumps: Vec::new(),
+                logs_discarded: false,
+                modules: Vec::new(),
+                module_errors: Vec::new(),
+                timeline: None,
+            }],
+            total_time: Duration::ZERO,
+            experiment_dir: PathBuf::new(),
+            cache: Default::default(),
+        }
+    }
+
+    #[test]
+    fn custom_sections_are_included_in_the_report() {
+        let results = results();
+        let mut renderer = Renderer::new();
+        renderer.add_global("team", "Runtime");
+        renderer.add_filter("shout", |s: String| s.to_uppercase());
+        renderer
+            .add_template(REPORT_SECTIONS, "<h2>Owned by {{ team }}</h2>")
+            .unwrap();
+        renderer
+            .add_template(
+                TEST_CASE_SECTIONS,
+                "<p>{{ report.display_name | shout }}</p>",
+            )
+            .unwrap();
+
+        let html = renderer.html(&results).unwrap();
+
+        assert!(html.contains("<h2>Owned by Runtime</h2>"));
+        assert!(html.contains("<p>WASMER/PYTHON</p>"));
+        assert!(!DEFAULT.html(&results).unwrap().contains("WASMER/PYTHON"));
+    }
+
+    #[test]
+    fn timeline_is_relative_to_the_start_of_the_run() {
+        let at = |secs: i64| Utc.timestamp_opt(1_700_000_000 + secs, 0).unwrap();
+        let mut early = results().reports.remove(0);
+        early.timeline = Some(Timeline {
+            download: Some(Phase {
+                start: at(0),
+                end: at(2),
+            }),
+            execution: Some(Phase {
+                start: at(5),
+                end: at(10),
+            }),
+        });
+        let mut late = results().reports.remove(0);
+        late.display_name = "wasmer/cowsay".to_string();
+        late.timeline = Some(Timeline {
+            download: None,
+            execution: Some(Phase {
+                start: at(15),
+                end: at(20),
+            }),
+        });
+        let mut index = Index::new();
+        for report in [late, early] {
+            index.add(&report, ());
+        }
+
+        let rows = timeline(&index.timelines);
+
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].display_name, "wasmer/python");
+        assert_eq!(
+            rows[0].download,
+            Some(TimelineBar {
+                left: 0.0,
+                width: 10.0,
+                duration: "2.0s".to_string(),
+            })
+        );
+        assert_eq!(rows[0].execution.as_ref().unwrap().left, 25.0);
+        assert_eq!(rows[1].download, None);
+        assert_eq!(rows[1].execution.as_ref().unwrap().left, 75.0);
+        assert_eq!(rows[1].execution.as_ref().unwrap().width, 25.0);
+    }
+
+    #[test]
+    fn reports_can_be_rendered_straight_from_the_log() {
+        let temp = tempfile::tempdir().unwrap();
+        let mut results = results();
+        results.experiment_dir = temp.path().to_path_buf();
+        let mut log = serde_json::to_string(&results.reports[0]).unwrap();
+        log.push('\n');
+        std::fs::write(temp.path().join("reports.jsonl"), log).unwrap();
+        let summary = ResultsSummary {
+            experiment: results.experiment.clone(),
+            reports: vec![ReportSummary::new(&results.reports[0], 0)],
+            total_time: results.total_time,
+            experiment_dir: results.experiment_dir.clone(),
+            cache: results.cache.clone(),
+        };
+        let mut html = Vec::new();
+
+        Renderer::new()
+            .write_html(&summary, &[], &mut html)
+            .unwrap();
+
+        assert_eq!(
+            String::from_utf8(html).unwrap(),
+            Renderer::new().html(&results).unwrap()
+        );
+    }
+
+    #[test]
+    fn experiment_metadata_is_shown_in_the_report() {
+        let mut results = results();
+        results.experiment.name = Some("Python on wasmer 4.2".to_string());
+        results.experiment.description = Some("Checking the new WASIX release".to_string());
+        results
+            .experiment
+            .metadata
+            .insert("ticket".to_string(), "WAS-123".to_string());
+
+        let html = Renderer::new().html(&results).unwrap();
+
+        assert!(html.contains("<h2>Python on wasmer 4.2</h2>"));
+        assert!(html.contains("Checking the new WASIX release"));
+        assert!(html.contains("<td>ticket</td>"));
+        assert!(html.contains("<td>WAS-123</td>"));
+    }
+
+    #[test]
+    fn print_layout_lists_bugs_on_their_own_page() {
+        let html = Renderer::new().print_html(&results()).unwrap();
+
+        let bugs = html.find("<h2>Bugs</h2>").unwrap();
+        let page_break = html.rfind("<section class=\"page\">").unwrap();
+        assert!(page_break < bugs);
+        assert!(html[bugs..].contains("<td>wasmer/python</td>"));
+        assert!(!html.contains("<h2>Failures</h2>"));
+    }
+
+    #[test]
+    fn previous_runs_are_shown_as_a_trend() {
+        let results = results();
+        let history: Vec<_> = (1..=3)
+            .map(|day| {
+                let started = Utc.with_ymd_and_hms(2024, 6, day, 0, 0, 0).unwrap();
+                RunRecord::new("python", started, &results)
+            })
+            .collect();
+
+        let html = Renderer::new()
+            .html_with_history(&results, &history)
+            .unwrap();
+
+        assert!(html.contains("<h1>Trend</h1>"));
+        assert!(html.contains("The last 3 runs of this experiment, since 2024-06-01."));
+        assert!(!Renderer::new()
+            .html(&results)
+            .unwrap()
+            .contains("<h1>Trend</h1>"));
+    }
+
+    #[test]
+    fn failing_packages_can_be_grouped_by_their_imports() {
+        let mut results = results();
+        results.reports[0].modules = vec![ModuleInfo {
+            path: "bin/python.wasm".to_string(),
+            size: 1024,
+            imports: [(
+                "wasi_snapshot_preview1".to_string(),
+                vec!["fd_write".to_string(), "sock_open".to_string()],
+            )]
+            .into_iter()
+            .collect(),
+            exports: vec!["_start".to_string()],
+            memory: Some(MemoryLimits {
+                minimum: 17,
+                maximum: None,
+                shared: false,
+                memory64: false,
+            }),
+        }];
+
+        let html = Renderer::new().html(&results).unwrap();
+
+        assert!(html.contains(r#"data-imports="fd_write sock_open""#));
+        assert!(html.contains(r##"<a href="#import=sock_open"><code>sock_open</code></a>"##));
+        assert!(html.contains("<code>bin/python.wasm</code> (1.0 KiB"));
+    }
+
+    #[test]
+    fn owners_get_a_summary_of_their_failing_packages() {
+        let temp = tempfile::tempdir().unwrap();
+        std::fs::write(
+            temp.path().join("stderr.txt"),
+            "Starting up\nError: unknown import \"wasix_32v1::sock_open\"\n",
+        )
+        .unwrap();
+        let mut failure = results().reports.remove(0);
+        failure.display_name = "syrusakbary/cowsay".to_string();
+        failure.outcome = serde_json::from_value(serde_json::json!({
+            "outcome": "completed",
+            "base_dir": temp.path(),
+            "status": { "success": false, "code": 1 },
+            "run_time": { "secs": 1, "nanos": 0 },
+        }))
+        .unwrap();
+        let mut results = results();
+        results.reports.push(failure);
+
+        let summaries = Renderer::new().owner_summaries(&results).unwrap();
+
+        // wasmer/python was a bug, so only syrusakbary gets a summary
+        assert_eq!(summaries.keys().collect::<Vec<_>>(), ["syrusakbary"]);
+        let summary = &summaries["syrusakbary"];
+        assert!(summary.contains("| `syrusakbary/cowsay` | 1.0.0 | Exited with code 1 |"));
+        assert!(summary.contains("Error: unknown import \"wasix_32v1::sock_open\""));
+    }
+
+    #[test]
+    fn diverging_test_cases_are_marked_in_the_matrix() {
+        let before = results();
+        let after = results();
+        let diverged = [("wasmer/python", "1.0.0")];
+
+        let html = Renderer::new()
+            .matrix_html([("4.1", &before), ("4.2", &after)], &diverged)
+            .unwrap();
+
+        assert_eq!(html.matches("<td class=\"bug diverged\">").count(), 2);
+
+        let html = Renderer::new()
+            .matrix_html([("4.1", &before), ("4.2", &after)], &[])
+            .unwrap();
+
+        assert!(!html.contains("diverged\">"));
+    }
+}
@@ -0,0 +1,78 @@
+//! Printing reports to PDF with a headless Chromium-based browser, for people
+//! who need a static document rather than a live HTML file.
+
+use std::{
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+use crate::{
+    experiment::Results,
+    render::{file_url, RenderError, Renderer},
+};
+
+/// The environment variable used to point at a specific browser.
+pub const BROWSER_ENV: &str = "BOREALIS_BROWSER";
+
+/// Browsers to try, in order, when [`BROWSER_ENV`] isn't set.
+const BROWSERS: &[&str] = &[
+    "chromium",
+    "chromium-browser",
+    "google-chrome",
+    "google-chrome-stable",
+    "microsoft-edge",
+    "msedge",
+];
+
+impl Renderer<'_> {
+    /// Print a paginated summary of the results to a PDF.
+    ///
+    /// This needs Chromium, Chrome, or Edge to be installed. Use the
+    /// [`BROWSER_ENV`] environment variable if it isn't on the `$PATH`.
+    #[tracing::instrument(skip_all, fields(dest = %dest.display()))]
+    pub fn pdf(&self, results: &Results, dest: &Path) -> Result<(), RenderError> {
+        let html = self.print_html(results)?;
+
+        let temp = tempfile::Builder::new().suffix(".html").tempfile()?;
+        std::fs::write(temp.path(), html)?;
+
+        // Some browsers ignore relative paths for --print-to-pdf, so make sure
+        // it gets an absolute one.
+        let dest = std::env::current_dir()?.join(dest);
+
+        print_to_pdf(temp.path(), &dest)
+    }
+}
+
+fn print_to_pdf(html: &Path, dest: &Path) -> Result<(), RenderError> {
+    let candidates: Vec<PathBuf> = match std::env::var_os(BROWSER_ENV) {
+        Some(browser) => vec![browser.into()],
+        None => BROWSERS.iter().map(PathBuf::from).collect(),
+    };
+
+    for browser in candidates {
+        let result = Command::new(&browser)
+            .arg("--headless")
+            .arg("--disable-gpu")
+            .arg("--no-pdf-header-footer")
+            .arg(format!("--print-to-pdf={}", dest.display()))
+            .arg(file_url(html.display().to_string()))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+
+        match result {
+            Ok(status) if status.success() => {
+                tracing::debug!(browser = %browser.display(), "Printed the PDF");
+                return Ok(());
+            }
+            Ok(status) => return Err(RenderError::BrowserFailed(status)),
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+
+    Err(RenderError::BrowserNotFound)
+}
@@ -0,0 +1,210 @@
+//! A static website covering every recorded run, so there is a dashboard of
+//! registry compatibility over time that can be published anywhere (e.g.
+//! GitHub Pages or S3).
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    experiment::Results,
+    render::{RenderError, Renderer, REPORT_SECTIONS},
+    runs::IndexedRun,
+};
+
+const CHART_WIDTH: f64 = 600.0;
+const CHART_HEIGHT: f64 = 120.0;
+
+/// The directory (relative to the site's root) each run's pages go in.
+const RUNS_DIR: &str = "runs";
+
+/// What was written by [`Renderer::build_site()`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Site {
+    /// The site's landing page.
+    pub index: PathBuf,
+    /// How many run pages were written.
+    pub pages: usize,
+    /// Runs which were left out because their results couldn't be loaded
+    /// (e.g. because they were deleted).
+    pub skipped: Vec<String>,
+}
+
+impl Renderer<'_> {
+    /// Build a static site with an index of every run (plus a trend chart for
+    /// each experiment) and a copy of each run's report.
+    ///
+    /// Links to each test case's logs point at the machine the site was built
+    /// on, so they won't work once the site is published elsewhere.
+    #[tracing::instrument(skip_all, fields(output = %output.display()))]
+    pub fn build_site(&self, runs: &[IndexedRun], output: &Path) -> Result<Site, RenderError> {
+        let mut renderer = self.clone();
+        if renderer.env.get_template(REPORT_SECTIONS).is_err() {
+            renderer.add_template(
+                REPORT_SECTIONS,
+                r#"<p><a href="../../../index.html">&larr; All runs</a></p>"#,
+            )?;
+        }
+
+        let mut experiments: BTreeMap<&str, Vec<SiteRun<'_>>> = BTreeMap::new();
+        let mut skipped = Vec::new();
+
+        for run in runs {
+            let record = &run.record;
+            let results = match Results::load(record.results_path()) {
+                Ok(r) => r,
+                Err(e) => {
+                    tracing::warn!(
+                        error = &*e,
+                        run = %run.name,
+                        experiment = %record.experiment,
+                        "Skipping a run whose results couldn't be loaded",
+                    );
+                    skipped.push(run.name.clone());
+                    continue;
+                }
+            };
+
+            let page = format!("{RUNS_DIR}/{}/{}/index.html", run.name, record.experiment);
+            let path = output.join(&page);
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::fs::write(&path, renderer.html(&results)?)?;
+            std::fs::copy(record.results_path(), path.with_file_name("results.json"))?;
+
+            experiments
+                .entry(record.experiment.as_str())
+                .or_default()
+                .push(SiteRun { run, page });
+        }
+
+        let pages = experiments.values().map(Vec::len).sum();
+        let experiments: Vec<_> = experiments
+            .into_iter()
+            .map(|(name, mut runs)| {
+                runs.sort_by_key(|r| r.run.record.started);
+                let trend = trend(&runs);
+                runs.reverse();
+                minijinja::context! { name, runs, trend }
+            })
+            .collect();
+
+        let ctx = minijinja::context! {
+            experiments,
+            chart => minijinja::context! { width => CHART_WIDTH, height => CHART_HEIGHT },
+        };
+        let index = output.join("index.html");
+        std::fs::create_dir_all(output)?;
+        std::fs::write(&index, self.env.get_template("site")?.render(ctx)?)?;
+
+        Ok(Site {
+            index,
+            pages,
+            skipped,
+        })
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+struct SiteRun<'a> {
+    #[serde(flatten)]
+    run: &'a IndexedRun,
+    /// The run's report, relative to the site's root.
+    page: String,
+}
+
+/// A point on an experiment's success rate chart, oldest run first.
+#[derive(Debug, PartialEq, serde::Serialize)]
+struct TrendPoint {
+    x: f64,
+    y: f64,
+    label: String,
+}
+
+fn trend(runs: &[SiteRun<'_>]) -> Vec<TrendPoint> {
+    let step = if runs.len() > 1 {
+        CHART_WIDTH / (runs.len() - 1) as f64
+    } else {
+        0.0
+    };
+
+    runs.iter()
+        .enumerate()
+        .map(|(i, r)| {
+            let record = &r.run.record;
+            let rate = record.success_rate();
+            TrendPoint {
+                x: (i as f64 * step).round(),
+                y: ((1.0 - rate) * CHART_HEIGHT).round(),
+                label: format!(
+                    "{} ({}): {:.1}% success, {} bugs",
+                    r.run.name,
+                    record.started.format("%Y-%m-%d"),
+                    rate * 100.0,
+                    record.bugs
+                ),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::history::RunRecord;
+
+    fn run(name: &str, day: u32, success: usize, experiment_dir: PathBuf) -> IndexedRun {
+        IndexedRun {
+            name: name.to_string(),
+            record: RunRecord {
+                experiment: "python".to_string(),
+                package: "wasmer/python".to_string(),
+                started: Utc.with_ymd_and_hms(2024, 6, day, 0, 0, 0).unwrap(),
+                experiment_dir,
+                total: 4,
+                success,
+                failures: 4 - success,
+                bugs: 0,
+                total_time: Duration::from_secs(1),
+            },
+        }
+    }
+
+    #[test]
+    fn build_a_site_from_previous_runs() {
+        let temp = tempfile::tempdir().unwrap();
+        let results = Results {
+            experiment: serde_json::from_value(serde_json::json!({ "package": "wasmer/python" }))
+                .unwrap(),
+            reports: Vec::new(),
+            total_time: Duration::ZERO,
+            experiment_dir: temp.path().join("first"),
+            cache: Default::default(),
+        };
+        std::fs::create_dir_all(&results.experiment_dir).unwrap();
+        results
+            .save(temp.path().join("first/results.json"))
+            .unwrap();
+        let runs = vec![
+            run("first", 1, 2, temp.path().join("first")),
+            run("deleted", 2, 4, temp.path().join("deleted")),
+        ];
+        let output = temp.path().join("site");
+
+        let site = Renderer::new().build_site(&runs, &output).unwrap();
+
+        assert_eq!(site.pages, 1);
+        assert_eq!(site.skipped, ["deleted"]);
+        let index = std::fs::read_to_string(site.index).unwrap();
+        assert!(index.contains(r#"href="runs/first/python/index.html""#));
+        let report = std::fs::read_to_string(output.join("runs/first/python/index.html")).unwrap();
+        assert!(report.contains("All runs"));
+        assert!(output.join("runs/first/python/results.json").exists());
+    }
+}
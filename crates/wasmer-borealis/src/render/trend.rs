@@ -0,0 +1,139 @@
+//! Sparklines showing how an experiment's success rate and bug count have
+//! changed over its most recent runs.
+
+use crate::history::RunRecord;
+
+/// How many of an experiment's most recent runs are shown in its report.
+pub const TREND_RUNS: usize = 20;
+
+const WIDTH: f64 = 300.0;
+const HEIGHT: f64 = 40.0;
+
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub(crate) struct Trend {
+    width: f64,
+    height: f64,
+    points: Vec<Point>,
+    first: Summary,
+    last: Summary,
+}
+
+/// One run on the sparkline, oldest first.
+#[derive(Debug, PartialEq, serde::Serialize)]
+struct Point {
+    x: f64,
+    /// Where the success rate goes.
+    success: f64,
+    /// Where the bug count goes.
+    bugs: f64,
+    label: String,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize)]
+struct Summary {
+    started: String,
+    success_rate: String,
+    bugs: usize,
+}
+
+impl Trend {
+    /// Chart the [`TREND_RUNS`] most recent runs of an experiment.
+    ///
+    /// Returns `None` when there aren't at least two runs to compare.
+    pub(crate) fn new(history: &[RunRecord]) -> Option<Self> {
+        let mut runs: Vec<&RunRecord> = history.iter().collect();
+        runs.sort_by_key(|r| r.started);
+        let runs = &runs[runs.len().saturating_sub(TREND_RUNS)..];
+
+        let (first, last) = match runs {
+            [first, .., last] => (*first, *last),
+            _ => return None,
+        };
+
+        let step = WIDTH / (runs.len() - 1) as f64;
+        let max_bugs = runs.iter().map(|r| r.bugs).max().unwrap_or_default().max(1);
+
+        let points = runs
+            .iter()
+            .enumerate()
+            .map(|(i, r)| Point {
+                x: (i as f64 * step).round(),
+                success: ((1.0 - r.success_rate()) * HEIGHT).round(),
+                bugs: ((1.0 - r.bugs as f64 / max_bugs as f64) * HEIGHT).round(),
+                label: format!(
+                    "{}: {:.1}% success, {} bugs",
+                    r.started.format("%Y-%m-%d %H:%M"),
+                    r.success_rate() * 100.0,
+                    r.bugs
+                ),
+            })
+            .collect();
+
+        Some(Trend {
+            width: WIDTH,
+            height: HEIGHT,
+            points,
+            first: Summary::new(first),
+            last: Summary::new(last),
+        })
+    }
+}
+
+impl Summary {
+    fn new(record: &RunRecord) -> Self {
+        Summary {
+            started: record.started.format("%Y-%m-%d").to_string(),
+            success_rate: format!("{:.1}%", record.success_rate() * 100.0),
+            bugs: record.bugs,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn record(day: u32, success: usize, bugs: usize) -> RunRecord {
+        RunRecord {
+            experiment: "python".to_string(),
+            package: "wasmer/python".to_string(),
+            started: Utc.with_ymd_and_hms(2024, 6, day, 0, 0, 0).unwrap(),
+            experiment_dir: Default::default(),
+            total: 10,
+            success,
+            failures: 10 - success - bugs,
+            bugs,
+            total_time: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn a_single_run_has_no_trend() {
+        assert!(Trend::new(&[record(1, 10, 0)]).is_none());
+    }
+
+    #[test]
+    fn plot_the_most_recent_runs_oldest_first() {
+        let mut history: Vec<_> = (1..=TREND_RUNS as u32 + 5)
+            .map(|day| record(day, 5, 0))
+            .collect();
+        history.push(record(28, 8, 2));
+        history.reverse();
+
+        let trend = Trend::new(&history).unwrap();
+
+        assert_eq!(trend.points.len(), TREND_RUNS);
+        assert_eq!(trend.first.started, "2024-06-07");
+        let last = trend.points.last().unwrap();
+        assert_eq!(last.x, WIDTH);
+        assert_eq!(last.success, 8.0);
+        assert_eq!(last.bugs, 0.0);
+        assert_eq!(trend.points[0].success, HEIGHT / 2.0);
+        assert_eq!(trend.points[0].bugs, HEIGHT);
+        assert_eq!(trend.last.success_rate, "80.0%");
+    }
+}
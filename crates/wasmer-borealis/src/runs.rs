@@ -0,0 +1,245 @@
+//! An index of the experiment runs on this machine, so old runs can be found
+//! by name instead of digging through directories named after UUIDs.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Error};
+
+use crate::history::{append_jsonl, read_jsonl, RunRecord};
+
+const INDEX_FILENAME: &str = "index.jsonl";
+const LATEST_DIR: &str = "latest";
+
+/// An index of named runs, plus a `latest/<experiment>` symlink pointing at
+/// the most recent run of each experiment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RunIndex {
+    dir: PathBuf,
+}
+
+impl RunIndex {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        RunIndex { dir: dir.into() }
+    }
+
+    /// The index used when no other location is specified.
+    pub fn default_location() -> PathBuf {
+        crate::DIRS.data_local_dir().join("runs")
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Where a run's results are written to unless told otherwise.
+    pub fn run_dir(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// Add a run to the index and point the experiment's `latest` symlink at
+    /// it.
+    pub fn record(&self, run: &IndexedRun) -> Result<(), Error> {
+        append_jsonl(&self.index_path(), run)?;
+        self.update_latest(&run.record.experiment)
+    }
+
+    /// Every run in the index, oldest first.
+    ///
+    /// A single run of several experiments has one entry per experiment.
+    pub fn runs(&self) -> Result<Vec<IndexedRun>, Error> {
+        read_jsonl(&self.index_path())
+    }
+
+    /// Get the entries for every run with this name.
+    pub fn find(&self, name: &str) -> Result<Vec<IndexedRun>, Error> {
+        let mut runs = self.runs()?;
+        runs.retain(|r| r.name == name);
+        Ok(runs)
+    }
+
+    /// Remove a run from the index and delete its results.
+    pub fn remove(&self, name: &str) -> Result<Vec<IndexedRun>, Error> {
+        let (removed, kept): (Vec<_>, Vec<_>) =
+            self.runs()?.into_iter().partition(|r| r.name == name);
+
+        for run in &removed {
+            let dir = &run.record.experiment_dir;
+            if dir.exists() {
+                std::fs::remove_dir_all(dir)
+                    .with_context(|| format!("Unable to delete \"{}\"", dir.display()))?;
+            }
+        }
+
+        // Clean up the parent directory when several experiments were run
+        let run_dir = self.run_dir(name);
+        if run_dir.exists() && run_dir.read_dir()?.next().is_none() {
+            std::fs::remove_dir(&run_dir)
+                .with_context(|| format!("Unable to delete \"{}\"", run_dir.display()))?;
+        }
+
+        let mut index = String::new();
+        for run in &kept {
+            index.push_str(&serde_json::to_string(run)?);
+            index.push('\n');
+        }
+        std::fs::write(self.index_path(), index)
+            .with_context(|| format!("Unable to write \"{}\"", self.index_path().display()))?;
+
+        for run in &removed {
+            self.update_latest(&run.record.experiment)?;
+        }
+
+        Ok(removed)
+    }
+
+    /// Delete everything except the `keep` most recent runs, returning the
+    /// names of the runs that were removed.
+    pub fn prune(&self, keep: usize) -> Result<Vec<String>, Error> {
+        // Each run may have several entries (one per experiment)
+        let mut started = BTreeMap::new();
+        for run in self.runs()? {
+            let entry = started.entry(run.name).or_insert(run.record.started);
+            *entry = (*entry).max(run.record.started);
+        }
+
+        let mut names: Vec<_> = started.into_iter().collect();
+        names.sort_by_key(|(_, started)| std::cmp::Reverse(*started));
+
+        let mut removed = Vec::new();
+        for (name, _) in names.into_iter().skip(keep) {
+            self.remove(&name)?;
+            removed.push(name);
+        }
+
+        Ok(removed)
+    }
+
+    /// The symlink to the most recent run of an experiment.
+    pub fn latest(&self, experiment: &str) -> PathBuf {
+        self.dir.join(LATEST_DIR).join(experiment)
+    }
+
+    fn index_path(&self) -> PathBuf {
+        self.dir.join(INDEX_FILENAME)
+    }
+
+    fn update_latest(&self, experiment: &str) -> Result<(), Error> {
+        let link = self.latest(experiment);
+        if link.symlink_metadata().is_ok() {
+            std::fs::remove_file(&link)
+                .with_context(|| format!("Unable to remove \"{}\"", link.display()))?;
+        }
+
+        let target = self
+            .runs()?
+            .into_iter()
+            .filter(|r| r.record.experiment == experiment)
+            .max_by_key(|r| r.record.started)
+            .map(|r| r.record.experiment_dir);
+
+        let Some(target) = target else {
+            return Ok(());
+        };
+
+        if let Some(parent) = link.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Unable to create \"{}\"", parent.display()))?;
+        }
+        symlink_dir(&target, &link).with_context(|| {
+            format!(
+                "Unable to link \"{}\" to \"{}\"",
+                link.display(),
+                target.display()
+            )
+        })
+    }
+}
+
+/// An entry in the [`RunIndex`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IndexedRun {
+    /// The run's name (e.g. `nightly-2024-06-01`).
+    pub name: String,
+    #[serde(flatten)]
+    pub record: RunRecord,
+}
+
+#[cfg(unix)]
+fn symlink_dir(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn symlink_dir(target: &Path, link: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_dir(target, link)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+
+    fn run(index: &RunIndex, name: &str, day: u32) -> IndexedRun {
+        let experiment_dir = index.run_dir(name);
+        std::fs::create_dir_all(&experiment_dir).unwrap();
+
+        IndexedRun {
+            name: name.to_string(),
+            record: RunRecord {
+                experiment: "python".to_string(),
+                package: "wasmer/python".to_string(),
+                started: Utc.with_ymd_and_hms(2024, 6, day, 0, 0, 0).unwrap(),
+                experiment_dir,
+                total: 1,
+                success: 1,
+                failures: 0,
+                bugs: 0,
+                total_time: Duration::from_secs(1),
+            },
+        }
+    }
+
+    #[test]
+    fn latest_always_points_at_the_most_recent_run() {
+        let temp = tempfile::tempdir().unwrap();
+        let index = RunIndex::new(temp.path());
+        let first = run(&index, "first", 1);
+        let second = run(&index, "second", 2);
+
+        index.record(&first).unwrap();
+        index.record(&second).unwrap();
+
+        assert_eq!(index.runs().unwrap(), [first.clone(), second.clone()]);
+        assert_eq!(index.find("first").unwrap(), [first.clone()]);
+        let latest = index.latest("python");
+        assert_eq!(latest.read_link().unwrap(), second.record.experiment_dir);
+
+        assert_eq!(index.remove("second").unwrap(), [second.clone()]);
+
+        assert!(!second.record.experiment_dir.exists());
+        assert_eq!(index.runs().unwrap(), [first.clone()]);
+        assert_eq!(latest.read_link().unwrap(), first.record.experiment_dir);
+    }
+
+    #[test]
+    fn pruning_keeps_the_most_recent_runs() {
+        let temp = tempfile::tempdir().unwrap();
+        let index = RunIndex::new(temp.path());
+        for (name, day) in [("b", 2), ("c", 3), ("a", 1)] {
+            index.record(&run(&index, name, day)).unwrap();
+        }
+
+        assert_eq!(index.prune(2).unwrap(), ["a"]);
+
+        let names: Vec<_> = index.runs().unwrap().into_iter().map(|r| r.name).collect();
+        assert_eq!(names, ["b", "c"]);
+        assert!(index.prune(2).unwrap().is_empty());
+    }
+}
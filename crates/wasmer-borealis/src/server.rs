@@ -0,0 +1,754 @@
+//! A small HTTP API for launching and monitoring experiments.
+//!
+//! | Method | Path                        | Description                                  |
+//! | ------ | --------------------------- | -------------------------------------------- |
+//! | `POST` | `/experiments`              | Start an experiment from an uploaded config  |
+//! | `GET`  | `/experiments`              | List all experiments                         |
+//! | `GET`  | `/experiments/{id}`         | Get an experiment's status and progress      |
+//! | `GET`  | `/experiments/{id}/results` | Get the `results.json` for a finished run    |
+//!
+//! Every request must include an `Authorization: Bearer <token>` header with
+//! the server's API token. Uploaded experiments can't run arbitrary commands
+//! on the host or reach outside the server's registry, so `hooks`,
+//! `analyzers`, `local` packages, `wasmer` binaries built from git or loaded
+//! from a path, extra arguments and environment variables (including in
+//! `overrides`), and `test-cases` from other registries are rejected.
+
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context, Error};
+use hyper::{
+    body::HttpBody,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, StatusCode,
+};
+use indexmap::IndexMap;
+use uuid::Uuid;
+
+use crate::{
+    config::{Experiment, WasmerVersion},
+    experiment::{Category, ExperimentBuilder, Progress, Results, TestCase},
+};
+
+/// The largest experiment that can be uploaded.
+const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// How many experiments can run at the same time by default.
+const DEFAULT_MAX_RUNNING: usize = 2;
+
+/// How many finished experiments are remembered before the oldest ones are
+/// forgotten (their files are left on disk).
+const MAX_FINISHED_JOBS: usize = 100;
+
+type BuilderFactory = dyn Fn(Experiment) -> ExperimentBuilder + Send + Sync;
+
+/// A HTTP server which runs experiments in the background.
+#[derive(Clone)]
+pub struct Server {
+    experiments_dir: PathBuf,
+    api_token: Arc<str>,
+    factory: Arc<BuilderFactory>,
+    max_running: usize,
+    jobs: Arc<Mutex<IndexMap<Uuid, Job>>>,
+}
+
+impl Server {
+    /// Create a new [`Server`].
+    ///
+    /// Each experiment's files will be saved to a sub-directory of
+    /// `experiments_dir`, and the `factory` is used to configure the
+    /// [`ExperimentBuilder`] for each experiment (e.g. to set the registry
+    /// endpoint or cache directory). Clients must send the `api_token` as a
+    /// bearer token.
+    pub fn new(
+        experiments_dir: impl Into<PathBuf>,
+        api_token: impl Into<String>,
+        factory: impl Fn(Experiment) -> ExperimentBuilder + Send + Sync + 'static,
+    ) -> Self {
+        Server {
+            experiments_dir: experiments_dir.into(),
+            api_token: api_token.into().into(),
+            factory: Arc::new(factory),
+            max_running: DEFAULT_MAX_RUNNING,
+            jobs: Arc::default(),
+        }
+    }
+
+    /// Reject new experiments while this many are already running.
+    pub fn with_max_running(self, max_running: usize) -> Self {
+        Server {
+            max_running,
+            ..self
+        }
+    }
+
+    /// Serve the API on a particular address until the process is stopped.
+    pub async fn serve(self, addr: SocketAddr) -> Result<(), Error> {
+        let make_service = make_service_fn(move |_| {
+            let server = self.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let server = server.clone();
+                    async move { Ok::<_, Infallible>(server.handle(req).await) }
+                }))
+            }
+        });
+
+        let server = hyper::Server::try_bind(&addr)
+            .with_context(|| format!("Unable to bind to {addr}"))?
+            .serve(make_service);
+
+        tracing::info!(addr = %server.local_addr(), "Listening for requests");
+
+        server.await?;
+
+        Ok(())
+    }
+
+    #[tracing::instrument(skip_all, fields(method = %req.method(), path = req.uri().path()))]
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        let segments: Vec<String> = req
+            .uri()
+            .path()
+            .split('/')
+            .filter(|s| !s.is_empty())
+            .map(String::from)
+            .collect();
+        let segments: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+
+        let result = match self.authenticate(&req) {
+            Err(e) => Err(e),
+            Ok(()) => match (req.method(), segments.as_slice()) {
+                (&Method::POST, ["experiments"]) => self.start(req).await,
+                (&Method::GET, ["experiments"]) => Ok(self.list()),
+                (&Method::GET, ["experiments", id]) => self.status(id),
+                (&Method::GET, ["experiments", id, "results"]) => self.results(id),
+                _ => Err(ApiError::new(StatusCode::NOT_FOUND, "Not found")),
+            },
+        };
+
+        match result {
+            Ok(response) => response,
+            Err(e) => e.into_response(),
+        }
+    }
+
+    fn authenticate(&self, req: &Request<Body>) -> Result<(), ApiError> {
+        let token = req
+            .headers()
+            .get(hyper::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.split_once(' '))
+            .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("bearer"))
+            .map(|(_, token)| token.trim());
+
+        match token {
+            Some(token) if constant_time_eq(token.as_bytes(), self.api_token.as_bytes()) => Ok(()),
+            _ => Err(ApiError::new(
+                StatusCode::UNAUTHORIZED,
+                "A valid API token is required",
+            )),
+        }
+    }
+
+    async fn start(&self, req: Request<Body>) -> Result<Response<Body>, ApiError> {
+        let body = read_body(req.into_body(), MAX_BODY_SIZE).await?;
+        let experiment = parse_experiment(&body)
+            .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, format!("{e:#}")))?;
+        check_allowed(&experiment)
+            .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, e))?;
+
+        let id = Uuid::new_v4();
+        let experiment_dir = self.experiments_dir.join(id.to_string());
+        let progress = SharedProgress::default();
+
+        let job = Job {
+            id,
+            package: experiment.package.clone(),
+            experiment_dir: experiment_dir.clone(),
+            state: State::Running,
+            progress: progress.clone(),
+        };
+
+        {
+            let mut jobs = self.jobs.lock().unwrap();
+            let running = jobs
+                .values()
+                .filter(|j| matches!(j.state, State::Running))
+                .count();
+            if running >= self.max_running {
+                return Err(ApiError::new(
+                    StatusCode::TOO_MANY_REQUESTS,
+                    format!("{running} experiments are already running"),
+                ));
+            }
+            forget_finished_jobs(&mut jobs, MAX_FINISHED_JOBS);
+            jobs.insert(id, job);
+        }
+
+        let factory = Arc::clone(&self.factory);
+        let jobs = Arc::clone(&self.jobs);
+
+        let spawned = std::thread::Builder::new()
+            .name(format!("experiment-{id}"))
+            .spawn(move || {
+                let builder = factory(experiment)
+                    .with_experiment_dir(&experiment_dir)
+                    .with_progress(progress);
+
+                let state = match builder.run() {
+                    Ok(_) => State::Completed,
+                    Err(e) => {
+                        let e = Error::from(e);
+                        tracing::warn!(%id, error = &*e, "Experiment failed");
+                        State::Failed {
+                            error: format!("{e:?}"),
+                        }
+                    }
+                };
+
+                if let Some(job) = jobs.lock().unwrap().get_mut(&id) {
+                    job.state = state;
+                }
+            });
+
+        if let Err(e) = spawned {
+            self.jobs.lock().unwrap().shift_remove(&id);
+            return Err(ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+
+        tracing::info!(%id, "Experiment started");
+
+        let job = self.jobs.lock().unwrap()[&id].summary();
+        Ok(json(StatusCode::ACCEPTED, &job))
+    }
+
+    fn list(&self) -> Response<Body> {
+        let jobs: Vec<_> = self
+            .jobs
+            .lock()
+            .unwrap()
+            .values()
+            .map(Job::summary)
+            .collect();
+        json(StatusCode::OK, &jobs)
+    }
+
+    fn status(&self, id: &str) -> Result<Response<Body>, ApiError> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = lookup(&jobs, id)?;
+        Ok(json(StatusCode::OK, &job.summary()))
+    }
+
+    fn results(&self, id: &str) -> Result<Response<Body>, ApiError> {
+        let results_path = {
+            let jobs = self.jobs.lock().unwrap();
+            let job = lookup(&jobs, id)?;
+
+            match &job.state {
+                State::Completed => job.experiment_dir.join("results.json"),
+                State::Running => {
+                    return Err(ApiError::new(
+                        StatusCode::CONFLICT,
+                        "The experiment is still running",
+                    ))
+                }
+                State::Failed { error } => return Err(ApiError::new(StatusCode::CONFLICT, error)),
+            }
+        };
+
+        let results = Results::load(results_path)
+            .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, format!("{e:?}")))?;
+
+        Ok(json(StatusCode::OK, &results))
+    }
+}
+
+impl std::fmt::Debug for Server {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Server")
+            .field("experiments_dir", &self.experiments_dir)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Read the request body, refusing anything bigger than `limit` bytes.
+async fn read_body(mut body: Body, limit: usize) -> Result<Vec<u8>, ApiError> {
+    let too_large = || {
+        ApiError::new(
+            StatusCode::PAYLOAD_TOO_LARGE,
+            format!("Experiments can't be bigger than {limit} bytes"),
+        )
+    };
+
+    if body.size_hint().lower() > limit as u64 {
+        return Err(too_large());
+    }
+
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e))?;
+        if buffer.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        buffer.extend_from_slice(&chunk);
+    }
+
+    Ok(buffer)
+}
+
+/// Make sure an uploaded experiment can't run arbitrary commands on the
+/// host.
+///
+/// Arguments and environment variables are rejected outright because flags
+/// like `--mapdir` and `--dir` (or `$WASMER_*` variables) would give the
+/// package access to the host's filesystem.
+fn check_allowed(experiment: &Experiment) -> Result<(), String> {
+    let mut forbidden = Vec::new();
+
+    if !experiment.hooks.is_empty() {
+        forbidden.push("hooks");
+    }
+    if !experiment.analyzers.is_empty() {
+        forbidden.push("analyzers");
+    }
+    if experiment.local.is_some() {
+        forbidden.push("local");
+    }
+    if matches!(
+        experiment.wasmer.version,
+        WasmerVersion::Local { .. } | WasmerVersion::Git { .. }
+    ) {
+        forbidden.push("a wasmer.version built from git or loaded from a path");
+    }
+    if !experiment.args.is_empty() {
+        forbidden.push("args");
+    }
+    if !experiment.env.is_empty() {
+        forbidden.push("env");
+    }
+    if !experiment.wasmer.args.is_empty() {
+        forbidden.push("wasmer.args");
+    }
+    if !experiment.wasmer.env.is_empty() {
+        forbidden.push("wasmer.env");
+    }
+    if experiment
+        .overrides
+        .values()
+        .any(|o| o.args.is_some() || !o.env.is_empty())
+    {
+        forbidden.push("overrides with args or env");
+    }
+    if experiment.test_cases.iter().any(|t| t.registry.is_some()) {
+        forbidden.push("test-cases from another registry");
+    }
+
+    if forbidden.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "Uploaded experiments can't use {}",
+            forbidden.join(", ")
+        ))
+    }
+}
+
+/// Forget the oldest finished jobs so there are at most `max` of them.
+fn forget_finished_jobs(jobs: &mut IndexMap<Uuid, Job>, max: usize) {
+    let finished: Vec<Uuid> = jobs
+        .values()
+        .filter(|j| !matches!(j.state, State::Running))
+        .map(|j| j.id)
+        .collect();
+
+    for id in finished.iter().take(finished.len().saturating_sub(max)) {
+        jobs.shift_remove(id);
+    }
+}
+
+/// Compare two byte strings without leaking where they differ through the
+/// time taken.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Parse an uploaded experiment, ignoring the `$schema` property that is
+/// normally present in `*.experiment.json` files.
+fn parse_experiment(body: &[u8]) -> Result<Experiment, Error> {
+    let mut value: serde_json::Value =
+        serde_json::from_slice(body).context("The body wasn't valid JSON")?;
+
+    if let Some(obj) = value.as_object_mut() {
+        obj.remove("$schema");
+    }
+
+    serde_json::from_value(value).context("Invalid experiment")
+}
+
+fn lookup<'a>(jobs: &'a IndexMap<Uuid, Job>, id: &str) -> Result<&'a Job, ApiError> {
+    let id: Uuid = id
+        .parse()
+        .map_err(|_| ApiError::new(StatusCode::NOT_FOUND, "Unknown experiment"))?;
+
+    jobs.get(&id)
+        .ok_or_else(|| ApiError::new(StatusCode::NOT_FOUND, "Unknown experiment"))
+}
+
+fn json(status: StatusCode, value: &impl serde::Serialize) -> Response<Body> {
+    let body = serde_json::to_vec_pretty(value).expect("Serialization should never fail");
+
+    Response::builder()
+        .status(status)
+        .header(hyper::header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, message: impl ToString) -> Self {
+        ApiError {
+            status,
+            message: message.to_string(),
+        }
+    }
+
+    fn into_response(self) -> Response<Body> {
+        json(self.status, &serde_json::json!({ "error": self.message }))
+    }
+}
+
+#[derive(Debug)]
+struct Job {
+    id: Uuid,
+    package: String,
+    experiment_dir: PathBuf,
+    state: State,
+    progress: SharedProgress,
+}
+
+impl Job {
+    fn summary(&self) -> JobSummary {
+        JobSummary {
+            id: self.id,
+            package: self.package.clone(),
+            experiment_dir: self.experiment_dir.clone(),
+            state: self.state.clone(),
+            progress: self.progress.0.lock().unwrap().clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JobSummary {
+    id: Uuid,
+    package: String,
+    experiment_dir: PathBuf,
+    #[serde(flatten)]
+    state: State,
+    progress: Counts,
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+#[serde(tag = "state", rename_all = "kebab-case")]
+enum State {
+    Running,
+    Completed,
+    Failed { error: String },
+}
+
+/// A [`Progress`] implementation that keeps track of how far through an
+/// experiment we are.
+#[derive(Debug, Default, Clone)]
+struct SharedProgress(Arc<Mutex<Counts>>);
+
+#[derive(Debug, Default, Clone, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct Counts {
+    discovered: usize,
+    downloaded: usize,
+    cache_hits: usize,
+    bytes_downloaded: u64,
+    success: usize,
+    failures: usize,
+    bugs: usize,
+}
+
+impl Progress for SharedProgress {
+    fn discovered(&mut self, _test_case: TestCase) {
+        self.0.lock().unwrap().discovered += 1;
+    }
+
+    fn finished(&mut self, _test_case: TestCase, category: Category) {
+        let mut counts = self.0.lock().unwrap();
+
+        match category {
+            Category::Success => counts.success += 1,
+            Category::Failure => counts.failures += 1,
+            Category::Bug => counts.bugs += 1,
+        }
+    }
+
+    fn cache_hit(&mut self, _test_case: TestCase) {
+        self.0.lock().unwrap().cache_hits += 1;
+    }
+
+    fn cache_miss(
+        &mut self,
+        _test_case: TestCase,
+        _duration: std::time::Duration,
+        bytes_downloaded: u64,
+    ) {
+        let mut counts = self.0.lock().unwrap();
+        counts.downloaded += 1;
+        counts.bytes_downloaded += bytes_downloaded;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOKEN: &str = "s3cr3t";
+
+    fn server(dir: &std::path::Path) -> Server {
+        let cache_dir = dir.join("cache");
+        Server::new(dir.join("experiments"), TOKEN, move |experiment| {
+            ExperimentBuilder::new(experiment)
+                .with_endpoint("http://127.0.0.1:1/graphql")
+                .unwrap()
+                .with_cache_dir(&cache_dir)
+        })
+    }
+
+    fn request(method: Method, path: &str, body: impl Into<Body>) -> Request<Body> {
+        Request::builder()
+            .method(method)
+            .uri(path)
+            .header(hyper::header::AUTHORIZATION, format!("Bearer {TOKEN}"))
+            .body(body.into())
+            .unwrap()
+    }
+
+    async fn send(server: &Server, req: Request<Body>) -> (StatusCode, serde_json::Value) {
+        let response = server.handle(req).await;
+        let status = response.status();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    fn insert_job(server: &Server, state: State) -> Uuid {
+        let id = Uuid::new_v4();
+        let job = Job {
+            id,
+            package: "wasmer/python".to_string(),
+            experiment_dir: server.experiments_dir.join(id.to_string()),
+            state,
+            progress: SharedProgress::default(),
+        };
+        server.jobs.lock().unwrap().insert(id, job);
+        id
+    }
+
+    #[tokio::test]
+    async fn requests_need_the_api_token() {
+        let temp = tempfile::tempdir().unwrap();
+        let server = server(temp.path());
+
+        let missing = Request::get("/experiments").body(Body::empty()).unwrap();
+        let wrong = Request::get("/experiments")
+            .header(hyper::header::AUTHORIZATION, "Bearer nope")
+            .body(Body::empty())
+            .unwrap();
+
+        assert_eq!(send(&server, missing).await.0, StatusCode::UNAUTHORIZED);
+        assert_eq!(send(&server, wrong).await.0, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn list_experiments() {
+        let temp = tempfile::tempdir().unwrap();
+        let server = server(temp.path());
+        let id = insert_job(&server, State::Running);
+
+        let (status, body) = send(&server, request(Method::GET, "/experiments", "")).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body[0]["id"], id.to_string());
+        assert_eq!(body[0]["state"], "running");
+    }
+
+    #[tokio::test]
+    async fn get_an_experiments_status() {
+        let temp = tempfile::tempdir().unwrap();
+        let server = server(temp.path());
+        let id = insert_job(
+            &server,
+            State::Failed {
+                error: "Oops".to_string(),
+            },
+        );
+
+        let (status, body) = send(
+            &server,
+            request(Method::GET, &format!("/experiments/{id}"), ""),
+        )
+        .await;
+        let (unknown, _) = send(
+            &server,
+            request(Method::GET, &format!("/experiments/{}", Uuid::new_v4()), ""),
+        )
+        .await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["state"], "failed");
+        assert_eq!(body["error"], "Oops");
+        assert_eq!(unknown, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn results_are_only_available_once_finished() {
+        let temp = tempfile::tempdir().unwrap();
+        let server = server(temp.path());
+        let running = insert_job(&server, State::Running);
+        let completed = insert_job(&server, State::Completed);
+        let experiment_dir = server.experiments_dir.join(completed.to_string());
+        std::fs::create_dir_all(&experiment_dir).unwrap();
+        let results = serde_json::json!({
+            "experiment": { "package": "wasmer/python" },
+            "reports": [],
+            "total_time": { "secs": 1, "nanos": 0 },
+            "experiment_dir": experiment_dir,
+        });
+        std::fs::write(
+            experiment_dir.join("results.json"),
+            serde_json::to_vec(&results).unwrap(),
+        )
+        .unwrap();
+
+        let (status, _) = send(
+            &server,
+            request(Method::GET, &format!("/experiments/{running}/results"), ""),
+        )
+        .await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let (status, body) = send(
+            &server,
+            request(
+                Method::GET,
+                &format!("/experiments/{completed}/results"),
+                "",
+            ),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["experiment"]["package"], "wasmer/python");
+    }
+
+    #[tokio::test]
+    async fn start_an_experiment() {
+        let temp = tempfile::tempdir().unwrap();
+        let server = server(temp.path());
+        let experiment = r#"{ "$schema": "...", "package": "wasmer/python" }"#;
+
+        let (status, body) = send(&server, request(Method::POST, "/experiments", experiment)).await;
+
+        assert_eq!(status, StatusCode::ACCEPTED);
+        assert_eq!(body["package"], "wasmer/python");
+        assert_eq!(server.jobs.lock().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn experiments_which_run_commands_are_rejected() {
+        let temp = tempfile::tempdir().unwrap();
+        let server = server(temp.path());
+        let experiments = [
+            r#"{ "package": "wasmer/python", "hooks": { "pre-run": ["rm -rf /"] } }"#,
+            r#"{ "package": "wasmer/python", "analyzers": [{ "name": "x", "command": "sh" }] }"#,
+            r#"{ "package": "wasmer/python", "local": { "path": "/etc" } }"#,
+            r#"{ "package": "wasmer/python", "wasmer": { "args": [], "version": { "path": "/bin/sh" } } }"#,
+            r#"{ "package": "wasmer/python", "wasmer": { "args": [], "version": { "git": "https://example.com", "rev": "main" } } }"#,
+            r#"{ "package": "wasmer/python", "args": ["--help"] }"#,
+            r#"{ "package": "wasmer/python", "env": { "HOME": "/root" } }"#,
+            r#"{ "package": "wasmer/python", "wasmer": { "args": ["--mapdir=/host:/"] } }"#,
+            r#"{ "package": "wasmer/python", "wasmer": { "args": [], "env": { "WASMER_DIR": "/root/.wasmer" } } }"#,
+            r#"{ "package": "wasmer/python", "overrides": { "wasmer": { "args": ["--dir=/"] } } }"#,
+            r#"{ "package": "wasmer/python", "overrides": { "wasmer": { "env": { "A": "b" } } } }"#,
+            r#"{ "package": "wasmer/python", "test-cases": [{ "registry": "evil.example.com", "namespace": "wasmer", "name": "python", "version": "1.0.0" }] }"#,
+        ];
+
+        for experiment in experiments {
+            let (status, body) =
+                send(&server, request(Method::POST, "/experiments", experiment)).await;
+
+            assert_eq!(
+                status,
+                StatusCode::UNPROCESSABLE_ENTITY,
+                "{experiment}: {body}"
+            );
+            assert!(
+                body.to_string().contains("Uploaded experiments can't use"),
+                "{experiment}: {body}"
+            );
+        }
+        assert!(server.jobs.lock().unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn uploads_and_running_experiments_are_limited() {
+        let temp = tempfile::tempdir().unwrap();
+        let server = server(temp.path()).with_max_running(1);
+        let huge = format!(
+            r#"{{ "package": "wasmer/python", "args": ["{}"] }}"#,
+            "x".repeat(MAX_BODY_SIZE)
+        );
+
+        let (status, _) = send(&server, request(Method::POST, "/experiments", huge)).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        insert_job(&server, State::Running);
+        let experiment = r#"{ "package": "wasmer/python" }"#;
+        let (status, _) = send(&server, request(Method::POST, "/experiments", experiment)).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+    }
+
+    #[test]
+    fn only_the_most_recent_finished_jobs_are_kept() {
+        let temp = tempfile::tempdir().unwrap();
+        let server = server(temp.path());
+        let old = insert_job(&server, State::Completed);
+        let running = insert_job(&server, State::Running);
+        let recent = insert_job(&server, State::Completed);
+
+        forget_finished_jobs(&mut server.jobs.lock().unwrap(), 1);
+
+        let jobs = server.jobs.lock().unwrap();
+        assert!(!jobs.contains_key(&old));
+        assert!(jobs.contains_key(&running));
+        assert!(jobs.contains_key(&recent));
+    }
+
+    #[tokio::test]
+    async fn unknown_routes() {
+        let temp = tempfile::tempdir().unwrap();
+        let server = server(temp.path());
+
+        let (status, _) = send(&server, request(Method::DELETE, "/experiments", "")).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use crate::{
+    experiment::{ExitStatus, Outcome, Report, Results, SerializableError, TestCase},
+    registry::queries::{PackageDistribution, PackageVersion},
+};
+
+/// Get the root directory for this repository.
+pub(crate) fn project_root() -> &'static Path {
+    let root_dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .ancestors()
+        .nth(2)
+        .unwrap();
+    assert!(root_dir.join(".git").exists());
+
+    root_dir
+}
+
+/// Create a [`TestCase`] for a package in the `wasmer` namespace.
+pub(crate) fn test_case(name: &str, version: &str) -> TestCase {
+    TestCase {
+        registry: "registry.wasmer.io".to_string(),
+        namespace: "wasmer".to_string(),
+        package_name: name.to_string(),
+        package_version: PackageVersion {
+            id: cynic::Id::new(format!("{name}@{version}")),
+            version: version.to_string(),
+            distribution: PackageDistribution {
+                download_url: format!("https://example.com/{name}.tar.gz"),
+                pirita_download_url: None,
+                size: None,
+                pirita_size: None,
+            },
+            created_at: None,
+        },
+        variant: None,
+    }
+}
+
+/// Create a [`Report`] for a package in the `wasmer` namespace, with nothing
+/// but its outcome filled in.
+pub(crate) fn report(name: &str, version: &str, outcome: Outcome) -> Report {
+    Report {
+        display_name: format!("wasmer/{name}"),
+        package_version: test_case(name, version).package_version,
+        outcome,
+        analyses: Vec::new(),
+        annotation: None,
+        command: None,
+        benchmark: None,
+        startup: None,
+        repetitions: None,
+        stdout_digest: None,
+        rerun: None,
+        coredumps: Vec::new(),
+        logs_discarded: false,
+        modules: Vec::new(),
+        module_errors: Vec::new(),
+        timeline: None,
+    }
+}
+
+/// An [`Outcome`] for a test case which ran for `seconds` and then passed or
+/// failed.
+pub(crate) fn completed(success: bool, seconds: u64) -> Outcome {
+    Outcome::Completed {
+        status: ExitStatus {
+            success,
+            code: if success { 0 } else { 1 },
+        },
+        run_time: Duration::from_secs(seconds),
+        base_dir: PathBuf::new(),
+    }
+}
+
+/// An [`Outcome`] for a test case whose package couldn't be downloaded.
+pub(crate) fn fetch_failed() -> Outcome {
+    Outcome::FetchFailed {
+        error: SerializableError {
+            error: "404".to_string(),
+            detailed_error: "404".to_string(),
+            causes: Vec::new(),
+        },
+    }
+}
+
+/// Create the [`Results`] for a run of the `wasmer/wasi` experiment.
+pub(crate) fn results(reports: Vec<Report>) -> Results {
+    Results {
+        experiment: serde_json::from_value(serde_json::json!({ "package": "wasmer/wasi" }))
+            .unwrap(),
+        reports,
+        total_time: Duration::ZERO,
+        experiment_dir: PathBuf::new(),
+        cache: Default::default(),
+    }
+}
+
+/// Check that a particular file has the desired contents.
+///
+/// If the file is missing or outdated, this function will update the file and
+/// trigger a panic to fail any test this is called from.
+pub(crate) fn ensure_file_contents(path: impl AsRef<Path>, contents: impl AsRef<str>) {
+    let path = path.as_ref();
+    let contents = normalize_newlines(contents.as_ref());
+
+    if let Ok(old_contents) = std::fs::read_to_string(path) {
+        if contents == normalize_newlines(&old_contents) {
+            // File is already up to date
+            return;
+        }
+    }
+
+    let display_path = path.strip_prefix(project_root()).unwrap_or(path);
+
+    eprintln!("{} was not up-to-date, updating...", display_path.display());
+
+    if std::env::var("CI").is_ok() {
+        eprintln!("Note: run `cargo test` locally and commit the updated files");
+    }
+
+    if let Some(parent) = path.parent() {
+        let _ = std::fs::create_dir_all(parent);
+    }
+    std::fs::write(path, contents).unwrap();
+    panic!("some file was not up to date and has been updated. Please re-run the tests.");
+}
+
+fn normalize_newlines(s: &str) -> String {
+    s.replace("\r\n", "\n")
+}
@@ -0,0 +1,1009 @@
+//! Resolving a [`WasmerVersion`] to a `wasmer` CLI binary, downloading it if
+//! necessary.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Error};
+use reqwest::Client;
+use semver::Version;
+use sha2::{Digest, Sha256};
+use tempfile::TempDir;
+
+use crate::{
+    config::{Channel, WasmerVersion},
+    experiment::{hex, stable_hash},
+};
+
+const RELEASES_API: &str = "https://api.github.com/repos/wasmerio/wasmer/releases";
+
+/// The release asset containing just one archive's SHA-256 sum, where
+/// `{archive}` is replaced with the archive's name.
+const ARCHIVE_CHECKSUM: &str = "{archive}.sha256";
+
+/// Release assets which may list the SHA-256 sums of every archive in a
+/// release, in order of preference.
+const CHECKSUM_LISTS: &[&str] = &["SHA256SUMS", "sha256sums.txt", "checksums.txt"];
+
+/// The file an installed release's verified SHA-256 sum is recorded in, next
+/// to its `bin/` directory.
+const VERIFIED_MARKER: &str = ".sha256";
+
+/// A directory containing the `wasmer` CLI binaries downloaded by borealis.
+#[derive(Debug, Clone)]
+pub struct Toolchains {
+    dir: PathBuf,
+    client: Client,
+    allow_unverified: bool,
+}
+
+impl Toolchains {
+    pub fn new(dir: impl Into<PathBuf>, client: Client) -> Self {
+        Toolchains {
+            dir: dir.into(),
+            client,
+            allow_unverified: false,
+        }
+    }
+
+    /// Install release archives even when the release doesn't publish a
+    /// SHA-256 sum to check them against.
+    ///
+    /// Archives which don't match a published sum are always rejected.
+    pub fn with_allow_unverified(self, allow_unverified: bool) -> Self {
+        Toolchains {
+            allow_unverified,
+            ..self
+        }
+    }
+
+    /// Use the `toolchains/` folder inside borealis's cache directory.
+    pub fn in_cache_dir(cache_dir: &Path) -> Result<Self, Error> {
+        // Note: This is deliberately separate from the registry client so we
+        // don't leak registry credentials to third parties.
+        let client = Client::builder()
+            .user_agent(concat!(
+                env!("CARGO_PKG_NAME"),
+                "/",
+                env!("CARGO_PKG_VERSION")
+            ))
+            .build()?;
+
+        Ok(Toolchains::new(cache_dir.join("toolchains"), client))
+    }
+
+    /// The directory toolchains are installed to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// List every `wasmer` CLI that has been downloaded or built, sorted by
+    /// name.
+    pub fn installed(&self) -> Result<Vec<InstalledToolchain>, Error> {
+        let mut installed = Vec::new();
+
+        for entry in read_dir(&self.dir)? {
+            let Some(name) = entry.file_name().to_str().map(String::from) else {
+                continue;
+            };
+            if INTERNAL_DIRS.contains(&name.as_str()) || name.starts_with('.') {
+                continue;
+            }
+
+            for platform in read_dir(&entry.path())? {
+                let path = platform.path();
+                let binary = path.join("bin").join(binary_name());
+                if !binary.exists() {
+                    continue;
+                }
+
+                installed.push(InstalledToolchain {
+                    name: name.clone(),
+                    platform: platform.file_name().to_string_lossy().into_owned(),
+                    size: dir_size(&path)?,
+                    binary,
+                    path,
+                });
+            }
+        }
+
+        installed.sort_by(|a, b| (&a.name, &a.platform).cmp(&(&b.name, &b.platform)));
+
+        Ok(installed)
+    }
+
+    /// Remove an installed toolchain.
+    pub fn remove(&self, toolchain: &InstalledToolchain) -> Result<(), Error> {
+        std::fs::remove_dir_all(&toolchain.path)
+            .with_context(|| format!("Unable to remove \"{}\"", toolchain.path.display()))?;
+
+        // Clean up the parent directory if it is now empty
+        if let Some(parent) = toolchain.path.parent() {
+            if read_dir(parent)?.is_empty() {
+                std::fs::remove_dir(parent)
+                    .with_context(|| format!("Unable to remove \"{}\"", parent.display()))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove every toolchain, as well as any git checkouts and build
+    /// artifacts.
+    pub fn remove_all(&self) -> Result<(), Error> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Ok(_) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => {
+                Err(Error::new(e).context(format!("Unable to remove \"{}\"", self.dir.display())))
+            }
+        }
+    }
+
+    /// Every stable release of the `wasmer` CLI, oldest first.
+    pub async fn releases(&self) -> Result<Vec<Version>, Error> {
+        let mut versions = Vec::new();
+
+        for page in 1.. {
+            let releases = self.fetch_releases(page).await?;
+            if releases.is_empty() {
+                break;
+            }
+
+            versions.extend(
+                releases
+                    .into_iter()
+                    .filter(|r| !r.prerelease)
+                    .filter_map(|r| r.tag_name.trim_start_matches('v').parse().ok()),
+            );
+        }
+
+        versions.sort();
+        versions.dedup();
+
+        Ok(versions)
+    }
+
+    /// Expand a [`WasmerVersion::Range`] into every release it matches.
+    ///
+    /// Any other [`WasmerVersion`] is returned as-is.
+    pub async fn expand(&self, version: &WasmerVersion) -> Result<Vec<WasmerVersion>, Error> {
+        let WasmerVersion::Range(req) = version else {
+            return Ok(vec![version.clone()]);
+        };
+
+        let matching: Vec<_> = self
+            .releases()
+            .await?
+            .into_iter()
+            .filter(|v| req.matches(v))
+            .map(WasmerVersion::Release)
+            .collect();
+
+        anyhow::ensure!(!matching.is_empty(), "No wasmer releases match \"{req}\"");
+
+        Ok(matching)
+    }
+
+    /// Get the path to the `wasmer` CLI for a particular [`WasmerVersion`],
+    /// downloading it if it isn't already available.
+    #[tracing::instrument(skip(self))]
+    pub async fn resolve(&self, version: &WasmerVersion) -> Result<PathBuf, Error> {
+        match version {
+            WasmerVersion::Local { path } => Ok(path.clone()),
+            WasmerVersion::Latest => Ok(PathBuf::from(binary_name())),
+            WasmerVersion::Release(version) => self.install_release(version).await,
+            WasmerVersion::Channel(Channel::Nightly) => {
+                let release = self.latest_prerelease().await?;
+                self.install(&release).await
+            }
+            WasmerVersion::Git { git, rev } => self.build_from_git(git, rev).await,
+            WasmerVersion::Range(req) => {
+                let latest = self
+                    .releases()
+                    .await?
+                    .into_iter()
+                    .filter(|v| req.matches(v))
+                    .last()
+                    .with_context(|| format!("No wasmer releases match \"{req}\""))?;
+                self.install_release(&latest).await
+            }
+        }
+    }
+
+    async fn install_release(&self, version: &Version) -> Result<PathBuf, Error> {
+        let tag = format!("v{version}");
+
+        if let Some(binary) = self.cached(&tag)? {
+            return Ok(binary);
+        }
+
+        let release = self.fetch_release(&tag).await?;
+        self.install(&release).await
+    }
+
+    async fn fetch_release(&self, tag: &str) -> Result<GithubRelease, Error> {
+        self.client
+            .get(format!("{RELEASES_API}/tags/{tag}"))
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .with_context(|| format!("Unable to fetch the wasmer {tag} release"))?
+            .json()
+            .await
+            .with_context(|| format!("Unable to parse the wasmer {tag} release"))
+    }
+
+    async fn fetch_releases(&self, page: usize) -> Result<Vec<GithubRelease>, Error> {
+        self.client
+            .get(RELEASES_API)
+            .query(&[("per_page", "100"), ("page", &page.to_string())])
+            .header(reqwest::header::ACCEPT, "application/vnd.github+json")
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .context("Unable to fetch the list of wasmer releases")?
+            .json()
+            .await
+            .context("Unable to parse the list of wasmer releases")
+    }
+
+    /// Build the `wasmer` CLI from a git repository, caching the resulting
+    /// binary by commit hash.
+    async fn build_from_git(&self, repo: &str, rev: &str) -> Result<PathBuf, Error> {
+        let checkout = self
+            .dir
+            .join("git")
+            .join(format!("{:016x}", stable_hash(0, repo)));
+
+        let commit = fetch(repo, rev, &checkout).await?;
+        let install_dir = self.dir.join(format!("git-{commit}")).join(platform()?);
+        let binary = install_dir.join("bin").join(binary_name());
+
+        if binary.exists() {
+            tracing::debug!(%commit, binary=%binary.display(), "Using a cached wasmer CLI");
+            return Ok(binary);
+        }
+
+        tracing::info!(%repo, %rev, %commit, "Building the wasmer CLI");
+
+        git(&checkout, ["checkout", "--force", "--detach", &commit]).await?;
+
+        let target_dir = self.dir.join("git-target");
+        let mut cmd = tokio::process::Command::new("cargo");
+        cmd.arg("build")
+            .arg("--release")
+            .arg("--package=wasmer-cli")
+            .arg("--bin=wasmer")
+            .arg("--features=cranelift")
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .current_dir(&checkout);
+        run(&mut cmd).await?;
+
+        let built = target_dir.join("release").join(binary_name());
+        tokio::fs::create_dir_all(binary.parent().unwrap())
+            .await
+            .with_context(|| format!("Unable to create \"{}\"", install_dir.display()))?;
+        tokio::fs::copy(&built, &binary).await.with_context(|| {
+            format!(
+                "Unable to copy \"{}\" to \"{}\"",
+                built.display(),
+                binary.display()
+            )
+        })?;
+
+        Ok(binary)
+    }
+
+    /// Find the most recent pre-release with an archive for this platform.
+    async fn latest_prerelease(&self) -> Result<GithubRelease, Error> {
+        let releases = self.fetch_releases(1).await?;
+
+        let archive = archive_name()?;
+
+        releases
+            .into_iter()
+            .filter(|r| r.prerelease)
+            .find(|r| r.asset(&archive).is_some())
+            .with_context(|| format!("No pre-release has a \"{archive}\" asset"))
+    }
+
+    /// The `wasmer` binary for a release, if it has already been installed.
+    ///
+    /// Installs which were never verified against a published SHA-256 sum
+    /// are refused unless unverified installs are allowed.
+    fn cached(&self, tag: &str) -> Result<Option<PathBuf>, Error> {
+        let install_dir = self.dir.join(tag).join(platform()?);
+        let binary = install_dir.join("bin").join(binary_name());
+
+        if !binary.exists() {
+            return Ok(None);
+        }
+
+        if !install_dir.join(VERIFIED_MARKER).exists() {
+            anyhow::ensure!(
+                self.allow_unverified,
+                "The cached {tag} wasmer CLI at \"{}\" was never verified. Remove it with \"wasmer-borealis wasmer prune {tag}\" so it can be downloaded again, or use --allow-unverified to use it anyway",
+                binary.display(),
+            );
+            tracing::warn!(%tag, binary=%binary.display(), "Using an unverified wasmer CLI");
+        }
+
+        tracing::debug!(%tag, binary=%binary.display(), "Using a cached wasmer CLI");
+        Ok(Some(binary))
+    }
+
+    /// Download, verify, and unpack a release archive, unless it has already
+    /// been installed.
+    async fn install(&self, release: &GithubRelease) -> Result<PathBuf, Error> {
+        let tag = &release.tag_name;
+        if let Some(binary) = self.cached(tag)? {
+            return Ok(binary);
+        }
+
+        let install_dir = self.dir.join(tag).join(platform()?);
+        let binary = install_dir.join("bin").join(binary_name());
+        let archive_name = archive_name()?;
+        let url = &release
+            .asset(&archive_name)
+            .with_context(|| format!("The {tag} release doesn't have a \"{archive_name}\" asset"))?
+            .browser_download_url;
+
+        tracing::info!(%tag, %url, "Downloading the wasmer CLI");
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .with_context(|| format!("Unable to create \"{}\"", self.dir.display()))?;
+        let temp = TempDir::new_in(&self.dir).context("Unable to create a temporary directory")?;
+
+        let archive = temp.path().join("wasmer.tar.gz");
+        let payload = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .with_context(|| format!("Unable to download \"{url}\""))?
+            .bytes()
+            .await
+            .with_context(|| format!("Unable to download \"{url}\""))?;
+        let sha256 = self.verify(release, &archive_name, &payload).await?;
+        tokio::fs::write(&archive, &payload)
+            .await
+            .with_context(|| format!("Unable to save to \"{}\"", archive.display()))?;
+
+        let unpacked = temp.path().join("unpacked");
+        unpack(&archive, &unpacked).await?;
+        anyhow::ensure!(
+            unpacked.join("bin").join(binary_name()).exists(),
+            "The archive at \"{url}\" doesn't contain a wasmer binary",
+        );
+        if let Some(sha256) = sha256 {
+            let marker = unpacked.join(VERIFIED_MARKER);
+            tokio::fs::write(&marker, sha256)
+                .await
+                .with_context(|| format!("Unable to save to \"{}\"", marker.display()))?;
+        }
+
+        if let Some(parent) = install_dir.parent() {
+            tokio::fs::create_dir_all(parent)
+                .await
+                .with_context(|| format!("Unable to create \"{}\"", parent.display()))?;
+        }
+        tokio::fs::rename(&unpacked, &install_dir)
+            .await
+            .with_context(|| format!("Unable to install to \"{}\"", install_dir.display()))?;
+
+        Ok(binary)
+    }
+
+    /// Check a downloaded archive against the SHA-256 sum published with its
+    /// release, returning the verified sum (or `None` if the release doesn't
+    /// publish one and unverified installs are allowed).
+    async fn verify(
+        &self,
+        release: &GithubRelease,
+        archive_name: &str,
+        payload: &[u8],
+    ) -> Result<Option<String>, Error> {
+        let tag = &release.tag_name;
+
+        let Some(expected) = self.published_checksum(release, archive_name).await? else {
+            anyhow::ensure!(
+                self.allow_unverified,
+                "The {tag} release doesn't publish a SHA-256 sum for \"{archive_name}\", so it can't be verified. Use --allow-unverified to install it anyway",
+            );
+            tracing::warn!(%tag, archive=%archive_name, "Installing an unverified wasmer CLI");
+            return Ok(None);
+        };
+
+        let actual = hex(&Sha256::digest(payload));
+        anyhow::ensure!(
+            actual.eq_ignore_ascii_case(&expected),
+            "The SHA-256 sum of \"{archive_name}\" from the {tag} release doesn't match the published one (expected {expected}, got {actual})",
+        );
+        tracing::debug!(%tag, sha256=%actual, "Verified the wasmer CLI");
+
+        Ok(Some(actual))
+    }
+
+    /// Look through the release's checksum files for the archive's SHA-256
+    /// sum.
+    async fn published_checksum(
+        &self,
+        release: &GithubRelease,
+        archive_name: &str,
+    ) -> Result<Option<String>, Error> {
+        let archive_checksum = ARCHIVE_CHECKSUM.replace("{archive}", archive_name);
+
+        if let Some(asset) = release.asset(&archive_checksum) {
+            let sums = self.download_text(&asset.browser_download_url).await?;
+            // This file is only for our archive, so it may be just the sum
+            if let Some(sum) = find_checksum(&sums, archive_name, true) {
+                return Ok(Some(sum));
+            }
+        }
+
+        for name in CHECKSUM_LISTS {
+            let Some(asset) = release.asset(name) else {
+                continue;
+            };
+
+            let sums = self.download_text(&asset.browser_download_url).await?;
+            if let Some(sum) = find_checksum(&sums, archive_name, false) {
+                return Ok(Some(sum));
+            }
+        }
+
+        Ok(None)
+    }
+
+    async fn download_text(&self, url: &str) -> Result<String, Error> {
+        self.client
+            .get(url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .with_context(|| format!("Unable to download \"{url}\""))?
+            .text()
+            .await
+            .with_context(|| format!("Unable to download \"{url}\""))
+    }
+}
+
+/// Find a file's SHA-256 sum in the output of `sha256sum`, or (if `bare` is
+/// set) a file which only contains a sum.
+fn find_checksum(sums: &str, filename: &str, bare: bool) -> Option<String> {
+    let is_sha256 = |s: &str| s.len() == 64 && s.bytes().all(|b| b.is_ascii_hexdigit());
+
+    for line in sums.lines() {
+        let mut words = line.split_whitespace();
+        let (Some(sum), name) = (words.next(), words.next()) else {
+            continue;
+        };
+        if !is_sha256(sum) {
+            continue;
+        }
+
+        match name {
+            // "sha256sum --binary" prefixes the filename with a "*"
+            Some(name) if name.trim_start_matches('*') == filename => {
+                return Some(sum.to_ascii_lowercase())
+            }
+            None if bare && sums.trim() == sum => return Some(sum.to_ascii_lowercase()),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Ask a `wasmer` CLI which version it is.
+pub async fn version_of(wasmer: &Path) -> Result<Version, Error> {
+    let output = tokio::process::Command::new(wasmer)
+        .arg("--version")
+        .output()
+        .await
+        .with_context(|| format!("Unable to run \"{}\"", wasmer.display()))?;
+    anyhow::ensure!(
+        output.status.success(),
+        "\"{} --version\" failed ({})",
+        wasmer.display(),
+        output.status
+    );
+
+    parse_version(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Does `wasmer run` accept a particular flag (e.g. `--coredump-on-trap`)?
+pub async fn run_supports(wasmer: &Path, flag: &str) -> Result<bool, Error> {
+    let output = tokio::process::Command::new(wasmer)
+        .arg("run")
+        .arg("--help")
+        .output()
+        .await
+        .with_context(|| format!("Unable to run \"{}\"", wasmer.display()))?;
+
+    let help = String::from_utf8_lossy(&output.stdout);
+    Ok(help
+        .split(|c: char| c.is_whitespace() || c == ',' || c == '=' || c == '<')
+        .any(|word| word == flag))
+}
+
+/// Parse the output of `wasmer --version` (e.g. `wasmer 4.2.3 (abc 2023-10-11)`).
+fn parse_version(output: &str) -> Result<Version, Error> {
+    output
+        .split_whitespace()
+        .nth(1)
+        .and_then(|v| v.trim_start_matches('v').parse().ok())
+        .with_context(|| format!("Unable to find the version in \"{}\"", output.trim()))
+}
+
+/// A `wasmer` CLI that was downloaded or built by [`Toolchains`].
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct InstalledToolchain {
+    /// The release's tag (e.g. `v4.2.0`) or `git-{commit}` for binaries built
+    /// from source.
+    pub name: String,
+    /// The platform this toolchain was installed for (e.g. `linux-amd64`).
+    pub platform: String,
+    /// The `wasmer` binary.
+    pub binary: PathBuf,
+    /// The directory containing the toolchain.
+    pub path: PathBuf,
+    /// The toolchain's size on disk, in bytes.
+    pub size: u64,
+}
+
+/// Directories under the toolchains directory which are used while building
+/// binaries from git.
+const INTERNAL_DIRS: &[&str] = &["git", "git-target"];
+
+fn read_dir(dir: &Path) -> Result<Vec<std::fs::DirEntry>, Error> {
+    match std::fs::read_dir(dir) {
+        Ok(entries) => entries
+            .collect::<Result<_, _>>()
+            .with_context(|| format!("Unable to read \"{}\"", dir.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Vec::new()),
+        Err(e) => Err(Error::new(e).context(format!("Unable to read \"{}\"", dir.display()))),
+    }
+}
+
+fn dir_size(dir: &Path) -> Result<u64, Error> {
+    let mut size = 0;
+
+    for entry in read_dir(dir)? {
+        let meta = entry.metadata()?;
+        if meta.is_dir() {
+            size += dir_size(&entry.path())?;
+        } else {
+            size += meta.len();
+        }
+    }
+
+    Ok(size)
+}
+
+/// Make sure `rev` is available in a local clone of `repo`, returning the
+/// full commit hash it refers to.
+async fn fetch(repo: &str, rev: &str, checkout: &Path) -> Result<String, Error> {
+    // Both are passed straight to git, which would treat a leading "-" as an
+    // option (e.g. "--upload-pack=...")
+    anyhow::ensure!(
+        !rev.starts_with('-'),
+        "\"{rev}\" isn't a valid git revision"
+    );
+    anyhow::ensure!(
+        !repo.starts_with('-'),
+        "\"{repo}\" isn't a valid git repository"
+    );
+
+    if !checkout.join(".git").exists() {
+        tokio::fs::create_dir_all(checkout)
+            .await
+            .with_context(|| format!("Unable to create \"{}\"", checkout.display()))?;
+        git(checkout, ["init", "--quiet"]).await?;
+        git(checkout, ["remote", "add", "origin", repo]).await?;
+    }
+
+    // Branches and tags are fetched from the remote, while bare commit hashes
+    // (or refs like "refs/pull/123/head") need to be fetched explicitly.
+    git(checkout, ["fetch", "--quiet", "--tags", "origin"]).await?;
+
+    for candidate in [format!("origin/{rev}"), rev.to_string()] {
+        let spec = format!("{candidate}^{{commit}}");
+        if let Ok(commit) = git(checkout, ["rev-parse", "--verify", "--quiet", &spec]).await {
+            return Ok(commit);
+        }
+    }
+
+    git(checkout, ["fetch", "--quiet", "origin", rev])
+        .await
+        .with_context(|| format!("Unable to find \"{rev}\" in \"{repo}\""))?;
+    git(checkout, ["rev-parse", "--verify", "FETCH_HEAD^{commit}"]).await
+}
+
+async fn git<const N: usize>(dir: &Path, args: [&str; N]) -> Result<String, Error> {
+    let mut cmd = tokio::process::Command::new("git");
+    cmd.args(args).current_dir(dir);
+    run(&mut cmd).await
+}
+
+/// Run a command to completion, returning its trimmed stdout.
+async fn run(cmd: &mut tokio::process::Command) -> Result<String, Error> {
+    tracing::debug!(cmd=?cmd.as_std(), "Running");
+
+    let output = cmd
+        .stdin(std::process::Stdio::null())
+        .output()
+        .await
+        .with_context(|| {
+            format!(
+                "Unable to start \"{}\", is it installed?",
+                cmd.as_std().get_program().to_string_lossy()
+            )
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        anyhow::bail!(
+            "{:?} failed ({}): {}",
+            cmd.as_std(),
+            output.status,
+            stderr.trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Extract a `*.tar.gz` archive using the system's `tar` command.
+async fn unpack(archive: &Path, dest: &Path) -> Result<(), Error> {
+    tokio::fs::create_dir_all(dest)
+        .await
+        .with_context(|| format!("Unable to create \"{}\"", dest.display()))?;
+
+    let status = tokio::process::Command::new("tar")
+        .arg("-xzf")
+        .arg(archive)
+        .arg("-C")
+        .arg(dest)
+        .status()
+        .await
+        .context("Unable to run \"tar\", is it installed?")?;
+
+    anyhow::ensure!(
+        status.success(),
+        "Unable to unpack \"{}\" ({status})",
+        archive.display()
+    );
+
+    Ok(())
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct GithubRelease {
+    tag_name: String,
+    prerelease: bool,
+    assets: Vec<GithubAsset>,
+}
+
+impl GithubRelease {
+    fn asset(&self, name: &str) -> Option<&GithubAsset> {
+        self.assets.iter().find(|a| a.name == name)
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct GithubAsset {
+    name: String,
+    browser_download_url: String,
+}
+
+/// The name used by wasmer's release artifacts for the current platform.
+fn platform() -> Result<&'static str, Error> {
+    let platform = match (std::env::consts::OS, std::env::consts::ARCH) {
+        ("linux", "x86_64") => "linux-amd64",
+        ("linux", "aarch64") => "linux-aarch64",
+        ("macos", "x86_64") => "darwin-amd64",
+        ("macos", "aarch64") => "darwin-arm64",
+        ("windows", "x86_64") => "windows-amd64",
+        (os, arch) => anyhow::bail!("Wasmer doesn't publish releases for {os}-{arch}"),
+    };
+
+    Ok(platform)
+}
+
+fn archive_name() -> Result<String, Error> {
+    platform().map(|p| format!("wasmer-{p}.tar.gz"))
+}
+
+fn binary_name() -> &'static str {
+    if cfg!(windows) {
+        "wasmer.exe"
+    } else {
+        "wasmer"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashMap, convert::Infallible, sync::Arc};
+
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Request, Response,
+    };
+
+    use super::*;
+
+    #[test]
+    fn parse_the_wasmer_version() {
+        let inputs = [
+            ("wasmer 4.2.3\n", Version::new(4, 2, 3)),
+            ("wasmer 3.1.1 (d1c1ed2 2023-01-25)", Version::new(3, 1, 1)),
+        ];
+
+        for (output, expected) in inputs {
+            assert_eq!(parse_version(output).unwrap(), expected);
+        }
+        assert!(parse_version("command not found").is_err());
+    }
+
+    #[test]
+    fn find_the_published_checksum() {
+        let sha = "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08";
+        let sums = format!(
+            "{}  wasmer-darwin-arm64.tar.gz\n{}  wasmer-linux-amd64.tar.gz\n",
+            "0".repeat(64),
+            sha.to_uppercase(),
+        );
+
+        assert_eq!(
+            find_checksum(&sums, "wasmer-linux-amd64.tar.gz", false).as_deref(),
+            Some(sha)
+        );
+        assert_eq!(
+            find_checksum(&format!("{sha} *wasmer.tar.gz"), "wasmer.tar.gz", false).as_deref(),
+            Some(sha)
+        );
+        assert_eq!(
+            find_checksum(&format!("{sha}\n"), "wasmer.tar.gz", true).as_deref(),
+            Some(sha)
+        );
+        // A bare sum in a list of sums could belong to any of the archives
+        assert_eq!(
+            find_checksum(&format!("{sha}\n"), "wasmer.tar.gz", false),
+            None
+        );
+        assert_eq!(
+            find_checksum(&sums, "wasmer-windows-amd64.tar.gz", false),
+            None
+        );
+        assert_eq!(find_checksum("not a checksum", "wasmer.tar.gz", true), None);
+    }
+
+    #[tokio::test]
+    async fn local_binaries_are_used_as_is() {
+        let toolchains = Toolchains::new("/does/not/exist", Client::new());
+        let version = WasmerVersion::Local {
+            path: PathBuf::from("/usr/local/bin/wasmer"),
+        };
+
+        let binary = toolchains.resolve(&version).await.unwrap();
+
+        assert_eq!(binary, PathBuf::from("/usr/local/bin/wasmer"));
+    }
+
+    #[tokio::test]
+    async fn releases_are_reused_once_installed() {
+        let temp = tempfile::tempdir().unwrap();
+        let install_dir = temp.path().join("v4.2.0").join(platform().unwrap());
+        let bin = install_dir.join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join(binary_name()), "").unwrap();
+        std::fs::write(install_dir.join(VERIFIED_MARKER), "").unwrap();
+        let toolchains = Toolchains::new(temp.path(), Client::new());
+
+        let binary = toolchains
+            .resolve(&WasmerVersion::Release("4.2.0".parse().unwrap()))
+            .await
+            .unwrap();
+
+        assert_eq!(binary, bin.join(binary_name()));
+    }
+
+    #[tokio::test]
+    async fn unverified_installs_are_refused() {
+        let temp = tempfile::tempdir().unwrap();
+        let bin = temp
+            .path()
+            .join("v4.2.0")
+            .join(platform().unwrap())
+            .join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        std::fs::write(bin.join(binary_name()), "").unwrap();
+        let version = WasmerVersion::Release("4.2.0".parse().unwrap());
+        let toolchains = Toolchains::new(temp.path(), Client::new());
+
+        let error = toolchains.resolve(&version).await.unwrap_err();
+        assert!(error.to_string().contains("was never verified"));
+
+        let binary = toolchains
+            .with_allow_unverified(true)
+            .resolve(&version)
+            .await
+            .unwrap();
+        assert_eq!(binary, bin.join(binary_name()));
+    }
+
+    /// A release whose assets are served from a local server.
+    async fn release(assets: Vec<(&'static str, &'static str)>) -> GithubRelease {
+        let files: Arc<HashMap<String, &'static str>> = Arc::new(
+            assets
+                .iter()
+                .map(|(name, contents)| (format!("/{name}"), *contents))
+                .collect(),
+        );
+        let make_service = make_service_fn(move |_| {
+            let files = Arc::clone(&files);
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req: Request<Body>| {
+                    let response = match files.get(req.uri().path()) {
+                        Some(contents) => Response::new(Body::from(*contents)),
+                        None => Response::builder().status(404).body(Body::empty()).unwrap(),
+                    };
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+        let server = hyper::Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+        let base_url = format!("http://{}", server.local_addr());
+        tokio::spawn(server);
+
+        GithubRelease {
+            tag_name: "v4.2.0".to_string(),
+            prerelease: false,
+            assets: assets
+                .into_iter()
+                .map(|(name, _)| GithubAsset {
+                    name: name.to_string(),
+                    browser_download_url: format!("{base_url}/{name}"),
+                })
+                .collect(),
+        }
+    }
+
+    #[tokio::test]
+    async fn archives_are_checked_against_the_published_sum() {
+        // printf archive | sha256sum
+        let sha = "0eb3e36bfb24dcd9bb1d1bece1531216b59539a8fde17ee80224af0653c92aa3";
+        let toolchains = Toolchains::new("/does/not/exist", Client::new());
+        let verified = release(vec![(
+            "SHA256SUMS",
+            "0eb3e36bfb24dcd9bb1d1bece1531216b59539a8fde17ee80224af0653c92aa3  wasmer.tar.gz\n",
+        )])
+        .await;
+        let bare = release(vec![(
+            "wasmer.tar.gz.sha256",
+            "0eb3e36bfb24dcd9bb1d1bece1531216b59539a8fde17ee80224af0653c92aa3\n",
+        )])
+        .await;
+
+        for release in [&verified, &bare] {
+            let sum = toolchains
+                .verify(release, "wasmer.tar.gz", b"archive")
+                .await
+                .unwrap();
+            assert_eq!(sum.as_deref(), Some(sha));
+
+            let error = toolchains
+                .verify(release, "wasmer.tar.gz", b"tampered")
+                .await
+                .unwrap_err();
+            assert!(error.to_string().contains("doesn't match"));
+        }
+    }
+
+    #[tokio::test]
+    async fn archives_without_a_published_sum_are_refused() {
+        let toolchains = Toolchains::new("/does/not/exist", Client::new());
+        let unpublished = release(Vec::new()).await;
+        // A bare sum is only trusted from the archive's own checksum file
+        let ambiguous = release(vec![(
+            "SHA256SUMS",
+            "0eb3e36bfb24dcd9bb1d1bece1531216b59539a8fde17ee80224af0653c92aa3\n",
+        )])
+        .await;
+
+        for release in [&unpublished, &ambiguous] {
+            let error = toolchains
+                .verify(release, "wasmer.tar.gz", b"archive")
+                .await
+                .unwrap_err();
+            assert!(error.to_string().contains("can't be verified"));
+
+            let sum = toolchains
+                .clone()
+                .with_allow_unverified(true)
+                .verify(release, "wasmer.tar.gz", b"archive")
+                .await
+                .unwrap();
+            assert_eq!(sum, None);
+        }
+    }
+
+    #[test]
+    fn list_and_remove_installed_toolchains() {
+        let temp = tempfile::tempdir().unwrap();
+        for name in ["v4.2.0", "v4.1.0", "git-target"] {
+            let bin = temp.path().join(name).join("linux-amd64").join("bin");
+            std::fs::create_dir_all(&bin).unwrap();
+            std::fs::write(bin.join(binary_name()), "binary").unwrap();
+        }
+        let toolchains = Toolchains::new(temp.path(), Client::new());
+
+        let installed = toolchains.installed().unwrap();
+
+        let names: Vec<_> = installed.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["v4.1.0", "v4.2.0"]);
+        assert_eq!(installed[0].size, 6);
+
+        toolchains.remove(&installed[0]).unwrap();
+        assert!(!temp.path().join("v4.1.0").exists());
+        assert_eq!(toolchains.installed().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn resolve_git_revisions() {
+        let temp = tempfile::tempdir().unwrap();
+        let upstream = temp.path().join("upstream");
+        std::fs::create_dir_all(&upstream).unwrap();
+        git(&upstream, ["init", "--quiet"]).await.unwrap();
+        git(
+            &upstream,
+            [
+                "-c",
+                "user.name=borealis",
+                "-c",
+                "user.email=borealis@example.com",
+                "commit",
+                "--quiet",
+                "--allow-empty",
+                "-m",
+                "Initial commit",
+            ],
+        )
+        .await
+        .unwrap();
+        git(&upstream, ["tag", "v1.0.0"]).await.unwrap();
+        let head = git(&upstream, ["rev-parse", "HEAD"]).await.unwrap();
+        let repo = upstream.display().to_string();
+        let checkout = temp.path().join("checkout");
+
+        assert_eq!(fetch(&repo, "v1.0.0", &checkout).await.unwrap(), head);
+        assert_eq!(fetch(&repo, &head[..8], &checkout).await.unwrap(), head);
+        assert!(fetch(&repo, "does-not-exist", &checkout).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn git_options_are_never_accepted_as_revisions() {
+        let temp = tempfile::tempdir().unwrap();
+        let checkout = temp.path().join("checkout");
+        let pwned = temp.path().join("pwned");
+        let rev = format!("--upload-pack=touch {}", pwned.display());
+
+        let error = fetch("https://example.com/wasmer.git", &rev, &checkout)
+            .await
+            .unwrap_err();
+
+        assert!(error.to_string().contains("isn't a valid git revision"));
+        assert!(fetch("--upload-pack=true", "main", &checkout)
+            .await
+            .is_err());
+        assert!(!checkout.exists());
+        assert!(!pwned.exists());
+    }
+}
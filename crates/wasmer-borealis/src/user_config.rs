@@ -0,0 +1,299 @@
+//! Per-user defaults for the `wasmer-borealis` CLI, read from
+//! `~/.config/borealis/config.toml`.
+//!
+//! Command-line flags and environment variables always take precedence over
+//! anything in this file.
+
+use std::{
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{Context, Error};
+use serde_json::{Map, Value};
+
+/// Defaults for the `wasmer-borealis` CLI.
+#[derive(Debug, Default, Clone, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct UserConfig {
+    /// The registry to query packages from.
+    pub registry: Option<String>,
+    /// An environment variable to read the registry token from.
+    pub token_env: Option<String>,
+    /// A shell command which prints the registry token (e.g. a password
+    /// manager's CLI).
+    pub token_command: Option<String>,
+    /// The directory downloaded packages will be cached in.
+    pub cache_dir: Option<PathBuf>,
+    /// The maximum number of test cases to run at the same time.
+    pub parallelism: Option<NonZeroUsize>,
+    /// Delete all but this many of the most recent runs after each run.
+    pub keep_runs: Option<usize>,
+    /// The SMTP server run summaries are emailed through (e.g.
+    /// `smtps://smtp.example.com` or `smtp://localhost:25`).
+    pub smtp_server: Option<String>,
+    /// The user to log in to the SMTP server as.
+    pub smtp_username: Option<String>,
+    /// An environment variable to read the SMTP password from.
+    pub smtp_password_env: Option<String>,
+    /// The address run summaries are emailed from.
+    pub email_from: Option<String>,
+    /// A comma-separated list of addresses to email run summaries to.
+    pub email_to: Option<String>,
+}
+
+impl UserConfig {
+    /// Where the config file lives, unless `$BOREALIS_CONFIG` says
+    /// otherwise.
+    pub fn default_location() -> PathBuf {
+        match std::env::var_os("BOREALIS_CONFIG") {
+            Some(path) => PathBuf::from(path),
+            None => crate::DIRS.config_dir().join("config.toml"),
+        }
+    }
+
+    /// Load the config file, falling back to the defaults if it doesn't
+    /// exist.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+
+        match std::fs::read_to_string(path) {
+            Ok(src) => UserConfig::from_toml(&src)
+                .with_context(|| format!("Unable to load \"{}\"", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(UserConfig::default()),
+            Err(e) => Err(Error::new(e).context(format!("Unable to read \"{}\"", path.display()))),
+        }
+    }
+
+    /// Parse the config file's contents.
+    ///
+    /// The file only ever contains a handful of top-level keys, so this
+    /// understands just enough TOML for strings, integers, and booleans.
+    pub fn from_toml(src: &str) -> Result<Self, Error> {
+        let table = parse_table(src)?;
+
+        serde_json::from_value(Value::Object(table)).map_err(|e| {
+            let message = e.to_string();
+            match crate::config::suggestion(&message) {
+                Some(suggestion) => anyhow::anyhow!("{message}, did you mean `{suggestion}`?"),
+                None => Error::new(e),
+            }
+        })
+    }
+
+    /// Get the registry token from wherever the config file says it lives.
+    pub fn token(&self) -> Result<Option<String>, Error> {
+        if let Some(name) = &self.token_env {
+            let token = std::env::var(name)
+                .with_context(|| format!("Unable to read the token from ${name}"))?;
+            return Ok(Some(token));
+        }
+
+        if let Some(command) = &self.token_command {
+            let output = shell(command)
+                .output()
+                .with_context(|| format!("Unable to run \"{command}\""))?;
+            anyhow::ensure!(
+                output.status.success(),
+                "\"{command}\" failed ({})",
+                output.status
+            );
+            let token = String::from_utf8(output.stdout)
+                .context("The token wasn't valid UTF-8")?
+                .trim()
+                .to_string();
+            return Ok(Some(token));
+        }
+
+        Ok(None)
+    }
+
+    /// Get the SMTP password from the environment variable the config file
+    /// names, if any.
+    pub fn smtp_password(&self) -> Result<Option<String>, Error> {
+        match &self.smtp_password_env {
+            Some(name) => std::env::var(name)
+                .map(Some)
+                .with_context(|| format!("Unable to read the SMTP password from ${name}")),
+            None => Ok(None),
+        }
+    }
+
+    /// The addresses from `email-to`.
+    pub fn email_recipients(&self) -> Vec<&str> {
+        self.email_to
+            .iter()
+            .flat_map(|to| to.split(','))
+            .map(str::trim)
+            .filter(|address| !address.is_empty())
+            .collect()
+    }
+}
+
+fn shell(command: &str) -> Command {
+    if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+/// Parse top-level `key = value` pairs into a JSON object.
+fn parse_table(src: &str) -> Result<Map<String, Value>, Error> {
+    let mut table = Map::new();
+
+    for (i, line) in src.lines().enumerate() {
+        let line_number = i + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        anyhow::ensure!(
+            !line.starts_with('['),
+            "Tables aren't supported (line {line_number})"
+        );
+        let (key, value) = line
+            .split_once('=')
+            .with_context(|| format!("Expected \"key = value\" on line {line_number}"))?;
+        let key = key.trim().trim_matches('"').to_string();
+        let value = parse_value(value.trim())
+            .with_context(|| format!("Invalid value for \"{key}\" on line {line_number}"))?;
+
+        anyhow::ensure!(
+            table.insert(key.clone(), value).is_none(),
+            "\"{key}\" is set more than once (line {line_number})"
+        );
+    }
+
+    Ok(table)
+}
+
+fn parse_value(raw: &str) -> Result<Value, Error> {
+    if let Some(rest) = raw.strip_prefix('"') {
+        let (value, rest) = basic_string(rest)?;
+        ensure_only_comment(rest)?;
+        return Ok(Value::String(value));
+    }
+
+    if let Some(rest) = raw.strip_prefix('\'') {
+        let (value, rest) = rest.split_once('\'').context("Unterminated string")?;
+        ensure_only_comment(rest)?;
+        return Ok(Value::String(value.to_string()));
+    }
+
+    let raw = raw.split('#').next().unwrap_or_default().trim();
+    match raw {
+        "true" => Ok(Value::Bool(true)),
+        "false" => Ok(Value::Bool(false)),
+        _ => raw
+            .replace('_', "")
+            .parse::<u64>()
+            .map(Value::from)
+            .with_context(|| format!("Expected a string, integer, or boolean, found \"{raw}\"")),
+    }
+}
+
+/// Read the rest of a double-quoted string, returning its value and whatever
+/// came after the closing quote.
+fn basic_string(src: &str) -> Result<(String, &str), Error> {
+    let mut value = String::new();
+    let mut chars = src.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Ok((value, &src[i + 1..])),
+            '\\' => {
+                let escaped = match chars.next() {
+                    Some((_, 'n')) => '\n',
+                    Some((_, 't')) => '\t',
+                    Some((_, '"')) => '"',
+                    Some((_, '\\')) => '\\',
+                    other => anyhow::bail!("Unsupported escape sequence: {other:?}"),
+                };
+                value.push(escaped);
+            }
+            c => value.push(c),
+        }
+    }
+
+    anyhow::bail!("Unterminated string")
+}
+
+fn ensure_only_comment(rest: &str) -> Result<(), Error> {
+    let rest = rest.trim();
+    anyhow::ensure!(
+        rest.is_empty() || rest.starts_with('#'),
+        "Unexpected \"{rest}\" after the value"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_a_config_file() {
+        let src = r#"
+            # Defaults for the CI image
+            registry = "staging"
+            token-env = 'CI_WASMER_TOKEN'  # set by the pipeline
+            cache-dir = "C:\\borealis\\cache"
+            parallelism = 8
+            keep-runs = 1_000
+            smtp-server = "smtps://smtp.example.com"
+            email-to = "alice@example.com, bob@example.com"
+        "#;
+
+        let config = UserConfig::from_toml(src).unwrap();
+
+        assert_eq!(
+            config,
+            UserConfig {
+                registry: Some("staging".to_string()),
+                token_env: Some("CI_WASMER_TOKEN".to_string()),
+                token_command: None,
+                cache_dir: Some(PathBuf::from(r"C:\borealis\cache")),
+                parallelism: NonZeroUsize::new(8),
+                keep_runs: Some(1000),
+                smtp_server: Some("smtps://smtp.example.com".to_string()),
+                smtp_username: None,
+                smtp_password_env: None,
+                email_from: None,
+                email_to: Some("alice@example.com, bob@example.com".to_string()),
+            }
+        );
+        assert_eq!(
+            config.email_recipients(),
+            ["alice@example.com", "bob@example.com"]
+        );
+    }
+
+    #[test]
+    fn mistakes_are_reported() {
+        let error = UserConfig::from_toml("cache-directory = \"/tmp\"").unwrap_err();
+        assert!(
+            error.to_string().contains("did you mean `cache-dir`?"),
+            "{error}"
+        );
+
+        assert!(UserConfig::from_toml("[run]\nregistry = \"prod\"").is_err());
+        assert!(UserConfig::from_toml("parallelism = 0").is_err());
+        assert!(UserConfig::from_toml("registry = \"prod").is_err());
+    }
+
+    #[test]
+    fn missing_config_files_use_the_defaults() {
+        let temp = tempfile::tempdir().unwrap();
+
+        let config = UserConfig::load(temp.path().join("config.toml")).unwrap();
+
+        assert_eq!(config, UserConfig::default());
+    }
+}